@@ -0,0 +1,74 @@
+//! Throughput of decryption and encryption, both in memory and in batch mode.
+//!
+//! Run with `cargo bench`. Batch benchmarks decrypt a temporary folder of audio files, which is where allocation and copying used to dominate.
+
+use criterion::{
+    BatchSize, Criterion, Throughput, black_box, criterion_group,
+    criterion_main,
+};
+use rpgm_asset_decrypter_lib::{
+    Decrypter, Encrypter, FileType,
+    batch::{BatchOptions, decrypt_dir},
+};
+use std::{env::temp_dir, fs};
+
+const MZ_OGG_ABOBA: &str = "./tests/assets/mz/test-aboba.ogg_";
+const BATCH_FILES: usize = 64;
+
+fn in_memory(c: &mut Criterion) {
+    let encrypted = fs::read(MZ_OGG_ABOBA).unwrap();
+    let mut decrypter = Decrypter::new();
+    let decrypted = decrypter.decrypt(&encrypted, FileType::OGG).unwrap();
+    let encrypter = Encrypter::with_key(decrypter.encryption_key().unwrap());
+
+    let mut group = c.benchmark_group("in_memory");
+    group.throughput(Throughput::Bytes(encrypted.len() as u64));
+
+    group.bench_function("decrypt", |b| {
+        b.iter(|| decrypter.decrypt(black_box(&encrypted), FileType::OGG));
+    });
+    group.bench_function("decrypt_in_place", |b| {
+        b.iter_batched_ref(
+            || encrypted.clone(),
+            |data| {
+                decrypter
+                    .decrypt_in_place(black_box(data), FileType::OGG)
+                    .map(<[u8]>::len)
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("encrypt", |b| {
+        b.iter(|| encrypter.encrypt(black_box(&decrypted)));
+    });
+
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    let encrypted = fs::read(MZ_OGG_ABOBA).unwrap();
+    let dir = temp_dir().join("rpgm-asset-decrypter-bench");
+    let input = dir.join("input");
+    let output = dir.join("output");
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&input).unwrap();
+
+    for i in 0..BATCH_FILES {
+        fs::write(input.join(format!("{i}.ogg_")), &encrypted).unwrap();
+    }
+
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Bytes((encrypted.len() * BATCH_FILES) as u64));
+    group.sample_size(20);
+
+    group.bench_function("decrypt_dir", |b| {
+        b.iter(|| decrypt_dir(&input, &output, &BatchOptions::new()).unwrap());
+    });
+
+    group.finish();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+criterion_group!(benches, in_memory, batch);
+criterion_main!(benches);
@@ -0,0 +1,381 @@
+#![warn(clippy::all, clippy::pedantic)]
+
+use clap::{Parser, Subcommand};
+use rpgm_asset_decrypter_lib::{
+    Decrypter, Encrypter, EncryptionKey, Engine, Error, FileError, FileType,
+    audit::audit,
+    batch::{
+        BatchOptions, BatchReport, FileStatus, RepackOptions, decrypt_dir,
+        encrypt_dir,
+    },
+    fs::{encrypted_files, write_atomic},
+    key_recovery::scan_scripts,
+    project::find_encryption_info,
+    scan::{find_duplicates, inventory, write_csv, write_json},
+    system_json::extract_key_from_system_json_file,
+};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    ffi::OsStr,
+    fs::read,
+    io::{Write, stdout},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decrypt `rpgmvp`/`png_`, `rpgmvo`/`ogg_`, `rpgmvm`/`m4a_`, `rpgmvw`/`webp_`, `rpgmvwav`/`wav_` and `rpgmvj`/`json_` files, or whole directories of them.
+    Decrypt {
+        /// Encrypted files or directories to decrypt.
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Directory to write decrypted files to. Defaults to the directory of each input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Encryption key. Determined from the files if not specified.
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+    /// Encrypt `png`, `ogg`, `m4a`, `webp`, `wav` and `json` files, or whole directories of them. Directories' `json` files are left as is.
+    Encrypt {
+        /// Files or directories to encrypt.
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Directory to write encrypted files to. Defaults to the directory of each input.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Encryption key.
+        #[arg(short, long)]
+        key: String,
+        /// Use MZ extensions (`png_`, `ogg_`, `m4a_`, `webp_`, `wav_`, `json_`) instead of MV ones.
+        #[arg(long)]
+        mz: bool,
+    },
+    /// Print the encryption key of a game directory, `System.json` or encrypted file.
+    Key {
+        /// Game directory, `System.json` or encrypted file.
+        input: PathBuf,
+    },
+    /// Print the key and the number of encrypted assets of each type in a directory.
+    Scan {
+        /// Directory to scan.
+        input: PathBuf,
+        /// Also list assets with identical decrypted content.
+        #[arg(long)]
+        duplicates: bool,
+    },
+    /// Check that encrypted assets in a directory decrypt correctly, and print the ones that don't.
+    Audit {
+        /// Directory to check.
+        input: PathBuf,
+        /// Encryption key. Determined from the directory if not specified.
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+    /// Print a CSV list of encrypted and decrypted assets in a directory, with their metadata.
+    Inventory {
+        /// Directory to list.
+        input: PathBuf,
+        /// Print JSON instead of CSV.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn output_path(input: &Path, output_dir: Option<&Path>) -> PathBuf {
+    match output_dir {
+        Some(dir) => dir.join(input.file_name().unwrap_or_default()),
+        None => input.to_path_buf(),
+    }
+}
+
+fn encrypted_type_of(path: &Path) -> Result<FileType, FileError> {
+    path.extension()
+        .and_then(|ext| FileType::try_from(ext).ok())
+        .ok_or_else(|| FileError::new(path, Error::UnsupportedFileType))
+}
+
+fn decrypt(
+    decrypter: &mut Decrypter,
+    input: &Path,
+    output_dir: Option<&Path>,
+) -> Result<(), FileError> {
+    let file_type = encrypted_type_of(input)?;
+
+    let mut data = read(input).map_err(|err| FileError::new(input, err))?;
+    let plain = decrypter
+        .decrypt_in_place(&mut data, file_type)
+        .map_err(|err| FileError::new(input, err))?;
+
+    let output =
+        output_path(input, output_dir).with_extension(file_type.to_string());
+    write_atomic(&output, plain)
+}
+
+fn print_report(input: &Path, action: &str, report: &BatchReport) {
+    for file in report.failed() {
+        if let FileStatus::Failed(err) = &file.status {
+            eprintln!("{err}");
+        }
+    }
+
+    println!(
+        "{}: {action} {}, skipped {}, failed {}",
+        input.display(),
+        report.succeeded().count(),
+        report.skipped().count(),
+        report.failed().count()
+    );
+}
+
+fn decrypt_tree(
+    input: &Path,
+    output: Option<&Path>,
+    key: Option<EncryptionKey>,
+) -> Result<(), FileError> {
+    let mut options = BatchOptions::new();
+    options.key = key;
+
+    let report = decrypt_dir(input, output.unwrap_or(input), &options)?;
+    print_report(input, "decrypted", &report);
+    Ok(())
+}
+
+fn encrypt(
+    encrypter: &Encrypter,
+    input: &Path,
+    output: &Path,
+) -> Result<(), FileError> {
+    let file_type = input
+        .extension()
+        .and_then(OsStr::to_str)
+        .and_then(FileType::from_decrypted_extension)
+        .ok_or_else(|| FileError::new(input, Error::UnsupportedFileType))?;
+
+    let data = read(input).map_err(|err| FileError::new(input, err))?;
+    let data = encrypter.encrypt(&data);
+
+    let output =
+        output.with_extension(encrypter.encrypted_extension(file_type));
+    write_atomic(&output, &data)
+}
+
+fn encrypt_tree(
+    input: &Path,
+    output: Option<&Path>,
+    key: EncryptionKey,
+    engine: Engine,
+) -> Result<(), FileError> {
+    let options = RepackOptions::new().key(key).engine(engine);
+    let report = encrypt_dir(input, output.unwrap_or(input), &options)?;
+    print_report(input, "encrypted", &report);
+    Ok(())
+}
+
+/// Determines the key of a game directory, `System.json` or encrypted file.
+///
+/// Directories are first searched for `System.json`, including renamed and obfuscated ones, and only then for encrypted assets, PNGs first.
+fn find_key(input: &Path) -> Result<Option<String>, FileError> {
+    if input.is_dir() {
+        if let Some((_, info)) = find_encryption_info(input) {
+            return Ok(Some(info.key));
+        }
+
+        if let Some(found) = scan_scripts(input)?.first() {
+            return Ok(Some(found.key.to_string()));
+        }
+
+        let files = encrypted_files(input)?;
+        let source = files
+            .iter()
+            .find(|path| encrypted_type_of(path).is_ok_and(FileType::is_png))
+            .or_else(|| files.first());
+
+        return match source {
+            Some(path) => find_key(path),
+            None => Ok(None),
+        };
+    }
+
+    if input.extension().is_some_and(|ext| ext == "json") {
+        return extract_key_from_system_json_file(input)
+            .map(|info| Some(info.key));
+    }
+
+    let file_type = encrypted_type_of(input)?;
+    let data = read(input).map_err(|err| FileError::new(input, err))?;
+
+    Decrypter::new()
+        .set_key_from_file(&data, file_type)
+        .map(|key| Some(key.to_owned()))
+        .map_err(|err| FileError::new(input, err))
+}
+
+fn scan(input: &Path, duplicates: bool) -> Result<(), FileError> {
+    let mut counts = BTreeMap::new();
+
+    for file in encrypted_files(input)? {
+        let ext = file
+            .extension()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default()
+            .to_owned();
+        *counts.entry(ext).or_insert(0usize) += 1;
+    }
+
+    match find_key(input)? {
+        Some(key) => println!("key: {key}"),
+        None => println!("key: not found"),
+    }
+
+    for (ext, count) in counts {
+        println!("{ext}: {count}");
+    }
+
+    if duplicates {
+        let duplicates = find_duplicates(input);
+
+        for group in &duplicates.groups {
+            println!("duplicates ({} bytes each):", group.size());
+
+            for asset in &group.assets {
+                println!("  {}", asset.path.display());
+            }
+        }
+
+        for err in &duplicates.errors {
+            eprintln!("{err}");
+        }
+
+        println!(
+            "{} duplicate groups, {} redundant bytes",
+            duplicates.groups.len(),
+            duplicates.redundant_bytes()
+        );
+    }
+
+    Ok(())
+}
+
+fn print_audit(
+    input: &Path,
+    key: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let key = match key {
+        Some(key) => key.to_owned(),
+        None => find_key(input)?.ok_or(Error::KeyNotSet)?,
+    };
+    let report = audit(input, &EncryptionKey::parse_lossy(&key)?);
+
+    for finding in &report.findings {
+        println!("{}: {}", finding.asset.path.display(), finding.issue);
+    }
+
+    for err in &report.errors {
+        eprintln!("{err}");
+    }
+
+    println!(
+        "{} assets checked, {} broken",
+        report.checked,
+        report.findings.len()
+    );
+
+    if report.is_clean() {
+        Ok(())
+    } else {
+        Err("audit found problems".into())
+    }
+}
+
+fn print_inventory(
+    input: &Path,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let records = inventory(input)?;
+    let mut out = stdout().lock();
+
+    if json {
+        write_json(&records, &mut out)?;
+        writeln!(out)?;
+    } else {
+        write_csv(&records, &mut out)?;
+    }
+
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let mut decrypter = Decrypter::new();
+
+    match cli.command {
+        Command::Decrypt {
+            inputs,
+            output,
+            key,
+        } => {
+            // Pasted keys often come with quotes or a trailing newline.
+            let key =
+                key.as_deref().map(EncryptionKey::parse_lossy).transpose()?;
+
+            if let Some(key) = key {
+                decrypter.set_key(key);
+            }
+
+            for input in &inputs {
+                if input.is_dir() {
+                    decrypt_tree(input, output.as_deref(), key)?;
+                } else {
+                    decrypt(&mut decrypter, input, output.as_deref())?;
+                }
+            }
+        }
+        Command::Encrypt {
+            inputs,
+            output,
+            key,
+            mz,
+        } => {
+            let key = EncryptionKey::parse_lossy(&key)?;
+            let engine = if mz { Engine::Mz } else { Engine::Mv };
+            let encrypter = Encrypter::with_key(key).engine(engine);
+
+            for input in &inputs {
+                if input.is_dir() {
+                    encrypt_tree(input, output.as_deref(), key, engine)?;
+                } else {
+                    let output = output_path(input, output.as_deref());
+                    encrypt(&encrypter, input, &output)?;
+                }
+            }
+        }
+        Command::Key { input } => match find_key(&input)? {
+            Some(key) => println!("{key}"),
+            None => return Err(Error::KeyNotSet.into()),
+        },
+        Command::Scan { input, duplicates } => scan(&input, duplicates)?,
+        Command::Audit { input, key } => print_audit(&input, key.as_deref())?,
+        Command::Inventory { input, json } => print_inventory(&input, json)?,
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Minimal JSON reading helpers.
+//!
+//! Shared by [`system_json`](crate::system_json) and by parsers of other JSON documents RPG Maker tools deal with, e.g. `app.asar` headers, so each of them doesn't need its own string unescaping.
+
+use alloc::{string::String, vec::Vec};
+
+/// Reads JSON string from `data` at `pos`, skipping whitespace before it.
+///
+/// # Parameters
+///
+/// - `data` - JSON data.
+/// - `pos` - Position to read at. On success, it's advanced past the closing quote.
+///
+/// # Returns
+///
+/// - Unescaped string.
+/// - [`None`] if there's no valid string at `pos`, e.g. it's unterminated, contains invalid UTF-8, an unknown escape or an unpaired surrogate.
+#[must_use]
+pub fn read_string(data: &[u8], pos: &mut usize) -> Option<String> {
+    let mut cursor = *pos;
+
+    while data.get(cursor)?.is_ascii_whitespace() {
+        cursor += 1;
+    }
+
+    if data[cursor] != b'"' {
+        return None;
+    }
+
+    cursor += 1;
+
+    let mut units = Vec::new();
+    let mut out = String::new();
+
+    loop {
+        let end = cursor
+            + data
+                .get(cursor..)?
+                .iter()
+                .position(|&byte| byte == b'"' || byte == b'\\')?;
+        out.push_str(core::str::from_utf8(&data[cursor..end]).ok()?);
+        cursor = end + 1;
+
+        if data[end] == b'"' {
+            *pos = cursor;
+            return Some(out);
+        }
+
+        let escaped = *data.get(cursor)?;
+        cursor += 1;
+
+        match escaped {
+            b'"' => out.push('"'),
+            b'\\' => out.push('\\'),
+            b'/' => out.push('/'),
+            b'n' => out.push('\n'),
+            b'r' => out.push('\r'),
+            b't' => out.push('\t'),
+            b'b' => out.push('\u{8}'),
+            b'f' => out.push('\u{c}'),
+            b'u' => {
+                // Surrogate pairs are written as two consecutive escapes.
+                units.clear();
+
+                loop {
+                    let hex = data.get(cursor..cursor + 4)?;
+                    let hex = core::str::from_utf8(hex).ok()?;
+                    units.push(u16::from_str_radix(hex, 16).ok()?);
+                    cursor += 4;
+
+                    let is_high = (0xD800..0xDC00).contains(units.last()?);
+
+                    if !is_high || data.get(cursor..cursor + 2) != Some(b"\\u")
+                    {
+                        break;
+                    }
+
+                    cursor += 2;
+                }
+
+                for char in char::decode_utf16(units.iter().copied()) {
+                    out.push(char.ok()?);
+                }
+            }
+            _ => return None,
+        }
+    }
+}
@@ -0,0 +1,2748 @@
+#![warn(clippy::all, clippy::pedantic)]
+#![allow(clippy::needless_doctest_main)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_possible_wrap)]
+#![allow(clippy::cast_sign_loss)]
+#![allow(clippy::deref_addrof)]
+//! Dependency-free core of [`rpgm-asset-decrypter-lib`](https://docs.rs/rpgm-asset-decrypter-lib).
+//!
+//! Contains only the pure key derivation and decryption/encryption logic for RPG Maker MV/MZ assets, without any filesystem helpers.
+//! Most users should depend on `rpgm-asset-decrypter-lib`, which re-exports everything from this crate.
+//!
+//! The crate is `no_std` with `alloc` when its default `std` feature is disabled. [`stream`] module, `pool::VecPool`, [`Error::Io`] and `OsStr` conversions require `std`.
+//!
+//! With `serde` feature, [`FileType`], [`Engine`] and [`Error`] can be serialized and deserialized: file types and engines as lowercase strings, e.g. `png` and `mz`, and errors as objects tagged with `kind`, e.g. `{ "kind": "key_not_set" }`.
+//!
+//! The crate contains no `unsafe` code, and all parsing of untrusted data is bounds-checked: malformed input results in an [`Error`], never in a panic or undefined behavior.
+#![cfg_attr(not(feature = "std"), no_std)]
+#![forbid(unsafe_code)]
+
+extern crate alloc;
+
+pub mod json;
+#[cfg(feature = "md5")]
+mod md5;
+pub mod pool;
+pub mod registry;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod system_json;
+pub mod variant;
+pub mod verify;
+
+use alloc::{borrow::Cow, format, string::String, vec::Vec};
+use core::{convert::TryFrom, fmt::Display, str::FromStr};
+#[cfg(feature = "std")]
+use std::{ffi::OsStr, io};
+
+macro_rules! sizeof {
+    ($t:ty) => {{ core::mem::size_of::<$t>() }};
+}
+
+const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
+
+/// Returns `hex` digits, produced from [`HEX_CHARS`], as a string.
+fn hex_str(hex: &[u8]) -> &str {
+    // Hex is always ASCII, so the fallback is never taken.
+    core::str::from_utf8(hex).unwrap_or_default()
+}
+
+pub const HEADER_LENGTH: usize = 16;
+
+pub const KEY_LENGTH: usize = 16;
+pub const KEY_STR_LENGTH: usize = 32;
+
+/// Maximum number of encryption layers [`Decrypter::decrypt_recursive`] and [`inspect`] peel, see [`Error::TooManyLayers`].
+pub const MAX_LAYERS: usize = 8;
+
+// Key used in RPG Maker encrypted files when "Encryption key" is left unfilled.
+pub const DEFAULT_KEY: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+// RPG Maker's encoding is essentially taking source file's header (16 bytes) and xor'ing it upon a MD5 key produced from encryption key string. Most projects leave encryption key string empty, so resulting 'encryption' is just header xor'd with default MD5 key.
+
+// For PNG, header is always the same, so we can expect valid decryption.
+const PNG_HEADER: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d,
+    0x49, 0x48, 0x44, 0x52,
+];
+
+// 0 - 3 - OggS
+// 4 - version, always 0
+// 5 - header type, always 0x02, since first page always announces the beginning of the stream
+// 6 - 13 - granule position, always 0, since first page has no actual data
+//* 14 - 15 - part of 4-byte bitstream serial number, that actually differs between files
+const OGG_HEADER: [u8; HEADER_LENGTH] =
+    [79, 103, 103, 83, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+//* 0 - 3 - type box size, actually differs between files
+// 4 - 7 - ftyp, always the same
+// 8 - 11 - M4A_, always the same, may be different 4 characters, but extremely unlikely
+// 12 - 15 - minor version, mostly junk, doesn't matter
+const M4A_HEADER: [u8; HEADER_LENGTH] =
+    [0, 0, 0, 28, 102, 116, 121, 112, 77, 52, 65, 32, 0, 0, 2, 0];
+
+//* 0 - 3 - RIFF
+//* 4 - 7 - RIFF chunk size, which is file size minus 8, differs between files
+// 8 - 11 - WEBP
+// 12 - 14 - VP8
+//* 15 - either ` `, `L` or `X`, depending on the VP8 chunk type
+const WEBP_HEADER: [u8; HEADER_LENGTH] = *b"RIFF\0\0\0\0WEBPVP8 ";
+
+//* 0 - 3 - RIFF
+//* 4 - 7 - RIFF chunk size, which is file size minus 8, differs between files
+// 8 - 11 - WAVE
+// 12 - 15 - `fmt `, the format chunk always comes first
+const WAV_HEADER: [u8; HEADER_LENGTH] = *b"RIFF\0\0\0\0WAVEfmt ";
+
+/// Returns the signature template of `file_type`, and the mask of its bytes that are the same in every file of the type.
+///
+/// Bit `i` of the mask is set if byte `i` of the template is reliable.
+fn signature_of(file_type: FileType) -> ([u8; HEADER_LENGTH], u16) {
+    let mut signature = [0; HEADER_LENGTH];
+
+    let mask = match file_type {
+        FileType::PNG => {
+            signature.copy_from_slice(PNG_HEADER);
+            0xFFFF
+        }
+        FileType::OGG => {
+            signature = OGG_HEADER;
+            // Bytes 14 - 15 are part of the serial number.
+            0x3FFF
+        }
+        FileType::M4A => {
+            signature = M4A_HEADER;
+            // Only `ftyp` is reliable, box size and brand vary.
+            0x00F0
+        }
+        FileType::WEBP => {
+            signature = WEBP_HEADER;
+            // RIFF chunk size and VP8 chunk type vary.
+            0x7F0F
+        }
+        FileType::WAV => {
+            signature = WAV_HEADER;
+            // RIFF chunk size varies.
+            0xFF0F
+        }
+        // JSON has no fixed header, so no byte is reliable.
+        FileType::JSON => 0,
+        FileType::Other(template) => {
+            signature = template;
+            0xFFFF
+        }
+    };
+
+    (signature, mask)
+}
+
+// For finding type box size
+const M4A_POST_HEADER_BOXES: &[&[u8]] =
+    &[b"moov", b"mdat", b"free", b"skip", b"wide", b"pnot"];
+
+// Every encrypted file includes this header.
+pub const RPGM_HEADER: &[u8] = &[
+    0x52, 0x50, 0x47, 0x4d, 0x56, 0x00, 0x00, 0x00, 0x00, 0x03, 0x01, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+];
+
+pub const MV_PNG_EXT: &str = "rpgmvp";
+pub const MZ_PNG_EXT: &str = "png_";
+pub const MV_OGG_EXT: &str = "rpgmvo";
+pub const MZ_OGG_EXT: &str = "ogg_";
+pub const MV_M4A_EXT: &str = "rpgmvm";
+pub const MZ_M4A_EXT: &str = "m4a_";
+pub const MV_WEBP_EXT: &str = "rpgmvw";
+pub const MZ_WEBP_EXT: &str = "webp_";
+/// `rpgmvw` is already taken by WEBP, so MV-style WAV files get a longer extension.
+pub const MV_WAV_EXT: &str = "rpgmvwav";
+pub const MZ_WAV_EXT: &str = "wav_";
+pub const MV_JSON_EXT: &str = "rpgmvj";
+pub const MZ_JSON_EXT: &str = "json_";
+/// Encrypted extension of [`FileType::Other`] files, for both engines.
+pub const OTHER_ENCRYPTED_EXT: &str = "bin_";
+
+pub const PNG_EXT: &str = "png";
+pub const OGG_EXT: &str = "ogg";
+pub const M4A_EXT: &str = "m4a";
+pub const WEBP_EXT: &str = "webp";
+pub const WAV_EXT: &str = "wav";
+pub const JSON_EXT: &str = "json";
+/// Decrypted extension of [`FileType::Other`] files.
+pub const OTHER_EXT: &str = "bin";
+
+pub const ENCRYPTED_ASSET_EXTS: &[&str] = &[
+    MV_PNG_EXT,
+    MV_OGG_EXT,
+    MV_M4A_EXT,
+    MV_WEBP_EXT,
+    MV_WAV_EXT,
+    MV_JSON_EXT,
+    MZ_PNG_EXT,
+    MZ_OGG_EXT,
+    MZ_M4A_EXT,
+    MZ_WEBP_EXT,
+    MZ_WAV_EXT,
+    MZ_JSON_EXT,
+];
+/// Doesn't include [`JSON_EXT`], so that repacking a game doesn't encrypt its plain data files.
+pub const DECRYPTED_ASSETS_EXTS: &[&str] =
+    &[PNG_EXT, OGG_EXT, M4A_EXT, WEBP_EXT, WAV_EXT];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[repr(u8)]
+pub enum FileType {
+    PNG,
+    OGG,
+    M4A,
+    WEBP,
+    /// RIFF/WAVE audio, which some MV plugins encrypt the same way as the built-in formats.
+    WAV,
+    /// Data files, e.g. `data/*.json`, which some obfuscation plugins encrypt the same way as assets.
+    ///
+    /// JSON has no fixed header to use as known plaintext, so the key can't be derived from such files, and must be set beforehand.
+    /// Any decrypted content is accepted for this type, so [`Error::KeyMismatch`] is never returned for it.
+    JSON,
+    /// Any other format with a fixed 16-byte signature, which is used as the known plaintext for key derivation.
+    ///
+    /// Never produced from extensions or content detection, only constructed explicitly.
+    Other([u8; HEADER_LENGTH]),
+}
+
+impl FileType {
+    #[must_use]
+    pub fn is_png(self) -> bool {
+        matches!(self, Self::PNG)
+    }
+
+    #[must_use]
+    pub fn is_ogg(self) -> bool {
+        matches!(self, Self::OGG)
+    }
+
+    #[must_use]
+    pub fn is_m4a(self) -> bool {
+        matches!(self, Self::M4A)
+    }
+
+    #[must_use]
+    pub fn is_webp(self) -> bool {
+        matches!(self, Self::WEBP)
+    }
+
+    #[must_use]
+    pub fn is_wav(self) -> bool {
+        matches!(self, Self::WAV)
+    }
+
+    #[must_use]
+    pub fn is_json(self) -> bool {
+        matches!(self, Self::JSON)
+    }
+
+    /// Returns the file type of a decrypted file with extension `ext`, e.g. `png`.
+    #[must_use]
+    pub fn from_decrypted_extension(ext: &str) -> Option<FileType> {
+        match ext {
+            PNG_EXT => Some(Self::PNG),
+            OGG_EXT => Some(Self::OGG),
+            M4A_EXT => Some(Self::M4A),
+            WEBP_EXT => Some(Self::WEBP),
+            WAV_EXT => Some(Self::WAV),
+            JSON_EXT => Some(Self::JSON),
+            _ => None,
+        }
+    }
+
+    /// Guesses the file type from decrypted file content, instead of relying on the file extension.
+    ///
+    /// Only the first 16 bytes are inspected, so the output of [`Decrypter::decrypt_header_only`] is enough.
+    ///
+    /// # Returns
+    ///
+    /// - Detected [`FileType`].
+    /// - [`None`] if the content doesn't look like any of the supported types.
+    #[must_use]
+    pub fn detect(data: &[u8]) -> Option<FileType> {
+        if data.starts_with(&PNG_HEADER[..8]) {
+            Some(Self::PNG)
+        } else if data.starts_with(b"OggS") {
+            Some(Self::OGG)
+        } else if data.get(4..8) == Some(b"ftyp") {
+            Some(Self::M4A)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP")
+        {
+            Some(Self::WEBP)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WAVE")
+        {
+            Some(Self::WAV)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for FileType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PNG => f.write_str("png"),
+            Self::OGG => f.write_str("ogg"),
+            Self::M4A => f.write_str(M4A_EXT),
+            Self::WEBP => f.write_str(WEBP_EXT),
+            Self::WAV => f.write_str(WAV_EXT),
+            Self::JSON => f.write_str(JSON_EXT),
+            Self::Other(_) => f.write_str(OTHER_EXT),
+        }
+    }
+}
+
+impl TryFrom<&str> for FileType {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            MV_PNG_EXT | MZ_PNG_EXT => Ok(FileType::PNG),
+            MV_OGG_EXT | MZ_OGG_EXT => Ok(FileType::OGG),
+            MV_M4A_EXT | MZ_M4A_EXT => Ok(FileType::M4A),
+            MV_WEBP_EXT | MZ_WEBP_EXT => Ok(FileType::WEBP),
+            MV_WAV_EXT | MZ_WAV_EXT => Ok(FileType::WAV),
+            MV_JSON_EXT | MZ_JSON_EXT => Ok(FileType::JSON),
+            _ => Err("Extension not supported"),
+        }
+    }
+}
+
+// [`PathBuf::extension`] returns &OsStr, so implement this for convenience.
+#[cfg(feature = "std")]
+impl TryFrom<&OsStr> for FileType {
+    type Error = &'static str;
+
+    fn try_from(value: &OsStr) -> Result<Self, Self::Error> {
+        if value == MV_PNG_EXT || value == MZ_PNG_EXT {
+            Ok(FileType::PNG)
+        } else if value == MV_OGG_EXT || value == MZ_OGG_EXT {
+            Ok(FileType::OGG)
+        } else if value == MV_M4A_EXT || value == MZ_M4A_EXT {
+            Ok(FileType::M4A)
+        } else if value == MV_WEBP_EXT || value == MZ_WEBP_EXT {
+            Ok(FileType::WEBP)
+        } else if value == MV_WAV_EXT || value == MZ_WAV_EXT {
+            Ok(FileType::WAV)
+        } else if value == MV_JSON_EXT || value == MZ_JSON_EXT {
+            Ok(FileType::JSON)
+        } else {
+            Err("Extension not supported")
+        }
+    }
+}
+
+/// RPG Maker engine, which determines extensions of encrypted files.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Engine {
+    /// RPG Maker MV: `rpgmvp`, `rpgmvo`, `rpgmvm`.
+    Mv,
+    /// RPG Maker MZ: `png_`, `ogg_`, `m4a_`.
+    Mz,
+}
+
+impl Engine {
+    /// Returns the engine that uses `ext` for encrypted files, or [`None`] if `ext` is not an encrypted asset extension.
+    #[must_use]
+    pub fn from_encrypted_extension(ext: &str) -> Option<Engine> {
+        match ext {
+            MV_PNG_EXT | MV_OGG_EXT | MV_M4A_EXT | MV_WEBP_EXT | MV_WAV_EXT
+            | MV_JSON_EXT => Some(Self::Mv),
+            MZ_PNG_EXT | MZ_OGG_EXT | MZ_M4A_EXT | MZ_WEBP_EXT | MZ_WAV_EXT
+            | MZ_JSON_EXT => Some(Self::Mz),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the extension of encrypted files of `file_type`, as used by `engine`.
+///
+/// `FileType::PNG` with `Engine::Mv` -> `rpgmvp`, with `Engine::Mz` -> `png_`.
+#[must_use]
+pub const fn encrypted_extension_for(
+    file_type: FileType,
+    engine: Engine,
+) -> &'static str {
+    match (file_type, engine) {
+        (FileType::PNG, Engine::Mv) => MV_PNG_EXT,
+        (FileType::PNG, Engine::Mz) => MZ_PNG_EXT,
+        (FileType::OGG, Engine::Mv) => MV_OGG_EXT,
+        (FileType::OGG, Engine::Mz) => MZ_OGG_EXT,
+        (FileType::M4A, Engine::Mv) => MV_M4A_EXT,
+        (FileType::M4A, Engine::Mz) => MZ_M4A_EXT,
+        (FileType::WEBP, Engine::Mv) => MV_WEBP_EXT,
+        (FileType::WEBP, Engine::Mz) => MZ_WEBP_EXT,
+        (FileType::WAV, Engine::Mv) => MV_WAV_EXT,
+        (FileType::WAV, Engine::Mz) => MZ_WAV_EXT,
+        (FileType::JSON, Engine::Mv) => MV_JSON_EXT,
+        (FileType::JSON, Engine::Mz) => MZ_JSON_EXT,
+        (FileType::Other(_), _) => OTHER_ENCRYPTED_EXT,
+    }
+}
+
+/// State of asset data, as determined by [`AssetState::detect`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum AssetState {
+    /// Data starts with the RPG Maker header.
+    Encrypted,
+    /// Data is a plain asset of the given type.
+    Decrypted(FileType),
+    /// Data is neither, e.g. it's corrupted or not an asset at all.
+    Unknown,
+}
+
+impl AssetState {
+    /// Determines whether `data` is encrypted, already decrypted, or neither, so batch tools can skip already decrypted files instead of reporting them as corrupted.
+    ///
+    /// Only the first 16 bytes are inspected.
+    #[must_use]
+    pub fn detect(data: &[u8]) -> AssetState {
+        if data.starts_with(RPGM_HEADER) {
+            Self::Encrypted
+        } else if let Some(file_type) = FileType::detect(data) {
+            Self::Decrypted(file_type)
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// Metadata of an asset, returned by [`inspect`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct AssetInfo {
+    /// Whether the data is encrypted, already decrypted, or has invalid header.
+    pub state: AssetState,
+    /// Number of encryption layers: `0` for data that isn't encrypted, `1` for encrypted assets, and more for assets encrypted repeatedly, see [`Error::DoubleEncrypted`]. The rest of the fields describe the innermost asset.
+    pub layers: usize,
+    /// Detected file type, or [`None`] if it couldn't be determined.
+    pub file_type: Option<FileType>,
+    /// Key derived from the data, or [`None`] if the data isn't encrypted or its type couldn't be determined.
+    pub key: Option<EncryptionKey>,
+    /// Length of the asset's own data, without the RPG Maker header.
+    pub payload_length: usize,
+    /// Width and height of PNG image.
+    pub dimensions: Option<(u32, u32)>,
+    /// Bitstream serial number of OGG audio.
+    pub serial_number: Option<u32>,
+    /// Major brand of M4A audio's `ftyp` box, e.g. `M4A `. For encrypted data, it's reconstructed from the plain part of the box.
+    pub brand: Option<[u8; 4]>,
+}
+
+/// Result of [`Decrypter::decrypt_auto`].
+#[derive(Debug)]
+pub struct AutoDecrypted {
+    /// Decrypted data, without the RPG Maker header.
+    pub data: Vec<u8>,
+    /// Type the data was decrypted as.
+    pub file_type: FileType,
+    /// [`Error::TypeMismatch`], if the declared type differs from [`AutoDecrypted::file_type`], e.g. because the file was renamed to confuse rippers.
+    pub warning: Option<Error>,
+}
+
+/// Returns RIFF chunk size of decrypted `payload`, which is its length minus RIFF chunk header.
+///
+/// # Errors
+///
+/// - [`Error::Malformed`] - if the size doesn't fit RIFF's 32-bit field.
+fn riff_size(payload: &[u8]) -> Result<u32, Error> {
+    u32::try_from(payload.len().saturating_sub(8)).map_err(|_| {
+        Error::Malformed {
+            offset: 4,
+            reason: "RIFF data exceeds 4 GiB",
+        }
+    })
+}
+
+/// Reconstructs major brand and minor version of M4A `ftyp` box, which are encrypted, from its plain `compatible_brands`.
+///
+/// Major brand is conventionally repeated as the first compatible brand. Minor version isn't, so it's guessed from the muxer the brands are typical for: `FFmpeg` writes `iso2` brand and minor version 512, while Apple tools write `mp42` brand and minor version 0.
+///
+/// Returns [`None`] if there are no compatible brands, or the first one isn't a valid brand, so [`M4A_HEADER`] defaults are kept.
+fn m4a_brand(compatible_brands: Option<&[u8]>) -> Option<([u8; 4], u32)> {
+    let mut brands = compatible_brands?.chunks_exact(4);
+    let first = brands.next()?;
+
+    if !first
+        .iter()
+        .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ')
+    {
+        return None;
+    }
+
+    let mut brand = [0; 4];
+    brand.copy_from_slice(first);
+
+    let minor_version = match core::iter::once(first)
+        .chain(brands)
+        .find(|&compatible| matches!(compatible, b"iso2" | b"mp42"))
+    {
+        Some(b"mp42") => 0,
+        _ => 0x200,
+    };
+
+    Some((brand, minor_version))
+}
+
+/// Recovers the encrypted first two bytes of OGG serial number from the plain CRC of the first `page` of encrypted payload, whose `header` is [`OGG_HEADER`].
+///
+/// The CRC is solved for 4 bytes: the top two bytes of granule position, which is 0 in the first page, and the serial number bytes. Unlike taking them from the second page, this works for multiplexed streams, and makes the page's CRC valid.
+///
+/// Returns [`None`] if the solved granule position isn't 0, i.e. the page doesn't match `header`.
+fn solve_ogg_serialno(
+    header: &[u8; HEADER_LENGTH],
+    page: &[u8],
+) -> Option<[u8; 2]> {
+    const CRC_POS: usize = 22;
+
+    let crc = page.get(CRC_POS..CRC_POS + 4)?;
+    let expected = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+
+    let mut head = *header;
+    head[12..16].fill(0);
+
+    let crc = [
+        &head[..],
+        &page[HEADER_LENGTH..CRC_POS],
+        &[0; 4],
+        &page[CRC_POS + 4..],
+    ]
+    .iter()
+    .fold(0, |crc, part| verify::ogg_crc_update(crc, part));
+
+    match verify::solve_ogg_crc(expected ^ crc, page.len() - HEADER_LENGTH) {
+        [0, 0, first, second] => Some([first, second]),
+        _ => None,
+    }
+}
+
+/// Types [`Decrypter::decrypt_auto`] tries, besides the declared one.
+const AUTO_FILE_TYPES: [FileType; 5] = [
+    FileType::PNG,
+    FileType::OGG,
+    FileType::M4A,
+    FileType::WEBP,
+    FileType::WAV,
+];
+
+/// Guesses the type of encrypted `payload` from its plain part, which starts at offset 16.
+fn detect_encrypted_type(payload: &[u8]) -> Option<FileType> {
+    const OGG_PAGE_HEADER_SIZE: usize = 27;
+
+    // The first page's segment table is plain, so it's enough to locate the second page.
+    if let Some(&segment_count) = payload.get(OGG_PAGE_HEADER_SIZE - 1) {
+        let segment_count = segment_count as usize;
+
+        if let Some(segment_table) = payload
+            .get(OGG_PAGE_HEADER_SIZE..OGG_PAGE_HEADER_SIZE + segment_count)
+        {
+            let page_end = OGG_PAGE_HEADER_SIZE
+                + segment_count
+                + segment_table
+                    .iter()
+                    .map(|&segment| segment as usize)
+                    .sum::<usize>();
+
+            if payload.get(page_end..page_end + 4) == Some(b"OggS") {
+                return Some(FileType::OGG);
+            }
+        }
+    }
+
+    // IHDR chunk data and its CRC are plain, and the chunk type is always the same.
+    if let (Some(ihdr), Some(crc)) = (payload.get(16..29), payload.get(29..33))
+    {
+        let mut chunk = [0; 17];
+        chunk[..4].copy_from_slice(b"IHDR");
+        chunk[4..].copy_from_slice(ihdr);
+
+        if verify::png_crc(&chunk).to_be_bytes() == crc {
+            return Some(FileType::PNG);
+        }
+    }
+
+    if payload.get(16..HEADER_LENGTH + 64).map_or(false, |start| {
+        start
+            .chunks_exact(4)
+            .any(|chunk| M4A_POST_HEADER_BOXES.contains(&chunk))
+    }) {
+        return Some(FileType::M4A);
+    }
+
+    // `fmt ` chunk size and audio format are plain, and take only a few well-known values.
+    if let (Some(size), Some(format)) =
+        (payload.get(16..20), payload.get(20..22))
+    {
+        let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]);
+        let format = u16::from_le_bytes([format[0], format[1]]);
+
+        if matches!(size, 16 | 18 | 40) && matches!(format, 1 | 3 | 0xFFFE) {
+            return Some(FileType::WAV);
+        }
+    }
+
+    // VP8X chunk is always 10 bytes long and is followed by another chunk, and VP8/VP8L chunks take the whole rest of the file.
+    if let Some(size) = payload.get(16..20) {
+        let size =
+            u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+        let next_chunk = payload.get(30..34);
+
+        if (size == 10
+            && next_chunk.map_or(false, |chunk| {
+                chunk
+                    .iter()
+                    .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ')
+            }))
+            || size
+                .checked_add(1)
+                .and_then(|size| (size / 2 * 2).checked_add(20))
+                == Some(payload.len())
+        {
+            return Some(FileType::WEBP);
+        }
+    }
+
+    None
+}
+
+/// Inspects asset `data` without fully decrypting it.
+///
+/// For encrypted data, the file type is determined from the plain part of the data, and then the key is derived from it.
+/// Only the first 80 bytes are inspected, besides OGG, whose first page must be present, and if it isn't standard, the header of the second one too.
+///
+/// # Parameters
+///
+/// - `data` - The data of RPG Maker file, either encrypted or not.
+///
+/// # Returns
+///
+/// - [`AssetInfo`] describing the data.
+/// - [`Error`] if encrypted data ends unexpectedly, or has too many layers.
+///
+/// # Errors
+///
+/// - [`Error::UnexpectedEOF`] - if `data` is encrypted, and has no payload after the RPG Maker header.
+/// - [`Error::TooManyLayers`] - if `data` has more than [`MAX_LAYERS`] encryption layers.
+pub fn inspect(data: &[u8]) -> Result<AssetInfo, Error> {
+    let state = AssetState::detect(data);
+
+    let mut info = AssetInfo {
+        state,
+        layers: 0,
+        file_type: None,
+        key: None,
+        payload_length: data.len(),
+        dimensions: None,
+        serial_number: None,
+        brand: None,
+    };
+
+    let mut head = [0; HEADER_LENGTH * 2];
+
+    match state {
+        AssetState::Unknown => return Ok(info),
+        AssetState::Decrypted(file_type) => {
+            let length = data.len().min(head.len());
+            head[..length].copy_from_slice(&data[..length]);
+            info.file_type = Some(file_type);
+        }
+        AssetState::Encrypted => {
+            let mut payload = &data[HEADER_LENGTH..];
+
+            if payload.is_empty() {
+                return Err(Error::UnexpectedEOF);
+            }
+
+            info.layers = 1;
+            let mut decrypter = Decrypter::new();
+
+            // Layers of repeated encryption are peeled, until the innermost asset is reached.
+            for file_type in AUTO_FILE_TYPES {
+                if let Some((key, layers)) =
+                    decrypter.nested_layers(payload, file_type)?
+                {
+                    decrypter.set_key(EncryptionKey::from_bytes(key));
+                    info.file_type = Some(file_type);
+                    info.layers += layers;
+                    payload = &payload[layers * HEADER_LENGTH..];
+                    break;
+                }
+            }
+
+            info.payload_length = payload.len();
+
+            if !decrypter.has_key {
+                info.file_type = detect_encrypted_type(payload);
+
+                let Some(file_type) = info.file_type else {
+                    return Ok(info);
+                };
+
+                if decrypter.set_key_from_payload(payload, file_type).is_err() {
+                    return Ok(info);
+                }
+            }
+
+            info.key = decrypter.encryption_key();
+
+            let length = payload.len().min(head.len());
+            head[..length].copy_from_slice(&payload[..length]);
+            decrypter.xor_buffer(&mut head);
+        }
+    }
+
+    match info.file_type {
+        Some(FileType::PNG) if info.payload_length >= 24 => {
+            let width = [head[16], head[17], head[18], head[19]];
+            let height = [head[20], head[21], head[22], head[23]];
+            info.dimensions =
+                Some((u32::from_be_bytes(width), u32::from_be_bytes(height)));
+        }
+        Some(FileType::OGG) if info.payload_length >= 18 => {
+            let serial_number = [head[14], head[15], head[16], head[17]];
+            info.serial_number = Some(u32::from_le_bytes(serial_number));
+        }
+        Some(FileType::M4A) if info.payload_length >= 12 => {
+            info.brand = Some([head[8], head[9], head[10], head[11]]);
+        }
+        _ => {}
+    }
+
+    Ok(info)
+}
+
+/// Returns whether `data` starts with the RPG Maker encryption header (see [`RPGM_HEADER`]).
+///
+/// Only the first 16 bytes are inspected, so it's cheap enough to call for every file of an asset browser. Use [`inspect`] to also learn the type of the data.
+#[must_use]
+pub fn is_encrypted_data(data: &[u8]) -> bool {
+    data.starts_with(RPGM_HEADER)
+}
+
+/// Checks that `data` starts with the header expected by `magic`.
+pub(crate) fn check_magic(data: &[u8], magic: Magic) -> Result<(), Error> {
+    let expected = match magic {
+        Magic::Standard => Some(RPGM_HEADER),
+        Magic::Custom(ref expected) => Some(&expected[..]),
+        Magic::Any => None,
+    };
+
+    if expected.map_or(false, |expected| data.starts_with(expected)) {
+        return Ok(());
+    }
+
+    if let Some(file_type) = FileType::detect(data) {
+        return Err(Error::AlreadyDecrypted(file_type));
+    }
+
+    let Some(header) = data.get(..HEADER_LENGTH) else {
+        return Err(Error::InvalidHeader);
+    };
+
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    let mut expected_header = [0; HEADER_LENGTH];
+    expected_header.copy_from_slice(expected);
+    let mut got = [0; HEADER_LENGTH];
+    got.copy_from_slice(header);
+
+    Err(Error::InvalidMagic {
+        expected: expected_header,
+        got,
+    })
+}
+
+/// Header [`Decrypter`] expects encrypted files to start with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+enum Magic {
+    /// [`RPGM_HEADER`].
+    #[default]
+    Standard,
+    /// Header of a forked engine.
+    Custom([u8; HEADER_LENGTH]),
+    /// Any 16 bytes.
+    Any,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    KeyNotSet,
+    InvalidKeyLength,
+    InvalidKey,
+    MalformedKey {
+        position: usize,
+        reason: &'static str,
+    },
+    InvalidHeader,
+    InvalidMagic {
+        expected: [u8; HEADER_LENGTH],
+        got: [u8; HEADER_LENGTH],
+    },
+    AlreadyDecrypted(FileType),
+    UnexpectedEOF,
+    Malformed {
+        offset: usize,
+        reason: &'static str,
+    },
+    TypeMismatch {
+        declared: FileType,
+        detected: FileType,
+    },
+    Timeout,
+    InvalidJson,
+    UnsupportedFileType,
+    KeyMismatch {
+        expected_signature: [u8; HEADER_LENGTH],
+        got: [u8; HEADER_LENGTH],
+    },
+    PatchMismatch,
+    /// Data is an encrypted asset encrypted once more with the same key, see [`Decrypter::decrypt_recursive`].
+    DoubleEncrypted,
+    /// Data has more than [`MAX_LAYERS`] encryption layers, e.g. it's zero-filled, so every block looks like another layer.
+    TooManyLayers,
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::KeyNotSet => f.write_str(
+                "Key must be set using any of `set_key` methods before calling `encrypt` function.",
+            ),
+            Self::InvalidKeyLength => {
+                f.write_str("Key must have a fixed length of 32 characters.")
+            }
+            Self::InvalidKey => {
+                f.write_str("Key must only contain hexadecimal characters.")
+            }
+            Self::MalformedKey { position, reason } => write!(
+                f,
+                "Key is malformed at position {position}: {reason}."
+            ),
+            Self::InvalidHeader => f.write_str(
+                "Passed data has invalid header. RPG Maker encrypted files should always start with RPGMV header. Either passed data is not RPG Maker data or it's corrupted.",
+            ),
+            Self::InvalidMagic { expected, got } => write!(
+                f,
+                "Passed data starts with {got:02x?} instead of the expected header {expected:02x?}. Either passed data is not RPG Maker data or it's corrupted."
+            ),
+            Self::AlreadyDecrypted(file_type) => write!(
+                f,
+                "Passed data is not encrypted, it's already a plain {file_type} file."
+            ),
+            Self::UnexpectedEOF => f.write_str(
+                "Unexpected end of file encountered. Either passed data is not RPG Maker data or it's corrupted.",
+            ),
+            Self::Malformed { offset, reason } => write!(
+                f,
+                "Malformed data at offset {offset}: {reason}. Either passed data is not of the declared type or it's corrupted."
+            ),
+            Self::TypeMismatch { declared, detected } => write!(
+                f,
+                "File extension declares {declared}, but its content is {detected}."
+            ),
+            Self::Timeout => f.write_str("Operation timed out."),
+            Self::InvalidJson => f.write_str(
+                "Passed data is not a valid JSON object. Either it's not a `System.json` file or it's corrupted.",
+            ),
+            Self::UnsupportedFileType => f.write_str(
+                "File extension is not one of supported asset extensions.",
+            ),
+            Self::KeyMismatch {
+                expected_signature,
+                got,
+            } => write!(
+                f,
+                "Decrypted header {got:02x?} doesn't match the expected signature {expected_signature:02x?}. The key is most likely wrong."
+            ),
+            Self::PatchMismatch => f.write_str(
+                "Patch doesn't match the file it's applied to. Either it was created for another file, or it's corrupted.",
+            ),
+            Self::DoubleEncrypted => f.write_str(
+                "Passed data is encrypted more than once: it decrypts to another RPG Maker header. Use `decrypt_recursive` to remove all layers.",
+            ),
+            Self::TooManyLayers => write!(
+                f,
+                "Passed data has more than {MAX_LAYERS} encryption layers. Either passed data is not RPG Maker data or it's corrupted."
+            ),
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl Error {
+    /// Returns the byte offset in the passed data, at which the failure occurred, if it's known.
+    ///
+    /// Offsets are relative to the start of the file, including its RPG Maker header.
+    #[must_use]
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::InvalidMagic { .. } => Some(0),
+            Self::KeyMismatch { .. } | Self::DoubleEncrypted => {
+                Some(HEADER_LENGTH)
+            }
+            Self::Malformed { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the failure is specific to the attempt, rather than to the data itself, so retrying, possibly with another key or file type, may succeed.
+    ///
+    /// Batch tools can use it to tell files worth retrying from broken ones.
+    #[must_use]
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::KeyNotSet
+            | Self::KeyMismatch { .. }
+            | Self::TypeMismatch { .. }
+            | Self::AlreadyDecrypted(_)
+            | Self::DoubleEncrypted
+            | Self::Timeout => true,
+            #[cfg(feature = "std")]
+            Self::Io(err) => matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+            ),
+            _ => false,
+        }
+    }
+
+    /// Shifts the offset of the error by `by` bytes, for errors produced while parsing a part of the data.
+    pub(crate) fn offset_by(self, by: usize) -> Self {
+        match self {
+            Self::Malformed { offset, reason } => Self::Malformed {
+                offset: offset + by,
+                reason,
+            },
+            err => err,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Encryption key of RPG Maker MV/MZ assets.
+///
+/// Holds the 16 raw key bytes, and displays and parses as the 32-character hex string used in `System.json`.
+/// [`core::fmt::Debug`] output only shows [`EncryptionKey::fingerprint`], so keys don't leak into logs; use [`Display`] to print the key itself.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EncryptionKey([u8; KEY_LENGTH]);
+
+impl core::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "EncryptionKey(#{})", self.fingerprint())
+    }
+}
+
+impl EncryptionKey {
+    /// Creates a key from raw bytes.
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; KEY_LENGTH]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parses a key from 32-character hex string, e.g. `encryptionKey` field of `System.json`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidKeyLength`] - if `key`'s length is not 32 bytes.
+    /// - [`Error::InvalidKey`] - if `key` contains non-hex characters.
+    pub fn from_hex_str(key: &str) -> Result<Self, Error> {
+        if key.len() != KEY_STR_LENGTH {
+            return Err(Error::InvalidKeyLength);
+        }
+
+        let mut bytes = [0; KEY_LENGTH];
+
+        for (byte, pair) in bytes.iter_mut().zip(key.as_bytes().chunks(2)) {
+            let pair =
+                core::str::from_utf8(pair).map_err(|_| Error::InvalidKey)?;
+            *byte =
+                u8::from_str_radix(pair, 16).map_err(|_| Error::InvalidKey)?;
+        }
+
+        Ok(Self(bytes))
+    }
+
+    /// Parses a key the way users paste it, e.g. `"150F15E73422E0A5BA5B59F997FC2350"\n` copied out of `System.json`.
+    ///
+    /// Surrounding whitespace and a single pair of surrounding quotes (`"` or `'`) are trimmed, and hex digits may be of any case.
+    /// Unlike [`EncryptionKey::from_hex_str`], errors point at the exact malformed part of `key`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::MalformedKey`] - if `key` is empty, has an unmatched quote, contains a non-hex character, or doesn't have exactly 32 hex digits. `position` is the byte offset in `key` of the offending part.
+    pub fn parse_lossy(key: &str) -> Result<Self, Error> {
+        let malformed =
+            |position: usize, reason| Error::MalformedKey { position, reason };
+        // Every part is a subslice of `key`, so its position is the distance between their starts.
+        let position =
+            |part: &str| part.as_ptr() as usize - key.as_ptr() as usize;
+
+        let mut hex = key.trim();
+
+        if let Some(quote) =
+            hex.chars().next().filter(|c| matches!(c, '"' | '\''))
+        {
+            if hex.len() < 2 || !hex.ends_with(quote) {
+                return Err(malformed(
+                    position(hex),
+                    "unmatched opening quote",
+                ));
+            }
+
+            hex = hex[1..hex.len() - 1].trim();
+        } else if hex.ends_with(['"', '\'']) {
+            return Err(malformed(
+                position(hex) + hex.len() - 1,
+                "unmatched closing quote",
+            ));
+        }
+
+        let start = position(hex);
+
+        if hex.is_empty() {
+            return Err(malformed(start, "key is empty"));
+        }
+
+        if let Some(index) = hex.find(|c: char| !c.is_ascii_hexdigit()) {
+            return Err(malformed(start + index, "not a hex digit"));
+        }
+
+        if hex.len() < KEY_STR_LENGTH {
+            return Err(malformed(
+                start + hex.len(),
+                "key is shorter than 32 hex digits",
+            ));
+        }
+
+        if hex.len() > KEY_STR_LENGTH {
+            return Err(malformed(
+                start + KEY_STR_LENGTH,
+                "key is longer than 32 hex digits",
+            ));
+        }
+
+        Self::from_hex_str(hex)
+    }
+
+    /// Extracts a key from MV/MZ `System.json` data.
+    ///
+    /// See [`system_json::extract_key_from_system_json`].
+    ///
+    /// # Errors
+    ///
+    /// - Any error of [`system_json::extract_key_from_system_json`].
+    /// - [`Error::InvalidKey`] - if `encryptionKey` contains non-hex characters.
+    pub fn from_system_json(data: &[u8]) -> Result<Self, Error> {
+        Self::from_hex_str(
+            &system_json::extract_key_from_system_json(data)?.key,
+        )
+    }
+
+    /// Returns raw key bytes.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; KEY_LENGTH] {
+        &self.0
+    }
+
+    /// Returns the key as a lowercase hex string.
+    #[must_use]
+    pub fn to_hex(&self) -> [u8; KEY_STR_LENGTH] {
+        let mut hex = [0; KEY_STR_LENGTH];
+
+        for (i, value) in self.0.iter().enumerate() {
+            hex[i * 2] = HEX_CHARS[(value >> 4) as usize];
+            hex[i * 2 + 1] = HEX_CHARS[(value & 0x0F) as usize];
+        }
+
+        hex
+    }
+
+    /// Returns a short identifier of the key: the first 6 hex characters of its FNV-1a hash.
+    ///
+    /// It tells keys apart in logs and UIs, e.g. which of the known keys a file was decrypted with, but the key can't be recovered from it.
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        let hash =
+            self.0
+                .iter()
+                .fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+                    (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+                });
+        format!("{:06x}", hash >> 40)
+    }
+}
+
+impl Display for EncryptionKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(hex_str(&self.to_hex()))
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex_str(s)
+    }
+}
+
+impl From<[u8; KEY_LENGTH]> for EncryptionKey {
+    fn from(bytes: [u8; KEY_LENGTH]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Options of [`Decrypter::encrypt_with_options`] and [`Decrypter::decrypt_with_options`], which obfuscate encrypted files beyond the standard RPG Maker format.
+///
+/// Files encrypted with non-default options can't be read by RPG Maker itself, or by tools unaware of the options, so the game must decrypt them with the same options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EncryptOptions {
+    /// Header written instead of [`RPGM_HEADER`].
+    pub custom_magic: Option<[u8; HEADER_LENGTH]>,
+    /// Fake signature written between the header and the encrypted data, so the key derived from it is wrong.
+    pub fake_signature: Option<[u8; HEADER_LENGTH]>,
+}
+
+impl EncryptOptions {
+    /// Creates default options, which produce standard RPG Maker files.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [`EncryptOptions::custom_magic`].
+    #[must_use]
+    pub fn custom_magic(mut self, magic: [u8; HEADER_LENGTH]) -> Self {
+        self.custom_magic = Some(magic);
+        self
+    }
+
+    /// Sets [`EncryptOptions::fake_signature`].
+    #[must_use]
+    pub fn fake_signature(mut self, signature: [u8; HEADER_LENGTH]) -> Self {
+        self.fake_signature = Some(signature);
+        self
+    }
+
+    /// Returns the header written in front of encrypted data.
+    fn magic(&self) -> &[u8] {
+        match &self.custom_magic {
+            Some(magic) => magic,
+            None => RPGM_HEADER,
+        }
+    }
+
+    /// Returns the length of data preceding encrypted data.
+    fn prefix_length(&self) -> usize {
+        HEADER_LENGTH
+            + if self.fake_signature.is_some() {
+                HEADER_LENGTH
+            } else {
+                0
+            }
+    }
+}
+
+/// Encrypts assets with a fixed key.
+///
+/// Unlike [`Decrypter`], the key is required at construction, so encryption never fails with [`Error::KeyNotSet`].
+///
+/// # Example
+///
+/// ```
+/// use rpgm_asset_decrypter_core::{EncryptionKey, Encrypter, Engine, FileType};
+///
+/// let key: EncryptionKey = "d41d8cd98f00b204e9800998ecf8427e".parse().unwrap();
+/// let encrypter = Encrypter::with_key(key).engine(Engine::Mz);
+///
+/// let encrypted = encrypter.encrypt(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+/// assert_eq!(encrypter.encrypted_extension(FileType::PNG), "png_");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encrypter {
+    key: EncryptionKey,
+    engine: Engine,
+    options: EncryptOptions,
+}
+
+impl Encrypter {
+    /// Creates a new encrypter with `key`, producing [`Engine::Mv`] files with default [`EncryptOptions`].
+    #[must_use]
+    pub fn with_key(key: EncryptionKey) -> Self {
+        Self {
+            key,
+            engine: Engine::Mv,
+            options: EncryptOptions::default(),
+        }
+    }
+
+    /// Sets the engine, whose extensions are returned from [`Encrypter::encrypted_extension`].
+    #[must_use]
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Sets [`EncryptOptions`] to obfuscate output with.
+    #[must_use]
+    pub fn options(mut self, options: EncryptOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the encrypter's key.
+    #[must_use]
+    pub fn key(&self) -> EncryptionKey {
+        self.key
+    }
+
+    /// Returns the extension of encrypted `file_type` for the encrypter's engine.
+    #[must_use]
+    pub fn encrypted_extension(&self, file_type: FileType) -> &'static str {
+        encrypted_extension_for(file_type, self.engine)
+    }
+
+    /// Encrypts file content, and returns encrypted copy, including the header.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of `.png`, `.ogg`, `.m4a`, `.webp` or `.wav` file.
+    #[must_use]
+    pub fn encrypt(&self, file_content: &[u8]) -> Vec<u8> {
+        let mut output_data = Vec::with_capacity(
+            self.options.prefix_length() + file_content.len(),
+        );
+        output_data.extend(self.options.magic());
+
+        if let Some(signature) = &self.options.fake_signature {
+            output_data.extend(signature);
+        }
+
+        let data_start = output_data.len();
+        output_data.extend(file_content);
+        self.encrypt_in_place(&mut output_data[data_start..]);
+        output_data
+    }
+
+    /// Encrypts file content in-place.
+    ///
+    /// # Note
+    ///
+    /// Encrypted data comes without the header, so you need to manually prepend it. With default [`EncryptOptions`], the header is [`RPGM_HEADER`].
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of `.png`, `.ogg`, `.m4a`, `.webp` or `.wav` file.
+    pub fn encrypt_in_place(&self, file_content: &mut [u8]) {
+        for (byte, key) in file_content.iter_mut().zip(self.key.as_bytes()) {
+            *byte ^= key;
+        }
+    }
+
+    /// Encrypts many files, returning encrypted copies, including the header, in the same order.
+    ///
+    /// # Parameters
+    ///
+    /// - `files` - The data of `.png`, `.ogg`, `.m4a`, `.webp` or `.wav` files, e.g. `&[Vec<u8>]`.
+    #[must_use]
+    pub fn encrypt_many<I>(&self, files: I) -> Vec<Vec<u8>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        files
+            .into_iter()
+            .map(|file| self.encrypt(file.as_ref()))
+            .collect()
+    }
+}
+
+/// [`core::fmt::Debug`] output is redacted: it shows [`EncryptionKey::fingerprint`] of the key, but neither the key nor [`Decrypter::last_header_mask`], which the key can be derived from.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+pub struct Decrypter {
+    key_hex: [u8; KEY_STR_LENGTH],
+    key: [u8; KEY_LENGTH],
+    has_key: bool,
+    /// Encrypted first block of the last decrypted file, see [`Decrypter::last_header_mask`].
+    header_mask: Option<[u8; HEADER_LENGTH]>,
+    magic: Magic,
+}
+
+impl core::fmt::Debug for Decrypter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Decrypter")
+            .field("key", &self.encryption_key())
+            .field("expected_magic", &self.magic)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Decrypter {
+    /// Creates a new Decrypter instance.
+    ///
+    /// Decrypter requires a key, which you can set from [`Decrypter::set_key_from_str`] and [`Decrypter::set_key_from_file`] functions.
+    /// You can get the key string from `encryptionKey` field in `System.json` file or from any encrypted RPG Maker file.
+    ///
+    /// [`Decrypter::decrypt`] function will automatically determine the key from the input file, so you usually don't need to set it manually.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    /// Sets the real key bytes, and converts them to human-readable hex.
+    fn set_key_from_bytes(&mut self, key: &[u8; KEY_LENGTH]) {
+        self.key_hex = EncryptionKey::from_bytes(*key).to_hex();
+        self.key = *key;
+        self.has_key = true;
+    }
+
+    #[inline]
+    /// Either decrypts or encrypts the passed buffer, depending on the place this function was invoked from.
+    ///
+    /// Actual encryption is just: xor buffer's first 16 bytes with key.
+    fn xor_buffer(&self, buffer: &mut [u8]) {
+        for (i, item) in buffer.iter_mut().enumerate().take(HEADER_LENGTH) {
+            *item ^= self.key[i];
+        }
+    }
+
+    /// Remembers the encrypted first block of `body`, the data past the RPG Maker header, before it's decrypted.
+    fn remember_mask(&mut self, body: &[u8]) {
+        let length = body.len().min(HEADER_LENGTH);
+
+        let mut mask = [0; HEADER_LENGTH];
+        mask[..length].copy_from_slice(&body[..length]);
+        self.header_mask = Some(mask);
+    }
+
+    /// Returns the encrypted first 16 bytes past the RPG Maker header of the last successfully decrypted file, which decryption replaces with plain ones.
+    ///
+    /// Together with the decrypted data, they are the exact pre-image of the file, which forensics and byte-exact round-trip tooling need, e.g. to pass to [`encrypt_with_original_header`].
+    /// Bytes past the end of files shorter than 16 bytes are zero. Set by every decryption function of [`Decrypter`], including [`Decrypter::decrypt_header_only`].
+    ///
+    /// # Returns
+    ///
+    /// - Encrypted first block of the last decrypted file.
+    /// - [`None`], if nothing was decrypted yet.
+    #[inline]
+    #[must_use]
+    pub fn last_header_mask(&self) -> Option<[u8; HEADER_LENGTH]> {
+        self.header_mask
+    }
+
+    /// Returns the key of `payload`, the encrypted data past the RPG Maker header, if it's an encrypted asset of `file_type` itself, encrypted once or more with the same key, and the number of these extra layers.
+    ///
+    /// The first block of such payload decrypts to the expected magic header, which gives the key, and the next one to the signature of `file_type`, which confirms it. If there are more layers, the next blocks decrypt to the magic header again, until the one with the signature, so e.g. zeroed blocks aren't taken for layers.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::TooManyLayers`] - if the payload has more than [`MAX_LAYERS`] layers in total.
+    fn nested_layers(
+        &self,
+        payload: &[u8],
+        file_type: FileType,
+    ) -> Result<Option<([u8; KEY_LENGTH], usize)>, Error> {
+        let Some(magic) = self.expected_magic() else {
+            return Ok(None);
+        };
+        let (signature, mask) = signature_of(file_type);
+
+        // Without reliable signature bytes, any data would match.
+        if mask == 0 {
+            return Ok(None);
+        }
+
+        let Some(outer) = payload.get(..HEADER_LENGTH) else {
+            return Ok(None);
+        };
+
+        let mut key = [0; KEY_LENGTH];
+
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = outer[i] ^ magic[i];
+        }
+
+        let decrypts_to = |block: &[u8], expected: &[u8], mask: u16| {
+            (0..HEADER_LENGTH).all(|i| {
+                mask & (1 << i) == 0 || block[i] ^ key[i] == expected[i]
+            })
+        };
+
+        // The outer header and the first block of the payload are layers already.
+        for layers in 1..MAX_LAYERS {
+            let Some(inner) = payload
+                .get(layers * HEADER_LENGTH..(layers + 1) * HEADER_LENGTH)
+            else {
+                return Ok(None);
+            };
+
+            if decrypts_to(inner, &signature, mask) {
+                return Ok(Some((key, layers)));
+            }
+
+            if !decrypts_to(inner, &magic, 0xFFFF) {
+                return Ok(None);
+            }
+        }
+
+        Err(Error::TooManyLayers)
+    }
+
+    /// Checks that `body`, the encrypted data past the RPG Maker header, decrypts with the current key to the signature of `file_type`.
+    ///
+    /// Only the bytes of the signature that are the same in every file of the type are compared.
+    fn check_key(&self, body: &[u8], file_type: FileType) -> Result<(), Error> {
+        let length = body.len().min(HEADER_LENGTH);
+
+        let mut got = [0; HEADER_LENGTH];
+        got[..length].copy_from_slice(&body[..length]);
+        self.xor_buffer(&mut got[..length]);
+
+        let (expected_signature, mask) = signature_of(file_type);
+
+        if (0..length)
+            .any(|i| mask & (1 << i) != 0 && got[i] != expected_signature[i])
+        {
+            if self.expected_magic() == Some(got) {
+                return Err(Error::DoubleEncrypted);
+            }
+
+            return Err(Error::KeyMismatch {
+                expected_signature,
+                got,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reads the bitstream serial number of the OGG page at `offset` in `data`, and advances `offset` past the page.
+    fn read_ogg_page_serialno(
+        data: &[u8],
+        offset: &mut usize,
+    ) -> Result<u32, Error> {
+        const HEADER_SIZE: usize = 27;
+        const SERIALNO_POS: usize = 14;
+
+        let Some(header) = offset
+            .checked_add(HEADER_SIZE)
+            .and_then(|header_end| data.get(*offset..header_end))
+        else {
+            return Err(Error::UnexpectedEOF);
+        };
+
+        // Capture pattern of the first page is encrypted along with the rest of the header, so only the following ones can be checked.
+        if *offset != 0 && !header.starts_with(b"OggS") {
+            return Err(Error::Malformed {
+                offset: *offset,
+                reason: "expected OGG page",
+            });
+        }
+
+        let segment_count = header[26] as usize;
+        let segment_table_start = *offset + HEADER_SIZE;
+
+        let Some(segment_table) =
+            data.get(segment_table_start..segment_table_start + segment_count)
+        else {
+            return Err(Error::UnexpectedEOF);
+        };
+
+        let body_length: usize =
+            segment_table.iter().map(|&segment| segment as usize).sum();
+
+        *offset = segment_table_start + segment_count + body_length;
+
+        let mut serialno = [0; sizeof!(u32)];
+        serialno.copy_from_slice(
+            &header[SERIALNO_POS..SERIALNO_POS + sizeof!(u32)],
+        );
+
+        Ok(u32::from_le_bytes(serialno))
+    }
+
+    /// Returns the decrypter's key, or [`None`] if it's not set.
+    #[inline]
+    #[must_use]
+    pub fn key(&self) -> Option<&str> {
+        if !self.has_key {
+            return None;
+        }
+
+        Some(hex_str(&self.key_hex))
+    }
+
+    /// Returns the decrypter's key as [`EncryptionKey`], or [`None`] if it's not set.
+    #[inline]
+    #[must_use]
+    pub fn encryption_key(&self) -> Option<EncryptionKey> {
+        self.has_key.then(|| EncryptionKey::from_bytes(self.key))
+    }
+
+    /// Sets the decrypter's key.
+    #[inline]
+    pub fn set_key(&mut self, key: EncryptionKey) {
+        self.set_key_from_bytes(key.as_bytes());
+    }
+
+    /// Sets the decrypter's key to provided `&str` hex string.
+    ///
+    /// # Returns
+    ///
+    /// If key's length is not 32 bytes, the function fails and returns [`Error`].
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidKeyLength`] - if key's length is not 32 bytes.
+    /// - [`Error::InvalidKey`] - if key contains non-hex characters.
+    #[inline]
+    pub fn set_key_from_str(&mut self, key: &str) -> Result<(), Error> {
+        self.set_key(EncryptionKey::from_hex_str(key)?);
+        Ok(())
+    }
+
+    /// Clears the decrypter's key, so the next decryption determines it from the input file again, just like with a new decrypter.
+    ///
+    /// Key bytes are overwritten with zeros, and [`Decrypter::last_header_mask`] is cleared too. Other settings, e.g. [`Decrypter::set_expected_magic`], are kept.
+    #[inline]
+    pub fn clear_key(&mut self) {
+        self.key = [0; KEY_LENGTH];
+        self.key_hex = [0; KEY_STR_LENGTH];
+        self.has_key = false;
+        self.header_mask = None;
+    }
+
+    /// Resets the decrypter to the state of [`Decrypter::new`], so a long-lived instance can be reused for another game.
+    ///
+    /// Same as [`Decrypter::clear_key`], but also restores all other settings to their defaults.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.clear_key();
+        *self = Self::default();
+    }
+
+    /// Sets the magic header encrypted files are expected to start with, instead of [`RPGM_HEADER`].
+    ///
+    /// Some forked engines, e.g. Pixel Game Maker and several MV forks, replace `RPGMV` signature with their own, but encrypt files the same way.
+    /// Files encrypted by the decrypter start with `magic` too.
+    #[inline]
+    pub fn set_expected_magic(&mut self, magic: [u8; HEADER_LENGTH]) {
+        self.magic = Magic::Custom(magic);
+    }
+
+    /// Makes the decrypter accept encrypted files with any 16-byte header.
+    ///
+    /// Plain files are still rejected with [`Error::AlreadyDecrypted`], but any other data is treated as encrypted, so it should only be used on files which are known to be encrypted.
+    /// Files encrypted by the decrypter start with [`RPGM_HEADER`].
+    #[inline]
+    pub fn accept_any_magic(&mut self) {
+        self.magic = Magic::Any;
+    }
+
+    /// Returns the magic header encrypted files are expected to start with, or [`None`] if any header is accepted, see [`Decrypter::accept_any_magic`].
+    #[inline]
+    #[must_use]
+    pub fn expected_magic(&self) -> Option<[u8; HEADER_LENGTH]> {
+        match self.magic {
+            Magic::Standard => {
+                let mut magic = [0; HEADER_LENGTH];
+                magic.copy_from_slice(RPGM_HEADER);
+                Some(magic)
+            }
+            Magic::Custom(magic) => Some(magic),
+            Magic::Any => None,
+        }
+    }
+
+    /// Returns the header written in front of encrypted data.
+    fn header(&self) -> &[u8] {
+        match &self.magic {
+            Magic::Custom(magic) => magic,
+            Magic::Standard | Magic::Any => RPGM_HEADER,
+        }
+    }
+
+    /// Sets the key of decrypter from encrypted `file_content` data.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    ///
+    /// # Returns
+    ///
+    /// - Reference to the key string, if succeeded.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    /// - [`Error::KeyNotSet`] - if `file_type` is [`FileType::JSON`], which has no known plaintext.
+    #[inline]
+    pub fn set_key_from_file(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+    ) -> Result<&str, Error> {
+        check_magic(file_content, self.magic)?;
+        self.set_key_from_payload(&file_content[HEADER_LENGTH..], file_type)
+            .map_err(|err| err.offset_by(HEADER_LENGTH))?;
+        Ok(hex_str(&self.key_hex))
+    }
+
+    /// Sets the key of decrypter from encrypted `payload`, which is the data of RPG Maker file past its header.
+    ///
+    /// Offsets of returned errors are relative to `payload`.
+    fn set_key_from_payload(
+        &mut self,
+        payload: &[u8],
+        file_type: FileType,
+    ) -> Result<(), Error> {
+        if file_type.is_json() {
+            return Err(Error::KeyNotSet);
+        }
+
+        if self.nested_layers(payload, file_type)?.is_some() {
+            return Err(Error::DoubleEncrypted);
+        }
+
+        let Some(post_header) = payload.get(..HEADER_LENGTH) else {
+            return Err(Error::UnexpectedEOF);
+        };
+
+        // Header templates are constant, per-file parts are filled in this local copy.
+        let (mut signature, _) = signature_of(file_type);
+
+        // Get proper M4A header box size, and reconstruct the brand from the plain part of `ftyp` box.
+        //* Players don't care about anything else for M4A, since `ftypM4A_` in M4A header can be easily replaced by `ftypSHIT`, and FFmpeg will have ZERO complains.
+        //* The same goes for 12-15 bytes (inclusive), they can be overwritten with whatever integer. But byte-exact round trips need the original bytes.
+        if file_type.is_m4a() {
+            const CHUNK_SIZE: usize = sizeof!(u32);
+
+            let Some(file_start) = payload.get(..64) else {
+                return Err(Error::UnexpectedEOF);
+            };
+
+            let file_start_chunks = file_start.chunks_exact(CHUNK_SIZE);
+
+            for (i, chunk) in file_start_chunks.enumerate() {
+                if M4A_POST_HEADER_BOXES.contains(&chunk) {
+                    let Some(prev_chunk_i) = i.checked_sub(1) else {
+                        return Err(Error::Malformed {
+                            offset: 0,
+                            reason: "expected M4A box size",
+                        });
+                    };
+
+                    let header_type_box_size =
+                        (prev_chunk_i * CHUNK_SIZE) as u32;
+
+                    signature[..CHUNK_SIZE]
+                        .copy_from_slice(&header_type_box_size.to_be_bytes());
+
+                    if let Some((brand, minor_version)) = m4a_brand(
+                        payload.get(HEADER_LENGTH..prev_chunk_i * CHUNK_SIZE),
+                    ) {
+                        signature[8..12].copy_from_slice(&brand);
+                        signature[12..16]
+                            .copy_from_slice(&minor_version.to_be_bytes());
+                    }
+
+                    // The first box after `ftyp` determines its size, later ones are irrelevant.
+                    break;
+                }
+            }
+        }
+
+        // Since stream serial number is incorrect in OGG_HEADER because it's different for each file, we need to recover its encrypted part and replace it in the header.
+        // The first page's CRC is plain, so it's solved for the serial number, see `solve_ogg_serialno`. If the page isn't standard, the serial number is grabbed from the second page instead, since it's persistent across all pages of the stream.
+        if file_type.is_ogg() {
+            let mut offset = 0;
+
+            Decrypter::read_ogg_page_serialno(payload, &mut offset)?;
+
+            let serialno = if let Some(serialno) = payload
+                .get(..offset)
+                .and_then(|page| solve_ogg_serialno(&signature, page))
+            {
+                serialno
+            } else {
+                let serialno =
+                    Decrypter::read_ogg_page_serialno(payload, &mut offset)?;
+                let [first, second, ..] = serialno.to_le_bytes();
+                [first, second]
+            };
+
+            signature[14..16].copy_from_slice(&serialno);
+        }
+
+        // RIFF chunk size is the size of the whole decrypted file minus RIFF chunk header.
+        if file_type.is_wav() {
+            signature[4..8].copy_from_slice(&riff_size(payload)?.to_le_bytes());
+        }
+
+        // RIFF chunk size is the size of the whole decrypted file minus RIFF chunk header, and VP8 chunk type is determined by what follows the chunk.
+        if file_type.is_webp() {
+            const VP8X_CHUNK_SIZE: &[u8] = &[10, 0, 0, 0];
+            const VP8L_SIGNATURE: u8 = 0x2f;
+
+            let Some(chunk) = payload.get(HEADER_LENGTH..HEADER_LENGTH + 5)
+            else {
+                return Err(Error::UnexpectedEOF);
+            };
+
+            signature[4..8].copy_from_slice(&riff_size(payload)?.to_le_bytes());
+
+            signature[15] = if &chunk[..4] == VP8X_CHUNK_SIZE {
+                b'X'
+            } else if chunk[4] == VP8L_SIGNATURE {
+                b'L'
+            } else {
+                b' '
+            };
+        }
+
+        let mut key = [0; KEY_LENGTH];
+        for i in 0..HEADER_LENGTH {
+            key[i] = signature[i] ^ post_header[i];
+        }
+
+        self.set_key_from_bytes(&key);
+        Ok(())
+    }
+
+    /// Sets the decrypter's key from the plaintext passphrase, entered in "Encryption key" field of RPG Maker editor.
+    ///
+    /// RPG Maker derives the real key as MD5 hash of the passphrase, so this function does the same.
+    /// Empty passphrase produces [`DEFAULT_KEY`].
+    ///
+    /// # Returns
+    ///
+    /// - Reference to the derived key string.
+    #[cfg(feature = "md5")]
+    #[inline]
+    pub fn set_key_from_plaintext(&mut self, passphrase: &str) -> &str {
+        self.set_key_from_bytes(&md5::digest(passphrase.as_bytes()));
+        hex_str(&self.key_hex)
+    }
+
+    /// Decrypts only the first 16 bytes of RPG Maker file content, located right after the RPG Maker header.
+    /// Auto-determines the key from the input file, if it's not set.
+    ///
+    /// This is useful for quick type detection, dimension/duration probing and key verification over a lot of files, since the rest of the file body is never touched.
+    ///
+    /// # Note
+    ///
+    /// If the key is auto-determined from the passed file, the returned block always matches the expected signature of `file_type`.
+    /// To verify the key or detect the real file type, set the key beforehand.
+    /// Unlike [`Decrypter::decrypt`], this function never returns [`Error::KeyMismatch`], so the block can be inspected for any key.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file. Only the first 32 bytes are required.
+    /// - `file_type` - [`FileType`], representing the type of passed file content. Only used to determine the key, if it's not set.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header or ends unexpectedly.
+    /// - Decrypted first block of the file otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    #[inline]
+    pub fn decrypt_header_only(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+    ) -> Result<[u8; HEADER_LENGTH], Error> {
+        check_magic(file_content, self.magic)?;
+
+        if !self.has_key {
+            self.set_key_from_file(file_content, file_type)?;
+        }
+
+        let Some(post_header) =
+            file_content.get(HEADER_LENGTH..HEADER_LENGTH * 2)
+        else {
+            return Err(Error::UnexpectedEOF);
+        };
+
+        let mut block = [0; HEADER_LENGTH];
+        block.copy_from_slice(post_header);
+        self.header_mask = Some(block);
+        self.xor_buffer(&mut block);
+        Ok(block)
+    }
+
+    /// Decrypts RPG Maker file content.
+    /// Auto-determines the key from the input file.
+    ///
+    /// This function copies the contents of the file and returns decrypted [`Vec<u8>`] copy.
+    /// If you want to avoid copying, see [`Decrypter::decrypt_in_place`] function.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `file_type` - [`FileType`], representing the type of passed file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header.
+    /// - [`Vec<u8>`] containing decrypted data otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    /// - [`Error::KeyMismatch`] - if the key was set beforehand, and decrypted data doesn't start with the signature of `file_type`.
+    /// - [`Error::KeyNotSet`] - if the key wasn't set beforehand, and `file_type` is [`FileType::JSON`].
+    /// - [`Error::DoubleEncrypted`] - if passed `file_content` data is encrypted more than once, see [`Decrypter::decrypt_recursive`].
+    /// - [`Error::TooManyLayers`] - if passed `file_content` data looks like it has more than [`MAX_LAYERS`] encryption layers.
+    #[inline]
+    pub fn decrypt(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+    ) -> Result<Vec<u8>, Error> {
+        check_magic(file_content, self.magic)?;
+
+        if self.has_key {
+            self.check_key(&file_content[HEADER_LENGTH..], file_type)?;
+        } else {
+            self.set_key_from_file(file_content, file_type)?;
+        }
+
+        self.remember_mask(&file_content[HEADER_LENGTH..]);
+        let mut result = file_content[HEADER_LENGTH..].to_vec();
+        self.xor_buffer(&mut result);
+        Ok(result)
+    }
+
+    /// Same as [`Decrypter::decrypt`], but decrypts into a buffer acquired from `pool` instead of a freshly allocated one.
+    ///
+    /// Give the returned buffer back with [`pool::BufferPool::release`] once it's no longer needed. On error, nothing is acquired.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpgm_asset_decrypter_core::{Decrypter, Encrypter, FileType, DEFAULT_KEY};
+    /// use rpgm_asset_decrypter_core::pool::{BufferPool, VecPool};
+    ///
+    /// let encrypted = Encrypter::with_key(DEFAULT_KEY.parse().unwrap())
+    ///     .encrypt(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+    /// let pool = VecPool::new();
+    /// let mut decrypter = Decrypter::new();
+    ///
+    /// for _ in 0..3 {
+    ///     let decrypted = decrypter.decrypt_pooled(&encrypted, FileType::PNG, &pool).unwrap();
+    ///     assert!(decrypted.starts_with(b"\x89PNG"));
+    ///     pool.release(decrypted);
+    /// }
+    ///
+    /// assert_eq!(pool.len(), 1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Any error of [`Decrypter::decrypt`].
+    #[inline]
+    pub fn decrypt_pooled<P: pool::BufferPool + ?Sized>(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+        pool: &P,
+    ) -> Result<Vec<u8>, Error> {
+        check_magic(file_content, self.magic)?;
+
+        if self.has_key {
+            self.check_key(&file_content[HEADER_LENGTH..], file_type)?;
+        } else {
+            self.set_key_from_file(file_content, file_type)?;
+        }
+
+        let body = &file_content[HEADER_LENGTH..];
+        self.remember_mask(body);
+
+        let mut result = pool.acquire(body.len());
+        result.clear();
+        result.extend_from_slice(body);
+        self.xor_buffer(&mut result);
+        Ok(result)
+    }
+
+    /// Decrypts RPG Maker file content, borrowing from it when possible.
+    /// Auto-determines the key from the input file.
+    ///
+    /// XOR with a zero byte is a no-op, so if all key bytes applied to the content are zero, or the content has no payload at all, decrypted data is just a subslice of `file_content` and is returned as [`Cow::Borrowed`].
+    /// Otherwise, a single copy is made, like in [`Decrypter::decrypt`].
+    ///
+    /// Real keys are practically never zero, so for allocation-free access to arbitrary files, use [`Decrypter::decrypt_header_only`] together with `&file_content[32..]`, which is the rest of the data that is never encrypted.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `file_type` - [`FileType`], representing the type of passed file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header.
+    /// - [`Cow`] containing decrypted data otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    /// - [`Error::KeyMismatch`] - if the key was set beforehand, and decrypted data doesn't start with the signature of `file_type`.
+    #[inline]
+    pub fn decrypt_cow<'a>(
+        &mut self,
+        file_content: &'a [u8],
+        file_type: FileType,
+    ) -> Result<Cow<'a, [u8]>, Error> {
+        check_magic(file_content, self.magic)?;
+
+        if self.has_key {
+            self.check_key(&file_content[HEADER_LENGTH..], file_type)?;
+        } else {
+            self.set_key_from_file(file_content, file_type)?;
+        }
+
+        let body = &file_content[HEADER_LENGTH..];
+        let encrypted_length = body.len().min(HEADER_LENGTH);
+        self.remember_mask(body);
+
+        if self.key[..encrypted_length].iter().all(|&byte| byte == 0) {
+            return Ok(Cow::Borrowed(body));
+        }
+
+        let mut result = body.to_vec();
+        self.xor_buffer(&mut result);
+        Ok(Cow::Owned(result))
+    }
+
+    /// Decrypts RPG Maker file content, and also returns the original encrypted first 16 bytes of it.
+    /// Auto-determines the key from the input file.
+    ///
+    /// OGG and M4A headers are reconstructed heuristically, so the derived key may differ from the real one in a few bytes, and re-encrypting with the real key may not reproduce the original file.
+    /// Passing the returned bytes to [`encrypt_with_original_header`] reproduces the original encrypted file byte-for-byte, as long as the first 16 bytes of decrypted data weren't modified, which matters for patch/diff-based distribution.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `file_type` - [`FileType`], representing the type of passed file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header.
+    /// - [`Vec<u8>`] containing decrypted data, and the original encrypted bytes, padded with zeros if the data is shorter than 16 bytes.
+    ///
+    /// # Errors
+    ///
+    /// - Any error of [`Decrypter::decrypt`].
+    #[inline]
+    pub fn decrypt_with_original_header(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+    ) -> Result<(Vec<u8>, [u8; HEADER_LENGTH]), Error> {
+        let decrypted = self.decrypt(file_content, file_type)?;
+        Ok((decrypted, self.header_mask.unwrap_or_default()))
+    }
+
+    /// Decrypts RPG Maker file content, whose real type may differ from its extension.
+    /// Auto-determines the key from the input file.
+    ///
+    /// Every known type is tried, starting from the one guessed from the plain part of the data and the `declared` one, and each candidate output is validated with [`verify::validate`].
+    /// The first valid candidate wins. If none is valid, the one that is valid for the longest is returned.
+    /// Each candidate is a full copy of the data, so prefer [`Decrypter::decrypt`] when the type is trusted.
+    ///
+    /// If the key wasn't set beforehand, it's set to the key of the returned candidate.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `declared` - [`FileType`] declared by the file's extension, if any.
+    ///
+    /// # Returns
+    ///
+    /// - [`AutoDecrypted`] with the best matching candidate. If it doesn't match `declared`, [`AutoDecrypted::warning`] is set.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - Any error of [`Decrypter::decrypt`] - if no type could be decrypted at all.
+    pub fn decrypt_auto(
+        &mut self,
+        file_content: &[u8],
+        declared: Option<FileType>,
+    ) -> Result<AutoDecrypted, Error> {
+        check_magic(file_content, self.magic)?;
+
+        let mut candidates = Vec::with_capacity(AUTO_FILE_TYPES.len() + 2);
+        candidates.extend(
+            file_content
+                .get(HEADER_LENGTH..)
+                .and_then(detect_encrypted_type),
+        );
+        candidates.extend(declared);
+        candidates.extend(AUTO_FILE_TYPES);
+
+        let mut best_score = None;
+        let mut best = None;
+        let mut last_error = Error::UnsupportedFileType;
+
+        for (i, &file_type) in candidates.iter().enumerate() {
+            if candidates[..i].contains(&file_type) {
+                continue;
+            }
+
+            let mut decrypter = self.clone();
+
+            let data = match decrypter.decrypt(file_content, file_type) {
+                Ok(data) => data,
+                Err(err) => {
+                    last_error = err;
+                    continue;
+                }
+            };
+
+            // Ranked by validity, then by how far the data stays valid.
+            let score = match verify::validate(&data, file_type) {
+                verify::ValidationResult::Valid => (2, usize::MAX),
+                verify::ValidationResult::Unsupported => (1, 0),
+                verify::ValidationResult::Invalid { offset, .. } => (0, offset),
+            };
+
+            if best_score.map_or(true, |best_score| score > best_score) {
+                best_score = Some(score);
+                best = Some((decrypter, data, file_type));
+
+                if score.0 == 2 {
+                    break;
+                }
+            }
+        }
+
+        let Some((decrypter, data, file_type)) = best else {
+            return Err(last_error);
+        };
+
+        *self = decrypter;
+
+        Ok(AutoDecrypted {
+            data,
+            file_type,
+            warning: declared.filter(|&declared| declared != file_type).map(
+                |declared| Error::TypeMismatch {
+                    declared,
+                    detected: file_type,
+                },
+            ),
+        })
+    }
+
+    /// Decrypts RPG Maker file content.
+    /// Auto-determines the key from the input file.
+    ///
+    /// This function decrypts the passed file data in-place.
+    /// If you don't want to modify passed data, see [`Decrypter::decrypt`] function.
+    ///
+    /// # Note
+    ///
+    /// Decrypted data is only valid starting at offset 16. This function returns the reference to the correct slice.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `file_type` - [`FileType`], representing the type of passed file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header.
+    /// - Reference to a slice of the passed `file_content` data starting at offset 16 otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    /// - [`Error::KeyMismatch`] - if the key was set beforehand, and decrypted data doesn't start with the signature of `file_type`. Data is left untouched in this case.
+    /// - [`Error::DoubleEncrypted`] - if passed `file_content` data is encrypted more than once, see [`Decrypter::decrypt_recursive`]. Data is left untouched in this case.
+    /// - [`Error::TooManyLayers`] - if passed `file_content` data looks like it has more than [`MAX_LAYERS`] encryption layers.
+    #[inline]
+    pub fn decrypt_in_place<'a>(
+        &'a mut self,
+        file_content: &'a mut [u8],
+        file_type: FileType,
+    ) -> Result<&'a [u8], Error> {
+        check_magic(file_content, self.magic)?;
+
+        if self.has_key {
+            self.check_key(&file_content[HEADER_LENGTH..], file_type)?;
+        } else {
+            self.set_key_from_file(file_content, file_type)?;
+        }
+
+        self.remember_mask(&file_content[HEADER_LENGTH..]);
+        let sliced_past_header = &mut file_content[HEADER_LENGTH..];
+        self.xor_buffer(sliced_past_header);
+        Ok(sliced_past_header)
+    }
+
+    /// Decrypts RPG Maker file content, which may be encrypted more than once, e.g. by a broken pipeline that encrypted already encrypted files.
+    /// Auto-determines the key from the input file.
+    ///
+    /// Layers are peeled one by one, while the data decrypts to another RPG Maker header instead of the signature of `file_type` (see [`Error::DoubleEncrypted`]), so data encrypted once is decrypted the same way as by [`Decrypter::decrypt`].
+    /// All layers are expected to be encrypted with the same key, which is what such pipelines produce.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpgm_asset_decrypter_core::{Decrypter, Encrypter, Error, FileType, DEFAULT_KEY};
+    ///
+    /// let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    /// let encrypter = Encrypter::with_key(DEFAULT_KEY.parse().unwrap());
+    /// let twice = encrypter.encrypt(&encrypter.encrypt(png));
+    ///
+    /// let mut decrypter = Decrypter::new();
+    /// assert!(matches!(decrypter.decrypt(&twice, FileType::PNG), Err(Error::DoubleEncrypted)));
+    /// assert_eq!(decrypter.decrypt_recursive(&twice, FileType::PNG).unwrap(), png);
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of RPG Maker file.
+    /// - `file_type` - [`FileType`], representing the type of the innermost file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Error`], if passed `file_content` data has invalid header.
+    /// - [`Vec<u8>`] containing the innermost decrypted data otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - Any error of [`Decrypter::decrypt`], besides [`Error::DoubleEncrypted`].
+    /// - [`Error::TooManyLayers`] - if passed `file_content` data has more than [`MAX_LAYERS`] layers.
+    pub fn decrypt_recursive(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+    ) -> Result<Vec<u8>, Error> {
+        check_magic(file_content, self.magic)?;
+
+        let mut data = file_content.to_vec();
+        // Start of the current layer, which begins with the RPG Maker header.
+        let mut offset = 0;
+
+        for _ in 0..MAX_LAYERS {
+            match self.decrypt_in_place(&mut data[offset..], file_type) {
+                Ok(_) => {
+                    data.drain(..offset + HEADER_LENGTH);
+                    return Ok(data);
+                }
+                Err(Error::DoubleEncrypted) => {}
+                Err(err) => return Err(err),
+            }
+
+            let layer = &mut data[offset..];
+
+            if !self.has_key {
+                let (key, _) = self
+                    .nested_layers(&layer[HEADER_LENGTH..], file_type)?
+                    .ok_or(Error::DoubleEncrypted)?;
+                self.set_key(EncryptionKey::from_bytes(key));
+            }
+
+            self.xor_buffer(&mut layer[HEADER_LENGTH..]);
+            offset += HEADER_LENGTH;
+        }
+
+        Err(Error::TooManyLayers)
+    }
+
+    /// Decrypts many RPG Maker files, returning results in the same order.
+    /// Auto-determines the key from the first file it can be determined from, and reuses it for the rest.
+    ///
+    /// This is the same as calling [`Decrypter::decrypt`] for each file, but with a single results allocation, which makes it a convenient building block for parallel front-ends: split files into chunks, and decrypt each chunk with a clone of the same [`Decrypter`].
+    ///
+    /// # Parameters
+    ///
+    /// - `files` - The data of RPG Maker files, e.g. `&[Vec<u8>]`.
+    /// - `file_type` - Resolver returning [`FileType`] of the file with the given index and data.
+    ///
+    /// # Returns
+    ///
+    /// - Result of [`Decrypter::decrypt`] for each file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rpgm_asset_decrypter_core::{Decrypter, Encrypter, FileType, DEFAULT_KEY};
+    ///
+    /// let encrypter = Encrypter::with_key(DEFAULT_KEY.parse().unwrap());
+    /// let plain = [
+    ///     b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec(),
+    ///     b"\x89PNG\r\n\x1a\n\0\0\0\rIHDRdata".to_vec(),
+    /// ];
+    /// let encrypted = encrypter.encrypt_many(&plain);
+    ///
+    /// let decrypted = Decrypter::new().decrypt_many(&encrypted, |_, _| FileType::PNG);
+    /// assert_eq!(decrypted[1].as_ref().unwrap(), &plain[1]);
+    /// ```
+    pub fn decrypt_many<I, F>(
+        &mut self,
+        files: I,
+        mut file_type: F,
+    ) -> Vec<Result<Vec<u8>, Error>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+        F: FnMut(usize, &[u8]) -> FileType,
+    {
+        let files = files.into_iter();
+        let mut results = Vec::with_capacity(files.size_hint().0);
+
+        for (i, file) in files.enumerate() {
+            let file = file.as_ref();
+            results.push(self.decrypt(file, file_type(i, file)));
+        }
+
+        results
+    }
+
+    /// Encrypts file content.
+    ///
+    /// This function requires decrypter to have a key, which you can fetch from `System.json` file
+    /// or by calling [`Decrypter::set_key_from_file`] with the data from encrypted file.
+    ///
+    /// This function copies the contents of the file and returns encrypted [`Vec<u8>`] copy.
+    /// If you want to avoid copying, see [`Decrypter::encrypt_in_place`] function.
+    /// To encrypt with a known key without handling [`Error::KeyNotSet`], see [`Encrypter`].
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of `.png`, `.ogg` or `.m4a` file.
+    ///
+    /// # Returns
+    ///
+    /// - [`Vec<u8>`] containing encrypted data if decrypter key is set.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if decrypter's key is not set.
+    #[inline]
+    pub fn encrypt(&self, file_content: &[u8]) -> Result<Vec<u8>, Error> {
+        if !self.has_key {
+            return Err(Error::KeyNotSet);
+        }
+
+        // Single allocation: the content is copied right after the header, and encrypted there.
+        let mut output_data =
+            Vec::with_capacity(HEADER_LENGTH + file_content.len());
+        output_data.extend_from_slice(self.header());
+        output_data.extend_from_slice(file_content);
+        self.xor_buffer(&mut output_data[HEADER_LENGTH..]);
+        Ok(output_data)
+    }
+
+    /// Encrypts file content in-place.
+    ///
+    /// This function requires decrypter to have a key, which you can fetch from `System.json` file
+    /// or by calling [`Decrypter::set_key_from_file`] with the data from encrypted file.
+    ///
+    /// This function encrypts the passed file data in-place.
+    /// If you don't want to modify passed data, see [`Decrypter::encrypt`] function.
+    ///
+    /// # Note
+    ///
+    /// Encrypted data comes without the RPG Maker header, so you need to manually prepend it - but you can decide where and how to do it most efficient.
+    /// The header is exported as [`RPGM_HEADER`].
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of `.png`, `.ogg` or `.m4a` file.
+    ///
+    /// # Returns
+    ///
+    /// - Nothing, if decrypter key is set.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if decrypter's key is not set.
+    #[inline]
+    pub fn encrypt_in_place(
+        &self,
+        file_content: &mut [u8],
+    ) -> Result<(), Error> {
+        if !self.has_key {
+            return Err(Error::KeyNotSet);
+        }
+
+        self.xor_buffer(file_content);
+        Ok(())
+    }
+
+    /// Encrypts many files, returning encrypted copies in the same order.
+    ///
+    /// See [`Decrypter::encrypt`].
+    ///
+    /// # Parameters
+    ///
+    /// - `files` - The data of `.png`, `.ogg` or `.m4a` files, e.g. `&[Vec<u8>]`.
+    ///
+    /// # Returns
+    ///
+    /// - Encrypted copy of each file if decrypter key is set.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if decrypter's key is not set.
+    pub fn encrypt_many<I>(&self, files: I) -> Result<Vec<Vec<u8>>, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        if !self.has_key {
+            return Err(Error::KeyNotSet);
+        }
+
+        files
+            .into_iter()
+            .map(|file| self.encrypt(file.as_ref()))
+            .collect()
+    }
+
+    /// Decrypts file content encrypted with [`Decrypter::encrypt_with_options`].
+    /// Auto-determines the key from the input file, if it's not set.
+    ///
+    /// `options` must be the same as the ones used for encryption: custom magic is expected instead of the RPG Maker header, and the fake signature is stripped.
+    /// With default `options`, this is the same as [`Decrypter::decrypt`].
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of encrypted file.
+    /// - `file_type` - [`FileType`], representing the type of passed file content.
+    /// - `options` - [`EncryptOptions`] used for encryption.
+    ///
+    /// # Returns
+    ///
+    /// - [`Vec<u8>`] containing decrypted data.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with the expected magic.
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the magic, or doesn't continue with the expected fake signature.
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset, and no custom magic is used.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    /// - [`Error::KeyMismatch`] - if the key was set beforehand, and decrypted data doesn't start with the signature of `file_type`.
+    pub fn decrypt_with_options(
+        &mut self,
+        file_content: &[u8],
+        file_type: FileType,
+        options: &EncryptOptions,
+    ) -> Result<Vec<u8>, Error> {
+        if options.custom_magic.is_none() {
+            check_magic(file_content, self.magic)?;
+        } else if !file_content.starts_with(options.magic()) {
+            let Some(header) = file_content.get(..HEADER_LENGTH) else {
+                return Err(Error::InvalidHeader);
+            };
+
+            let mut expected = [0; HEADER_LENGTH];
+            expected.copy_from_slice(options.magic());
+            let mut got = [0; HEADER_LENGTH];
+            got.copy_from_slice(header);
+
+            return Err(Error::InvalidMagic { expected, got });
+        }
+
+        let Some(payload) = file_content.get(options.prefix_length()..) else {
+            return Err(Error::UnexpectedEOF);
+        };
+
+        if let Some(signature) = &options.fake_signature {
+            if &file_content[HEADER_LENGTH..HEADER_LENGTH * 2] != signature {
+                return Err(Error::InvalidHeader);
+            }
+        }
+
+        if self.has_key {
+            self.check_key(payload, file_type)?;
+        } else {
+            self.set_key_from_payload(payload, file_type)
+                .map_err(|err| err.offset_by(options.prefix_length()))?;
+        }
+
+        self.remember_mask(payload);
+        let mut result = payload.to_vec();
+        self.xor_buffer(&mut result);
+        Ok(result)
+    }
+
+    /// Encrypts file content, obfuscating the output according to `options`.
+    ///
+    /// This function requires decrypter to have a key.
+    /// Output must be decrypted with [`Decrypter::decrypt_with_options`] and the same `options`.
+    ///
+    /// # Parameters
+    ///
+    /// - `file_content` - The data of `.png`, `.ogg`, `.m4a`, `.webp` or `.wav` file.
+    /// - `options` - [`EncryptOptions`] to use.
+    ///
+    /// # Returns
+    ///
+    /// - [`Vec<u8>`] containing encrypted data if decrypter key is set.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if decrypter's key is not set.
+    pub fn encrypt_with_options(
+        &self,
+        file_content: &[u8],
+        options: &EncryptOptions,
+    ) -> Result<Vec<u8>, Error> {
+        if !self.has_key {
+            return Err(Error::KeyNotSet);
+        }
+
+        Ok(Encrypter::with_key(EncryptionKey::from_bytes(self.key))
+            .options(*options)
+            .encrypt(file_content))
+    }
+}
+
+/// Decrypts RPG Maker file content using a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt`].
+/// A new [`Decrypter`] is created internally, and the decryption key is
+/// auto-determined from the provided file data.
+///
+/// This function copies the contents of the file and returns a decrypted [`Vec<u8>`].
+/// If you want to avoid copying, use [`decrypt_in_place`] instead.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of RPG Maker file.
+/// - `file_type` - [`FileType`], representing the type of passed file content.
+///
+/// # Returns
+///
+/// - [`Error`] if the passed data has an invalid header or ends unexpectedly.
+/// - Decrypted [`Vec<u8>`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidMagic`] – if the provided `file_content` does not start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] – if the provided `file_content` is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] – if the provided `file_content` is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] – if the data ends unexpectedly.
+pub fn decrypt(
+    file_content: &[u8],
+    file_type: FileType,
+) -> Result<Vec<u8>, Error> {
+    Decrypter::new().decrypt(file_content, file_type)
+}
+
+/// Decrypts RPG Maker file content, which may be encrypted more than once, using a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_recursive`].
+///
+/// # Errors
+///
+/// - Any error of [`Decrypter::decrypt_recursive`].
+pub fn decrypt_recursive(
+    file_content: &[u8],
+    file_type: FileType,
+) -> Result<Vec<u8>, Error> {
+    Decrypter::new().decrypt_recursive(file_content, file_type)
+}
+
+/// Decrypts RPG Maker file content, whose real type may differ from its extension, using a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_auto`].
+///
+/// # Errors
+///
+/// - Any error of [`Decrypter::decrypt_auto`].
+pub fn decrypt_auto(
+    file_content: &[u8],
+    declared: Option<FileType>,
+) -> Result<AutoDecrypted, Error> {
+    Decrypter::new().decrypt_auto(file_content, declared)
+}
+
+/// Decrypts only the first 16 bytes of RPG Maker file content using a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_header_only`].
+/// A new [`Decrypter`] is created internally, and the decryption key is
+/// auto-determined from the provided file data.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of RPG Maker file. Only the first 32 bytes are required.
+/// - `file_type` - [`FileType`], representing the type of passed file content.
+///
+/// # Returns
+///
+/// - [`Error`] if the passed data has an invalid header or ends unexpectedly.
+/// - Decrypted first block of the file otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidMagic`] – if the provided `file_content` does not start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] – if the provided `file_content` is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] – if the provided `file_content` is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] – if the data ends unexpectedly.
+pub fn decrypt_header_only(
+    file_content: &[u8],
+    file_type: FileType,
+) -> Result<[u8; HEADER_LENGTH], Error> {
+    Decrypter::new().decrypt_header_only(file_content, file_type)
+}
+
+/// XORs the first block of an asset with `key` in place.
+///
+/// This is the whole cipher: the block is bytes 16 - 31 of RPG Maker file, right past [`RPGM_HEADER`], the same call both decrypts and encrypts it, and the rest of the file is plain.
+/// So custom containers can be handled without a [`Decrypter`] or the whole file, e.g. an asset streamed over HTTP range requests only needs its first 32 bytes fetched to be decrypted.
+///
+/// # Parameters
+///
+/// - `key` - Encryption key.
+/// - `block` - Encrypted or decrypted first block of the asset.
+pub fn xor_header(key: &EncryptionKey, block: &mut [u8; HEADER_LENGTH]) {
+    for (byte, key) in block.iter_mut().zip(key.as_bytes()) {
+        *byte ^= key;
+    }
+}
+
+/// Decrypts RPG Maker file content using a temporary [`Decrypter`] instance, borrowing from it when possible.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_cow`].
+/// A new [`Decrypter`] is created internally, and the decryption key is
+/// auto-determined from the provided file data.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of RPG Maker file.
+/// - `file_type` - [`FileType`], representing the type of passed file content.
+///
+/// # Returns
+///
+/// - [`Error`] if the passed data has an invalid header or ends unexpectedly.
+/// - [`Cow`] containing decrypted data otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidMagic`] – if the provided `file_content` does not start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] – if the provided `file_content` is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] – if the provided `file_content` is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] – if the data ends unexpectedly.
+pub fn decrypt_cow(
+    file_content: &[u8],
+    file_type: FileType,
+) -> Result<Cow<'_, [u8]>, Error> {
+    Decrypter::new().decrypt_cow(file_content, file_type)
+}
+
+/// Decrypts RPG Maker file content using a temporary [`Decrypter`] instance, and also returns the original encrypted first 16 bytes of it.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_with_original_header`].
+/// A new [`Decrypter`] is created internally, and the decryption key is
+/// auto-determined from the provided file data.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of RPG Maker file.
+/// - `file_type` - [`FileType`], representing the type of passed file content.
+///
+/// # Returns
+///
+/// - [`Error`] if the passed data has an invalid header or ends unexpectedly.
+/// - Decrypted [`Vec<u8>`] and the original encrypted bytes otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidMagic`] – if the provided `file_content` does not start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] – if the provided `file_content` is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] – if the provided `file_content` is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] – if the data ends unexpectedly.
+pub fn decrypt_with_original_header(
+    file_content: &[u8],
+    file_type: FileType,
+) -> Result<(Vec<u8>, [u8; HEADER_LENGTH]), Error> {
+    Decrypter::new().decrypt_with_original_header(file_content, file_type)
+}
+
+/// Decrypts RPG Maker file content in-place using a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::decrypt_in_place`].
+/// A new [`Decrypter`] is created internally, and the decryption key is
+/// auto-determined from the provided file data.
+///
+/// This function modifies the provided buffer directly.
+/// After successful decryption, the decrypted data is valid starting at offset 16.
+///
+/// If you do not want to modify data in-place, use [`decrypt`] instead.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of RPG Maker file.
+/// - `file_type` - [`FileType`], representing the type of passed file content.
+///
+/// # Returns
+///
+/// - [`Error`] if the passed data has an invalid header or ends unexpectedly.
+/// - Nothing otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidMagic`] – if the provided `file_content` does not start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] – if the provided `file_content` is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] – if the provided `file_content` is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] – if the data ends unexpectedly.
+pub fn decrypt_in_place(
+    file_content: &mut [u8],
+    file_type: FileType,
+) -> Result<(), Error> {
+    Decrypter::new().decrypt_in_place(file_content, file_type)?;
+    Ok(())
+}
+
+/// Encrypts file content using a key string and a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::encrypt`].
+/// A new [`Decrypter`] is created internally, and the key is set from the provided string.
+///
+/// This function copies the file contents and returns an encrypted [`Vec<u8>`].
+/// The output includes the RPG Maker encryption header (`RPGM_HEADER`).
+///
+/// If you want to avoid copying, use [`encrypt_in_place`] instead.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of `.png`, `.ogg` or `.m4a` file.
+/// - `key` - Encryption key string.
+///
+/// # Returns
+///
+/// - Encrypted [`Vec<u8>`] if the key is valid.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidKeyLength`] - if key's length is not 32 bytes.
+/// - [`Error::KeyNotSet`] – if key initialization fails.
+pub fn encrypt(file_content: &[u8], key: &str) -> Result<Vec<u8>, Error> {
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_str(key)?;
+    decrypter.encrypt(file_content)
+}
+
+/// Encrypts file content in-place using a key string and a temporary [`Decrypter`] instance.
+///
+/// This is a convenience wrapper around [`Decrypter::encrypt_in_place`].
+/// A new [`Decrypter`] is created internally, and the key is set from the provided string.
+///
+/// This function modifies the file data directly and produces *only* the encrypted payload.
+/// The RPG Maker encryption header is **not** added automatically; it must be prepended manually
+/// if producing a complete `.rpgmvp`, `.rpgmvo`, or `.rpgmvw` file.
+///
+/// If you do not want to modify data in-place, use [`encrypt`] instead.
+///
+/// # Parameters
+///
+/// - `file_content` - The data of `.png`, `.ogg` or `.m4a` file.
+/// - `key` - Encryption key string.
+///
+/// # Returns
+///
+/// - [`Error`] if key's length is not 32 bytes.
+/// - Nothing otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidKeyLength`] - if key's length is not 32 bytes.
+pub fn encrypt_in_place(
+    file_content: &mut [u8],
+    key: &str,
+) -> Result<(), Error> {
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_str(key)?;
+    decrypter.encrypt_in_place(file_content)?;
+    Ok(())
+}
+
+/// Encrypts file content by restoring the original encrypted first 16 bytes, returned from [`decrypt_with_original_header`].
+///
+/// No key is needed, since only the first 16 bytes are ever encrypted. The output includes the RPG Maker encryption header (`RPGM_HEADER`).
+///
+/// # Note
+///
+/// The first 16 bytes of `file_content` are replaced, so they must be the same as the ones returned from decryption. Any changes past them are preserved.
+///
+/// # Parameters
+///
+/// - `file_content` - Decrypted data.
+/// - `original_header` - Original encrypted bytes, returned from [`decrypt_with_original_header`].
+#[must_use]
+pub fn encrypt_with_original_header(
+    file_content: &[u8],
+    original_header: &[u8; HEADER_LENGTH],
+) -> Vec<u8> {
+    let length = file_content.len().min(HEADER_LENGTH);
+
+    let mut output_data =
+        Vec::with_capacity(HEADER_LENGTH + file_content.len());
+    output_data.extend(RPGM_HEADER);
+    output_data.extend(&original_header[..length]);
+    output_data.extend(&file_content[length..]);
+    output_data
+}
@@ -0,0 +1,108 @@
+//! Hook for supplying reusable buffers to decryption, instead of allocating a fresh [`Vec`] per file.
+//!
+//! High-throughput servers decrypting many files can pass a [`BufferPool`] to [`crate::Decrypter::decrypt_pooled`], and return the buffers to it once responses are sent, so steady-state decryption doesn't allocate at all.
+//! With `std` feature, [`VecPool`] is the default implementation.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, PoisonError};
+
+/// Source of reusable buffers, e.g. an arena or a free list.
+///
+/// Methods take `&self`, so a single pool can be shared between threads and decrypters; implementations use interior mutability.
+pub trait BufferPool {
+    /// Returns an empty buffer, preferably with at least `capacity` bytes of capacity. Buffers grow as needed, so any buffer is correct.
+    fn acquire(&self, capacity: usize) -> Vec<u8>;
+
+    /// Gives `buffer`, no longer needed by the caller, back to the pool for reuse. The pool may also just drop it.
+    fn release(&self, buffer: Vec<u8>);
+}
+
+/// Default [`BufferPool`]: a thread-safe free list of buffers.
+///
+/// Keeps at most [`VecPool::max_buffers`] released buffers, and drops the rest, so memory held by the pool stays bounded by the largest files seen.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct VecPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+#[cfg(feature = "std")]
+impl Default for VecPool {
+    fn default() -> Self {
+        Self {
+            buffers: Mutex::default(),
+            max_buffers: VecPool::DEFAULT_MAX_BUFFERS,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl VecPool {
+    /// Default maximum number of kept buffers.
+    pub const DEFAULT_MAX_BUFFERS: usize = 32;
+
+    /// Creates an empty pool, keeping at most [`VecPool::DEFAULT_MAX_BUFFERS`] buffers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of kept buffers. `0` disables reuse.
+    #[must_use]
+    pub fn max_buffers(mut self, max_buffers: usize) -> Self {
+        self.max_buffers = max_buffers;
+        self
+    }
+
+    /// Returns the number of buffers currently kept for reuse.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns whether no buffers are currently kept.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(feature = "std")]
+impl BufferPool for VecPool {
+    fn acquire(&self, capacity: usize) -> Vec<u8> {
+        let mut buffers =
+            self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Prefer the smallest buffer that fits, so large buffers are left for large files.
+        let fitting = buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, buffer)| buffer.capacity() >= capacity)
+            .min_by_key(|(_, buffer)| buffer.capacity())
+            .map(|(i, _)| i);
+
+        let mut buffer = match fitting {
+            Some(i) => buffers.swap_remove(i),
+            None => buffers.pop().unwrap_or_default(),
+        };
+        drop(buffers);
+
+        buffer.clear();
+        buffer.reserve(capacity);
+        buffer
+    }
+
+    fn release(&self, buffer: Vec<u8>) {
+        let mut buffers =
+            self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if buffers.len() < self.max_buffers && buffer.capacity() != 0 {
+            buffers.push(buffer);
+        }
+    }
+}
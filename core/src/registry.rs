@@ -0,0 +1,116 @@
+//! Registry of known-plaintext signatures for custom encrypted file types.
+
+use crate::{FileType, HEADER_LENGTH};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::convert::TryFrom;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    encrypted_ext: String,
+    decrypted_ext: String,
+    signature: [u8; HEADER_LENGTH],
+}
+
+/// Maps extensions of custom encrypted file types to their known 16-byte plaintext headers.
+///
+/// Some games encrypt files besides images and audio, e.g. fonts or videos, with their own extensions.
+/// Registered extensions resolve to [`FileType::Other`] with the registered signature, which [`crate::Decrypter::set_key_from_file`] uses as known plaintext.
+/// Built-in extensions (see [`crate::ENCRYPTED_ASSET_EXTS`]) always take precedence, and can't be overridden, besides [`FileType::JSON`] ones: they have no known plaintext of their own, so a registered signature supplies it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SignatureRegistry {
+    entries: Vec<Entry>,
+}
+
+impl SignatureRegistry {
+    /// Creates an empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a custom file type.
+    ///
+    /// Registering the same `encrypted_ext` again replaces the previous entry.
+    ///
+    /// # Parameters
+    ///
+    /// - `encrypted_ext` - Extension of encrypted files, without the leading dot, e.g. `rpgmvj`.
+    /// - `decrypted_ext` - Extension decrypted files should be written with, e.g. `json`.
+    /// - `signature` - First 16 bytes every decrypted file of this type starts with.
+    pub fn register(
+        &mut self,
+        encrypted_ext: impl Into<String>,
+        decrypted_ext: impl Into<String>,
+        signature: [u8; HEADER_LENGTH],
+    ) -> &mut Self {
+        let entry = Entry {
+            encrypted_ext: encrypted_ext.into(),
+            decrypted_ext: decrypted_ext.into(),
+            signature,
+        };
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.encrypted_ext == entry.encrypted_ext)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+
+        self
+    }
+
+    fn entry(&self, encrypted_ext: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.encrypted_ext == encrypted_ext)
+    }
+
+    /// Returns the signature registered for `encrypted_ext`.
+    #[must_use]
+    pub fn signature(
+        &self,
+        encrypted_ext: &str,
+    ) -> Option<[u8; HEADER_LENGTH]> {
+        self.entry(encrypted_ext).map(|entry| entry.signature)
+    }
+
+    /// Returns the file type of encrypted files with `encrypted_ext` extension: built-in type, if it's a built-in extension, or [`FileType::Other`] with the registered signature.
+    #[must_use]
+    pub fn file_type(&self, encrypted_ext: &str) -> Option<FileType> {
+        match FileType::try_from(encrypted_ext) {
+            Ok(file_type) if !file_type.is_json() => Some(file_type),
+            builtin => self
+                .signature(encrypted_ext)
+                .map(FileType::Other)
+                .or(builtin.ok()),
+        }
+    }
+
+    /// Returns the extension decrypted files with `encrypted_ext` extension should be written with.
+    #[must_use]
+    pub fn decrypted_extension(&self, encrypted_ext: &str) -> Option<String> {
+        match FileType::try_from(encrypted_ext) {
+            Ok(file_type) if !file_type.is_json() => {
+                Some(file_type.to_string())
+            }
+            builtin => self
+                .entry(encrypted_ext)
+                .map(|entry| entry.decrypted_ext.clone())
+                .or_else(|| {
+                    builtin.ok().map(|file_type| file_type.to_string())
+                }),
+        }
+    }
+
+    /// Returns registered encrypted extensions, in registration order.
+    pub fn encrypted_extensions(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.encrypted_ext.as_str())
+    }
+}
@@ -0,0 +1,345 @@
+//! Stable serde representations of [`FileType`], [`Engine`], [`EncryptionKey`] and [`Error`].
+//!
+//! - [`FileType`] is a lowercase string: `png`, `ogg`, `m4a`, `webp`, `wav`, `json`, or `other:` followed by the hex signature of [`FileType::Other`].
+//! - [`Engine`] is a lowercase string: `mv` or `mz`.
+//! - [`EncryptionKey`] is its hex string, e.g. `d41d8cd98f00b204e9800998ecf8427e`.
+//! - [`Error`] is an object tagged with `kind`, the snake-cased variant name, e.g. `{ "kind": "malformed", "offset": 4, "reason": "expected OGG page" }`. Headers and signatures are hex strings.
+
+use crate::{EncryptionKey, Engine, Error, FileType, HEADER_LENGTH};
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+};
+use core::fmt;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, Visitor},
+};
+
+/// Prefix of serialized [`FileType::Other`].
+const OTHER_PREFIX: &str = "other:";
+
+/// Reason of deserialized errors, whose own reason is not one of [`KNOWN_REASONS`].
+const UNKNOWN_REASON: &str = "unknown reason";
+
+/// Reasons of [`Error::MalformedKey`] and [`Error::Malformed`] produced by this crate and `rpgm-asset-decrypter-lib`.
+const KNOWN_REASONS: &[&str] = &[
+    "unmatched opening quote",
+    "unmatched closing quote",
+    "key is empty",
+    "not a hex digit",
+    "key is shorter than 32 hex digits",
+    "key is longer than 32 hex digits",
+    "RIFF data exceeds 4 GiB",
+    "expected OGG page",
+    "expected M4A box size",
+    "expected Vorbis or Opus identification header",
+    "asar size pickle is truncated",
+    "asar header is truncated",
+];
+
+/// Returns `bytes` as a hex string.
+fn hex(bytes: [u8; HEADER_LENGTH]) -> String {
+    EncryptionKey::from_bytes(bytes).to_string()
+}
+
+/// Parses a hex string produced by [`hex`].
+fn parse_hex<E: de::Error>(hex: &str) -> Result<[u8; HEADER_LENGTH], E> {
+    EncryptionKey::from_hex_str(hex)
+        .map(|bytes| *bytes.as_bytes())
+        .map_err(de::Error::custom)
+}
+
+/// Returns `reason` as `&'static str`, which [`Error`] holds: the matching one of [`KNOWN_REASONS`], or [`UNKNOWN_REASON`], so untrusted input never allocates static memory.
+fn static_reason(reason: &str) -> &'static str {
+    KNOWN_REASONS
+        .iter()
+        .find(|known| **known == reason)
+        .copied()
+        .unwrap_or(UNKNOWN_REASON)
+}
+
+impl Serialize for FileType {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Other(signature) => serializer.serialize_str(&format!(
+                "{}{}",
+                OTHER_PREFIX,
+                hex(*signature)
+            )),
+            file_type => serializer.collect_str(file_type),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FileType {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct FileTypeVisitor;
+
+        impl Visitor<'_> for FileTypeVisitor {
+            type Value = FileType;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("file type name, e.g. `png`")
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                value: &str,
+            ) -> Result<FileType, E> {
+                if let Some(signature) = value.strip_prefix(OTHER_PREFIX) {
+                    return parse_hex(signature).map(FileType::Other);
+                }
+
+                FileType::from_decrypted_extension(value).ok_or_else(|| {
+                    E::unknown_variant(
+                        value,
+                        &["png", "ogg", "m4a", "webp", "wav", "json", "other:"],
+                    )
+                })
+            }
+        }
+
+        deserializer.deserialize_str(FileTypeVisitor)
+    }
+}
+
+impl Serialize for Engine {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Self::Mv => "mv",
+            Self::Mz => "mz",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Engine {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct EngineVisitor;
+
+        impl Visitor<'_> for EngineVisitor {
+            type Value = Engine;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("`mv` or `mz`")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Engine, E> {
+                match value {
+                    "mv" => Ok(Engine::Mv),
+                    "mz" => Ok(Engine::Mz),
+                    _ => Err(E::unknown_variant(value, &["mv", "mz"])),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(EngineVisitor)
+    }
+}
+
+impl Serialize for EncryptionKey {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptionKey {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct EncryptionKeyVisitor;
+
+        impl Visitor<'_> for EncryptionKeyVisitor {
+            type Value = EncryptionKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("32 hex characters long key")
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                value: &str,
+            ) -> Result<EncryptionKey, E> {
+                EncryptionKey::from_hex_str(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(EncryptionKeyVisitor)
+    }
+}
+
+/// Serialized form of [`Error`].
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ErrorRepr {
+    KeyNotSet,
+    InvalidKeyLength,
+    InvalidKey,
+    MalformedKey {
+        position: usize,
+        reason: String,
+    },
+    InvalidHeader,
+    InvalidMagic {
+        expected: String,
+        got: String,
+    },
+    AlreadyDecrypted {
+        file_type: FileType,
+    },
+    #[serde(rename = "unexpected_eof")]
+    UnexpectedEOF,
+    Malformed {
+        offset: usize,
+        reason: String,
+    },
+    TypeMismatch {
+        declared: FileType,
+        detected: FileType,
+    },
+    Timeout,
+    InvalidJson,
+    UnsupportedFileType,
+    KeyMismatch {
+        expected_signature: String,
+        got: String,
+    },
+    PatchMismatch,
+    DoubleEncrypted,
+    TooManyLayers,
+    /// Only the message of I/O errors is kept.
+    Io {
+        message: String,
+    },
+}
+
+impl From<&Error> for ErrorRepr {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::KeyNotSet => Self::KeyNotSet,
+            Error::InvalidKeyLength => Self::InvalidKeyLength,
+            Error::InvalidKey => Self::InvalidKey,
+            Error::MalformedKey { position, reason } => Self::MalformedKey {
+                position: *position,
+                reason: (*reason).to_owned(),
+            },
+            Error::InvalidHeader => Self::InvalidHeader,
+            Error::InvalidMagic { expected, got } => Self::InvalidMagic {
+                expected: hex(*expected),
+                got: hex(*got),
+            },
+            Error::AlreadyDecrypted(file_type) => Self::AlreadyDecrypted {
+                file_type: *file_type,
+            },
+            Error::UnexpectedEOF => Self::UnexpectedEOF,
+            Error::Malformed { offset, reason } => Self::Malformed {
+                offset: *offset,
+                reason: (*reason).to_owned(),
+            },
+            Error::TypeMismatch { declared, detected } => Self::TypeMismatch {
+                declared: *declared,
+                detected: *detected,
+            },
+            Error::Timeout => Self::Timeout,
+            Error::InvalidJson => Self::InvalidJson,
+            Error::UnsupportedFileType => Self::UnsupportedFileType,
+            Error::KeyMismatch {
+                expected_signature,
+                got,
+            } => Self::KeyMismatch {
+                expected_signature: hex(*expected_signature),
+                got: hex(*got),
+            },
+            Error::PatchMismatch => Self::PatchMismatch,
+            Error::DoubleEncrypted => Self::DoubleEncrypted,
+            Error::TooManyLayers => Self::TooManyLayers,
+            #[cfg(feature = "std")]
+            Error::Io(err) => Self::Io {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl ErrorRepr {
+    fn into_error<E: de::Error>(self) -> Result<Error, E> {
+        Ok(match self {
+            Self::KeyNotSet => Error::KeyNotSet,
+            Self::InvalidKeyLength => Error::InvalidKeyLength,
+            Self::InvalidKey => Error::InvalidKey,
+            Self::MalformedKey { position, reason } => Error::MalformedKey {
+                position,
+                reason: static_reason(&reason),
+            },
+            Self::InvalidHeader => Error::InvalidHeader,
+            Self::InvalidMagic { expected, got } => Error::InvalidMagic {
+                expected: parse_hex(&expected)?,
+                got: parse_hex(&got)?,
+            },
+            Self::AlreadyDecrypted { file_type } => {
+                Error::AlreadyDecrypted(file_type)
+            }
+            Self::UnexpectedEOF => Error::UnexpectedEOF,
+            Self::Malformed { offset, reason } => Error::Malformed {
+                offset,
+                reason: static_reason(&reason),
+            },
+            Self::TypeMismatch { declared, detected } => {
+                Error::TypeMismatch { declared, detected }
+            }
+            Self::Timeout => Error::Timeout,
+            Self::InvalidJson => Error::InvalidJson,
+            Self::UnsupportedFileType => Error::UnsupportedFileType,
+            Self::KeyMismatch {
+                expected_signature,
+                got,
+            } => Error::KeyMismatch {
+                expected_signature: parse_hex(&expected_signature)?,
+                got: parse_hex(&got)?,
+            },
+            Self::PatchMismatch => Error::PatchMismatch,
+            Self::DoubleEncrypted => Error::DoubleEncrypted,
+            Self::TooManyLayers => Error::TooManyLayers,
+            #[cfg(feature = "std")]
+            Self::Io { message } => Error::Io(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                message,
+            )),
+            #[cfg(not(feature = "std"))]
+            Self::Io { .. } => {
+                return Err(E::custom("I/O errors require `std` feature"));
+            }
+        })
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        ErrorRepr::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Error {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        ErrorRepr::deserialize(deserializer)?.into_error()
+    }
+}
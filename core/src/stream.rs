@@ -0,0 +1,173 @@
+//! Streaming decryption and encryption via [`Read`] and [`Write`] adapters.
+//!
+//! Encryption only touches the first 16 bytes of a file, so large audio files can be processed without loading them into memory.
+
+use crate::{
+    Decrypter, Error, FileType, HEADER_LENGTH, KEY_LENGTH, check_magic,
+};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+// Enough to fit M4A type box and the first two OGG pages' headers for key derivation.
+const PREFIX_LENGTH: usize = 4096;
+
+/// [`Read`] adapter that strips the RPG Maker header from the wrapped reader and decrypts the data on the fly.
+pub struct DecryptReader<R: Read> {
+    inner: R,
+    prefix: Vec<u8>,
+    prefix_pos: usize,
+}
+
+/// Buffered prefix of decrypted data is left out.
+impl<R: Read + fmt::Debug> fmt::Debug for DecryptReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecryptReader")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Creates a new [`DecryptReader`].
+    ///
+    /// Reads up to 4 KiB from `inner` upfront to validate the header, and to determine the key if `decrypter` doesn't have one.
+    ///
+    /// # Parameters
+    ///
+    /// - `inner` - Reader of RPG Maker file data.
+    /// - `decrypter` - [`Decrypter`] whose key is used. If its key is not set, it's determined from the data.
+    /// - `file_type` - [`FileType`], representing the type of the data. Used to determine the key if it's not set, or to check it otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidMagic`] - if the data doesn't start with `decrypter`'s expected magic header, see [`Decrypter::set_expected_magic`].
+    /// - [`Error::InvalidHeader`] - if the data is too short to contain the header.
+    /// - [`Error::AlreadyDecrypted`] - if the data is a plain, not encrypted asset.
+    /// - [`Error::UnexpectedEOF`] - if the data ends unexpectedly.
+    /// - [`Error::KeyMismatch`] - if `decrypter`'s key was set, and decrypted data doesn't start with the signature of `file_type`.
+    /// - [`Error::Io`] - if reading from `inner` fails.
+    pub fn new(
+        mut inner: R,
+        decrypter: &mut Decrypter,
+        file_type: FileType,
+    ) -> Result<Self, Error> {
+        let mut prefix = Vec::with_capacity(PREFIX_LENGTH);
+        (&mut inner)
+            .take(PREFIX_LENGTH as u64)
+            .read_to_end(&mut prefix)?;
+
+        check_magic(&prefix, decrypter.magic)?;
+
+        if decrypter.has_key {
+            decrypter.check_key(&prefix[HEADER_LENGTH..], file_type)?;
+        } else {
+            decrypter.set_key_from_file(&prefix, file_type)?;
+        }
+
+        decrypter.remember_mask(&prefix[HEADER_LENGTH..]);
+        decrypter.xor_buffer(&mut prefix[HEADER_LENGTH..]);
+
+        Ok(Self {
+            inner,
+            prefix,
+            prefix_pos: HEADER_LENGTH,
+        })
+    }
+
+    /// Returns the wrapped reader.
+    ///
+    /// Data that was read from it upfront, but not yet returned from this adapter, is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.prefix_pos < self.prefix.len() {
+            let remaining = &self.prefix[self.prefix_pos..];
+            let len = remaining.len().min(buf.len());
+
+            buf[..len].copy_from_slice(&remaining[..len]);
+            self.prefix_pos += len;
+            return Ok(len);
+        }
+
+        self.inner.read(buf)
+    }
+}
+
+/// [`Write`] adapter that prepends the RPG Maker header to the wrapped writer and encrypts the data on the fly.
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    key: [u8; KEY_LENGTH],
+    written: usize,
+}
+
+/// Key is redacted.
+impl<W: Write + fmt::Debug> fmt::Debug for EncryptWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptWriter")
+            .field("inner", &self.inner)
+            .field("written", &self.written)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Creates a new [`EncryptWriter`], and writes `decrypter`'s header to `inner`, see [`Decrypter::set_expected_magic`].
+    ///
+    /// # Parameters
+    ///
+    /// - `inner` - Writer to write encrypted data to.
+    /// - `decrypter` - [`Decrypter`] whose key is used.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if `decrypter`'s key is not set.
+    /// - [`Error::Io`] - if writing to `inner` fails.
+    pub fn new(mut inner: W, decrypter: &Decrypter) -> Result<Self, Error> {
+        if !decrypter.has_key {
+            return Err(Error::KeyNotSet);
+        }
+
+        inner.write_all(decrypter.header())?;
+
+        Ok(Self {
+            inner,
+            key: decrypter.key,
+            written: 0,
+        })
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written >= HEADER_LENGTH {
+            return self.inner.write(buf);
+        }
+
+        let len = (HEADER_LENGTH - self.written).min(buf.len());
+        let mut block = [0; HEADER_LENGTH];
+
+        for (i, (out, byte)) in block.iter_mut().zip(&buf[..len]).enumerate() {
+            *out = byte ^ self.key[self.written + i];
+        }
+
+        // Partial write would desync the key offset, so the block is always written whole.
+        self.inner.write_all(&block[..len])?;
+        self.written += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,390 @@
+//! Helpers for RPG Maker MV/MZ `System.json` file.
+//!
+//! Implements just enough of JSON to read the encryption-related fields, so consumers don't need to pull in a full JSON parser.
+//!
+//! Parsing is tolerant to what games and plugins do to the file: UTF-8 byte order mark is skipped, and if the data isn't a valid JSON object, e.g. because it's minified into a script or wrapped by an obfuscator, the fields are searched for by name (see [`search_encryption_info`]).
+
+use crate::{EncryptionKey, Error, KEY_STR_LENGTH, json};
+use alloc::{string::String, vec::Vec};
+
+/// Encryption-related fields of `System.json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionInfo {
+    /// Value of `encryptionKey` field.
+    pub key: String,
+    /// Value of `hasEncryptedImages` field, `false` if it's absent.
+    pub has_encrypted_images: bool,
+    /// Value of `hasEncryptedAudio` field, `false` if it's absent.
+    pub has_encrypted_audio: bool,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+/// UTF-8 byte order mark, which some editors and plugins prepend to `System.json`.
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+impl<'a> Reader<'a> {
+    /// Creates a reader over `data`, starting past its byte order mark, if any.
+    fn new(data: &'a [u8]) -> Self {
+        let pos = if data.starts_with(BOM) { BOM.len() } else { 0 };
+        Self { data, pos }
+    }
+
+    /// Creates a reader over `data`, starting at `pos`.
+    fn at(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(byte) = self.data.get(self.pos) {
+            if !byte.is_ascii_whitespace() {
+                return Some(*byte);
+            }
+
+            self.pos += 1;
+        }
+
+        None
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), Error> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(Error::InvalidJson)
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        json::read_string(self.data, &mut self.pos).ok_or(Error::InvalidJson)
+    }
+
+    fn literal(&mut self) -> &'a [u8] {
+        self.peek();
+        let start = self.pos;
+
+        while let Some(byte) = self.data.get(self.pos) {
+            if matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace()
+            {
+                break;
+            }
+
+            self.pos += 1;
+        }
+
+        &self.data[start..self.pos]
+    }
+
+    fn bool(&mut self) -> Result<bool, Error> {
+        match self.literal() {
+            b"true" => Ok(true),
+            b"false" | b"null" => Ok(false),
+            _ => Err(Error::InvalidJson),
+        }
+    }
+
+    fn skip_value(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            Some(b'"') => {
+                self.string()?;
+            }
+            Some(open @ (b'{' | b'[')) => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+
+                if self.peek() == Some(close) {
+                    self.pos += 1;
+                    return Ok(());
+                }
+
+                loop {
+                    if open == b'{' {
+                        self.string()?;
+                        self.expect(b':')?;
+                    }
+
+                    self.skip_value()?;
+
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(byte) if byte == close => {
+                            self.pos += 1;
+                            break;
+                        }
+                        _ => return Err(Error::InvalidJson),
+                    }
+                }
+            }
+            Some(_) => {
+                if self.literal().is_empty() {
+                    return Err(Error::InvalidJson);
+                }
+            }
+            None => return Err(Error::InvalidJson),
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses MV/MZ `System.json` data and returns its encryption-related fields.
+///
+/// Byte order mark is skipped. If `data` is not a valid JSON object, the fields are searched for by name with [`search_encryption_info`] instead.
+///
+/// # Parameters
+///
+/// - `data` - Contents of `System.json` file.
+///
+/// # Returns
+///
+/// - [`EncryptionInfo`] if `encryptionKey` field is present and valid.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::InvalidJson`] - if `data` is not a valid JSON object, and doesn't contain a valid `encryptionKey` field either.
+/// - [`Error::KeyNotSet`] - if `encryptionKey` field is absent.
+/// - [`Error::InvalidKeyLength`] - if `encryptionKey` length is not 32 characters.
+pub fn extract_key_from_system_json(
+    data: &[u8],
+) -> Result<EncryptionInfo, Error> {
+    match parse_system_json(data) {
+        Err(Error::InvalidJson) => {
+            search_encryption_info(data).map_err(|_| Error::InvalidJson)
+        }
+        result => result,
+    }
+}
+
+/// Parses `data` as a JSON object, see [`extract_key_from_system_json`].
+fn parse_system_json(data: &[u8]) -> Result<EncryptionInfo, Error> {
+    let mut reader = Reader::new(data);
+    let mut key = None;
+    let mut has_encrypted_images = false;
+    let mut has_encrypted_audio = false;
+
+    reader.expect(b'{')?;
+
+    if reader.peek() == Some(b'}') {
+        return Err(Error::KeyNotSet);
+    }
+
+    loop {
+        let name = reader.string()?;
+        reader.expect(b':')?;
+
+        match name.as_str() {
+            "encryptionKey" => key = Some(reader.string()?),
+            "hasEncryptedImages" => has_encrypted_images = reader.bool()?,
+            "hasEncryptedAudio" => has_encrypted_audio = reader.bool()?,
+            _ => reader.skip_value()?,
+        }
+
+        match reader.peek() {
+            Some(b',') => reader.pos += 1,
+            Some(b'}') => break,
+            _ => return Err(Error::InvalidJson),
+        }
+    }
+
+    let key = key.ok_or(Error::KeyNotSet)?;
+
+    if key.len() != KEY_STR_LENGTH {
+        return Err(Error::InvalidKeyLength);
+    }
+
+    Ok(EncryptionInfo {
+        key,
+        has_encrypted_images,
+        has_encrypted_audio,
+    })
+}
+
+/// Returns the value of the first `"field": value` pair in `data`, anywhere in it, for which `parse` succeeds.
+fn search_field<'a, T>(
+    data: &'a [u8],
+    field: &str,
+    parse: impl Fn(&mut Reader<'a>) -> Result<T, Error>,
+) -> Option<T> {
+    let needle_length = field.len() + 2;
+
+    (0..data.len().saturating_sub(needle_length - 1)).find_map(|start| {
+        let candidate = &data[start..start + needle_length];
+
+        if candidate[0] != b'"'
+            || candidate[needle_length - 1] != b'"'
+            || &candidate[1..needle_length - 1] != field.as_bytes()
+        {
+            return None;
+        }
+
+        let mut reader = Reader::at(data, start + needle_length);
+        reader.expect(b':').ok()?;
+        parse(&mut reader).ok()
+    })
+}
+
+/// Searches `data` for encryption-related fields of `System.json` by their names, regardless of what surrounds them.
+///
+/// This finds the key in files that aren't valid JSON objects, such as minified scripts, JSON nested in other data, or files renamed and wrapped by plugins. Only `encryptionKey` fields whose values are valid keys are considered, so the first such field wins.
+///
+/// # Returns
+///
+/// - [`EncryptionInfo`] if a valid `encryptionKey` field is found. Flags are `false` if their fields aren't found.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::KeyNotSet`] - if there's no `encryptionKey` field with a valid key.
+pub fn search_encryption_info(data: &[u8]) -> Result<EncryptionInfo, Error> {
+    let key = search_field(data, "encryptionKey", |reader| {
+        let key = reader.string()?;
+        EncryptionKey::from_hex_str(&key)?;
+        Ok(key)
+    })
+    .ok_or(Error::KeyNotSet)?;
+
+    let flag = |field| search_field(data, field, Reader::bool).unwrap_or(false);
+
+    Ok(EncryptionInfo {
+        key,
+        has_encrypted_images: flag("hasEncryptedImages"),
+        has_encrypted_audio: flag("hasEncryptedAudio"),
+    })
+}
+
+/// Location of a top-level field's value in `System.json` data.
+enum FieldLocation {
+    /// Value occupies this range.
+    Value(core::ops::Range<usize>),
+    /// Field is absent, and should be inserted at this position, i.e. before the closing brace.
+    Absent { insert_at: usize, is_empty: bool },
+}
+
+fn locate_field(data: &[u8], field: &str) -> Result<FieldLocation, Error> {
+    let mut reader = Reader::new(data);
+
+    reader.expect(b'{')?;
+
+    if reader.peek() == Some(b'}') {
+        return Ok(FieldLocation::Absent {
+            insert_at: reader.pos,
+            is_empty: true,
+        });
+    }
+
+    loop {
+        let name = reader.string()?;
+        reader.expect(b':')?;
+
+        reader.peek();
+        let start = reader.pos;
+        reader.skip_value()?;
+
+        if name == field {
+            return Ok(FieldLocation::Value(start..reader.pos));
+        }
+
+        match reader.peek() {
+            Some(b',') => reader.pos += 1,
+            Some(b'}') => {
+                return Ok(FieldLocation::Absent {
+                    insert_at: reader.pos,
+                    is_empty: false,
+                });
+            }
+            _ => return Err(Error::InvalidJson),
+        }
+    }
+}
+
+/// Sets top-level `field` of `data` to raw JSON `value`, leaving the rest of `data` untouched. Absent fields are appended.
+fn set_field(
+    data: &mut Vec<u8>,
+    field: &str,
+    value: &[u8],
+) -> Result<(), Error> {
+    match locate_field(data, field)? {
+        FieldLocation::Value(range) => {
+            data.splice(range, value.iter().copied());
+        }
+        FieldLocation::Absent {
+            insert_at,
+            is_empty,
+        } => {
+            let mut inserted = Vec::new();
+
+            if !is_empty {
+                inserted.push(b',');
+            }
+
+            inserted.push(b'"');
+            inserted.extend_from_slice(field.as_bytes());
+            inserted.extend_from_slice(b"\":");
+            inserted.extend_from_slice(value);
+
+            data.splice(insert_at..insert_at, inserted);
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets `hasEncryptedImages` and `hasEncryptedAudio` fields of MV/MZ `System.json` data.
+///
+/// Only the values of these fields are rewritten, so the rest of `data`, including its formatting, is preserved. Absent fields are appended to the end of the object.
+///
+/// # Errors
+///
+/// - [`Error::InvalidJson`] - if `data` is not a valid JSON object.
+pub fn set_encryption_flags(
+    data: &mut Vec<u8>,
+    images: bool,
+    audio: bool,
+) -> Result<(), Error> {
+    let literal =
+        |value: bool| -> &[u8] { if value { b"true" } else { b"false" } };
+
+    set_field(data, "hasEncryptedImages", literal(images))?;
+    set_field(data, "hasEncryptedAudio", literal(audio))
+}
+
+/// Sets encryption-related fields of MV/MZ `System.json` data: `encryptionKey`, `hasEncryptedImages` and `hasEncryptedAudio`.
+///
+/// Like [`set_encryption_flags`], only the values of these fields are rewritten, and absent fields are appended to the end of the object.
+///
+/// # Parameters
+///
+/// - `data` - Contents of `System.json` file.
+/// - `key` - Hex key to write to `encryptionKey`. If [`None`], `encryptionKey` is left untouched.
+/// - `images` - Value of `hasEncryptedImages`.
+/// - `audio` - Value of `hasEncryptedAudio`.
+///
+/// # Errors
+///
+/// - [`Error::InvalidKeyLength`] - if `key` length is not 32 characters.
+/// - [`Error::InvalidKey`] - if `key` contains non-hex characters.
+/// - [`Error::InvalidJson`] - if `data` is not a valid JSON object.
+pub fn set_encryption(
+    data: &mut Vec<u8>,
+    key: Option<&str>,
+    images: bool,
+    audio: bool,
+) -> Result<(), Error> {
+    if let Some(key) = key {
+        EncryptionKey::from_hex_str(key)?;
+
+        let mut value = Vec::with_capacity(KEY_STR_LENGTH + 2);
+        value.push(b'"');
+        value.extend_from_slice(key.as_bytes());
+        value.push(b'"');
+
+        set_field(data, "encryptionKey", &value)?;
+    }
+
+    set_encryption_flags(data, images, audio)
+}
@@ -0,0 +1,203 @@
+//! Encryption schemes of modified engines, which use longer headers and keys than standard RPG Maker.
+//!
+//! Such schemes work the same way as the standard one: a fixed header is prepended to the file, and the first bytes of the file are xored with the key, only the lengths differ.
+//! [`VariantCipher`] handles any of them, while [`crate::Decrypter`] stays specialized for the standard 16-byte scheme.
+
+use crate::{AssetState, Error, HEADER_LENGTH, KEY_LENGTH, RPGM_HEADER};
+use alloc::vec::Vec;
+
+/// Lengths of the header and the key of an encryption scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Variant {
+    /// Standard RPG Maker MV/MZ scheme: 16-byte header and 16-byte key.
+    #[default]
+    Standard16,
+    /// 32-byte header and 32-byte key, used by some modified engines.
+    Extended32,
+    /// Custom lengths.
+    Custom {
+        /// Length of the header, preceding the encrypted data.
+        header_length: usize,
+        /// Length of the key, and of the encrypted part of the data.
+        key_length: usize,
+    },
+}
+
+impl Variant {
+    /// Returns the length of the header, preceding the encrypted data.
+    #[must_use]
+    pub const fn header_length(self) -> usize {
+        match self {
+            Variant::Standard16 => HEADER_LENGTH,
+            Variant::Extended32 => HEADER_LENGTH * 2,
+            Variant::Custom { header_length, .. } => header_length,
+        }
+    }
+
+    /// Returns the length of the key, and of the encrypted part of the data.
+    #[must_use]
+    pub const fn key_length(self) -> usize {
+        match self {
+            Variant::Standard16 => KEY_LENGTH,
+            Variant::Extended32 => KEY_LENGTH * 2,
+            Variant::Custom { key_length, .. } => key_length,
+        }
+    }
+
+    /// Returns the part of [`RPGM_HEADER`] the header starts with.
+    ///
+    /// Headers longer than 16 bytes start with [`RPGM_HEADER`], and the rest of them is engine-specific, so it isn't checked. Shorter headers are its prefix.
+    #[must_use]
+    pub fn magic(self) -> &'static [u8] {
+        &RPGM_HEADER[..self.header_length().min(HEADER_LENGTH)]
+    }
+}
+
+/// Decrypts and encrypts files of any [`Variant`] with a fixed key.
+///
+/// # Example
+///
+/// ```
+/// use rpgm_asset_decrypter_core::variant::{Variant, VariantCipher};
+///
+/// let cipher = VariantCipher::new(Variant::Extended32, &[7; 32]).unwrap();
+/// let encrypted = cipher.encrypt(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+///
+/// assert_eq!(encrypted.len(), 32 + 16);
+/// assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct VariantCipher {
+    variant: Variant,
+    key: Vec<u8>,
+}
+
+/// Key is redacted.
+impl core::fmt::Debug for VariantCipher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("VariantCipher")
+            .field("variant", &self.variant)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VariantCipher {
+    /// Creates a cipher of `variant` with raw `key` bytes.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidKeyLength`] - if `key`'s length is not [`Variant::key_length`].
+    pub fn new(variant: Variant, key: &[u8]) -> Result<Self, Error> {
+        if key.len() != variant.key_length() {
+            return Err(Error::InvalidKeyLength);
+        }
+
+        Ok(Self {
+            variant,
+            key: key.to_vec(),
+        })
+    }
+
+    /// Creates a cipher of `variant` with a key parsed from hex string, which is twice as long as [`Variant::key_length`].
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidKeyLength`] - if `key`'s length is not twice [`Variant::key_length`].
+    /// - [`Error::InvalidKey`] - if `key` contains non-hex characters.
+    pub fn from_hex_str(variant: Variant, key: &str) -> Result<Self, Error> {
+        if key.len() != variant.key_length() * 2 {
+            return Err(Error::InvalidKeyLength);
+        }
+
+        let key = key
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                core::str::from_utf8(pair)
+                    .ok()
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or(Error::InvalidKey)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(variant, &key)
+    }
+
+    /// Returns the variant of the cipher.
+    #[must_use]
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Returns the key bytes.
+    #[must_use]
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    fn xor_buffer(&self, buffer: &mut [u8]) {
+        for (byte, key_byte) in buffer.iter_mut().zip(&self.key) {
+            *byte ^= key_byte;
+        }
+    }
+
+    /// Decrypts file content.
+    ///
+    /// # Returns
+    ///
+    /// - [`Vec<u8>`] containing decrypted data.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::AlreadyDecrypted`] - if passed `file_content` data is a plain, not encrypted asset.
+    /// - [`Error::InvalidMagic`] - if passed `file_content` data doesn't start with [`Variant::magic`].
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data is too short to contain the header.
+    pub fn decrypt(&self, file_content: &[u8]) -> Result<Vec<u8>, Error> {
+        let magic = self.variant.magic();
+
+        if !file_content.starts_with(magic) {
+            if let AssetState::Decrypted(file_type) =
+                AssetState::detect(file_content)
+            {
+                return Err(Error::AlreadyDecrypted(file_type));
+            }
+
+            let Some(header) = file_content.get(..magic.len()) else {
+                return Err(Error::InvalidHeader);
+            };
+
+            let mut expected = [0; HEADER_LENGTH];
+            expected[..magic.len()].copy_from_slice(magic);
+            let mut got = [0; HEADER_LENGTH];
+            got[..header.len()].copy_from_slice(header);
+
+            return Err(Error::InvalidMagic { expected, got });
+        }
+
+        let Some(payload) = file_content.get(self.variant.header_length()..)
+        else {
+            return Err(Error::InvalidHeader);
+        };
+
+        let mut result = payload.to_vec();
+        self.xor_buffer(&mut result);
+        Ok(result)
+    }
+
+    /// Encrypts file content.
+    ///
+    /// The header is [`Variant::magic`], padded with zeros to [`Variant::header_length`].
+    #[must_use]
+    pub fn encrypt(&self, file_content: &[u8]) -> Vec<u8> {
+        let header_length = self.variant.header_length();
+        let magic = self.variant.magic();
+
+        let mut result = Vec::with_capacity(header_length + file_content.len());
+        result.extend_from_slice(magic);
+        result.resize(header_length, 0);
+        result.extend_from_slice(file_content);
+        self.xor_buffer(&mut result[header_length..]);
+        result
+    }
+}
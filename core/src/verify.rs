@@ -0,0 +1,355 @@
+//! Format-specific validation of decrypted data.
+//!
+//! Decrypting with a wrong key doesn't fail, it just produces garbage in the first 16 bytes. Validating the output detects that immediately.
+
+use crate::FileType;
+use core::convert::TryFrom;
+
+/// Result of [`validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationResult {
+    /// Data is structurally valid.
+    Valid,
+    /// Data is invalid at `offset`, for the given reason.
+    Invalid {
+        /// Offset of the first invalid structure in the data.
+        offset: usize,
+        /// Human-readable reason.
+        reason: &'static str,
+    },
+    /// There's no validator for the file type, e.g. [`FileType::Other`].
+    Unsupported,
+}
+
+impl ValidationResult {
+    /// Returns whether the data is [`ValidationResult::Valid`].
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Valid)
+    }
+}
+
+const fn invalid(offset: usize, reason: &'static str) -> ValidationResult {
+    ValidationResult::Invalid { offset, reason }
+}
+
+/// Builds a CRC-32 lookup table for `polynomial`, either in reflected (LSB-first) or normal (MSB-first) bit order.
+const fn crc_table(polynomial: u32, reflected: bool) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = if reflected {
+            i as u32
+        } else {
+            (i as u32) << 24
+        };
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if reflected {
+                if crc & 1 == 0 {
+                    crc >> 1
+                } else {
+                    (crc >> 1) ^ polynomial
+                }
+            } else if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ polynomial
+            };
+
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+const OGG_CRC_POLYNOMIAL: u32 = 0x04c1_1db7;
+
+// PNG uses the common reflected CRC-32, OGG uses the same polynomial, but in normal bit order and without final XOR.
+const PNG_CRC_TABLE: [u32; 256] = crc_table(0xedb8_8320, true);
+const OGG_CRC_TABLE: [u32; 256] = crc_table(OGG_CRC_POLYNOMIAL, false);
+
+pub(crate) fn png_crc(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+
+    for byte in data {
+        crc = PNG_CRC_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize]
+            ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+/// Computes the CRC of OGG page `data`. The page's own CRC field must be zeroed.
+pub(crate) fn ogg_crc(data: &[u8]) -> u32 {
+    ogg_crc_update(0, data)
+}
+
+/// Continues computing the CRC of OGG page data, `crc` of whose preceding part is already computed.
+pub(crate) fn ogg_crc_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = OGG_CRC_TABLE[(((crc >> 24) ^ u32::from(*byte)) & 0xff) as usize]
+            ^ (crc << 8);
+    }
+
+    crc
+}
+
+/// Returns 4 bytes which, written in place of zeroed bytes followed by `trailing` more bytes of OGG page, change the page's CRC by `difference`.
+///
+/// OGG CRC has neither initial value nor final XOR, so it's linear: the change is the CRC of the bytes followed by `trailing` zeros, i.e. the bytes as a polynomial multiplied by x^(32 + 8 * `trailing`) modulo the CRC polynomial.
+/// x is invertible modulo the polynomial, so dividing `difference` by x that many times gives the only solution.
+pub(crate) fn solve_ogg_crc(difference: u32, trailing: usize) -> [u8; 4] {
+    let mut value = difference;
+
+    for _ in 0..trailing.saturating_mul(8).saturating_add(32) {
+        value = if value & 1 == 0 {
+            value >> 1
+        } else {
+            ((value ^ OGG_CRC_POLYNOMIAL) >> 1) | 0x8000_0000
+        };
+    }
+
+    value.to_be_bytes()
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads little-endian `u32` at `offset` of `data`, or returns [`None`] if it's out of bounds.
+#[must_use]
+pub fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn validate_png(data: &[u8]) -> ValidationResult {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if !data.starts_with(SIGNATURE) {
+        return invalid(0, "Invalid PNG signature.");
+    }
+
+    let mut offset = SIGNATURE.len();
+
+    loop {
+        let Some(length) = be_u32(data, offset) else {
+            return invalid(offset, "PNG ends without IEND chunk.");
+        };
+
+        let chunk_end = (offset + 8).saturating_add(length as usize);
+
+        let (Some(chunk), Some(crc)) =
+            (data.get(offset + 4..chunk_end), be_u32(data, chunk_end))
+        else {
+            return invalid(offset, "PNG chunk exceeds the data.");
+        };
+
+        if png_crc(chunk) != crc {
+            return invalid(offset, "PNG chunk CRC mismatch.");
+        }
+
+        if &chunk[..4] == b"IEND" {
+            return ValidationResult::Valid;
+        }
+
+        offset = chunk_end + 4;
+    }
+}
+
+fn validate_ogg(data: &[u8]) -> ValidationResult {
+    const HEADER_SIZE: usize = 27;
+    const CRC_POS: usize = 22;
+
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let Some(header) = data.get(offset..offset + HEADER_SIZE) else {
+            return invalid(offset, "OGG page header exceeds the data.");
+        };
+
+        if &header[..4] != b"OggS" || header[4] != 0 {
+            return invalid(offset, "Invalid OGG page signature.");
+        }
+
+        let segment_count = header[26] as usize;
+        let Some(segment_table) = data
+            .get(offset + HEADER_SIZE..offset + HEADER_SIZE + segment_count)
+        else {
+            return invalid(offset, "OGG segment table exceeds the data.");
+        };
+
+        let body_length: usize =
+            segment_table.iter().map(|&size| size as usize).sum();
+        let page_end = offset + HEADER_SIZE + segment_count + body_length;
+
+        let Some(page) = data.get(offset..page_end) else {
+            return invalid(offset, "OGG page exceeds the data.");
+        };
+
+        let mut page = page.to_vec();
+        let expected = le_u32(&page, CRC_POS).unwrap_or_default();
+        page[CRC_POS..CRC_POS + 4].fill(0);
+
+        if ogg_crc(&page) != expected {
+            return invalid(offset, "OGG page CRC mismatch.");
+        }
+
+        offset = page_end;
+    }
+
+    if offset == 0 {
+        return invalid(0, "OGG has no pages.");
+    }
+
+    ValidationResult::Valid
+}
+
+fn validate_m4a(data: &[u8]) -> ValidationResult {
+    if data.get(4..8) != Some(b"ftyp") {
+        return invalid(0, "M4A doesn't start with ftyp box.");
+    }
+
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let (Some(size), Some(box_type)) =
+            (be_u32(data, offset), data.get(offset + 4..offset + 8))
+        else {
+            return invalid(offset, "M4A box header exceeds the data.");
+        };
+
+        if !box_type
+            .iter()
+            .all(|&byte| byte.is_ascii_alphanumeric() || byte == b' ')
+        {
+            return invalid(offset, "Invalid M4A box type.");
+        }
+
+        let size = match size {
+            // Box extends to the end of the data.
+            0 => data.len() - offset,
+            // Actual size is in the 64-bit field after the type.
+            1 => match data.get(offset + 8..offset + 16) {
+                Some(large) => {
+                    let mut bytes = [0; 8];
+                    bytes.copy_from_slice(large);
+                    usize::try_from(u64::from_be_bytes(bytes))
+                        .unwrap_or(usize::MAX)
+                }
+                None => {
+                    return invalid(offset, "M4A box header exceeds the data.");
+                }
+            },
+            size => size as usize,
+        };
+
+        if size < 8 || data.len() - offset < size {
+            return invalid(offset, "M4A box size is out of bounds.");
+        }
+
+        offset += size;
+    }
+
+    ValidationResult::Valid
+}
+
+fn validate_webp(data: &[u8]) -> ValidationResult {
+    if !data.starts_with(b"RIFF") || data.get(8..12) != Some(b"WEBP") {
+        return invalid(0, "Invalid WEBP signature.");
+    }
+
+    if le_u32(data, 4).map(|size| (size as usize).saturating_add(8))
+        != Some(data.len())
+    {
+        return invalid(4, "WEBP RIFF size doesn't match the data.");
+    }
+
+    let mut offset = 12;
+
+    while offset < data.len() {
+        let Some(size) = le_u32(data, offset + 4) else {
+            return invalid(offset, "WEBP chunk header exceeds the data.");
+        };
+
+        // Chunks are padded to even size.
+        let chunk_end =
+            (offset + 8).saturating_add((size as usize).saturating_add(1) & !1);
+
+        if chunk_end > data.len() {
+            return invalid(offset, "WEBP chunk exceeds the data.");
+        }
+
+        offset = chunk_end;
+    }
+
+    ValidationResult::Valid
+}
+
+fn validate_wav(data: &[u8]) -> ValidationResult {
+    if !data.starts_with(b"RIFF") || data.get(8..12) != Some(b"WAVE") {
+        return invalid(0, "Invalid WAV signature.");
+    }
+
+    if le_u32(data, 4).map(|size| (size as usize).saturating_add(8))
+        != Some(data.len())
+    {
+        return invalid(4, "WAV RIFF size doesn't match the data.");
+    }
+
+    if data.get(12..16) != Some(b"fmt ") {
+        return invalid(12, "WAV doesn't start with `fmt ` chunk.");
+    }
+
+    let mut offset = 12;
+
+    while offset < data.len() {
+        let Some(size) = le_u32(data, offset + 4) else {
+            return invalid(offset, "WAV chunk header exceeds the data.");
+        };
+
+        // Chunks are padded to even size.
+        let chunk_end =
+            (offset + 8).saturating_add((size as usize).saturating_add(1) & !1);
+
+        if chunk_end > data.len() {
+            return invalid(offset, "WAV chunk exceeds the data.");
+        }
+
+        offset = chunk_end;
+    }
+
+    ValidationResult::Valid
+}
+
+/// Validates the structure of decrypted `data` of `file_type`.
+///
+/// - PNG: signature, chunk CRCs, and presence of `IEND` chunk.
+/// - OGG: page signatures and CRCs.
+/// - M4A: `ftyp` box at the start, box types and sizes.
+/// - WEBP: RIFF size and chunk sizes.
+/// - WAV: RIFF size, `fmt ` chunk at the start, and chunk sizes.
+///
+/// # Returns
+///
+/// - [`ValidationResult::Unsupported`] for [`FileType::JSON`] and [`FileType::Other`].
+/// - [`ValidationResult::Valid`] or [`ValidationResult::Invalid`] otherwise.
+#[must_use]
+pub fn validate(data: &[u8], file_type: FileType) -> ValidationResult {
+    match file_type {
+        FileType::PNG => validate_png(data),
+        FileType::OGG => validate_ogg(data),
+        FileType::M4A => validate_m4a(data),
+        FileType::WEBP => validate_webp(data),
+        FileType::WAV => validate_wav(data),
+        FileType::JSON | FileType::Other(_) => ValidationResult::Unsupported,
+    }
+}
@@ -0,0 +1,26 @@
+//! Decrypts arbitrary data as every file type, deriving the key from the data itself.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpgm_asset_decrypter_core::{Decrypter, FileType, decrypt_auto, inspect};
+
+const FILE_TYPES: [FileType; 7] = [
+    FileType::PNG,
+    FileType::OGG,
+    FileType::M4A,
+    FileType::WEBP,
+    FileType::WAV,
+    FileType::JSON,
+    FileType::Other([0; 16]),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let _ = inspect(data);
+    let _ = decrypt_auto(data, None);
+
+    for file_type in FILE_TYPES {
+        let _ = Decrypter::new().decrypt(data, file_type);
+        let _ = Decrypter::new().set_key_from_file(data, file_type);
+    }
+});
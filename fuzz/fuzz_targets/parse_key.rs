@@ -0,0 +1,11 @@
+//! Parses arbitrary strings as keys.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpgm_asset_decrypter_core::EncryptionKey;
+
+fuzz_target!(|key: &str| {
+    let _ = EncryptionKey::parse_lossy(key);
+    let _ = EncryptionKey::from_hex_str(key);
+});
@@ -0,0 +1,10 @@
+//! Parses arbitrary data as `System.json`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpgm_asset_decrypter_core::system_json::extract_key_from_system_json;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = extract_key_from_system_json(data);
+});
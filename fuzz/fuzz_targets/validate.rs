@@ -0,0 +1,18 @@
+//! Validates arbitrary data as every file type.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpgm_asset_decrypter_core::{FileType, verify::validate};
+
+fuzz_target!(|data: &[u8]| {
+    for file_type in [
+        FileType::PNG,
+        FileType::OGG,
+        FileType::M4A,
+        FileType::WEBP,
+        FileType::WAV,
+    ] {
+        let _ = validate(data, file_type);
+    }
+});
@@ -0,0 +1,73 @@
+#ifndef RPGM_ASSET_DECRYPTER_H
+#define RPGM_ASSET_DECRYPTER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* ABI version this header describes. Compare against rpgm_abi_version() at load time. */
+#define RPGM_ABI_VERSION 1
+
+#define RPGM_FILE_TYPE_PNG 0
+#define RPGM_FILE_TYPE_OGG 1
+#define RPGM_FILE_TYPE_M4A 2
+#define RPGM_FILE_TYPE_WEBP 3
+#define RPGM_FILE_TYPE_WAV 4
+#define RPGM_FILE_TYPE_JSON 5
+
+typedef enum RpgmStatus {
+    RPGM_OK = 0,
+    RPGM_NULL_POINTER = 1,
+    RPGM_KEY_NOT_SET = 2,
+    RPGM_INVALID_KEY_LENGTH = 3,
+    RPGM_INVALID_HEADER = 4,
+    RPGM_UNEXPECTED_EOF = 5,
+    RPGM_UNSUPPORTED_FILE_TYPE = 6,
+    RPGM_PANIC = 7,
+    RPGM_OTHER = 8,
+} RpgmStatus;
+
+/* Byte buffer allocated by the library. Release with rpgm_buffer_free(). */
+typedef struct RpgmBuffer {
+    uint8_t *data;
+    size_t len;
+} RpgmBuffer;
+
+/* Opaque decrypter handle. */
+typedef struct RpgmDecrypter RpgmDecrypter;
+
+/* Returns the ABI version of the loaded library. */
+uint32_t rpgm_abi_version(void);
+
+/* Returns the semver version of the loaded library as a static NUL-terminated string. Must not be freed. */
+const char *rpgm_version(void);
+
+/* Creates a new decrypter. Release with rpgm_decrypter_free(). */
+RpgmDecrypter *rpgm_decrypter_new(void);
+
+/* Releases a decrypter. Passing NULL is a no-op. */
+void rpgm_decrypter_free(RpgmDecrypter *decrypter);
+
+/* Sets the key to the NUL-terminated 32-character hex string. */
+RpgmStatus rpgm_set_key(RpgmDecrypter *decrypter, const char *key);
+
+/* Writes the key as a NUL-terminated string into out, which must have room for 33 bytes. */
+RpgmStatus rpgm_get_key(const RpgmDecrypter *decrypter, char *out);
+
+/* Decrypts RPG Maker file data. Determines the key from the data, if it's not set. */
+RpgmStatus rpgm_decrypt(RpgmDecrypter *decrypter, const uint8_t *data, size_t len, uint8_t file_type, RpgmBuffer *out);
+
+/* Encrypts file data, prepending the RPG Maker header. Requires the key to be set. */
+RpgmStatus rpgm_encrypt(const RpgmDecrypter *decrypter, const uint8_t *data, size_t len, RpgmBuffer *out);
+
+/* Releases a buffer returned by the library. */
+void rpgm_buffer_free(RpgmBuffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RPGM_ASSET_DECRYPTER_H */
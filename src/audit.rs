@@ -0,0 +1,203 @@
+//! Integrity audit of encrypted assets, to diagnose why a game shows black images or silent audio.
+//!
+//! [`audit`] decrypts the first block of every encrypted asset with the game's key, and checks it against the signature of the asset's type, so broken files can be told apart by the cause: corruption, a wrong key, double encryption, or a plain asset under an encrypted extension.
+
+use crate::{
+    AssetInfo, AssetState, Decrypter, EncryptionKey, Error, FileError,
+    FileType, HEADER_LENGTH, RPGM_HEADER,
+    container::{AsarContainer, is_asar},
+    inspect,
+    scan::{HEAD_LENGTH, ScannedAsset, encrypted_assets, read_asset},
+    xor_header,
+};
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+/// Problem of an asset found by [`audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// Asset doesn't start with the RPG Maker header, and isn't a plain asset either, e.g. its start is damaged.
+    InvalidHeader,
+    /// Asset is a plain asset of the given type under an encrypted extension, which the game fails to decrypt.
+    NotEncrypted(FileType),
+    /// Asset ends before its first encrypted block.
+    Truncated,
+    /// Asset is encrypted twice, e.g. an encrypted asset was encrypted again while repacking.
+    DoubleEncrypted,
+    /// Asset decrypts to the signature of another type than its extension declares.
+    TypeMismatch {
+        /// Type the asset decrypts to.
+        detected: FileType,
+    },
+    /// Asset is intact, but encrypted with another key, e.g. it was copied from another game.
+    WrongKey {
+        /// [`EncryptionKey::fingerprint`] of the key derived from the asset.
+        fingerprint: String,
+    },
+    /// Asset's first block decrypts to garbage, and its key can't be derived from it either.
+    Corrupted,
+}
+
+impl Display for AuditIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => f.write_str("invalid RPG Maker header"),
+            Self::NotEncrypted(file_type) => {
+                write!(f, "not encrypted, plain {file_type} data")
+            }
+            Self::Truncated => f.write_str("truncated"),
+            Self::DoubleEncrypted => f.write_str("encrypted twice"),
+            Self::TypeMismatch { detected } => {
+                write!(f, "decrypts to {detected} data")
+            }
+            Self::WrongKey { fingerprint } => {
+                write!(f, "encrypted with another key (#{fingerprint})")
+            }
+            Self::Corrupted => f.write_str("corrupted"),
+        }
+    }
+}
+
+/// Asset with a problem, found by [`audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditFinding {
+    /// The asset.
+    pub asset: ScannedAsset,
+    /// Its problem.
+    pub issue: AuditIssue,
+}
+
+/// Result of [`audit`].
+#[derive(Debug, Default)]
+pub struct AuditReport {
+    /// Number of checked assets, including the ones with problems.
+    pub checked: usize,
+    /// Assets with problems, sorted by path.
+    pub findings: Vec<AuditFinding>,
+    /// Errors of scanning or reading assets, which weren't checked.
+    pub errors: Vec<FileError>,
+}
+
+impl AuditReport {
+    /// Returns whether all assets were checked, and none has problems.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty() && self.errors.is_empty()
+    }
+}
+
+/// Checks the start of encrypted asset `head` of type `file_type` against `key`.
+fn check(
+    head: &[u8],
+    file_type: FileType,
+    key: &EncryptionKey,
+) -> Option<AuditIssue> {
+    match AssetState::detect(head) {
+        AssetState::Encrypted => {}
+        AssetState::Decrypted(file_type) => {
+            return Some(AuditIssue::NotEncrypted(file_type));
+        }
+        AssetState::Unknown => return Some(AuditIssue::InvalidHeader),
+    }
+
+    let Some(first_block) = head.get(..HEADER_LENGTH * 2) else {
+        return Some(AuditIssue::Truncated);
+    };
+
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key(*key);
+
+    match decrypter.decrypt_in_place(&mut first_block.to_vec(), file_type) {
+        Ok(_) => return None,
+        Err(Error::DoubleEncrypted) => {
+            return Some(AuditIssue::DoubleEncrypted);
+        }
+        Err(_) => {}
+    }
+
+    let mut block = [0; HEADER_LENGTH];
+    block.copy_from_slice(&first_block[HEADER_LENGTH..]);
+    xor_header(key, &mut block);
+
+    if let Some(detected) = FileType::detect(&block) {
+        return Some(AuditIssue::TypeMismatch { detected });
+    }
+
+    // Encrypted twice with another outer key, the asset is the RPG Maker header followed by the encrypted asset itself, which still decrypts with `key`.
+    if let Some(inner_block) = head.get(HEADER_LENGTH * 2..HEADER_LENGTH * 3) {
+        let mut inner = RPGM_HEADER.to_vec();
+        inner.extend_from_slice(inner_block);
+
+        if decrypter.decrypt_in_place(&mut inner, file_type).is_ok() {
+            return Some(AuditIssue::DoubleEncrypted);
+        }
+    }
+
+    match inspect(head) {
+        Ok(AssetInfo {
+            file_type: Some(detected),
+            key: Some(key),
+            ..
+        }) if detected == file_type => Some(AuditIssue::WrongKey {
+            fingerprint: key.fingerprint(),
+        }),
+        _ => Some(AuditIssue::Corrupted),
+    }
+}
+
+/// Recursively scans `root` for encrypted assets, like [`encrypted_assets`], and checks that every one of them decrypts with `key` to the signature of its type.
+///
+/// Only the start of every asset is read. Assets of `app.asar` archives are read from the archive.
+///
+/// # Parameters
+///
+/// - `root` - Root directory to scan, e.g. game's root or `www` folder.
+/// - `key` - Key of the game, e.g. from [`crate::project::find_encryption_info`].
+///
+/// # Returns
+///
+/// - [`AuditReport`] with assets that have problems, see [`AuditIssue`], and errors of assets that couldn't be scanned or read.
+#[must_use]
+pub fn audit(root: &Path, key: &EncryptionKey) -> AuditReport {
+    let mut report = AuditReport::default();
+
+    let mut asar = if is_asar(root) {
+        match AsarContainer::open(root) {
+            Ok(container) => Some(container),
+            Err(err) => {
+                report.errors.push(err);
+                return report;
+            }
+        }
+    } else {
+        None
+    };
+
+    for asset in encrypted_assets(root) {
+        let asset = match asset {
+            Ok(asset) => asset,
+            Err(err) => {
+                report.errors.push(err);
+                continue;
+            }
+        };
+
+        let head = match read_asset(asar.as_mut(), &asset, HEAD_LENGTH) {
+            Ok(head) => head,
+            Err(err) => {
+                report.errors.push(FileError::new(&asset.path, err));
+                continue;
+            }
+        };
+
+        report.checked += 1;
+
+        if let Some(issue) = check(&head, asset.file_type, key) {
+            report.findings.push(AuditFinding { asset, issue });
+        }
+    }
+
+    report
+}
@@ -0,0 +1,2209 @@
+//! Batch processing of RPG Maker assets.
+
+use crate::{
+    Decrypter, ENCRYPTED_ASSET_EXTS, Encrypter, EncryptionKey, Engine, Error,
+    FileError, FileType, HEADER_LENGTH,
+    container::{AsarContainer, is_asar},
+    fs::{
+        WalkOptions, copy_metadata, decrypted_files, read_into_with_timeout,
+        read_with_timeout, walk_with, write_atomic, write_tail_with_timeout,
+        write_with_timeout,
+    },
+    key_store::KeyStore,
+    manifest::{self, Manifest, ManifestRecord, ManifestWriter},
+    path::{
+        OutputNaming, decrypted_path_with_extension, encrypted_name,
+        matches_glob,
+    },
+    project::{DetectEngine, find_encryption_info},
+    registry::SignatureRegistry,
+    system_json::{
+        extract_key_from_system_json, extract_key_from_system_json_file,
+        set_encryption,
+    },
+    vfs::{DirFs, VirtualFs},
+};
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::{create_dir_all, metadata, remove_dir_all, remove_file, rename},
+    io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, available_parallelism},
+    time::{Duration, Instant},
+};
+
+/// Options for [`decrypt_dir`].
+///
+/// [`std::fmt::Debug`] output is redacted: keys of [`BatchOptions::key`] and [`BatchOptions::key_overrides`] are replaced with their [`EncryptionKey::fingerprint`]s.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Default)]
+pub struct BatchOptions {
+    /// Encryption key. If not set, it's read from `System.json` of the game `input` belongs to (see [`crate::project::find_encryption_info`]), or, if there's none, determined from the first PNG asset, or from the first asset at all, if there are no PNGs.
+    ///
+    /// Keys read from `System.json` or derived from files are checked per directory: files of a directory that don't match it are decrypted with a key derived from that directory instead.
+    pub key: Option<EncryptionKey>,
+    /// Keys of files matching glob patterns (see [`crate::path::matches_glob`]), relative to `input` or its `www` folder, e.g. `audio/**`. The first matching pattern wins over [`BatchOptions::key`].
+    ///
+    /// Some games use different keys for different directories, because they were edited after deployment.
+    pub key_overrides: Vec<(String, EncryptionKey)>,
+    /// How `input` is walked: whether symbolic links are followed, and how deep.
+    pub walk: WalkOptions,
+    /// Glob patterns (see [`crate::path::matches_glob`]) of files to process, relative to `input`, e.g. `img/pictures/**`. If empty, all files are processed.
+    ///
+    /// Patterns are also matched relative to `www` folder of deployed MV games, so `img/pictures/**` selects the same files whether `input` is the game root or its `www` folder.
+    pub include: Vec<String>,
+    /// Glob patterns (see [`crate::path::matches_glob`]) of files to leave out, relative to `input` or its `www` folder, e.g. `audio/bgm/**`. Exclusions win over [`BatchOptions::include`].
+    ///
+    /// Files that aren't selected aren't reported, and keys aren't derived from them.
+    pub exclude: Vec<String>,
+    /// How decrypted outputs are named.
+    pub naming: OutputNaming,
+    /// What happens when a file's content doesn't match its extension.
+    pub mismatch: MismatchPolicy,
+    /// Per-file and per-run timeouts.
+    pub timeouts: Timeouts,
+    /// Custom file types to decrypt in addition to the built-in ones.
+    pub signatures: SignatureRegistry,
+    /// Where decrypted outputs are written. If not set, they're written under the `output` directory passed to [`decrypt_dir`].
+    pub output_policy: Option<OutputPolicy>,
+    /// What happens when a file's output already exists.
+    pub collision: CollisionPolicy,
+    /// Store of known keys. If set and [`BatchOptions::key`] is not, the key of the project containing `input` is taken from it, and a newly derived key is recorded to it.
+    pub key_store: Option<Arc<Mutex<KeyStore>>>,
+    /// Whether the run is all-or-nothing.
+    ///
+    /// If set, outputs are staged next to their destinations, and moved into place only if no file failed. Otherwise, staged outputs and directories created by the run are removed, and processed files are reported as [`FileStatus::RolledBack`].
+    /// With [`OutputPolicy::Overwrite`], inputs are removed only after all outputs are in place.
+    /// [`ProgressEvent::Succeeded`] is emitted as soon as the file is staged.
+    pub transactional: bool,
+    /// Path to the manifest to append a record of every written output to (see [`crate::manifest`]).
+    ///
+    /// In [`BatchOptions::transactional`] runs, records are appended only once outputs are in place.
+    pub manifest: Option<PathBuf>,
+    /// Manifest of previous runs. Files recorded in it are skipped, as long as their outputs are intact (see [`ManifestRecord::is_intact`]).
+    pub resume_from: Option<Manifest>,
+    /// Whether records of [`BatchOptions::manifest`] include SHA-256 of the encrypted input and the decrypted output. Has no effect without a manifest.
+    pub checksums: bool,
+    /// Whether outputs get access and modification times, and, on Unix, permissions of their inputs (see [`crate::fs::copy_metadata`]).
+    pub preserve_metadata: bool,
+    /// Whether the run only plans its operations.
+    ///
+    /// Files are scanned, their types detected and keys derived as usual, but nothing is written, and no input is removed. Files are reported as [`FileStatus::Planned`], with the paths they would be written to according to [`BatchOptions::output_policy`], so the plan can be previewed with [`BatchReport::type_counts`] before touching the disk.
+    pub dry_run: bool,
+}
+
+impl fmt::Debug for BatchOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key_overrides: Vec<_> = self
+            .key_overrides
+            .iter()
+            .map(|(pattern, key)| (pattern, format!("#{}", key.fingerprint())))
+            .collect();
+
+        f.debug_struct("BatchOptions")
+            .field("key", &self.key)
+            .field("key_overrides", &key_overrides)
+            .field("walk", &self.walk)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("naming", &self.naming)
+            .field("mismatch", &self.mismatch)
+            .field("timeouts", &self.timeouts)
+            .field("signatures", &self.signatures)
+            .field("output_policy", &self.output_policy)
+            .field("collision", &self.collision)
+            .field("key_store", &self.key_store)
+            .field("transactional", &self.transactional)
+            .field("manifest", &self.manifest)
+            .field("resume_from", &self.resume_from)
+            .field("checksums", &self.checksums)
+            .field("preserve_metadata", &self.preserve_metadata)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+impl BatchOptions {
+    /// Creates default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [`BatchOptions::key`].
+    #[must_use]
+    pub fn key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Adds a key override of files matching glob `pattern` to [`BatchOptions::key_overrides`].
+    #[must_use]
+    pub fn key_override(
+        mut self,
+        pattern: impl Into<String>,
+        key: EncryptionKey,
+    ) -> Self {
+        self.key_overrides.push((pattern.into(), key));
+        self
+    }
+
+    /// Sets [`BatchOptions::walk`].
+    #[must_use]
+    pub fn walk(mut self, walk: WalkOptions) -> Self {
+        self.walk = walk;
+        self
+    }
+
+    /// Adds glob patterns to [`BatchOptions::include`].
+    #[must_use]
+    pub fn include(
+        mut self,
+        globs: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.include.extend(globs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds glob patterns to [`BatchOptions::exclude`].
+    #[must_use]
+    pub fn exclude(
+        mut self,
+        globs: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.exclude.extend(globs.into_iter().map(Into::into));
+        self
+    }
+
+    /// Returns whether file at `relative` path is selected by [`BatchOptions::include`] and [`BatchOptions::exclude`].
+    fn is_selected(&self, relative: &Path) -> bool {
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches_content_glob(pattern, relative)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| matches_content_glob(pattern, relative))
+    }
+
+    /// Sets [`BatchOptions::naming`].
+    #[must_use]
+    pub fn naming(mut self, naming: OutputNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets [`BatchOptions::mismatch`].
+    #[must_use]
+    pub fn mismatch(mut self, mismatch: MismatchPolicy) -> Self {
+        self.mismatch = mismatch;
+        self
+    }
+
+    /// Sets [`BatchOptions::timeouts`].
+    #[must_use]
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets [`BatchOptions::signatures`].
+    #[must_use]
+    pub fn signatures(mut self, signatures: SignatureRegistry) -> Self {
+        self.signatures = signatures;
+        self
+    }
+
+    /// Sets [`BatchOptions::output_policy`].
+    #[must_use]
+    pub fn output_policy(mut self, policy: OutputPolicy) -> Self {
+        self.output_policy = Some(policy);
+        self
+    }
+
+    /// Sets [`BatchOptions::collision`].
+    #[must_use]
+    pub fn collision(mut self, collision: CollisionPolicy) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    /// Sets [`BatchOptions::key_store`].
+    #[must_use]
+    pub fn key_store(mut self, store: Arc<Mutex<KeyStore>>) -> Self {
+        self.key_store = Some(store);
+        self
+    }
+
+    /// Sets [`BatchOptions::transactional`].
+    #[must_use]
+    pub fn transactional(mut self, transactional: bool) -> Self {
+        self.transactional = transactional;
+        self
+    }
+
+    /// Sets [`BatchOptions::manifest`].
+    #[must_use]
+    pub fn manifest(mut self, path: impl Into<PathBuf>) -> Self {
+        self.manifest = Some(path.into());
+        self
+    }
+
+    /// Sets [`BatchOptions::resume_from`].
+    #[must_use]
+    pub fn resume_from(mut self, manifest: Manifest) -> Self {
+        self.resume_from = Some(manifest);
+        self
+    }
+
+    /// Sets [`BatchOptions::checksums`].
+    #[must_use]
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Sets [`BatchOptions::preserve_metadata`].
+    #[must_use]
+    pub fn preserve_metadata(mut self, preserve: bool) -> Self {
+        self.preserve_metadata = preserve;
+        self
+    }
+
+    /// Sets [`BatchOptions::dry_run`].
+    #[must_use]
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Options for [`encrypt_dir`].
+#[derive(Debug, Clone, Default)]
+pub struct RepackOptions {
+    /// Encryption key. If not set, it's read from [`RepackOptions::system_json`].
+    pub key: Option<EncryptionKey>,
+    /// Path to the game's `System.json`.
+    pub system_json: Option<PathBuf>,
+    /// Engine, which determines encrypted extensions. If not set, it's detected from the output directory (see [`DetectEngine`]), falling back to [`Engine::Mv`].
+    pub engine: Option<Engine>,
+    /// Whether to set `hasEncryptedImages`/`hasEncryptedAudio` in [`RepackOptions::system_json`] after encrypting images/audio, and its `encryptionKey` to the key used.
+    pub update_system_json: bool,
+    /// Per-file and per-run timeouts.
+    pub timeouts: Timeouts,
+    /// Path to the manifest to append a record of every written output to (see [`crate::manifest`]).
+    pub manifest: Option<PathBuf>,
+    /// Whether records of [`RepackOptions::manifest`] include SHA-256 of the decrypted input and the encrypted output. Has no effect without a manifest.
+    pub checksums: bool,
+    /// Whether outputs get access and modification times, and, on Unix, permissions of their inputs (see [`crate::fs::copy_metadata`]).
+    pub preserve_metadata: bool,
+}
+
+impl RepackOptions {
+    /// Creates default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [`RepackOptions::key`].
+    #[must_use]
+    pub fn key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Sets [`RepackOptions::system_json`].
+    #[must_use]
+    pub fn system_json(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_json = Some(path.into());
+        self
+    }
+
+    /// Sets [`RepackOptions::engine`].
+    #[must_use]
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Sets [`RepackOptions::update_system_json`].
+    #[must_use]
+    pub fn update_system_json(mut self, update: bool) -> Self {
+        self.update_system_json = update;
+        self
+    }
+
+    /// Sets [`RepackOptions::timeouts`].
+    #[must_use]
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Sets [`RepackOptions::manifest`].
+    #[must_use]
+    pub fn manifest(mut self, path: impl Into<PathBuf>) -> Self {
+        self.manifest = Some(path.into());
+        self
+    }
+
+    /// Sets [`RepackOptions::checksums`].
+    #[must_use]
+    pub fn checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
+    }
+
+    /// Sets [`RepackOptions::preserve_metadata`].
+    #[must_use]
+    pub fn preserve_metadata(mut self, preserve: bool) -> Self {
+        self.preserve_metadata = preserve;
+        self
+    }
+}
+
+/// Final status of a single file in a batch run.
+#[derive(Debug)]
+pub enum FileStatus {
+    /// File was processed and written.
+    Succeeded,
+    /// File was skipped according to [`MismatchPolicy::Skip`], because it's already decrypted (see [`Error::AlreadyDecrypted`]), or because it's recorded in [`BatchOptions::resume_from`]. In the latter case, [`FileReport::output`] is the recorded output.
+    Skipped,
+    /// File was processed, but not written according to [`BatchOptions::dry_run`]. [`FileReport::output`] is the path it would be written to.
+    Planned,
+    /// File was processed, but its output was discarded, because another file failed in a [`BatchOptions::transactional`] run.
+    RolledBack,
+    /// Processing the file failed.
+    ///
+    /// If the run exceeded [`Timeouts::per_run`], files that weren't started fail with [`Error::Timeout`], so they can be picked up by another run.
+    Failed(FileError),
+}
+
+/// Report of a single file in a batch run.
+#[derive(Debug)]
+pub struct FileReport {
+    /// Path to the input file.
+    pub input: PathBuf,
+    /// Path to the written output file, if any.
+    pub output: Option<PathBuf>,
+    /// Decision made by the [`MismatchPolicy`], if the file got that far.
+    pub type_check: Option<TypeCheck>,
+    /// Type of the output, if the file got that far.
+    pub file_type: Option<FileType>,
+    /// Whether the output collided with an existing file, or with the output of another file of the run, see [`CollisionPolicy`].
+    pub collided: bool,
+    /// Final status of the file.
+    pub status: FileStatus,
+    /// Size of the input file, `0` if it couldn't be determined.
+    pub bytes_in: u64,
+    /// Number of bytes written, `0` if nothing was written.
+    pub bytes_out: u64,
+}
+
+/// Report of a batch run.
+///
+/// Files are always listed in the order of their input paths, regardless of the order they were processed in, so reports of different runs can be compared directly.
+///
+/// With `serde` feature, the report can be serialized, e.g. to JSON for CI pipelines, as an object with `key`, `succeeded` and `skipped` counts, `failed` array of `{ "path", "error" }` objects, `bytes_in`, `bytes_out`, `elapsed` in seconds, and `files` array of `{ "input", "output", "status", "error" }` objects.
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Key used for the run, if it was set or determined.
+    pub key: Option<EncryptionKey>,
+    /// Reports of all processed files, sorted by input path.
+    pub files: Vec<FileReport>,
+    /// Total size of all input files.
+    pub bytes_in: u64,
+    /// Total number of bytes written by succeeded files.
+    pub bytes_out: u64,
+    /// Wall-clock duration of the run.
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// Computes totals of the finished run, which started at `start`.
+    fn tally(&mut self, start: Instant) {
+        self.bytes_in = self.files.iter().map(|file| file.bytes_in).sum();
+        self.bytes_out = self.succeeded().map(|file| file.bytes_out).sum();
+        self.elapsed = start.elapsed();
+    }
+
+    /// Returns reports of files that were processed successfully.
+    pub fn succeeded(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Succeeded))
+    }
+
+    /// Returns reports of files that were skipped.
+    pub fn skipped(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Skipped))
+    }
+
+    /// Returns reports of files that failed.
+    pub fn failed(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Failed(_)))
+    }
+
+    /// Returns reports of files whose outputs collided, see [`CollisionPolicy`].
+    pub fn collisions(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| file.collided)
+    }
+
+    /// Returns reports of files that were planned, see [`BatchOptions::dry_run`].
+    pub fn planned(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|file| matches!(file.status, FileStatus::Planned))
+    }
+
+    /// Returns the numbers of succeeded and planned files of each output type, in order of the first file of the type.
+    #[must_use]
+    pub fn type_counts(&self) -> Vec<(FileType, usize)> {
+        let mut counts: Vec<(FileType, usize)> = Vec::new();
+
+        for file in self.succeeded().chain(self.planned()) {
+            let Some(file_type) = file.file_type else {
+                continue;
+            };
+
+            match counts.iter_mut().find(|(known, _)| *known == file_type) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((file_type, 1)),
+            }
+        }
+
+        counts
+    }
+}
+
+/// Serialized form of [`FileStatus::Failed`] error.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct FailureRepr<'a> {
+    path: &'a Path,
+    error: String,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> From<&'a FileError> for FailureRepr<'a> {
+    fn from(err: &'a FileError) -> Self {
+        Self {
+            path: &err.path,
+            error: err.source.to_string(),
+        }
+    }
+}
+
+/// Serialized form of [`FileReport`].
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct FileReportRepr<'a> {
+    input: &'a Path,
+    output: Option<&'a Path>,
+    status: &'static str,
+    error: Option<String>,
+    file_type: Option<String>,
+    collided: bool,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> From<&'a FileReport> for FileReportRepr<'a> {
+    fn from(file: &'a FileReport) -> Self {
+        let (status, error) = match &file.status {
+            FileStatus::Succeeded => ("succeeded", None),
+            FileStatus::Skipped => ("skipped", None),
+            FileStatus::Planned => ("planned", None),
+            FileStatus::RolledBack => ("rolled_back", None),
+            FileStatus::Failed(err) => ("failed", Some(err.source.to_string())),
+        };
+
+        Self {
+            input: &file.input,
+            output: file.output.as_deref(),
+            status,
+            error,
+            file_type: file.file_type.map(|file_type| file_type.to_string()),
+            collided: file.collided,
+            bytes_in: file.bytes_in,
+            bytes_out: file.bytes_out,
+        }
+    }
+}
+
+/// Serialized form of [`BatchReport`], with counts and failures summarized for machine consumption.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct BatchReportRepr<'a> {
+    key: Option<EncryptionKey>,
+    succeeded: usize,
+    skipped: usize,
+    failed: Vec<FailureRepr<'a>>,
+    bytes_in: u64,
+    bytes_out: u64,
+    elapsed: f64,
+    files: Vec<FileReportRepr<'a>>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for BatchReport {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        BatchReportRepr {
+            key: self.key,
+            succeeded: self.succeeded().count(),
+            skipped: self.skipped().count(),
+            failed: self
+                .files
+                .iter()
+                .filter_map(|file| match &file.status {
+                    FileStatus::Failed(err) => Some(err.into()),
+                    _ => None,
+                })
+                .collect(),
+            bytes_in: self.bytes_in,
+            bytes_out: self.bytes_out,
+            elapsed: self.elapsed.as_secs_f64(),
+            files: self.files.iter().map(Into::into).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Per-file event emitted during a batch run.
+#[derive(Debug, Clone, Copy)]
+pub enum ProgressEvent<'a> {
+    /// Processing of the file started.
+    Started {
+        /// Path to the input file.
+        input: &'a Path,
+        /// Size of the input file, `0` if it couldn't be determined.
+        bytes: u64,
+    },
+    /// File was processed and written.
+    Succeeded {
+        /// Path to the input file.
+        input: &'a Path,
+        /// Path to the written output file.
+        output: &'a Path,
+        /// Number of bytes written.
+        bytes: u64,
+    },
+    /// File was skipped according to [`MismatchPolicy::Skip`], or because it's already decrypted, or planned according to [`BatchOptions::dry_run`].
+    Skipped {
+        /// Path to the input file.
+        input: &'a Path,
+    },
+    /// Processing the file failed.
+    Failed {
+        /// Path to the input file.
+        input: &'a Path,
+        /// Error the file failed with.
+        error: &'a FileError,
+    },
+}
+
+impl<'a> ProgressEvent<'a> {
+    /// Returns the event reporting the final status of `file`.
+    fn finished(file: &'a FileReport) -> Self {
+        match (&file.status, &file.output) {
+            (FileStatus::Succeeded, Some(output)) => Self::Succeeded {
+                input: &file.input,
+                output,
+                bytes: file.bytes_out,
+            },
+            (FileStatus::Failed(error), _) => Self::Failed {
+                input: &file.input,
+                error,
+            },
+            _ => Self::Skipped { input: &file.input },
+        }
+    }
+}
+
+/// Receiver of [`ProgressEvent`]s, e.g. a progress bar.
+///
+/// Implemented for any `Fn(ProgressEvent)` closure.
+/// With `parallel` feature, events of different files may arrive concurrently and interleaved, so implementations must be [`Sync`].
+pub trait ProgressSink: Sync {
+    /// Called for every event of every file.
+    fn on_event(&self, event: ProgressEvent<'_>);
+}
+
+impl<F: Fn(ProgressEvent<'_>) + Sync> ProgressSink for F {
+    fn on_event(&self, event: ProgressEvent<'_>) {
+        self(event);
+    }
+}
+
+/// Timeouts applied to batch operations, so a hung network mount or dying disk results in [`Error::Timeout`] instead of a job that never finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timeouts {
+    /// Maximum time spent reading or writing a single file.
+    pub per_file: Option<Duration>,
+    /// Maximum time spent on the whole run. Checked between files; files that weren't started before it expired are left unprocessed.
+    pub per_run: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Returns whether the run that started at `start` has exceeded [`Timeouts::per_run`].
+    #[must_use]
+    pub fn run_expired(&self, start: Instant) -> bool {
+        self.per_run
+            .map_or(false, |per_run| start.elapsed() >= per_run)
+    }
+}
+
+/// Defines what happens when a file's decrypted content doesn't match the type its extension declares.
+///
+/// Renamed assets are common, e.g. an OGG file shipped as `.rpgmvp`.
+///
+/// # Note
+///
+/// Mismatches can only be detected when the key is known beforehand, e.g. set from `System.json` or derived from another file.
+/// A key derived from the file itself always produces content matching the declared type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MismatchPolicy {
+    /// Fail the file with [`Error::TypeMismatch`].
+    Error,
+    /// Trust the content, and write the file with the detected type's extension.
+    #[default]
+    TrustContent,
+    /// Don't write the file at all.
+    Skip,
+}
+
+/// Decision made for a single file according to [`MismatchPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeCheck {
+    /// Content matches the declared type, or its type couldn't be detected.
+    Match,
+    /// Content doesn't match the declared type, and the detected type was used instead.
+    Trusted {
+        declared: FileType,
+        detected: FileType,
+    },
+    /// Content doesn't match the declared type, and the file was skipped.
+    Skipped {
+        declared: FileType,
+        detected: FileType,
+    },
+}
+
+impl TypeCheck {
+    /// Returns the type the file should be written as, or [`None`] if it should be skipped.
+    #[must_use]
+    pub fn output_type(self, declared: FileType) -> Option<FileType> {
+        match self {
+            Self::Match => Some(declared),
+            Self::Trusted { detected, .. } => Some(detected),
+            Self::Skipped { .. } => None,
+        }
+    }
+}
+
+/// Defines what [`decrypt_dir`] does when a file's output already exists on disk.
+///
+/// Collided files are reported with [`FileReport::collided`] set, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Don't write the file at all, and report it as [`FileStatus::Skipped`], with the existing file as its output.
+    Skip,
+    /// Fail the file with [`Error::Io`] of [`std::io::ErrorKind::AlreadyExists`] kind.
+    Error,
+    /// Write the output under the first free name with a numeric suffix inserted before the extension.
+    ///
+    /// `img/image.png` -> `img/image.1.png`
+    ///
+    /// Outputs claimed by other files of the run are renamed the same way, instead of failing.
+    RenameWithSuffix,
+}
+
+/// Returns `output` with numeric `suffix` inserted before its extension.
+fn suffixed_path(output: &Path, suffix: usize) -> PathBuf {
+    match output.extension() {
+        Some(ext) => {
+            let mut new_ext = OsString::from(format!("{suffix}."));
+            new_ext.push(ext);
+            output.with_extension(new_ext)
+        }
+        None => output.with_extension(suffix.to_string()),
+    }
+}
+
+/// Defines where [`decrypt_dir`] writes decrypted outputs.
+///
+/// With any policy, two inputs are never written to the same output: the first file that claims the output wins, and the rest fail with [`Error::Io`] of [`std::io::ErrorKind::AlreadyExists`] kind, e.g. `image.rpgmvp` and `image.png_` in the same directory, unless [`CollisionPolicy::RenameWithSuffix`] is used.
+/// With `parallel` feature, which file is first is not determined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputPolicy {
+    /// Replaces encrypted files with decrypted ones: outputs are written next to the inputs with decrypted extensions, and the inputs are removed after successful writes.
+    ///
+    /// `img/image.rpgmvp` -> `img/image.png`
+    Overwrite,
+    /// Writes outputs next to the inputs, inserting the suffix before the decrypted extension, so that unencrypted assets with the same name are never clobbered.
+    ///
+    /// `img/image.rpgmvp` with `dec` suffix -> `img/image.dec.png`
+    SideBySide {
+        /// Suffix inserted before the extension.
+        suffix: String,
+    },
+    /// Writes outputs under `root`, preserving the directory structure, and names them according to [`BatchOptions::naming`].
+    SeparateTree {
+        /// Root directory to write outputs to.
+        root: PathBuf,
+    },
+}
+
+/// Returns the path decrypted `relative` input is written to according to [`BatchOptions::output_policy`].
+fn output_path_of(
+    options: &BatchOptions,
+    input_root: &Path,
+    output_root: &Path,
+    relative: &Path,
+    ext: &str,
+) -> PathBuf {
+    match &options.output_policy {
+        Some(OutputPolicy::Overwrite) => {
+            input_root.join(decrypted_path_with_extension(
+                relative,
+                ext,
+                &OutputNaming::ReplaceExtension,
+            ))
+        }
+        Some(OutputPolicy::SideBySide { suffix }) => {
+            input_root.join(decrypted_path_with_extension(
+                relative,
+                ext,
+                &OutputNaming::Suffix(suffix.clone()),
+            ))
+        }
+        Some(OutputPolicy::SeparateTree { root }) => root.join(
+            decrypted_path_with_extension(relative, ext, &options.naming),
+        ),
+        None => output_root.join(decrypted_path_with_extension(
+            relative,
+            ext,
+            &options.naming,
+        )),
+    }
+}
+
+/// Checks decrypted content against the type declared by the file's extension, and applies `policy` on mismatch.
+///
+/// # Parameters
+///
+/// - `decrypted` - Decrypted file data. Only the first 16 bytes are inspected, so the output of [`crate::Decrypter::decrypt_header_only`] is enough.
+/// - `declared` - [`FileType`] declared by the file's extension.
+/// - `policy` - [`MismatchPolicy`] to apply on mismatch.
+///
+/// # Returns
+///
+/// - [`TypeCheck`] describing the decision made for the file.
+/// - [`Error`] if the types mismatch and `policy` is [`MismatchPolicy::Error`].
+///
+/// # Errors
+///
+/// - [`Error::TypeMismatch`] - if the types mismatch and `policy` is [`MismatchPolicy::Error`].
+pub fn check_type(
+    decrypted: &[u8],
+    declared: FileType,
+    policy: MismatchPolicy,
+) -> Result<TypeCheck, Error> {
+    let detected = match FileType::detect(decrypted) {
+        Some(detected) if detected != declared => detected,
+        _ => return Ok(TypeCheck::Match),
+    };
+
+    match policy {
+        MismatchPolicy::Error => {
+            Err(Error::TypeMismatch { declared, detected })
+        }
+        MismatchPolicy::TrustContent => {
+            Ok(TypeCheck::Trusted { declared, detected })
+        }
+        MismatchPolicy::Skip => Ok(TypeCheck::Skipped { declared, detected }),
+    }
+}
+
+fn file_type_of(
+    path: &Path,
+    signatures: &SignatureRegistry,
+) -> Option<FileType> {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .and_then(|ext| signatures.file_type(ext))
+}
+
+/// Emits the outcome of processing a single file, under the batch `span`, which isn't entered on worker threads.
+#[cfg(feature = "tracing")]
+fn trace_file(span: &tracing::Span, report: &FileReport) {
+    let input = report.input.display();
+
+    match &report.status {
+        FileStatus::Failed(err) => {
+            tracing::warn!(parent: span, %input, error = %err.source, "failed");
+        }
+        status => tracing::debug!(parent: span, %input, ?status, "processed"),
+    }
+}
+
+/// Emits the summary of a finished batch.
+#[cfg(feature = "tracing")]
+fn trace_report(report: &BatchReport) {
+    tracing::info!(
+        succeeded = report.succeeded().count(),
+        skipped = report.skipped().count(),
+        failed = report.failed().count(),
+        bytes_in = report.bytes_in,
+        bytes_out = report.bytes_out,
+        elapsed = ?report.elapsed,
+        "batch finished"
+    );
+}
+
+/// Returns the error of `input`, whose `output` is claimed by another file of the run.
+fn claimed_error(input: &Path, output: &Path) -> FileError {
+    FileError::new(
+        input,
+        io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is the output of another file", output.display()),
+        ),
+    )
+}
+
+/// State shared by all files of a [`decrypt_dir`] run.
+#[derive(Default)]
+struct RunState {
+    /// Outputs claimed by already processed files.
+    claimed: Mutex<HashSet<PathBuf>>,
+    /// Topmost directories created by a [`BatchOptions::transactional`] run, removed on rollback.
+    created_dirs: Mutex<Vec<PathBuf>>,
+    /// Writer of [`BatchOptions::manifest`].
+    manifest: Option<ManifestWriter>,
+    /// Records of staged outputs of a [`BatchOptions::transactional`] run, appended to the manifest on commit.
+    pending: Mutex<Vec<ManifestRecord>>,
+}
+
+impl RunState {
+    fn new(options: &BatchOptions) -> Result<Self, FileError> {
+        Ok(Self {
+            manifest: options
+                .manifest
+                .as_deref()
+                .filter(|_| !options.dry_run)
+                .map(ManifestWriter::open)
+                .transpose()?,
+            ..Self::default()
+        })
+    }
+
+    /// Claims `output`, so no other file of the run writes to it. Returns whether it wasn't claimed yet.
+    fn try_claim(&self, output: &Path) -> bool {
+        self.claimed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(output.to_path_buf())
+    }
+
+    /// Same as [`RunState::try_claim`], but fails if `output` is already claimed.
+    fn claim(&self, input: &Path, output: &Path) -> Result<(), FileError> {
+        if self.try_claim(output) {
+            Ok(())
+        } else {
+            Err(claimed_error(input, output))
+        }
+    }
+
+    /// Claims the output of `input` according to [`BatchOptions::collision`], starting with `output`.
+    ///
+    /// Returns [`None`] if the file must be skipped.
+    fn claim_output(
+        &self,
+        input: &Path,
+        output: &Path,
+        policy: CollisionPolicy,
+        report: &mut FileReport,
+    ) -> Result<Option<PathBuf>, FileError> {
+        let rename = policy == CollisionPolicy::RenameWithSuffix;
+
+        for suffix in 0.. {
+            let candidate = if suffix == 0 {
+                output.to_path_buf()
+            } else {
+                suffixed_path(output, suffix)
+            };
+
+            if !self.try_claim(&candidate) {
+                report.collided = true;
+
+                if rename {
+                    continue;
+                }
+
+                return Err(claimed_error(input, &candidate));
+            }
+
+            if !candidate.exists() {
+                return Ok(Some(candidate));
+            }
+
+            report.collided = true;
+
+            match policy {
+                CollisionPolicy::Overwrite => return Ok(Some(candidate)),
+                CollisionPolicy::Skip => {
+                    report.output = Some(candidate);
+                    report.status = FileStatus::Skipped;
+                    return Ok(None);
+                }
+                CollisionPolicy::Error => {
+                    return Err(FileError::new(
+                        candidate,
+                        io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            "output already exists",
+                        ),
+                    ));
+                }
+                CollisionPolicy::RenameWithSuffix => {}
+            }
+        }
+
+        unreachable!("suffixes are exhausted")
+    }
+
+    /// Appends `record` to the manifest, or holds it until commit in [`BatchOptions::transactional`] runs.
+    fn record(
+        &self,
+        record: ManifestRecord,
+        options: &BatchOptions,
+    ) -> Result<(), FileError> {
+        match &self.manifest {
+            Some(_) if options.transactional => {
+                self.pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(record);
+                Ok(())
+            }
+            Some(manifest) => manifest.append(&record),
+            None => Ok(()),
+        }
+    }
+    /// Records the topmost missing ancestor of `dir`, which is about to be created.
+    fn record_created(&self, dir: &Path) {
+        if let Some(topmost) =
+            dir.ancestors().take_while(|dir| !dir.exists()).last()
+        {
+            self.created_dirs
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(topmost.to_path_buf());
+        }
+    }
+
+    /// Commits staged outputs of a [`BatchOptions::transactional`] run if no file failed, or rolls them back otherwise.
+    ///
+    /// Moving outputs into place can still fail for some of them, e.g. if the disk is removed mid-way. Such files are reported as failed, so the run can be retried.
+    fn finish(self, files: &mut [FileReport], options: &BatchOptions) {
+        let failed = files
+            .iter()
+            .any(|file| matches!(file.status, FileStatus::Failed(_)));
+        let Self {
+            created_dirs,
+            manifest,
+            pending,
+            ..
+        } = self;
+        let mut pending: HashMap<PathBuf, ManifestRecord> = pending
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
+            .map(|record| (record.output.clone(), record))
+            .collect();
+
+        for file in files {
+            let (FileStatus::Succeeded, Some(output)) =
+                (&file.status, &file.output)
+            else {
+                continue;
+            };
+
+            let staged = staged_path(output);
+
+            if failed {
+                let _ = remove_file(&staged);
+                file.status = FileStatus::RolledBack;
+                continue;
+            }
+
+            let result = rename(&staged, output)
+                .map_err(|err| FileError::new(output, err))
+                .and_then(|()| {
+                    if options.output_policy == Some(OutputPolicy::Overwrite) {
+                        remove_file(&file.input)
+                            .map_err(|err| FileError::new(&file.input, err))
+                    } else {
+                        Ok(())
+                    }
+                })
+                .and_then(|()| match (&manifest, pending.remove(output)) {
+                    (Some(manifest), Some(record)) => manifest.append(&record),
+                    _ => Ok(()),
+                });
+
+            if let Err(err) = result {
+                file.status = FileStatus::Failed(err);
+            }
+        }
+
+        if failed {
+            for dir in created_dirs
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+            {
+                let _ = remove_dir_all(dir);
+            }
+        }
+    }
+}
+
+/// Returns the path `output` is staged at in a [`BatchOptions::transactional`] run.
+///
+/// Staged file is placed in the same directory, so moving it into place is a rename on the same filesystem.
+fn staged_path(output: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(output.file_name().unwrap_or_default());
+    name.push(".staged");
+    output.with_file_name(name)
+}
+
+/// Decrypts a single file of a [`decrypt_dir`] run.
+///
+/// `data` is the buffer of the worker, whose allocation is reused between files. The header is never drained from it: the output is written from past it instead, so each file is copied only by the kernel.
+#[allow(clippy::too_many_arguments)]
+fn decrypt_one(
+    decrypter: &mut Decrypter,
+    data: &mut Vec<u8>,
+    input_root: &Path,
+    output_root: &Path,
+    input: &Path,
+    options: &BatchOptions,
+    state: &RunState,
+    report: &mut FileReport,
+) -> Result<u64, FileError> {
+    let Some(declared) = file_type_of(input, &options.signatures) else {
+        return Err(FileError::new(input, Error::UnsupportedFileType));
+    };
+
+    let relative = input.strip_prefix(input_root).unwrap_or(input);
+
+    if let Some(record) = options
+        .resume_from
+        .as_ref()
+        .and_then(|manifest| manifest.get(relative))
+        .filter(|record| record.is_intact())
+    {
+        state.claim(input, &record.output)?;
+        report.output = Some(record.output.clone());
+        report.status = FileStatus::Skipped;
+        return Ok(0);
+    }
+
+    read_into_with_timeout(input, data, options.timeouts.per_file)?;
+    let input_sha256 = (options.checksums && state.manifest.is_some())
+        .then(|| manifest::sha256(data));
+
+    let header = match decrypter.decrypt_header_only(data, declared) {
+        Ok(header) => header,
+        Err(Error::AlreadyDecrypted(_)) => {
+            report.status = FileStatus::Skipped;
+            return Ok(0);
+        }
+        Err(err) => return Err(FileError::new(input, err)),
+    };
+    let type_check = check_type(&header, declared, options.mismatch)
+        .map_err(|err| FileError::new(input, err))?;
+    report.type_check = Some(type_check);
+
+    let Some(output_type) = type_check.output_type(declared) else {
+        report.status = FileStatus::Skipped;
+        return Ok(0);
+    };
+    report.file_type = Some(output_type);
+
+    decrypter
+        .decrypt_in_place(data, output_type)
+        .map_err(|err| FileError::new(input, err))?;
+
+    let ext = match output_type {
+        FileType::Other(_) => input
+            .extension()
+            .and_then(OsStr::to_str)
+            .and_then(|ext| options.signatures.decrypted_extension(ext)),
+        _ => None,
+    }
+    .unwrap_or_else(|| output_type.to_string());
+    let output =
+        output_path_of(options, input_root, output_root, relative, &ext);
+
+    let Some(output) =
+        state.claim_output(input, &output, options.collision, report)?
+    else {
+        return Ok(0);
+    };
+
+    if options.dry_run {
+        report.output = Some(output);
+        report.status = FileStatus::Planned;
+        return Ok(0);
+    }
+
+    if let Some(parent) = output.parent() {
+        if options.transactional {
+            state.record_created(parent);
+        }
+
+        create_dir_all(parent).map_err(|err| FileError::new(parent, err))?;
+    }
+
+    let payload = &data[HEADER_LENGTH..];
+    let bytes = payload.len() as u64;
+    let record = state.manifest.as_ref().map(|_| ManifestRecord {
+        input: relative.to_path_buf(),
+        output: output.clone(),
+        size: bytes,
+        hash: manifest::hash(payload),
+        input_sha256,
+        output_sha256: input_sha256.map(|_| manifest::sha256(payload)),
+    });
+
+    write_output(input, &output, data, options)?;
+
+    if let Some(record) = record {
+        state.record(record, options)?;
+    }
+
+    report.output = Some(output);
+    report.status = FileStatus::Succeeded;
+    Ok(bytes)
+}
+
+/// Writes decrypted `data` of `input`, past its header, to `output`, or stages it there in [`BatchOptions::transactional`] runs.
+fn write_output(
+    input: &Path,
+    output: &Path,
+    data: &mut Vec<u8>,
+    options: &BatchOptions,
+) -> Result<(), FileError> {
+    // Renaming staged outputs into place keeps their metadata.
+    let written = if options.transactional {
+        staged_path(output)
+    } else {
+        output.to_path_buf()
+    };
+
+    write_tail_with_timeout(
+        &written,
+        data,
+        HEADER_LENGTH,
+        options.timeouts.per_file,
+    )?;
+
+    if options.preserve_metadata {
+        copy_metadata(input, &written)?;
+    }
+
+    if !options.transactional
+        && options.output_policy == Some(OutputPolicy::Overwrite)
+    {
+        remove_file(input).map_err(|err| FileError::new(input, err))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the file of `files` to derive the key from: the first PNG, or the first asset at all, if there are no PNGs.
+///
+/// PNG header is fixed, so key derived from it is the most reliable. JSON files don't have a known header, so they are never used.
+fn key_source<'a>(
+    files: &'a [PathBuf],
+    signatures: &SignatureRegistry,
+) -> Option<&'a PathBuf> {
+    files
+        .iter()
+        .find(|path| {
+            file_type_of(path, signatures).map_or(false, FileType::is_png)
+        })
+        .or_else(|| {
+            files.iter().find(|path| {
+                file_type_of(path, signatures)
+                    .map_or(false, |file_type| !file_type.is_json())
+            })
+        })
+}
+
+/// Keys of files that don't use the key of the run: [`BatchOptions::key_overrides`], and keys of directories whose files don't match the derived key.
+#[derive(Default)]
+struct FileKeys {
+    overrides: Vec<(String, Decrypter)>,
+    dirs: HashMap<PathBuf, Decrypter>,
+}
+
+/// Returns whether relative `path` matches glob `pattern`, either as is, or relative to `www` folder of deployed MV games.
+fn matches_content_glob(pattern: &str, path: &Path) -> bool {
+    matches_glob(pattern, path)
+        || path
+            .strip_prefix("www")
+            .map_or(false, |content| matches_glob(pattern, content))
+}
+
+impl FileKeys {
+    /// Collects [`BatchOptions::key_overrides`], and, if `decrypter`'s key is not set, determines it and keys of directories from `files`, besides ones with overridden keys.
+    fn resolve(
+        decrypter: &mut Decrypter,
+        input: &Path,
+        files: &[PathBuf],
+        options: &BatchOptions,
+    ) -> Self {
+        let mut keys = Self::default();
+
+        for (pattern, key) in &options.key_overrides {
+            let mut decrypter = Decrypter::new();
+            decrypter.set_key(*key);
+            keys.overrides.push((pattern.clone(), decrypter));
+        }
+
+        if decrypter.key().is_some() {
+            return keys;
+        }
+
+        let own_key_files: Vec<PathBuf> = files
+            .iter()
+            .filter(|path| keys.override_for(input, path).is_none())
+            .cloned()
+            .collect();
+
+        if resolve_key(decrypter, input, &own_key_files, options) {
+            keys.resolve_dirs(decrypter, &own_key_files, options);
+        }
+
+        keys
+    }
+
+    fn override_for(&self, input: &Path, path: &Path) -> Option<&Decrypter> {
+        let relative = path.strip_prefix(input).unwrap_or(path);
+
+        self.overrides
+            .iter()
+            .find(|(pattern, _)| matches_content_glob(pattern, relative))
+            .map(|(_, decrypter)| decrypter)
+    }
+
+    /// Checks the derived key of `decrypter` against a file of every directory, and determines a key of its own for each directory it doesn't match.
+    fn resolve_dirs(
+        &mut self,
+        decrypter: &Decrypter,
+        files: &[PathBuf],
+        options: &BatchOptions,
+    ) {
+        let mut dirs: BTreeMap<&Path, Vec<PathBuf>> = BTreeMap::new();
+
+        for path in files {
+            if let Some(dir) = path.parent() {
+                dirs.entry(dir).or_default().push(path.clone());
+            }
+        }
+
+        for (dir, files) in dirs {
+            let Some(path) = key_source(&files, &options.signatures) else {
+                continue;
+            };
+
+            let (Some(file_type), Ok(data)) = (
+                file_type_of(path, &options.signatures),
+                read_with_timeout(path, options.timeouts.per_file),
+            ) else {
+                continue;
+            };
+
+            if !matches!(
+                decrypter.clone().decrypt(&data, file_type),
+                Err(Error::KeyMismatch { .. })
+            ) {
+                continue;
+            }
+
+            let mut dir_decrypter = Decrypter::new();
+
+            if dir_decrypter.set_key_from_file(&data, file_type).is_ok() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    dir = %dir.display(),
+                    source = %path.display(),
+                    "directory uses its own key"
+                );
+                self.dirs.insert(dir.to_path_buf(), dir_decrypter);
+            }
+        }
+    }
+
+    /// Returns the decrypter of file at `path`, if it doesn't use the key of the run.
+    fn for_file(&self, input: &Path, path: &Path) -> Option<&Decrypter> {
+        self.override_for(input, path)
+            .or_else(|| path.parent().and_then(|dir| self.dirs.get(dir)))
+    }
+}
+
+/// Sets the key of `decrypter` from [`BatchOptions::key_store`], or reads it from `System.json` of the game `input` belongs to, or derives it from `files` (see [`key_source`]), and records it in the store.
+///
+/// Returns whether the key was read or derived, so it has to be checked per directory. Keys of the store are trusted like explicit ones.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn resolve_key(
+    decrypter: &mut Decrypter,
+    input: &Path,
+    files: &[PathBuf],
+    options: &BatchOptions,
+) -> bool {
+    if let Some(store) = &options.key_store {
+        let store = store.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some((root, key)) = store.find(input) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(root = %root.display(), "key found in key store");
+            decrypter.set_key(key);
+            return false;
+        }
+    }
+
+    if let Some((path, info)) = find_encryption_info(input) {
+        if decrypter.set_key_from_str(&info.key).is_ok() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                source = %path.display(),
+                "key read from System.json"
+            );
+        }
+    }
+
+    let key_source = key_source(files, &options.signatures)
+        .filter(|_| decrypter.key().is_none());
+
+    if let Some(path) = key_source {
+        if let (Some(file_type), Ok(data)) = (
+            file_type_of(path, &options.signatures),
+            read_with_timeout(path, options.timeouts.per_file),
+        ) {
+            let result = decrypter.set_key_from_file(&data, file_type);
+
+            #[cfg(feature = "tracing")]
+            match result {
+                Ok(_) => tracing::debug!(
+                    source = %path.display(),
+                    "key derived from file"
+                ),
+                Err(err) => tracing::warn!(
+                    source = %path.display(),
+                    error = %err,
+                    "failed to derive key"
+                ),
+            }
+        }
+    }
+
+    if let (Some(store), Some(key)) =
+        (&options.key_store, decrypter.encryption_key())
+    {
+        store
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(input, key);
+    }
+
+    decrypter.key().is_some()
+}
+
+/// Recursively decrypts all encrypted assets (see [`crate::ENCRYPTED_ASSET_EXTS`] and [`BatchOptions::signatures`]) under `input`, and writes them into `output`, preserving the directory structure.
+///
+/// Outputs are written atomically (see [`crate::fs::write_atomic`]), and named according to [`BatchOptions::naming`].
+/// With `parallel` feature, files are processed on the global rayon thread pool.
+/// `input` and `output` may be the same directory.
+///
+/// If `input` is an Electron `app.asar` archive, it's read as a virtual directory, the same way [`decrypt_vfs`] reads it, so options that rely on real paths fail the run, see [`decrypt_vfs`].
+///
+/// # Parameters
+///
+/// - `input` - Root directory to search for encrypted assets, e.g. game's root or `www` folder, any folder under them, or `app.asar` archive.
+/// - `output` - Root directory to write decrypted assets to.
+/// - `options` - [`BatchOptions`].
+///
+/// # Returns
+///
+/// - [`BatchReport`] with per-file results. Failing files don't stop the run.
+/// - [`FileError`] if the run couldn't start at all.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if walking `input` fails, or `input` is an `app.asar` archive, and it can't be opened, or `options` can't be honored for it.
+pub fn decrypt_dir(
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+) -> Result<BatchReport, FileError> {
+    decrypt_dir_with_progress(input, output, options, &|_: ProgressEvent<
+        '_,
+    >| {})
+}
+
+/// Same as [`decrypt_dir`], but reports per-file [`ProgressEvent`]s to `progress`.
+///
+/// Every file gets a [`ProgressEvent::Started`] event, followed by exactly one of the other events.
+///
+/// # Errors
+///
+/// - Any error of [`decrypt_dir`].
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(input = %input.display(), output = %output.display())
+    )
+)]
+pub fn decrypt_dir_with_progress(
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+    progress: &dyn ProgressSink,
+) -> Result<BatchReport, FileError> {
+    run_decrypt_dir(input, output, options, progress, None, None)
+}
+
+/// Same as [`decrypt_dir`], but processes files on `threads` scoped threads of its own (see [`std::thread::scope`]), instead of the global rayon thread pool.
+///
+/// Small tools get multi-core throughput this way without the `parallel` feature and its dependencies.
+/// `threads` of `0` means the number of available CPUs. Reports are sorted by input path, as with [`decrypt_dir`].
+///
+/// # Errors
+///
+/// - Any error of [`decrypt_dir`].
+///
+/// # Panics
+///
+/// Resumes the panic, if any of the threads panicked.
+pub fn decrypt_dir_threads(
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+    threads: usize,
+) -> Result<BatchReport, FileError> {
+    let threads = if threads == 0 {
+        available_parallelism().map_or(1, NonZeroUsize::get)
+    } else {
+        threads
+    };
+
+    run_decrypt_dir(
+        input,
+        output,
+        options,
+        &|_: ProgressEvent<'_>| {},
+        Some(threads),
+        None,
+    )
+}
+
+/// Runs `process` for each of `files` on up to `threads` scoped threads, each with its own clone of `decrypter` and its own buffer, and returns results in the order of `files`.
+fn process_scoped<R, F>(
+    files: Vec<PathBuf>,
+    threads: usize,
+    decrypter: &Decrypter,
+    process: &F,
+) -> Vec<R>
+where
+    R: Send,
+    F: Fn(&mut Decrypter, &mut Vec<u8>, PathBuf) -> R + Sync,
+{
+    let threads = threads.min(files.len()).max(1);
+    let queue = Mutex::new(files.into_iter().enumerate());
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut decrypter = decrypter.clone();
+                    let mut buffer = Vec::new();
+                    let mut results = Vec::new();
+
+                    // The lock is released before processing, so threads pick files one by one, and a slow file doesn't hold up the rest.
+                    while let Some((i, file)) = queue
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .next()
+                    {
+                        results.push((
+                            i,
+                            process(&mut decrypter, &mut buffer, file),
+                        ));
+                    }
+
+                    results
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err))
+            })
+            .collect()
+    });
+
+    results.sort_unstable_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Returns whether file at `path` under `input` is an encrypted asset selected by `options`, which [`decrypt_dir`] processes.
+pub(crate) fn is_decryptable(
+    input: &Path,
+    path: &Path,
+    options: &BatchOptions,
+) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .map_or(false, |ext| {
+            ENCRYPTED_ASSET_EXTS.contains(&ext)
+                || options.signatures.signature(ext).is_some()
+        })
+        && options.is_selected(path.strip_prefix(input).unwrap_or(path))
+}
+
+#[cfg(feature = "watch")]
+/// Same as [`decrypt_dir`], but processes only `files` under `input`, instead of walking it. Files must be sorted, and are expected to pass [`is_decryptable`].
+pub(crate) fn decrypt_files(
+    input: &Path,
+    output: &Path,
+    files: Vec<PathBuf>,
+    options: &BatchOptions,
+) -> Result<BatchReport, FileError> {
+    run_decrypt_dir(
+        input,
+        output,
+        options,
+        &|_: ProgressEvent<'_>| {},
+        None,
+        Some(files),
+    )
+}
+
+/// Implementation of [`decrypt_dir_with_progress`], processing files on `threads` scoped threads if set, see [`decrypt_dir_threads`], and processing only `files` if set, see [`decrypt_files`].
+fn run_decrypt_dir(
+    input: &Path,
+    output: &Path,
+    options: &BatchOptions,
+    progress: &dyn ProgressSink,
+    threads: Option<usize>,
+    files: Option<Vec<PathBuf>>,
+) -> Result<BatchReport, FileError> {
+    // Archives are processed sequentially, since their entries are read from a single file.
+    if is_asar(input) {
+        return run_decrypt_vfs(
+            &mut AsarContainer::open(input)?,
+            &mut DirFs::new(output),
+            options,
+            progress,
+        );
+    }
+
+    let start = Instant::now();
+    let mut decrypter = Decrypter::new();
+
+    if let Some(key) = options.key {
+        decrypter.set_key(key);
+    }
+
+    let files = match files {
+        Some(files) => files,
+        None => walk_with(input, &options.walk, |path| {
+            is_decryptable(input, path, options)
+        })?,
+    };
+
+    // Keys are determined once, before processing, so that all files, even if processed in parallel, share them.
+    let keys = FileKeys::resolve(&mut decrypter, input, &files, options);
+
+    let state = RunState::new(options)?;
+    #[cfg(feature = "tracing")]
+    let span = tracing::Span::current();
+
+    let process = |decrypter: &mut Decrypter,
+                   buffer: &mut Vec<u8>,
+                   input_file: PathBuf| {
+        let mut own_decrypter = keys.for_file(input, &input_file).cloned();
+        let decrypter = own_decrypter.as_mut().unwrap_or(decrypter);
+
+        let mut file_report = FileReport {
+            bytes_in: metadata(&input_file).map_or(0, |meta| meta.len()),
+            input: input_file,
+            output: None,
+            type_check: None,
+            file_type: None,
+            collided: false,
+            status: FileStatus::Skipped,
+            bytes_out: 0,
+        };
+
+        progress.on_event(ProgressEvent::Started {
+            input: &file_report.input,
+            bytes: file_report.bytes_in,
+        });
+
+        let result = if options.timeouts.run_expired(start) {
+            Err(FileError::new(&file_report.input, Error::Timeout))
+        } else {
+            let input_file = file_report.input.clone();
+            decrypt_one(
+                decrypter,
+                buffer,
+                input,
+                output,
+                &input_file,
+                options,
+                &state,
+                &mut file_report,
+            )
+        };
+
+        match result {
+            Ok(bytes) => file_report.bytes_out = bytes,
+            Err(err) => file_report.status = FileStatus::Failed(err),
+        }
+
+        progress.on_event(ProgressEvent::finished(&file_report));
+
+        #[cfg(feature = "tracing")]
+        trace_file(&span, &file_report);
+        file_report
+    };
+
+    // All branches preserve the order of `files`, so reports are sorted by input path.
+    // Each worker reuses a single buffer for all of its files, so the run doesn't allocate per file.
+    let files = match threads {
+        Some(threads) => process_scoped(files, threads, &decrypter, &process),
+        #[cfg(feature = "parallel")]
+        None => files
+            .into_par_iter()
+            .map_init(Vec::new, |buffer, input_file| {
+                process(&mut decrypter.clone(), buffer, input_file)
+            })
+            .collect(),
+        #[cfg(not(feature = "parallel"))]
+        None => {
+            let mut buffer = Vec::new();
+
+            files
+                .into_iter()
+                .map(|input_file| {
+                    process(&mut decrypter, &mut buffer, input_file)
+                })
+                .collect()
+        }
+    };
+
+    let mut report = BatchReport {
+        files,
+        ..BatchReport::default()
+    };
+
+    if options.transactional {
+        state.finish(&mut report.files, options);
+    }
+
+    report.key = decrypter.encryption_key();
+    report.tally(start);
+    #[cfg(feature = "tracing")]
+    trace_report(&report);
+    Ok(report)
+}
+
+/// Runs [`decrypt_dir`] on tokio's blocking thread pool, so it can be awaited without blocking the runtime.
+///
+/// This is only a wrapper of the synchronous run, not an async implementation: unlike [`crate::decrypt_file_async`], files are read and written with blocking I/O on a single blocking-pool thread, which is occupied until the run finishes. In exchange, it behaves exactly like [`decrypt_dir`], including [`Timeouts`] and the `parallel` feature.
+///
+/// # Errors
+///
+/// - Any error of [`decrypt_dir`].
+/// - [`Error::Io`] - if the blocking task was cancelled, e.g. because the runtime is shutting down.
+///
+/// # Panics
+///
+/// Resumes the panic, if the blocking task panicked.
+#[cfg(feature = "tokio")]
+pub async fn decrypt_dir_async(
+    input: PathBuf,
+    output: PathBuf,
+    options: BatchOptions,
+) -> Result<BatchReport, FileError> {
+    let task_input = input.clone();
+
+    match tokio::task::spawn_blocking(move || {
+        decrypt_dir(&task_input, &output, &options)
+    })
+    .await
+    {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => {
+            std::panic::resume_unwind(err.into_panic())
+        }
+        Err(err) => Err(FileError::new(
+            input,
+            std::io::Error::new(std::io::ErrorKind::Interrupted, err),
+        )),
+    }
+}
+
+/// Sets the key of `decrypter` from `System.json` of `input` (`data/System.json` or `www/data/System.json`), or, if there's none, from the first encrypted PNG among `files`, or from the first encrypted asset at all.
+fn resolve_vfs_key(
+    decrypter: &mut Decrypter,
+    input: &mut dyn VirtualFs,
+    files: &[PathBuf],
+    options: &BatchOptions,
+) {
+    if let Some(info) = ["data/System.json", "www/data/System.json"]
+        .iter()
+        .find_map(|path| input.read(Path::new(path)).ok())
+        .and_then(|data| extract_key_from_system_json(&data).ok())
+    {
+        if decrypter.set_key_from_str(&info.key).is_ok() {
+            return;
+        }
+    }
+
+    let key_source = key_source(files, &options.signatures);
+
+    if let Some(path) = key_source {
+        if let (Some(file_type), Ok(data)) =
+            (file_type_of(path, &options.signatures), input.read(path))
+        {
+            let _ = decrypter.set_key_from_file(&data, file_type);
+        }
+    }
+}
+
+/// Returns the name of the first option of `options` that [`decrypt_vfs`] can't honor, because it relies on real paths.
+fn unsupported_vfs_option(options: &BatchOptions) -> Option<&'static str> {
+    [
+        ("key_overrides", !options.key_overrides.is_empty()),
+        ("walk", options.walk != WalkOptions::default()),
+        ("mismatch", options.mismatch != MismatchPolicy::default()),
+        ("timeouts", options.timeouts != Timeouts::default()),
+        ("output_policy", options.output_policy.is_some()),
+        ("collision", options.collision != CollisionPolicy::default()),
+        ("key_store", options.key_store.is_some()),
+        ("transactional", options.transactional),
+        ("manifest", options.manifest.is_some()),
+        ("resume_from", options.resume_from.is_some()),
+        ("preserve_metadata", options.preserve_metadata),
+    ]
+    .iter()
+    .find(|&&(_, set)| set)
+    .map(|&(name, _)| name)
+}
+
+/// Decrypts all encrypted assets (see [`crate::ENCRYPTED_ASSET_EXTS`] and [`BatchOptions::signatures`]) of virtual filesystem `input`, and writes them into `output`, preserving the directory structure.
+///
+/// This is the batch layer of containers (see [`crate::container`]), which can't be walked like directories. [`BatchOptions::key`], [`BatchOptions::include`], [`BatchOptions::exclude`], [`BatchOptions::naming`], [`BatchOptions::signatures`] and [`BatchOptions::dry_run`] are honored. Other options rely on real paths, e.g. transactions, manifests, timeouts and other output policies, so the run fails if any of them is set; [`decrypt_dir`] keeps working on real directories directly.
+///
+/// # Parameters
+///
+/// - `input` - Filesystem to search for encrypted assets, e.g. [`crate::container::AsarContainer`] or [`crate::vfs::DirFs`].
+/// - `output` - Filesystem to write decrypted assets to.
+/// - `options` - [`BatchOptions`].
+///
+/// # Returns
+///
+/// - [`BatchReport`] with per-file results. Paths are reported with [`VirtualFs::full_path`] of their filesystem. Failing files don't stop the run.
+/// - [`FileError`] if the run couldn't start at all.
+///
+/// # Errors
+///
+/// - [`Error::Io`] of [`std::io::ErrorKind::InvalidInput`] kind - if `options` set an option that can't be honored.
+/// - Any error of [`VirtualFs::list`] of `input`.
+pub fn decrypt_vfs(
+    input: &mut dyn VirtualFs,
+    output: &mut dyn VirtualFs,
+    options: &BatchOptions,
+) -> Result<BatchReport, FileError> {
+    run_decrypt_vfs(input, output, options, &|_: ProgressEvent<'_>| {})
+}
+
+/// Implementation of [`decrypt_vfs`], reporting per-file [`ProgressEvent`]s to `progress`, like [`decrypt_dir_with_progress`] does.
+fn run_decrypt_vfs(
+    input: &mut dyn VirtualFs,
+    output: &mut dyn VirtualFs,
+    options: &BatchOptions,
+    progress: &dyn ProgressSink,
+) -> Result<BatchReport, FileError> {
+    if let Some(option) = unsupported_vfs_option(options) {
+        return Err(FileError::new(
+            input.full_path(Path::new("")),
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "`{option}` option is not supported for archives and other virtual filesystems"
+                ),
+            ),
+        ));
+    }
+
+    let start = Instant::now();
+    let mut decrypter = Decrypter::new();
+
+    if let Some(key) = options.key {
+        decrypter.set_key(key);
+    }
+
+    let files: Vec<PathBuf> = input
+        .list()?
+        .into_iter()
+        .filter(|path| {
+            file_type_of(path, &options.signatures).is_some()
+                && options.is_selected(path)
+        })
+        .collect();
+
+    if decrypter.key().is_none() {
+        resolve_vfs_key(&mut decrypter, input, &files, options);
+    }
+
+    let mut report = BatchReport {
+        files: Vec::with_capacity(files.len()),
+        ..BatchReport::default()
+    };
+
+    for relative in files {
+        let input_path = input.full_path(&relative);
+        let data = input.read(&relative);
+        let bytes_in = data.as_ref().map_or(0, |data| data.len() as u64);
+
+        progress.on_event(ProgressEvent::Started {
+            input: &input_path,
+            bytes: bytes_in,
+        });
+
+        let mut output_type = None;
+        let result = data.and_then(|data| {
+            let file_type = file_type_of(&relative, &options.signatures)
+                .ok_or(Error::UnsupportedFileType)
+                .and_then(|file_type| {
+                    decrypter
+                        .decrypt(&data, file_type)
+                        .map(|data| (data, file_type))
+                });
+            let (data, file_type) =
+                file_type.map_err(|err| FileError::new(&input_path, err))?;
+            output_type = Some(file_type);
+
+            let ext = match file_type {
+                FileType::Other(_) => {
+                    relative.extension().and_then(OsStr::to_str).and_then(
+                        |ext| options.signatures.decrypted_extension(ext),
+                    )
+                }
+                _ => None,
+            }
+            .unwrap_or_else(|| file_type.to_string());
+            let output_relative =
+                decrypted_path_with_extension(&relative, &ext, &options.naming);
+
+            if options.dry_run {
+                return Ok((output.full_path(&output_relative), None));
+            }
+
+            output.write(&output_relative, &data)?;
+            Ok((output.full_path(&output_relative), Some(data.len() as u64)))
+        });
+
+        let (output, status, bytes_out) = match result {
+            Ok((output, Some(bytes))) => {
+                (Some(output), FileStatus::Succeeded, bytes)
+            }
+            Ok((output, None)) => (Some(output), FileStatus::Planned, 0),
+            Err(err) => (None, FileStatus::Failed(err), 0),
+        };
+
+        report.files.push(FileReport {
+            input: input_path,
+            output,
+            type_check: None,
+            file_type: output_type,
+            collided: false,
+            status,
+            bytes_in,
+            bytes_out,
+        });
+
+        if let Some(file) = report.files.last() {
+            progress.on_event(ProgressEvent::finished(file));
+        }
+    }
+
+    report.key = decrypter.encryption_key();
+    report.tally(start);
+    Ok(report)
+}
+
+fn encrypt_one(
+    encrypter: &Encrypter,
+    engine: Engine,
+    input_root: &Path,
+    output_root: &Path,
+    input: &Path,
+    options: &RepackOptions,
+    manifest: Option<&ManifestWriter>,
+) -> Result<(PathBuf, u64), FileError> {
+    // Extensions are matched regardless of case, same as by `encrypted_name`.
+    if input
+        .extension()
+        .and_then(OsStr::to_str)
+        .and_then(|ext| {
+            FileType::from_decrypted_extension(&ext.to_ascii_lowercase())
+        })
+        .is_none()
+    {
+        return Err(FileError::new(input, Error::UnsupportedFileType));
+    }
+
+    let data = read_with_timeout(input, options.timeouts.per_file)?;
+    let input_sha256 = (options.checksums && manifest.is_some())
+        .then(|| manifest::sha256(&data));
+    let data = encrypter.encrypt(&data);
+
+    let relative = input.strip_prefix(input_root).unwrap_or(input);
+    let output = output_root.join(encrypted_name(relative, engine));
+
+    if let Some(parent) = output.parent() {
+        create_dir_all(parent).map_err(|err| FileError::new(parent, err))?;
+    }
+
+    let bytes = data.len() as u64;
+    let record = manifest.map(|_| ManifestRecord {
+        input: relative.to_path_buf(),
+        output: output.clone(),
+        size: bytes,
+        hash: manifest::hash(&data),
+        input_sha256,
+        output_sha256: input_sha256.map(|_| manifest::sha256(&data)),
+    });
+
+    write_with_timeout(&output, data, options.timeouts.per_file)?;
+
+    if options.preserve_metadata {
+        copy_metadata(input, &output)?;
+    }
+
+    if let (Some(manifest), Some(record)) = (manifest, record) {
+        manifest.append(&record)?;
+    }
+
+    Ok((output, bytes))
+}
+
+/// Recursively encrypts all decrypted assets (see [`crate::DECRYPTED_ASSETS_EXTS`]) under `input`, and writes them into `output`, preserving the directory structure.
+///
+/// This is the reverse of [`decrypt_dir`], used to repack edited assets back into the game.
+/// Outputs are written atomically (see [`crate::fs::write_atomic`]), with extensions of [`RepackOptions::engine`].
+/// With `parallel` feature, files are processed on the global rayon thread pool.
+///
+/// # Parameters
+///
+/// - `input` - Root directory to search for decrypted assets.
+/// - `output` - Root directory to write encrypted assets to, e.g. game's `www` folder.
+/// - `options` - [`RepackOptions`].
+///
+/// # Returns
+///
+/// - [`BatchReport`] with per-file results. Failing files don't stop the run.
+/// - [`FileError`] if the run couldn't start at all, or `System.json` couldn't be updated.
+///
+/// # Errors
+///
+/// - [`Error::KeyNotSet`] - if neither [`RepackOptions::key`] nor [`RepackOptions::system_json`] is set.
+/// - Any error of [`crate::system_json::extract_key_from_system_json_file`], if the key is read from `System.json`.
+/// - [`Error::InvalidJson`] - if `System.json` can't be updated.
+/// - [`Error::Io`] - if walking `input` or writing `System.json` fails.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip_all,
+        fields(input = %input.display(), output = %output.display())
+    )
+)]
+pub fn encrypt_dir(
+    input: &Path,
+    output: &Path,
+    options: &RepackOptions,
+) -> Result<BatchReport, FileError> {
+    run_encrypt_dir(input, output, options, None)
+}
+
+/// Same as [`encrypt_dir`], but processes only `files` under `input`, instead of walking it. Files must be sorted, and are expected to be decrypted assets.
+#[cfg(feature = "watch")]
+pub(crate) fn encrypt_files(
+    input: &Path,
+    output: &Path,
+    files: Vec<PathBuf>,
+    options: &RepackOptions,
+) -> Result<BatchReport, FileError> {
+    run_encrypt_dir(input, output, options, Some(files))
+}
+
+/// Implementation of [`encrypt_dir`], processing only `files` if set, see [`encrypt_files`].
+fn run_encrypt_dir(
+    input: &Path,
+    output: &Path,
+    options: &RepackOptions,
+    files: Option<Vec<PathBuf>>,
+) -> Result<BatchReport, FileError> {
+    let start = Instant::now();
+
+    let key = match (&options.key, &options.system_json) {
+        (Some(key), _) => *key,
+        (None, Some(system_json)) => {
+            let info = extract_key_from_system_json_file(system_json)?;
+            EncryptionKey::from_hex_str(&info.key)
+                .map_err(|err| FileError::new(system_json, err))?
+        }
+        (None, None) => return Err(FileError::new(input, Error::KeyNotSet)),
+    };
+
+    let engine = options
+        .engine
+        .or_else(|| Engine::detect(output))
+        .unwrap_or(Engine::Mv);
+
+    let encrypter = Encrypter::with_key(key).engine(engine);
+    let files = match files {
+        Some(files) => files,
+        None => decrypted_files(input)?,
+    };
+    let manifest = options
+        .manifest
+        .as_deref()
+        .map(ManifestWriter::open)
+        .transpose()?;
+    #[cfg(feature = "tracing")]
+    let span = tracing::Span::current();
+
+    let process = |input_file: PathBuf| {
+        let result = if options.timeouts.run_expired(start) {
+            Err(FileError::new(&input_file, Error::Timeout))
+        } else {
+            encrypt_one(
+                &encrypter,
+                engine,
+                input,
+                output,
+                &input_file,
+                options,
+                manifest.as_ref(),
+            )
+        };
+
+        let (output, status, bytes_out) = match result {
+            Ok((output, bytes)) => (Some(output), FileStatus::Succeeded, bytes),
+            Err(err) => (None, FileStatus::Failed(err), 0),
+        };
+
+        let file_report = FileReport {
+            bytes_in: metadata(&input_file).map_or(0, |meta| meta.len()),
+            file_type: input_file
+                .extension()
+                .and_then(OsStr::to_str)
+                .and_then(FileType::from_decrypted_extension),
+            input: input_file,
+            output,
+            type_check: None,
+            collided: false,
+            status,
+            bytes_out,
+        };
+
+        #[cfg(feature = "tracing")]
+        trace_file(&span, &file_report);
+        file_report
+    };
+
+    #[cfg(feature = "parallel")]
+    let files: Vec<_> = files.into_par_iter().map(process).collect();
+    #[cfg(not(feature = "parallel"))]
+    let files: Vec<_> = files.into_iter().map(process).collect();
+
+    let mut report = BatchReport {
+        key: Some(key),
+        files,
+        ..BatchReport::default()
+    };
+    report.tally(start);
+    #[cfg(feature = "tracing")]
+    trace_report(&report);
+
+    if let (true, Some(system_json)) =
+        (options.update_system_json, &options.system_json)
+    {
+        let mut images = false;
+        let mut audio = false;
+
+        for file in report.succeeded() {
+            match file_type_of(
+                file.output.as_deref().unwrap_or(&file.input),
+                &SignatureRegistry::new(),
+            ) {
+                Some(FileType::PNG | FileType::WEBP) => images = true,
+                Some(FileType::JSON) | None => {}
+                Some(_) => audio = true,
+            }
+        }
+
+        let mut data =
+            read_with_timeout(system_json, options.timeouts.per_file)?;
+
+        if let Ok(info) = extract_key_from_system_json(&data) {
+            images |= info.has_encrypted_images;
+            audio |= info.has_encrypted_audio;
+        }
+
+        let key = key.to_string();
+        set_encryption(&mut data, Some(&key), images, audio)
+            .map_err(|err| FileError::new(system_json, err))?;
+        write_atomic(system_json, &data)?;
+    }
+
+    Ok(report)
+}
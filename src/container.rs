@@ -0,0 +1,56 @@
+//! Reading encrypted assets straight out of game containers, such as NW.js `package.nw` archives and Electron `app.asar` files, without extracting them first.
+//!
+//! Containers implement [`VirtualFs`], so they can be passed to [`crate::batch::decrypt_vfs`] directly.
+
+mod asar;
+#[cfg(feature = "zip")]
+mod zip;
+
+pub(crate) use self::asar::is_asar;
+pub use self::asar::{AsarContainer, decrypt_asar};
+#[cfg(feature = "zip")]
+pub use self::zip::{ZipContainer, decrypt_zip};
+
+use crate::{ENCRYPTED_ASSET_EXTS, FileType};
+use std::{
+    convert::TryFrom,
+    path::{Component, Path},
+};
+
+#[cfg(doc)]
+use crate::vfs::VirtualFs;
+
+/// Returns the type of container entry `name`, if it has an encrypted asset extension.
+fn entry_type(name: &str) -> Option<FileType> {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ENCRYPTED_ASSET_EXTS.contains(ext))
+        .and_then(|ext| FileType::try_from(ext).ok())
+}
+
+/// Returns the name of the container entry at relative `path`, which always uses `/` as a separator.
+fn entry_name(path: &Path) -> String {
+    let mut name = String::new();
+
+    for component in path.components() {
+        if let Component::Normal(component) = component {
+            if !name.is_empty() {
+                name.push('/');
+            }
+
+            name.push_str(&component.to_string_lossy());
+        }
+    }
+
+    name
+}
+
+/// Returns names of encrypted asset entries among `names`, sorted.
+fn encrypted_names<'a>(names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut names: Vec<_> =
+        names.filter(|name| entry_type(name).is_some()).collect();
+
+    names.sort_unstable();
+    names
+}
@@ -0,0 +1,446 @@
+use super::{encrypted_names, entry_name, entry_type};
+use crate::{
+    Decrypter, EncryptionKey, Engine, Error, FileError,
+    batch::{BatchOptions, BatchReport, decrypt_vfs},
+    json,
+    scan::ScannedAsset,
+    vfs::{DirFs, VirtualFs, is_enclosed, read_only},
+};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Extension of Electron archives.
+pub(crate) const ASAR_EXT: &str = "asar";
+
+/// Maximum nesting of the header, which is way deeper than any real directory tree, but keeps malicious headers from overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Returns whether `path` is an existing `.asar` file, which batch and scan functions treat as a virtual directory.
+pub(crate) fn is_asar(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == ASAR_EXT) && path.is_file()
+}
+
+/// File entry of asar header.
+#[derive(Debug, Clone, Copy)]
+struct AsarEntry {
+    /// Offset of the file's data, relative to the end of the header.
+    offset: u64,
+    size: u64,
+    /// Whether the file is stored in `<archive>.unpacked` directory next to the archive, instead of the archive itself.
+    unpacked: bool,
+}
+
+/// Value of asar header, which is JSON.
+enum Json {
+    Object(Vec<(String, Json)>),
+    /// Array, whose items are never needed, e.g. `integrity.blocks`.
+    Array,
+    String(String),
+    /// Number, `true`, `false` or `null`, as written.
+    Literal(String),
+}
+
+impl Json {
+    fn field(&self, name: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number or a numeric string, since asar writes offsets as strings, to fit 64-bit integers.
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::String(value) | Self::Literal(value) => value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Parser of asar header.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<u8> {
+        while self.data.get(self.pos)?.is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: u8) -> Option<()> {
+        (self.peek()? == expected).then(|| self.pos += 1)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        json::read_string(self.data, &mut self.pos)
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Json> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        match self.peek()? {
+            b'"' => self.string().map(Json::String),
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+
+                let mut fields = Vec::new();
+
+                if self.peek()? == close {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        if open == b'{' {
+                            let name = self.string()?;
+                            self.expect(b':')?;
+                            fields.push((name, self.value(depth + 1)?));
+                        } else {
+                            self.value(depth + 1)?;
+                        }
+
+                        match self.peek()? {
+                            b',' => self.pos += 1,
+                            byte if byte == close => {
+                                self.pos += 1;
+                                break;
+                            }
+                            _ => return None,
+                        }
+                    }
+                }
+
+                Some(if open == b'{' {
+                    Json::Object(fields)
+                } else {
+                    Json::Array
+                })
+            }
+            _ => {
+                let start = self.pos;
+
+                while self.data.get(self.pos).map_or(false, |&byte| {
+                    byte.is_ascii_alphanumeric() || b"+-.".contains(&byte)
+                }) {
+                    self.pos += 1;
+                }
+
+                (self.pos > start).then(|| {
+                    Json::Literal(
+                        String::from_utf8_lossy(&self.data[start..self.pos])
+                            .into_owned(),
+                    )
+                })
+            }
+        }
+    }
+}
+
+/// Collects file entries of directory `node` of asar header, whose path is `prefix`, into `entries`.
+fn collect_entries(
+    node: &Json,
+    prefix: &str,
+    entries: &mut BTreeMap<String, AsarEntry>,
+) -> Result<(), Error> {
+    let Some(Json::Object(files)) = node.field("files") else {
+        return Err(Error::InvalidJson);
+    };
+
+    for (name, child) in files {
+        let path = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{prefix}/{name}")
+        };
+
+        if !is_enclosed(Path::new(&path)) {
+            continue;
+        }
+
+        if child.field("files").is_some() {
+            collect_entries(child, &path, entries)?;
+            continue;
+        }
+
+        // Symbolic links point to other entries, which are collected on their own.
+        if child.field("link").is_some() {
+            continue;
+        }
+
+        let unpacked = matches!(child.field("unpacked"), Some(Json::Literal(value)) if value == "true");
+        let size = child
+            .field("size")
+            .and_then(Json::as_u64)
+            .ok_or(Error::InvalidJson)?;
+        let offset = match child.field("offset") {
+            Some(offset) => offset.as_u64().ok_or(Error::InvalidJson)?,
+            None if unpacked => 0,
+            None => return Err(Error::InvalidJson),
+        };
+
+        entries.insert(
+            path,
+            AsarEntry {
+                offset,
+                size,
+                unpacked,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+fn not_found(message: &'static str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::NotFound, message))
+}
+
+/// Electron `app.asar` archive, read as a source of encrypted assets.
+///
+/// asar is an uncompressed archive with a JSON header, so entries are read straight from their offsets. Entries are addressed by their paths inside the archive, e.g. `img/pictures/image.png_`.
+/// Files Electron keeps outside the archive are read from `app.asar.unpacked` directory next to it.
+#[derive(Debug)]
+pub struct AsarContainer<R> {
+    reader: R,
+    /// Offset of the first file's data, which is the end of the header.
+    data_offset: u64,
+    entries: BTreeMap<String, AsarEntry>,
+    /// Path of the archive, which entries are reported under, if it was opened from a file.
+    path: PathBuf,
+    /// `<archive>.unpacked` directory, if the archive was opened from a file.
+    unpacked_dir: Option<PathBuf>,
+}
+
+impl AsarContainer<File> {
+    /// Opens asar archive at `path`.
+    ///
+    /// # Errors
+    ///
+    /// - Any error of [`AsarContainer::new`].
+    pub fn open(path: &Path) -> Result<Self, FileError> {
+        let mut container = File::open(path)
+            .map_err(Error::from)
+            .and_then(Self::new)
+            .map_err(|err| FileError::new(path, err))?;
+
+        let mut unpacked_dir = OsString::from(path);
+        unpacked_dir.push(".unpacked");
+        container.unpacked_dir = Some(unpacked_dir.into());
+        container.path = path.to_path_buf();
+
+        Ok(container)
+    }
+
+    /// Returns encrypted assets of the archive, like [`crate::scan::encrypted_assets`] does for directories.
+    pub(crate) fn assets(&self) -> Vec<ScannedAsset> {
+        self.encrypted_entries()
+            .into_iter()
+            .filter_map(|name| {
+                let ext = Path::new(name).extension()?.to_str()?;
+
+                Some(ScannedAsset {
+                    path: self.path.join(name),
+                    relative_path: PathBuf::from(name),
+                    file_type: entry_type(name)?,
+                    engine: Engine::from_encrypted_extension(ext)?,
+                    size: self.entries[name].size,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<R: Read + Seek> AsarContainer<R> {
+    /// Reads the header of asar archive from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if reading fails.
+    /// - [`Error::Malformed`] - if the header is truncated.
+    /// - [`Error::InvalidJson`] - if the header is not a valid asar header.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        // Header is prefixed with a pickle of its size, and is itself a pickle of a single string.
+        let mut size_pickle = [0; 8];
+        reader
+            .read_exact(&mut size_pickle)
+            .map_err(|_| Error::Malformed {
+                offset: 0,
+                reason: "asar size pickle is truncated",
+            })?;
+
+        let header_size = u64::from(u32::from_le_bytes([
+            size_pickle[4],
+            size_pickle[5],
+            size_pickle[6],
+            size_pickle[7],
+        ]));
+
+        let mut header = Vec::new();
+        (&mut reader).take(header_size).read_to_end(&mut header)?;
+
+        let json = header
+            .get(4..8)
+            .map(|len| u32::from_le_bytes([len[0], len[1], len[2], len[3]]))
+            .and_then(|len| header.get(8..8 + len as usize))
+            .ok_or(Error::Malformed {
+                offset: 8,
+                reason: "asar header is truncated",
+            })?;
+
+        let root = Parser { data: json, pos: 0 }
+            .value(0)
+            .ok_or(Error::InvalidJson)?;
+
+        let mut entries = BTreeMap::new();
+        collect_entries(&root, "", &mut entries)?;
+
+        Ok(Self {
+            reader,
+            data_offset: 8 + header_size,
+            entries,
+            path: PathBuf::new(),
+            unpacked_dir: None,
+        })
+    }
+
+    /// Returns paths of all file entries, sorted.
+    ///
+    /// Entries whose paths would escape the output directory (see [`decrypt_asar`]) are left out.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Returns paths of encrypted asset entries (see [`crate::ENCRYPTED_ASSET_EXTS`]), sorted.
+    #[must_use]
+    pub fn encrypted_entries(&self) -> Vec<&str> {
+        encrypted_names(self.entries())
+    }
+
+    /// Reads entry `name` and returns its data.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if there's no such entry, or reading it fails. Unpacked entries can only be read if the archive was opened with [`AsarContainer::open`].
+    /// - [`Error::UnexpectedEOF`] - if the entry exceeds the archive, or its offset is out of range.
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = *self
+            .entries
+            .get(name)
+            .ok_or_else(|| not_found("no such asar entry"))?;
+
+        if entry.unpacked {
+            let dir = self.unpacked_dir.as_ref().ok_or_else(|| {
+                not_found("unpacked asar entry without archive path")
+            })?;
+
+            return Ok(fs::read(dir.join(name))?);
+        }
+
+        let offset = self
+            .data_offset
+            .checked_add(entry.offset)
+            .ok_or(Error::UnexpectedEOF)?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+
+        let mut data = Vec::new();
+        (&mut self.reader).take(entry.size).read_to_end(&mut data)?;
+
+        if (data.len() as u64) < entry.size {
+            return Err(Error::UnexpectedEOF);
+        }
+
+        Ok(data)
+    }
+
+    /// Reads and decrypts encrypted asset entry `name`.
+    ///
+    /// Type of the asset is determined by the entry's extension. If `decrypter`'s key is not set, it's determined from the asset itself.
+    ///
+    /// # Returns
+    ///
+    /// - Decrypted data of the asset.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::UnsupportedFileType`] - if `name` doesn't have an encrypted asset extension.
+    /// - Any error of [`AsarContainer::read`].
+    /// - Any error of [`Decrypter::decrypt`].
+    pub fn decrypt(
+        &mut self,
+        decrypter: &mut Decrypter,
+        name: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let file_type = entry_type(name).ok_or(Error::UnsupportedFileType)?;
+        let data = self.read(name)?;
+        decrypter.decrypt(&data, file_type)
+    }
+}
+
+/// Read-only, [`VirtualFs::write`] always fails.
+impl<R: Read + Seek> VirtualFs for AsarContainer<R> {
+    fn list(&mut self) -> Result<Vec<PathBuf>, FileError> {
+        Ok(self.entries().map(PathBuf::from).collect())
+    }
+
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>, FileError> {
+        AsarContainer::read(self, &entry_name(path))
+            .map_err(|err| FileError::new(self.full_path(path), err))
+    }
+
+    fn write(&mut self, path: &Path, _: &[u8]) -> Result<(), FileError> {
+        Err(read_only(self.full_path(path)))
+    }
+
+    fn full_path(&self, path: &Path) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+/// Decrypts all encrypted assets of Electron asar archive at `archive`, e.g. MZ's `app.asar`, and writes them into `output`, preserving the directory structure of the archive.
+///
+/// The archive isn't extracted: each asset is read straight from its offset and decrypted in memory, and only decrypted outputs are written.
+/// [`crate::batch::decrypt_dir`] calls this when its input is an asar file.
+///
+/// # Parameters
+///
+/// - `archive` - Path to asar archive.
+/// - `output` - Root directory to write decrypted assets to.
+/// - `key` - Encryption key. If not set, it's read from the archive's `System.json`, or determined from the first PNG asset, or from the first asset at all, if there are no PNGs.
+///
+/// # Returns
+///
+/// - [`BatchReport`] with per-file results. Inputs are reported as `archive` joined with entry paths. Failing files don't stop the run.
+/// - [`FileError`] if the run couldn't start at all.
+///
+/// # Errors
+///
+/// - Any error of [`AsarContainer::open`].
+pub fn decrypt_asar(
+    archive: &Path,
+    output: &Path,
+    key: Option<EncryptionKey>,
+) -> Result<BatchReport, FileError> {
+    let mut options = BatchOptions::new();
+    options.key = key;
+
+    decrypt_vfs(
+        &mut AsarContainer::open(archive)?,
+        &mut DirFs::new(output),
+        &options,
+    )
+}
@@ -0,0 +1,181 @@
+use super::{encrypted_names, entry_name, entry_type};
+use crate::{
+    Decrypter, EncryptionKey, Error, FileError,
+    batch::{BatchOptions, BatchReport, decrypt_vfs},
+    vfs::{DirFs, VirtualFs, is_enclosed, read_only},
+};
+use ::zip::{ZipArchive, result::ZipError};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+fn zip_error(err: ZipError) -> Error {
+    match err {
+        ZipError::Io(err) => Error::Io(err),
+        ZipError::FileNotFound => {
+            Error::Io(io::Error::new(io::ErrorKind::NotFound, err))
+        }
+        err => Error::Io(io::Error::new(io::ErrorKind::InvalidData, err)),
+    }
+}
+
+/// Returns whether ZIP entry `name` can be safely extracted, i.e. it's a file, and it doesn't escape the directory it's extracted to.
+fn is_extractable(name: &str) -> bool {
+    !name.ends_with('/') && is_enclosed(Path::new(name))
+}
+
+/// ZIP archive, e.g. NW.js `package.nw`, read as a source of encrypted assets.
+///
+/// Entries are addressed by their names inside the archive, e.g. `www/img/pictures/image.rpgmvp`, and are decompressed one at a time, so the archive is never extracted as a whole.
+#[derive(Debug)]
+pub struct ZipContainer<R> {
+    archive: ZipArchive<R>,
+    /// Path of the archive, which entries are reported under, if it was opened from a file.
+    path: PathBuf,
+}
+
+impl ZipContainer<File> {
+    /// Opens ZIP archive at `path`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if opening the file fails, or it's not a valid ZIP archive.
+    pub fn open(path: &Path) -> Result<Self, FileError> {
+        let mut container = File::open(path)
+            .map_err(Error::from)
+            .and_then(Self::new)
+            .map_err(|err| FileError::new(path, err))?;
+
+        container.path = path.to_path_buf();
+        Ok(container)
+    }
+}
+
+impl<R: Read + Seek> ZipContainer<R> {
+    /// Reads the central directory of ZIP archive from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if reading fails, or the data is not a valid ZIP archive.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        ZipArchive::new(reader)
+            .map(|archive| Self {
+                archive,
+                path: PathBuf::new(),
+            })
+            .map_err(zip_error)
+    }
+
+    /// Returns names of all file entries, in archive order.
+    ///
+    /// Entries whose names would escape the output directory (see [`decrypt_zip`]) are left out.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.archive
+            .file_names()
+            .filter(|name| is_extractable(name))
+    }
+
+    /// Returns names of encrypted asset entries (see [`crate::ENCRYPTED_ASSET_EXTS`]), sorted.
+    #[must_use]
+    pub fn encrypted_entries(&self) -> Vec<&str> {
+        encrypted_names(self.entries())
+    }
+
+    /// Decompresses entry `name` and returns its data.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if there's no such entry, or decompressing it fails.
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, Error> {
+        let mut entry = self.archive.by_name(name).map_err(zip_error)?;
+
+        // Size is only a hint, since it's read from the archive itself.
+        let mut data = Vec::with_capacity(
+            usize::try_from(entry.size()).map_or(0, |size| size.min(1 << 26)),
+        );
+        entry.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Decompresses and decrypts encrypted asset entry `name`.
+    ///
+    /// Type of the asset is determined by the entry's extension. If `decrypter`'s key is not set, it's determined from the asset itself.
+    ///
+    /// # Returns
+    ///
+    /// - Decrypted data of the asset.
+    /// - [`Error`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::UnsupportedFileType`] - if `name` doesn't have an encrypted asset extension.
+    /// - Any error of [`ZipContainer::read`].
+    /// - Any error of [`Decrypter::decrypt`].
+    pub fn decrypt(
+        &mut self,
+        decrypter: &mut Decrypter,
+        name: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let file_type = entry_type(name).ok_or(Error::UnsupportedFileType)?;
+        let data = self.read(name)?;
+        decrypter.decrypt(&data, file_type)
+    }
+}
+
+/// Read-only, [`VirtualFs::write`] always fails.
+impl<R: Read + Seek> VirtualFs for ZipContainer<R> {
+    fn list(&mut self) -> Result<Vec<PathBuf>, FileError> {
+        let mut paths: Vec<_> = self.entries().map(PathBuf::from).collect();
+        paths.sort_unstable();
+        Ok(paths)
+    }
+
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>, FileError> {
+        ZipContainer::read(self, &entry_name(path))
+            .map_err(|err| FileError::new(self.full_path(path), err))
+    }
+
+    fn write(&mut self, path: &Path, _: &[u8]) -> Result<(), FileError> {
+        Err(read_only(self.full_path(path)))
+    }
+
+    fn full_path(&self, path: &Path) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+/// Decrypts all encrypted assets of ZIP archive at `archive`, e.g. NW.js `package.nw`, and writes them into `output`, preserving the directory structure of the archive.
+///
+/// The archive isn't extracted: each asset is decompressed and decrypted in memory, and only decrypted outputs are written.
+///
+/// # Parameters
+///
+/// - `archive` - Path to ZIP archive.
+/// - `output` - Root directory to write decrypted assets to.
+/// - `key` - Encryption key. If not set, it's read from the archive's `System.json`, or determined from the first PNG asset, or from the first asset at all, if there are no PNGs.
+///
+/// # Returns
+///
+/// - [`BatchReport`] with per-file results. Inputs are reported as `archive` joined with entry names. Failing files don't stop the run.
+/// - [`FileError`] if the run couldn't start at all.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if the archive can't be opened.
+pub fn decrypt_zip(
+    archive: &Path,
+    output: &Path,
+    key: Option<EncryptionKey>,
+) -> Result<BatchReport, FileError> {
+    let mut options = BatchOptions::new();
+    options.key = key;
+
+    decrypt_vfs(
+        &mut ZipContainer::open(archive)?,
+        &mut DirFs::new(output),
+        &options,
+    )
+}
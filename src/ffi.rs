@@ -0,0 +1,240 @@
+//! C ABI for non-Rust consumers.
+//!
+//! Enabled with the `ffi` feature. The matching C declarations live in `include/rpgm_asset_decrypter.h`.
+//!
+//! All functions return [`RpgmStatus`] codes instead of unwinding; panics are caught and reported as [`RpgmStatus::Panic`].
+//! Buffers returned by the library are owned by the caller, and must be released with [`rpgm_buffer_free`].
+
+use crate::{Decrypter, Error, FileType, KEY_STR_LENGTH};
+use std::{
+    ffi::CStr,
+    os::raw::c_char,
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr, slice,
+};
+
+/// Version of the C ABI exposed by this module.
+///
+/// Incremented on every incompatible change to the exported functions or their types. Must match `RPGM_ABI_VERSION` in `include/rpgm_asset_decrypter.h`.
+pub const ABI_VERSION: u32 = 1;
+
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "\0");
+
+/// Status code returned by the exported functions.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpgmStatus {
+    Ok = 0,
+    NullPointer = 1,
+    KeyNotSet = 2,
+    InvalidKeyLength = 3,
+    InvalidHeader = 4,
+    UnexpectedEof = 5,
+    UnsupportedFileType = 6,
+    Panic = 7,
+    Other = 8,
+}
+
+impl From<Error> for RpgmStatus {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::KeyNotSet => Self::KeyNotSet,
+            Error::InvalidKeyLength => Self::InvalidKeyLength,
+            // Reported as before the distinction existed, to keep the ABI stable.
+            Error::InvalidHeader
+            | Error::InvalidMagic { .. }
+            | Error::AlreadyDecrypted(_) => Self::InvalidHeader,
+            Error::UnexpectedEOF => Self::UnexpectedEof,
+            Error::UnsupportedFileType => Self::UnsupportedFileType,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Byte buffer allocated by the library.
+///
+/// Must be released with [`rpgm_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct RpgmBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl RpgmBuffer {
+    fn from_vec(data: Vec<u8>) -> Self {
+        let len = data.len();
+
+        Self {
+            data: Box::into_raw(data.into_boxed_slice()).cast(),
+            len,
+        }
+    }
+}
+
+fn guard(f: impl FnOnce() -> Result<(), RpgmStatus>) -> RpgmStatus {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => RpgmStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => RpgmStatus::Panic,
+    }
+}
+
+/// Returns the ABI version of the loaded library.
+///
+/// Host applications should compare it against `RPGM_ABI_VERSION` from the header they compiled against, and refuse to use the library on mismatch.
+#[no_mangle]
+pub extern "C" fn rpgm_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+/// Returns the semver version of the loaded library as a static NUL-terminated string.
+///
+/// The returned pointer is valid for the lifetime of the program and must not be freed.
+#[no_mangle]
+pub extern "C" fn rpgm_version() -> *const c_char {
+    VERSION.as_ptr().cast()
+}
+
+/// Creates a new decrypter. Must be released with [`rpgm_decrypter_free`].
+#[no_mangle]
+pub extern "C" fn rpgm_decrypter_new() -> *mut Decrypter {
+    Box::into_raw(Box::new(Decrypter::new()))
+}
+
+/// Releases a decrypter created with [`rpgm_decrypter_new`].
+///
+/// # Safety
+///
+/// `decrypter` must be either null or a pointer returned by [`rpgm_decrypter_new`], which wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_decrypter_free(decrypter: *mut Decrypter) {
+    if !decrypter.is_null() {
+        drop(Box::from_raw(decrypter));
+    }
+}
+
+/// Sets the decrypter's key to the NUL-terminated 32-character hex string.
+///
+/// # Safety
+///
+/// `decrypter` must be a valid pointer returned by [`rpgm_decrypter_new`], and `key` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_set_key(
+    decrypter: *mut Decrypter,
+    key: *const c_char,
+) -> RpgmStatus {
+    if decrypter.is_null() || key.is_null() {
+        return RpgmStatus::NullPointer;
+    }
+
+    guard(|| {
+        let key = CStr::from_ptr(key)
+            .to_str()
+            .map_err(|_| RpgmStatus::InvalidKeyLength)?;
+        (*decrypter).set_key_from_str(key)?;
+        Ok(())
+    })
+}
+
+/// Writes the decrypter's key as a NUL-terminated string into `out`, which must have room for 33 bytes.
+///
+/// # Safety
+///
+/// `decrypter` must be a valid pointer returned by [`rpgm_decrypter_new`], and `out` must be valid for writes of 33 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_get_key(
+    decrypter: *const Decrypter,
+    out: *mut c_char,
+) -> RpgmStatus {
+    if decrypter.is_null() || out.is_null() {
+        return RpgmStatus::NullPointer;
+    }
+
+    guard(|| {
+        let key = (*decrypter).key().ok_or(RpgmStatus::KeyNotSet)?;
+
+        ptr::copy_nonoverlapping(key.as_ptr(), out.cast(), KEY_STR_LENGTH);
+        *out.add(KEY_STR_LENGTH) = 0;
+        Ok(())
+    })
+}
+
+/// Decrypts RPG Maker file data into a newly allocated buffer.
+///
+/// If the decrypter's key is not set, it's determined from the data.
+/// `file_type` is `0` for PNG, `1` for OGG, `2` for M4A, `3` for WEBP, `4` for WAV and `5` for JSON.
+///
+/// # Safety
+///
+/// `decrypter` must be a valid pointer returned by [`rpgm_decrypter_new`], `data` must be valid for reads of `len` bytes, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_decrypt(
+    decrypter: *mut Decrypter,
+    data: *const u8,
+    len: usize,
+    file_type: u8,
+    out: *mut RpgmBuffer,
+) -> RpgmStatus {
+    if decrypter.is_null() || data.is_null() || out.is_null() {
+        return RpgmStatus::NullPointer;
+    }
+
+    guard(|| {
+        let file_type = match file_type {
+            0 => FileType::PNG,
+            1 => FileType::OGG,
+            2 => FileType::M4A,
+            3 => FileType::WEBP,
+            4 => FileType::WAV,
+            5 => FileType::JSON,
+            _ => return Err(RpgmStatus::UnsupportedFileType),
+        };
+
+        let data = slice::from_raw_parts(data, len);
+        let plain = (*decrypter).decrypt(data, file_type)?;
+
+        out.write(RpgmBuffer::from_vec(plain));
+        Ok(())
+    })
+}
+
+/// Encrypts `.png`, `.ogg` or `.m4a` file data into a newly allocated buffer, prepending the RPG Maker header.
+///
+/// # Safety
+///
+/// `decrypter` must be a valid pointer returned by [`rpgm_decrypter_new`], `data` must be valid for reads of `len` bytes, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_encrypt(
+    decrypter: *const Decrypter,
+    data: *const u8,
+    len: usize,
+    out: *mut RpgmBuffer,
+) -> RpgmStatus {
+    if decrypter.is_null() || data.is_null() || out.is_null() {
+        return RpgmStatus::NullPointer;
+    }
+
+    guard(|| {
+        let data = slice::from_raw_parts(data, len);
+        let encrypted = (*decrypter).encrypt(data)?;
+
+        out.write(RpgmBuffer::from_vec(encrypted));
+        Ok(())
+    })
+}
+
+/// Releases a buffer returned by the library.
+///
+/// # Safety
+///
+/// `buffer` must be either empty (null `data`) or returned by the library, and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn rpgm_buffer_free(buffer: RpgmBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
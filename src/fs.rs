@@ -0,0 +1,461 @@
+//! Filesystem helpers shared by the file-based APIs.
+
+use crate::{DECRYPTED_ASSETS_EXTS, ENCRYPTED_ASSET_EXTS, Error, FileError};
+use filetime::FileTime;
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, mpsc},
+    thread,
+    time::Duration,
+};
+
+/// Suffix appended to outputs until they are fully written.
+///
+/// It's specific to this crate, so [`cleanup_partials`] never removes files it didn't write.
+pub const PARTIAL_EXT: &str = "rpgm-part";
+
+/// Returns the path `path` is written to until it's fully flushed: `image.png` -> `image.png.rpgm-part`.
+#[must_use]
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut file_name = path
+        .file_name()
+        .map_or_else(OsString::new, ToOwned::to_owned);
+    file_name.push(".");
+    file_name.push(PARTIAL_EXT);
+    path.with_file_name(file_name)
+}
+
+fn write_partial(
+    path: &Path,
+    data: &[u8],
+    deadline: &Deadline,
+) -> io::Result<()> {
+    let partial = partial_path(path);
+
+    let result = File::create(&partial)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|()| deadline.commit(|| fs::rename(&partial, path)));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+
+    result
+}
+
+/// Writes `data` to `path` atomically.
+///
+/// Data is first written to a partial file next to `path` (see [`partial_path`]), flushed to disk, and only then renamed to `path`.
+/// If the process crashes mid-write, only the partial file is left behind, which can be removed with [`cleanup_partials`].
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if writing or renaming fails.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), FileError> {
+    write_partial(path, data, &Deadline::default())
+        .map_err(|err| FileError::new(path, err))
+}
+
+/// Async twin of [`write_atomic`], using `tokio::fs`.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if writing or renaming fails.
+#[cfg(feature = "tokio")]
+pub async fn write_atomic_async(
+    path: &Path,
+    data: &[u8],
+) -> Result<(), FileError> {
+    use tokio::io::AsyncWriteExt;
+
+    let partial = partial_path(path);
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&partial).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&partial, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&partial).await;
+    }
+
+    result.map_err(|err| FileError::new(path, err))
+}
+
+/// Copies access and modification times of `source` to `target`, and, on Unix, its permissions, so outputs of batch runs diff cleanly against their inputs.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading metadata of `source`, or updating `target` fails.
+pub fn copy_metadata(source: &Path, target: &Path) -> Result<(), FileError> {
+    let metadata =
+        fs::metadata(source).map_err(|err| FileError::new(source, err))?;
+
+    filetime::set_file_times(
+        target,
+        FileTime::from_last_access_time(&metadata),
+        FileTime::from_last_modification_time(&metadata),
+    )
+    .map_err(|err| FileError::new(target, err))?;
+
+    #[cfg(unix)]
+    fs::set_permissions(target, metadata.permissions())
+        .map_err(|err| FileError::new(target, err))?;
+
+    Ok(())
+}
+
+/// Defines how [`walk_with`] treats symbolic links, including Windows junctions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Links to files are collected, links to directories aren't descended into.
+    #[default]
+    FilesOnly,
+    /// All links are followed. Directories reached more than once, e.g. through a link back to their ancestor, are only walked the first time, so cycles are never walked forever.
+    Follow,
+    /// Links are ignored entirely.
+    Ignore,
+}
+
+/// Options of [`walk_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WalkOptions {
+    /// How symbolic links are treated.
+    pub symlinks: SymlinkPolicy,
+    /// Maximum depth of directories to descend into: with `0`, only files directly under the root are collected. Unlimited, if not set.
+    pub max_depth: Option<usize>,
+}
+
+impl WalkOptions {
+    /// Creates default options: links to files are collected, and depth is unlimited.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [`WalkOptions::symlinks`].
+    #[must_use]
+    pub fn symlinks(mut self, symlinks: SymlinkPolicy) -> Self {
+        self.symlinks = symlinks;
+        self
+    }
+
+    /// Sets [`WalkOptions::max_depth`].
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+}
+
+/// Recursively collects paths of files under `root` for which `filter` returns `true`.
+///
+/// Returned paths are sorted, so processing order and the order of any report built from them is stable between runs, regardless of the order the filesystem lists entries in.
+/// Same as [`walk_with`] with default [`WalkOptions`].
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory fails.
+pub fn walk(
+    root: &Path,
+    filter: impl FnMut(&Path) -> bool,
+) -> Result<Vec<PathBuf>, FileError> {
+    walk_with(root, &WalkOptions::default(), filter)
+}
+
+/// Same as [`walk`], but treats symbolic links and limits depth according to `options`.
+///
+/// Broken links are skipped.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory fails.
+pub fn walk_with(
+    root: &Path,
+    options: &WalkOptions,
+    mut filter: impl FnMut(&Path) -> bool,
+) -> Result<Vec<PathBuf>, FileError> {
+    let follow = options.symlinks == SymlinkPolicy::Follow;
+    let mut visited = HashSet::new();
+
+    if follow {
+        visited.insert(
+            fs::canonicalize(root).map_err(|err| FileError::new(root, err))?,
+        );
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = vec![(root.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = dirs.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|err| FileError::new(&dir, err))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|err| FileError::new(&dir, err))?;
+            let path = entry.path();
+            let mut file_type = entry
+                .file_type()
+                .map_err(|err| FileError::new(&path, err))?;
+
+            if file_type.is_symlink() {
+                let target = match options.symlinks {
+                    SymlinkPolicy::Ignore => continue,
+                    SymlinkPolicy::FilesOnly | SymlinkPolicy::Follow => {
+                        fs::metadata(&path)
+                    }
+                };
+
+                match target {
+                    Ok(metadata) if metadata.is_dir() && !follow => continue,
+                    Ok(metadata) => file_type = metadata.file_type(),
+                    Err(_) => continue,
+                }
+            }
+
+            if file_type.is_dir() {
+                let within_depth =
+                    options.max_depth.map_or(true, |max| depth < max);
+                let first_visit = !follow
+                    || fs::canonicalize(&path)
+                        .map_or(false, |real| visited.insert(real));
+
+                if within_depth && first_visit {
+                    dirs.push((path, depth + 1));
+                }
+            } else if filter(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort_unstable();
+    Ok(files)
+}
+
+/// Recursively collects paths of encrypted assets (files with any of [`ENCRYPTED_ASSET_EXTS`] extensions) under `root`, sorted by path.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory fails.
+pub fn encrypted_files(root: &Path) -> Result<Vec<PathBuf>, FileError> {
+    walk(root, |path| {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |ext| ENCRYPTED_ASSET_EXTS.contains(&ext))
+    })
+}
+
+/// Recursively collects paths of decrypted assets (files with any of [`DECRYPTED_ASSETS_EXTS`] extensions) under `root`, sorted by path.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory fails.
+pub fn decrypted_files(root: &Path) -> Result<Vec<PathBuf>, FileError> {
+    walk(root, |path| {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |ext| DECRYPTED_ASSETS_EXTS.contains(&ext))
+    })
+}
+
+/// Recursively removes stale partial files (see [`PARTIAL_EXT`]) left in `dir` by interrupted runs.
+///
+/// Other files are kept, even if their names end with `.part`.
+///
+/// # Returns
+///
+/// - Paths of the removed files, sorted.
+/// - [`FileError`] if reading a directory or removing a file fails.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory or removing a file fails.
+pub fn cleanup_partials(dir: &Path) -> Result<Vec<PathBuf>, FileError> {
+    let partials = walk(dir, |path| {
+        path.extension().map_or(false, |ext| ext == PARTIAL_EXT)
+    })?;
+
+    for path in &partials {
+        fs::remove_file(path).map_err(|err| FileError::new(path, err))?;
+    }
+
+    Ok(partials)
+}
+
+/// Stage of an operation run by [`with_timeout`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    #[default]
+    Running,
+    /// Operation made its effects visible, so its result must be awaited.
+    Committed,
+    /// Operation was reported as timed out, so it must not make its effects visible.
+    Expired,
+}
+
+/// Deadline of an operation run by [`with_timeout`], shared with its detached thread.
+#[derive(Debug, Default)]
+struct Deadline(Mutex<Stage>);
+
+impl Deadline {
+    /// Runs `commit`, the step making effects of the operation visible, e.g. renaming a partial file into place, unless the operation already timed out.
+    fn commit(
+        &self,
+        commit: impl FnOnce() -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut stage = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if *stage == Stage::Expired {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        commit()?;
+        *stage = Stage::Committed;
+        Ok(())
+    }
+
+    /// Marks the operation as timed out, unless it already committed.
+    ///
+    /// # Returns
+    ///
+    /// - Whether the operation expired.
+    fn expire(&self) -> bool {
+        let mut stage = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if *stage == Stage::Committed {
+            return false;
+        }
+
+        *stage = Stage::Expired;
+        true
+    }
+}
+
+fn with_timeout<T, F>(timeout: Option<Duration>, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&Deadline) -> io::Result<T> + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return Ok(f(&Deadline::default())?);
+    };
+
+    let deadline = Arc::new(Deadline::default());
+    let worker_deadline = Arc::clone(&deadline);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        // Receiver is gone if the operation timed out, nothing to do then.
+        let _ = sender.send(f(&worker_deadline));
+    });
+
+    let result = match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        // Committed operations finish right after, and their effects must be reported.
+        Err(_) if !deadline.expire() => {
+            receiver.recv().map_err(|_| Error::Timeout)?
+        }
+        Err(_) => return Err(Error::Timeout),
+    };
+
+    Ok(result?)
+}
+
+/// Reads the whole file at `path`, failing if it takes longer than `timeout`.
+///
+/// # Note
+///
+/// Blocking filesystem calls can't be cancelled, so on timeout the read is left running on a detached thread, and its result is discarded.
+///
+/// # Errors
+///
+/// - [`Error::Timeout`] - if reading takes longer than `timeout`.
+/// - [`Error::Io`] - if reading fails.
+pub fn read_with_timeout(
+    path: &Path,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, FileError> {
+    let owned_path = path.to_path_buf();
+
+    with_timeout(timeout, move |_| fs::read(owned_path))
+        .map_err(|err| FileError::new(path, err))
+}
+
+/// Same as [`read_with_timeout`], but reads into `buffer`, reusing its allocation between files.
+///
+/// On timeout, the allocation is lost with the detached read, and `buffer` is left empty.
+pub(crate) fn read_into_with_timeout(
+    path: &Path,
+    buffer: &mut Vec<u8>,
+    timeout: Option<Duration>,
+) -> Result<(), FileError> {
+    let owned_path = path.to_path_buf();
+    let mut data = mem::take(buffer);
+
+    *buffer = with_timeout(timeout, move |_| {
+        let mut file = File::open(owned_path)?;
+        let size = file.metadata().map_or(0, |meta| meta.len());
+
+        data.clear();
+        data.reserve(usize::try_from(size).unwrap_or(0));
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    })
+    .map_err(|err| FileError::new(path, err))?;
+
+    Ok(())
+}
+
+/// Writes `data` to the file at `path` atomically (see [`write_atomic`]), failing if it takes longer than `timeout`.
+///
+/// # Note
+///
+/// Blocking filesystem calls can't be cancelled, so on timeout the write is left running on a detached thread, and its result is discarded. The written data is never moved into place then: its partial file is removed instead, so `path` isn't created or replaced after the timeout is reported.
+///
+/// # Errors
+///
+/// - [`Error::Timeout`] - if writing takes longer than `timeout`.
+/// - [`Error::Io`] - if writing fails.
+pub fn write_with_timeout(
+    path: &Path,
+    data: Vec<u8>,
+    timeout: Option<Duration>,
+) -> Result<(), FileError> {
+    let owned_path = path.to_path_buf();
+
+    with_timeout(timeout, move |deadline| {
+        write_partial(&owned_path, &data, deadline)
+    })
+    .map_err(|err| FileError::new(path, err))
+}
+
+/// Same as [`write_with_timeout`], but writes `buffer[start..]`, so a prefix, e.g. the RPG Maker header, can be dropped without moving the rest of the data. The allocation is given back to `buffer` afterwards.
+pub(crate) fn write_tail_with_timeout(
+    path: &Path,
+    buffer: &mut Vec<u8>,
+    start: usize,
+    timeout: Option<Duration>,
+) -> Result<(), FileError> {
+    let owned_path = path.to_path_buf();
+    let data = mem::take(buffer);
+
+    *buffer = with_timeout(timeout, move |deadline| {
+        write_partial(&owned_path, &data[start..], deadline).map(|()| data)
+    })
+    .map_err(|err| FileError::new(path, err))?;
+
+    Ok(())
+}
@@ -0,0 +1,392 @@
+//! Declarative job descriptions, so GUIs and scripts can drive batch processing without bespoke glue per tool.
+//!
+//! A [`Job`] is a list of [`Task`]s, each running an [`Operation`] over an input directory into an output directory, see [`run_job`].
+//!
+//! With `serde` feature, jobs can be loaded from any serde format, e.g. JSON or TOML, and [`JobReport`]s can be serialized back:
+//!
+//! ```json
+//! {
+//!     "tasks": [
+//!         { "operation": "decrypt", "input": "game/www", "output": "assets", "include": ["img/**"] },
+//!         { "operation": "encrypt", "input": "assets", "output": "game/www", "system_json": "game/www/data/System.json", "engine": "mv" }
+//!     ]
+//! }
+//! ```
+
+use crate::{
+    EncryptionKey, Engine, FileError,
+    batch::{
+        BatchOptions, BatchReport, RepackOptions, decrypt_dir, encrypt_dir,
+    },
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Operation of a [`Task`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Operation {
+    /// Decrypt assets, see [`decrypt_dir`].
+    Decrypt,
+    /// Encrypt assets, see [`encrypt_dir`].
+    Encrypt,
+}
+
+/// Single step of a [`Job`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Task {
+    /// What to do.
+    pub operation: Operation,
+    /// Directory (or, for decryption, `app.asar` archive) to process.
+    pub input: PathBuf,
+    /// Directory to write outputs to.
+    pub output: PathBuf,
+    /// Encryption key, a hex string in job files. If not set, decryption determines it from the input, see [`BatchOptions::key`], and encryption reads it from [`Task::system_json`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub key: Option<EncryptionKey>,
+    /// Path to the game's `System.json`. Encryption only, see [`RepackOptions::system_json`]. Decryption tasks with it set fail.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub system_json: Option<PathBuf>,
+    /// Engine, which determines encrypted extensions. Encryption only, see [`RepackOptions::engine`]. Decryption tasks with it set fail.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub engine: Option<Engine>,
+    /// Globs of files to process. Decryption only, see [`BatchOptions::include`]. Encryption tasks with any fail, instead of encrypting all files.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub include: Vec<String>,
+    /// Globs of files to leave out. Decryption only, see [`BatchOptions::exclude`]. Encryption tasks with any fail, instead of encrypting all files.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub exclude: Vec<String>,
+}
+
+impl Task {
+    /// Creates a task running `operation` over `input` into `output`, with default options.
+    #[must_use]
+    pub fn new(
+        operation: Operation,
+        input: impl Into<PathBuf>,
+        output: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            operation,
+            input: input.into(),
+            output: output.into(),
+            key: None,
+            system_json: None,
+            engine: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Sets [`Task::key`].
+    #[must_use]
+    pub fn key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Sets [`Task::system_json`].
+    #[must_use]
+    pub fn system_json(mut self, path: impl Into<PathBuf>) -> Self {
+        self.system_json = Some(path.into());
+        self
+    }
+
+    /// Sets [`Task::engine`].
+    #[must_use]
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Returns the name of a field set on the task, which its operation doesn't use.
+    fn inapplicable_field(&self) -> Option<&'static str> {
+        match self.operation {
+            Operation::Decrypt if self.system_json.is_some() => {
+                Some("system_json")
+            }
+            Operation::Decrypt if self.engine.is_some() => Some("engine"),
+            Operation::Encrypt if !self.include.is_empty() => Some("include"),
+            Operation::Encrypt if !self.exclude.is_empty() => Some("exclude"),
+            _ => None,
+        }
+    }
+
+    /// Runs the task.
+    fn run(&self) -> Result<BatchReport, FileError> {
+        if let Some(field) = self.inapplicable_field() {
+            let operation = match self.operation {
+                Operation::Decrypt => "decrypt",
+                Operation::Encrypt => "encrypt",
+            };
+
+            return Err(FileError::new(
+                &self.input,
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{field}` doesn't apply to {operation} tasks"),
+                ),
+            ));
+        }
+
+        match self.operation {
+            Operation::Decrypt => {
+                let mut options = BatchOptions::new()
+                    .include(self.include.iter().cloned())
+                    .exclude(self.exclude.iter().cloned());
+                options.key = self.key;
+
+                decrypt_dir(&self.input, &self.output, &options)
+            }
+            Operation::Encrypt => {
+                let mut options = RepackOptions::new();
+                options.key = self.key;
+                options.system_json.clone_from(&self.system_json);
+                options.engine = self.engine;
+
+                encrypt_dir(&self.input, &self.output, &options)
+            }
+        }
+    }
+}
+
+/// List of [`Task`]s, run in order by [`run_job`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Job {
+    /// Tasks to run.
+    pub tasks: Vec<Task>,
+    /// Whether to skip the remaining tasks once a task fails to run, or any of its files fails.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fail_fast: bool,
+}
+
+impl Job {
+    /// Creates an empty job.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `task` to [`Job::tasks`].
+    #[must_use]
+    pub fn task(mut self, task: Task) -> Self {
+        self.tasks.push(task);
+        self
+    }
+
+    /// Sets [`Job::fail_fast`].
+    #[must_use]
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Resolves relative paths of all tasks against `base`, e.g. the directory of the job file, instead of the current directory.
+    #[must_use]
+    pub fn relative_to(mut self, base: &Path) -> Self {
+        let resolve = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        };
+
+        for task in &mut self.tasks {
+            resolve(&mut task.input);
+            resolve(&mut task.output);
+
+            if let Some(system_json) = &mut task.system_json {
+                resolve(system_json);
+            }
+        }
+
+        self
+    }
+}
+
+/// Outcome of a single [`Task`].
+#[derive(Debug)]
+pub enum TaskOutcome {
+    /// Task ran. Its files may still have failed individually, see [`BatchReport::failed`].
+    Completed(BatchReport),
+    /// Task couldn't run at all.
+    Failed(FileError),
+    /// Task wasn't run, because a previous task failed and [`Job::fail_fast`] is set.
+    Skipped,
+}
+
+/// Report of a single [`Task`].
+#[derive(Debug)]
+pub struct TaskReport {
+    /// Operation of the task.
+    pub operation: Operation,
+    /// Input of the task.
+    pub input: PathBuf,
+    /// Output of the task.
+    pub output: PathBuf,
+    /// Outcome of the task.
+    pub outcome: TaskOutcome,
+}
+
+impl TaskReport {
+    /// Returns whether the task ran, and none of its files failed.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        match &self.outcome {
+            TaskOutcome::Completed(report) => report.failed().next().is_none(),
+            TaskOutcome::Failed(_) | TaskOutcome::Skipped => false,
+        }
+    }
+}
+
+/// Report of a [`Job`], with a [`TaskReport`] for every task, in order.
+///
+/// With `serde` feature, the report can be serialized as an object with `succeeded` flag, `elapsed` in seconds, and `tasks` array of `{ "operation", "input", "output", "status", "error", "report" }` objects, where `status` is `completed`, `failed` or `skipped`, and `report` is the serialized [`BatchReport`] of completed tasks.
+#[derive(Debug, Default)]
+pub struct JobReport {
+    /// Reports of all tasks.
+    pub tasks: Vec<TaskReport>,
+    /// Wall-clock duration of the job.
+    pub elapsed: Duration,
+}
+
+impl JobReport {
+    /// Returns whether all tasks ran, and none of their files failed.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.tasks.iter().all(TaskReport::is_success)
+    }
+}
+
+/// Serialized form of [`TaskReport`].
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct TaskReportRepr<'a> {
+    operation: Operation,
+    input: &'a Path,
+    output: &'a Path,
+    status: &'static str,
+    error: Option<String>,
+    report: Option<&'a BatchReport>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> From<&'a TaskReport> for TaskReportRepr<'a> {
+    fn from(task: &'a TaskReport) -> Self {
+        let (status, error, report) = match &task.outcome {
+            TaskOutcome::Completed(report) => ("completed", None, Some(report)),
+            TaskOutcome::Failed(err) => ("failed", Some(err.to_string()), None),
+            TaskOutcome::Skipped => ("skipped", None, None),
+        };
+
+        Self {
+            operation: task.operation,
+            input: &task.input,
+            output: &task.output,
+            status,
+            error,
+            report,
+        }
+    }
+}
+
+/// Serialized form of [`JobReport`].
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct JobReportRepr<'a> {
+    succeeded: bool,
+    elapsed: f64,
+    tasks: Vec<TaskReportRepr<'a>>,
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for JobReport {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        JobReportRepr {
+            succeeded: self.is_success(),
+            elapsed: self.elapsed.as_secs_f64(),
+            tasks: self.tasks.iter().map(Into::into).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Runs all tasks of `job` in order.
+///
+/// Failures never abort the job: they're recorded in the [`JobReport`], and with [`Job::fail_fast`], the remaining tasks are reported as [`TaskOutcome::Skipped`].
+///
+/// A task fails with [`TaskOutcome::Failed`] on any error of [`decrypt_dir`] or [`encrypt_dir`], or with [`crate::Error::Io`] of [`std::io::ErrorKind::InvalidInput`] kind, if it sets fields its operation doesn't use, e.g. [`Task::include`] of an encryption task.
+///
+/// # Returns
+///
+/// - [`JobReport`] with a [`TaskReport`] for every task.
+#[must_use]
+pub fn run_job(job: &Job) -> JobReport {
+    let start = Instant::now();
+    let mut failed = false;
+
+    let tasks = job
+        .tasks
+        .iter()
+        .map(|task| {
+            let outcome = if failed && job.fail_fast {
+                TaskOutcome::Skipped
+            } else {
+                match task.run() {
+                    Ok(report) => TaskOutcome::Completed(report),
+                    Err(err) => TaskOutcome::Failed(err),
+                }
+            };
+
+            let report = TaskReport {
+                operation: task.operation,
+                input: task.input.clone(),
+                output: task.output.clone(),
+                outcome,
+            };
+            failed |= !report.is_success();
+            report
+        })
+        .collect();
+
+    JobReport {
+        tasks,
+        elapsed: start.elapsed(),
+    }
+}
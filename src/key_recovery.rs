@@ -0,0 +1,467 @@
+//! Recovery of encryption keys from encrypted files of possibly unknown types.
+//!
+//! [`crate::Decrypter::set_key_from_file`] only works for PNG, OGG and M4A, because it needs a known plaintext header.
+//! [`KeyRecovery`] instead matches the first encrypted block of several files against a library of common file signatures, and ranks resulting candidate keys by how many files they decrypt into something recognizable.
+//! [`brute_force`] tries keys derived from guessable passphrases instead, for games which ship without `System.json`.
+//! [`scan_scripts`] looks for keys hardcoded into the game's scripts, such as modified `rpg_core.js`, which repacked games use instead of `System.json`.
+
+use crate::{
+    DEFAULT_KEY, EncryptionKey, Error, FileError, HEADER_LENGTH, KEY_LENGTH,
+    KEY_STR_LENGTH, RPGM_HEADER, fs::walk,
+};
+#[cfg(feature = "md5")]
+use crate::{Decrypter, FileType, is_encrypted_data, verify};
+use std::{
+    ffi::OsStr,
+    fs::{metadata, read},
+    path::{Path, PathBuf},
+};
+
+/// Known plaintext header of a file format.
+///
+/// Formats rarely have 16 fixed bytes, so only bytes set in `mask` are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    /// Name of the format, e.g. `png`.
+    pub name: &'static str,
+    /// Expected first 16 bytes of the file.
+    pub bytes: [u8; HEADER_LENGTH],
+    /// Bit `i` is set if byte `i` of `bytes` is fixed.
+    pub mask: u16,
+}
+
+impl Signature {
+    /// Creates a signature from the expected bytes, where `None` means the byte differs between files.
+    #[must_use]
+    pub const fn new(
+        name: &'static str,
+        pattern: [Option<u8>; HEADER_LENGTH],
+    ) -> Self {
+        let mut bytes = [0; HEADER_LENGTH];
+        let mut mask = 0;
+        let mut i = 0;
+
+        while i < HEADER_LENGTH {
+            if let Some(byte) = pattern[i] {
+                bytes[i] = byte;
+                mask |= 1 << i;
+            }
+
+            i += 1;
+        }
+
+        Self { name, bytes, mask }
+    }
+
+    fn matches(&self, block: &[u8; HEADER_LENGTH], known: u16) -> bool {
+        let compared = self.mask & known;
+
+        compared.count_ones() >= MIN_COMPARED_BYTES
+            && (0..HEADER_LENGTH)
+                .filter(|i| compared & (1 << i) != 0)
+                .all(|i| block[i] == self.bytes[i])
+    }
+}
+
+// Fewer matching bytes are too likely to be a coincidence.
+const MIN_COMPARED_BYTES: u32 = 4;
+
+macro_rules! pattern {
+    ($($byte:tt),* $(,)?) => {
+        [$(pattern!(@byte $byte)),*]
+    };
+    (@byte _) => { None };
+    (@byte $byte:expr) => { Some($byte) };
+}
+
+/// Built-in signatures of formats commonly found in RPG Maker games.
+pub const SIGNATURES: &[Signature] = &[
+    Signature::new(
+        "png",
+        pattern![
+            0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00,
+            0x0d, b'I', b'H', b'D', b'R'
+        ],
+    ),
+    Signature::new(
+        "ogg",
+        pattern![b'O', b'g', b'g', b'S', 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, _, _],
+    ),
+    Signature::new(
+        "m4a",
+        pattern![
+            _, _, _, _, b'f', b't', b'y', b'p', b'M', b'4', b'A', b' ', _, _,
+            _, _
+        ],
+    ),
+    Signature::new(
+        "webp",
+        pattern![
+            b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', b'V',
+            b'P', b'8', _
+        ],
+    ),
+    Signature::new(
+        "wav",
+        pattern![
+            b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', b'f',
+            b'm', b't', b' '
+        ],
+    ),
+    Signature::new(
+        "jpeg",
+        pattern![
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10, b'J', b'F', b'I', b'F', 0x00,
+            0x01, _, _, _, _
+        ],
+    ),
+    Signature::new(
+        "gif",
+        pattern![
+            b'G', b'I', b'F', b'8', b'9', b'a', _, _, _, _, _, _, _, _, _, _
+        ],
+    ),
+    // RPG Maker database files, e.g. `Actors.json`.
+    Signature::new(
+        "json",
+        pattern![
+            b'[', b'\n', b'n', b'u', b'l', b'l', b',', b'\n', b'{', b'"', b'i',
+            b'd', b'"', b':', b'1', b','
+        ],
+    ),
+];
+
+/// Candidate key produced by [`KeyRecovery::candidates`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCandidate {
+    /// Key bytes. Only bytes set in `known` are meaningful.
+    pub key: [u8; KEY_LENGTH],
+    /// Bit `i` is set if byte `i` of `key` is known.
+    pub known: u16,
+    /// Share of files that this key decrypts into a recognizable signature, scaled by the share of known key bytes, from `0.0` to `1.0`.
+    pub confidence: f32,
+    /// Names of signatures matched by the files, in the order files were added. `None` for files that didn't match any.
+    pub matches: Vec<Option<&'static str>>,
+}
+
+impl KeyCandidate {
+    /// Returns whether all 16 key bytes are known.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.known == u16::MAX
+    }
+
+    /// Returns the key, if all its bytes are known.
+    #[must_use]
+    pub fn encryption_key(&self) -> Option<EncryptionKey> {
+        self.is_complete()
+            .then(|| EncryptionKey::from_bytes(self.key))
+    }
+}
+
+/// Recovers keys from several encrypted files of possibly unknown types.
+#[derive(Debug, Clone, Default)]
+pub struct KeyRecovery {
+    blocks: Vec<[u8; HEADER_LENGTH]>,
+    signatures: Vec<Signature>,
+}
+
+impl KeyRecovery {
+    /// Creates a new [`KeyRecovery`] with built-in [`SIGNATURES`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            signatures: SIGNATURES.to_vec(),
+        }
+    }
+
+    /// Adds a custom signature to match files against.
+    pub fn add_signature(&mut self, signature: Signature) -> &mut Self {
+        self.signatures.push(signature);
+        self
+    }
+
+    /// Adds an encrypted file. Only its first 32 bytes are used.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::InvalidHeader`] - if passed `file_content` data has invalid header.
+    /// - [`Error::UnexpectedEOF`] - if passed `file_content` data ends unexpectedly.
+    pub fn add_file(
+        &mut self,
+        file_content: &[u8],
+    ) -> Result<&mut Self, Error> {
+        if !file_content.starts_with(RPGM_HEADER) {
+            return Err(Error::InvalidHeader);
+        }
+
+        let block = file_content
+            .get(HEADER_LENGTH..HEADER_LENGTH * 2)
+            .ok_or(Error::UnexpectedEOF)?;
+
+        let mut encrypted = [0; HEADER_LENGTH];
+        encrypted.copy_from_slice(block);
+        self.blocks.push(encrypted);
+        Ok(self)
+    }
+
+    // Counts are small, so the precision loss is irrelevant.
+    #[allow(clippy::cast_precision_loss)]
+    fn score(&self, key: [u8; KEY_LENGTH], known: u16) -> KeyCandidate {
+        let matches: Vec<_> = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let mut decrypted = *block;
+
+                for (byte, key_byte) in decrypted.iter_mut().zip(key) {
+                    *byte ^= key_byte;
+                }
+
+                self.signatures
+                    .iter()
+                    .find(|signature| signature.matches(&decrypted, known))
+                    .map(|signature| signature.name)
+            })
+            .collect();
+
+        let recognized = matches.iter().filter(|name| name.is_some()).count();
+        let confidence = recognized as f32 / self.blocks.len().max(1) as f32
+            * known.count_ones() as f32
+            / HEADER_LENGTH as f32;
+
+        KeyCandidate {
+            key,
+            known,
+            confidence,
+            matches,
+        }
+    }
+
+    /// Returns candidate keys, sorted by confidence from highest to lowest.
+    ///
+    /// Candidates are derived from every added file against every signature, plus [`DEFAULT_KEY`], which most games use.
+    /// Candidates that don't decrypt any file into a recognizable signature are dropped.
+    #[must_use]
+    pub fn candidates(&self) -> Vec<KeyCandidate> {
+        let mut keys: Vec<([u8; KEY_LENGTH], u16)> = Vec::new();
+
+        if let Ok(default_key) = EncryptionKey::from_hex_str(DEFAULT_KEY) {
+            keys.push((*default_key.as_bytes(), u16::MAX));
+        }
+
+        for block in &self.blocks {
+            for signature in &self.signatures {
+                let mut key = [0; KEY_LENGTH];
+
+                for (i, byte) in key.iter_mut().enumerate() {
+                    if signature.mask & (1 << i) != 0 {
+                        *byte = block[i] ^ signature.bytes[i];
+                    }
+                }
+
+                if !keys.contains(&(key, signature.mask)) {
+                    keys.push((key, signature.mask));
+                }
+            }
+        }
+
+        let mut candidates: Vec<_> = keys
+            .into_iter()
+            .map(|(key, known)| self.score(key, known))
+            .filter(|candidate| candidate.confidence > 0.0)
+            .collect();
+
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        candidates
+    }
+}
+
+/// Returns whether decrypted `data` looks like a real file of `file_type`.
+#[cfg(feature = "md5")]
+fn is_plausible(data: &[u8], file_type: FileType) -> bool {
+    match file_type {
+        FileType::JSON => core::str::from_utf8(data)
+            .map_or(false, |text| text.trim_start().starts_with(['{', '['])),
+        FileType::Other(_) => false,
+        _ => verify::validate(data, file_type).is_valid(),
+    }
+}
+
+/// Searches `wordlist` for the passphrase `encrypted` file was encrypted with.
+///
+/// RPG Maker derives keys from passphrases by MD5-hashing them, so each candidate is hashed the same way (see [`Decrypter::set_key_from_plaintext`]), and its key is accepted if it decrypts the file into valid data of `file_type` (see [`verify::validate`]).
+/// Candidates are rejected by the first decrypted block first, so only plausible ones decrypt the whole file.
+///
+/// # Parameters
+///
+/// - `encrypted` - Encrypted file content.
+/// - `file_type` - Type of the file. [`FileType::Other`] can't be validated, so it never matches.
+/// - `wordlist` - Candidate passphrases.
+///
+/// # Returns
+///
+/// - Key derived from the first matching passphrase.
+/// - `None` if no passphrase matches, or `encrypted` is not an encrypted file.
+#[cfg(feature = "md5")]
+pub fn brute_force<S: AsRef<str>>(
+    encrypted: &[u8],
+    file_type: FileType,
+    wordlist: impl IntoIterator<Item = S>,
+) -> Option<EncryptionKey> {
+    if !is_encrypted_data(encrypted) || matches!(file_type, FileType::Other(_))
+    {
+        return None;
+    }
+
+    let mut decrypter = Decrypter::new();
+
+    wordlist.into_iter().find_map(|passphrase| {
+        decrypter.set_key_from_plaintext(passphrase.as_ref());
+
+        let block = decrypter.decrypt_header_only(encrypted, file_type).ok()?;
+        let block_matches = match file_type {
+            FileType::JSON => block
+                .iter()
+                .find(|byte| !byte.is_ascii_whitespace())
+                .map_or(true, |byte| matches!(byte, b'{' | b'[')),
+            _ => FileType::detect(&block) == Some(file_type),
+        };
+
+        if !block_matches {
+            return None;
+        }
+
+        let data = decrypter.decrypt(encrypted, file_type).ok()?;
+        is_plausible(&data, file_type)
+            .then(|| decrypter.encryption_key())
+            .flatten()
+    })
+}
+
+/// Extensions of files [`scan_scripts`] searches.
+const SCRIPT_EXTS: &[&str] = &["js", "mjs", "html", "htm"];
+
+/// Maximum size of files [`scan_scripts`] searches, so bundled media isn't read into memory.
+const MAX_SCRIPT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Key found by [`scan_scripts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptKey {
+    /// The key.
+    pub key: EncryptionKey,
+    /// Path of the script the key was found in.
+    pub path: PathBuf,
+    /// Offset of the key's string literal in the script.
+    pub offset: usize,
+}
+
+/// Returns keys assigned to `encryptionKey`-like identifiers or properties in script `data`, with offsets of their literals.
+fn key_assignments(data: &[u8]) -> Vec<(EncryptionKey, usize)> {
+    const NAME: &[u8] = b"ncryptionKey";
+
+    let skip_whitespace = |mut pos: usize| {
+        while data.get(pos).map_or(false, u8::is_ascii_whitespace) {
+            pos += 1;
+        }
+
+        pos
+    };
+
+    let mut keys = Vec::new();
+
+    for start in 1..data.len().saturating_sub(NAME.len()) {
+        if !matches!(data[start - 1], b'e' | b'E')
+            || &data[start..start + NAME.len()] != NAME
+        {
+            continue;
+        }
+
+        // Quoted property names, e.g. `"encryptionKey": "..."`.
+        let mut pos = start + NAME.len();
+        if matches!(data.get(pos), Some(b'"' | b'\'')) {
+            pos += 1;
+        }
+
+        pos = skip_whitespace(pos);
+
+        match (data.get(pos), data.get(pos + 1)) {
+            (Some(b'='), Some(next)) if *next != b'=' => {}
+            (Some(b':'), _) => {}
+            _ => continue,
+        }
+
+        let literal = skip_whitespace(pos + 1);
+        let (Some(&quote), Some(value), Some(&closing)) = (
+            data.get(literal),
+            data.get(literal + 1..literal + 1 + KEY_STR_LENGTH),
+            data.get(literal + 1 + KEY_STR_LENGTH),
+        ) else {
+            continue;
+        };
+
+        if !matches!(quote, b'"' | b'\'' | b'`') || closing != quote {
+            continue;
+        }
+
+        if let Some(key) = core::str::from_utf8(value)
+            .ok()
+            .and_then(|value| EncryptionKey::from_hex_str(value).ok())
+        {
+            keys.push((key, literal));
+        }
+    }
+
+    keys
+}
+
+/// Recursively searches scripts under `dir` (`.js`, `.mjs`, `.html` and `.htm` files, up to 16 MiB) for hardcoded keys, to recover the key of games that ship without `System.json`.
+///
+/// Keys are recognized in assignments to any identifier or property ending with `encryptionKey` or `EncryptionKey`, e.g. `this._encryptionKey = "..."` in modified `rpg_core.js`/`rmmz_core.js`, `$dataSystem.encryptionKey = '...'` in plugins, or `"encryptionKey": "..."` in inlined data. Only string literals of valid 32-character hex keys count, so runtime assignments, such as the stock `this._encryptionKey = $dataSystem.encryptionKey`, are ignored.
+///
+/// Files that can't be read are skipped.
+///
+/// # Returns
+///
+/// - Distinct keys in the order they're first found, with scripts sorted by path. Empty if there are none.
+/// - [`FileError`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory fails.
+pub fn scan_scripts(dir: &Path) -> Result<Vec<ScriptKey>, FileError> {
+    let scripts = walk(dir, |path| {
+        path.extension()
+            .and_then(OsStr::to_str)
+            .map_or(false, |ext| {
+                SCRIPT_EXTS.contains(&ext.to_ascii_lowercase().as_str())
+            })
+    })?;
+
+    let mut keys: Vec<ScriptKey> = Vec::new();
+
+    for path in scripts {
+        let Ok(data) = metadata(&path).map_err(Error::from).and_then(|meta| {
+            if meta.len() > MAX_SCRIPT_SIZE {
+                Err(Error::Io(std::io::ErrorKind::InvalidData.into()))
+            } else {
+                read(&path).map_err(Error::from)
+            }
+        }) else {
+            continue;
+        };
+
+        for (key, offset) in key_assignments(&data) {
+            if keys.iter().all(|found| found.key != key) {
+                keys.push(ScriptKey {
+                    key,
+                    path: path.clone(),
+                    offset,
+                });
+            }
+        }
+    }
+
+    Ok(keys)
+}
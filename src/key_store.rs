@@ -0,0 +1,197 @@
+//! Cache of encryption keys discovered per project.
+//!
+//! Deriving a key from a file requires reading and parsing it, e.g. walking OGG pages to find the serial number, so for games with thousands of assets, it's worth doing once per project and reusing the key everywhere.
+//!
+//! With `serde` feature, [`KeyStore`] can be saved and loaded in any serde format, e.g. JSON or TOML, so front-ends can remember keys of previously opened games.
+//! Keys are stored as hex strings, grouped by project path (`paths`) and content hash (`hashes`, see [`content_hash`]).
+
+use crate::{
+    Decrypter, EncryptionKey, Engine, Error, FileError, FileType,
+    fs::encrypted_files,
+    manifest,
+    project::{DetectEngine, system_json_path},
+    system_json::extract_key_from_system_json_file,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs::read,
+    path::{Path, PathBuf},
+};
+
+/// Keys of projects, keyed by project root or by content hash.
+///
+/// Keys recorded by root apply to the whole directory tree under it, so [`KeyStore::find`] can look up the key of any asset of a recorded project.
+/// Keys recorded by content hash survive moving the project around, see [`content_hash`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct KeyStore {
+    #[cfg_attr(feature = "serde", serde(rename = "paths", default))]
+    keys: BTreeMap<PathBuf, EncryptionKey>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    hashes: BTreeMap<String, EncryptionKey>,
+}
+
+/// Returns a hash of `data`, as a 16-character hex string, to record keys by with [`KeyStore::insert_hash`].
+///
+/// Hashing e.g. the project's `System.json` or its first encrypted asset identifies the game regardless of where it's located.
+/// The hash is [`manifest::hash`], so it's stable across versions and platforms, but not cryptographic.
+#[must_use]
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:016x}", manifest::hash(data))
+}
+
+impl KeyStore {
+    /// Creates an empty store.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the `key` of project at `root`, and returns the previously recorded key of it, if any.
+    pub fn insert(
+        &mut self,
+        root: impl Into<PathBuf>,
+        key: EncryptionKey,
+    ) -> Option<EncryptionKey> {
+        self.keys.insert(root.into(), key)
+    }
+
+    /// Returns the key recorded for exactly `root`.
+    #[must_use]
+    pub fn get(&self, root: &Path) -> Option<EncryptionKey> {
+        self.keys.get(root).copied()
+    }
+
+    /// Removes the key of project at `root`, and returns it.
+    pub fn remove(&mut self, root: &Path) -> Option<EncryptionKey> {
+        self.keys.remove(root)
+    }
+
+    /// Records the `key` of project with content `hash` (see [`content_hash`]), and returns the previously recorded key of it, if any.
+    pub fn insert_hash(
+        &mut self,
+        hash: impl Into<String>,
+        key: EncryptionKey,
+    ) -> Option<EncryptionKey> {
+        self.hashes.insert(hash.into(), key)
+    }
+
+    /// Returns the key recorded for content `hash`.
+    #[must_use]
+    pub fn get_hash(&self, hash: &str) -> Option<EncryptionKey> {
+        self.hashes.get(hash).copied()
+    }
+
+    /// Removes the key of project with content `hash`, and returns it.
+    pub fn remove_hash(&mut self, hash: &str) -> Option<EncryptionKey> {
+        self.hashes.remove(hash)
+    }
+
+    /// Returns the key of the nearest recorded project containing `path`, together with that project's root.
+    #[must_use]
+    pub fn find(&self, path: &Path) -> Option<(&Path, EncryptionKey)> {
+        path.ancestors().find_map(|ancestor| {
+            self.keys
+                .get_key_value(ancestor)
+                .map(|(root, key)| (root.as_path(), *key))
+        })
+    }
+
+    /// Returns recorded project roots and their keys, sorted by root.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, EncryptionKey)> {
+        self.keys.iter().map(|(root, key)| (root.as_path(), *key))
+    }
+
+    /// Returns recorded content hashes and their keys, sorted by hash.
+    pub fn iter_hashes(&self) -> impl Iterator<Item = (&str, EncryptionKey)> {
+        self.hashes.iter().map(|(hash, key)| (hash.as_str(), *key))
+    }
+
+    /// Returns the number of recorded keys, both by root and by content hash.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.keys.len() + self.hashes.len()
+    }
+
+    /// Returns whether no keys are recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.hashes.is_empty()
+    }
+
+    /// Returns the key of project at `root`, deriving and recording it, if it's not recorded yet (see [`KeyStore::find`]).
+    ///
+    /// The key is read from the project's `System.json`, or, if it's absent, derived from the first encrypted PNG, or from the first encrypted asset at all, if there are no PNGs.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::KeyNotSet`] - if the project has neither `System.json` nor encrypted assets.
+    /// - Any error of reading `System.json` or the asset, or of deriving the key.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip(self),
+            fields(root = %root.display())
+        )
+    )]
+    pub fn get_or_derive(
+        &mut self,
+        root: &Path,
+    ) -> Result<EncryptionKey, FileError> {
+        if let Some((_, key)) = self.find(root) {
+            return Ok(key);
+        }
+
+        let key = derive_key(root)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!("key derived");
+        self.keys.insert(root.to_path_buf(), key);
+        Ok(key)
+    }
+}
+
+fn derive_key(root: &Path) -> Result<EncryptionKey, FileError> {
+    if let Some(engine) = Engine::detect(root) {
+        let path = system_json_path(root, engine);
+
+        if path.is_file() {
+            let info = extract_key_from_system_json_file(&path)?;
+            return EncryptionKey::from_hex_str(&info.key)
+                .map_err(|err| FileError::new(&path, err));
+        }
+    }
+
+    let file_type_of = |path: &Path| {
+        path.extension()
+            .and_then(|ext| FileType::try_from(ext).ok())
+    };
+
+    let files = encrypted_files(root)?;
+    let Some((path, file_type)) = files
+        .iter()
+        .filter_map(|path| Some((path, file_type_of(path)?)))
+        .find(|(_, file_type)| file_type.is_png())
+        .or_else(|| {
+            files
+                .iter()
+                .filter_map(|path| Some((path, file_type_of(path)?)))
+                .find(|(_, file_type)| !file_type.is_json())
+        })
+    else {
+        return Err(FileError::new(root, Error::KeyNotSet));
+    };
+
+    let data = read(path).map_err(|err| FileError::new(path, err))?;
+    let mut decrypter = Decrypter::new();
+    decrypter
+        .set_key_from_file(&data, file_type)
+        .map_err(|err| FileError::new(path, err))?;
+
+    decrypter
+        .encryption_key()
+        .ok_or_else(|| FileError::new(path, Error::KeyNotSet))
+}
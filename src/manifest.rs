@@ -0,0 +1,445 @@
+//! Manifests of processed files, used to resume interrupted batch runs.
+//!
+//! Manifest is a [JSON Lines](https://jsonlines.org) file: every processed file is appended as a single JSON object as soon as its output is written, so a run that is interrupted still leaves a valid manifest of everything it finished.
+//!
+//! ```json
+//! {"input":"img/a.rpgmvp","output":"/games/out/img/a.png","size":1234,"hash":"af63bd4c8601b7df"}
+//! ```
+//!
+//! With checksums enabled (see [`crate::batch::BatchOptions::checksums`]), records also contain SHA-256 of both the input and the output, as `input_sha256` and `output_sha256`, so the manifest can prove which assets a repacked game differs in.
+
+use crate::{FileError, json};
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{File, OpenOptions, read, read_to_string},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+/// Single processed file in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestRecord {
+    /// Path to the input file, relative to the input root of the run.
+    pub input: PathBuf,
+    /// Path to the written output file.
+    pub output: PathBuf,
+    /// Size of the output file in bytes.
+    pub size: u64,
+    /// Hash of the output file (see [`hash`]).
+    pub hash: u64,
+    /// SHA-256 of the input file, if checksums were enabled.
+    pub input_sha256: Option<[u8; 32]>,
+    /// SHA-256 of the output file, if checksums were enabled.
+    pub output_sha256: Option<[u8; 32]>,
+}
+
+impl ManifestRecord {
+    /// Returns whether the output file still exists, and its contents match the record.
+    ///
+    /// If [`ManifestRecord::output_sha256`] is recorded, it's checked too.
+    #[must_use]
+    pub fn is_intact(&self) -> bool {
+        read(&self.output).map_or(false, |data| {
+            data.len() as u64 == self.size
+                && hash(&data) == self.hash
+                && self
+                    .output_sha256
+                    .map_or(true, |checksum| sha256(&data) == checksum)
+        })
+    }
+
+    fn to_json_line(&self) -> String {
+        let mut line = String::from("{\"input\":");
+        push_json_string(&mut line, &self.input.to_string_lossy());
+        line.push_str(",\"output\":");
+        push_json_string(&mut line, &self.output.to_string_lossy());
+        let _ = write!(
+            line,
+            ",\"size\":{},\"hash\":\"{:016x}\"",
+            self.size, self.hash
+        );
+
+        for (key, checksum) in [
+            ("input_sha256", &self.input_sha256),
+            ("output_sha256", &self.output_sha256),
+        ] {
+            if let Some(checksum) = checksum {
+                let _ = write!(line, ",\"{key}\":\"{}\"", to_hex(checksum));
+            }
+        }
+
+        line.push_str("}\n");
+        line
+    }
+
+    fn from_json_line(line: &str) -> Option<Self> {
+        let mut parser = Parser {
+            data: line.trim().as_bytes(),
+            pos: 0,
+        };
+
+        let mut input = None;
+        let mut output = None;
+        let mut size = None;
+        let mut hash = None;
+        let mut input_sha256 = None;
+        let mut output_sha256 = None;
+
+        parser.expect(b'{')?;
+
+        loop {
+            let key = parser.string()?;
+            parser.expect(b':')?;
+
+            match key.as_str() {
+                "input" => input = Some(PathBuf::from(parser.string()?)),
+                "output" => output = Some(PathBuf::from(parser.string()?)),
+                "size" => size = Some(parser.number()?),
+                "hash" => {
+                    hash =
+                        Some(u64::from_str_radix(&parser.string()?, 16).ok()?);
+                }
+                "input_sha256" => {
+                    input_sha256 = Some(from_hex(&parser.string()?)?);
+                }
+                "output_sha256" => {
+                    output_sha256 = Some(from_hex(&parser.string()?)?);
+                }
+                _ => return None,
+            }
+
+            match parser.next()? {
+                b',' => {}
+                b'}' => break,
+                _ => return None,
+            }
+        }
+
+        if parser.pos != parser.data.len() {
+            return None;
+        }
+
+        Some(Self {
+            input: input?,
+            output: output?,
+            size: size?,
+            hash: hash?,
+            input_sha256,
+            output_sha256,
+        })
+    }
+}
+
+/// Records of files processed by previous batch runs, keyed by their relative input paths.
+///
+/// Loaded manifest is passed to [`crate::batch::BatchOptions::resume_from`], so files that are already done are not processed again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    records: HashMap<PathBuf, ManifestRecord>,
+}
+
+impl Manifest {
+    /// Creates an empty manifest.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses manifest from JSON Lines `text`.
+    ///
+    /// Malformed lines, e.g. the last line of a run that was killed mid-write, are ignored. If an input is recorded more than once, the last record wins.
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let records = text
+            .lines()
+            .filter_map(ManifestRecord::from_json_line)
+            .map(|record| (record.input.clone(), record))
+            .collect();
+
+        Self { records }
+    }
+
+    /// Reads and parses manifest at `path` (see [`Manifest::parse`]).
+    ///
+    /// # Errors
+    ///
+    /// - [`crate::Error::Io`] - if reading fails.
+    pub fn load(path: &Path) -> Result<Self, FileError> {
+        read_to_string(path)
+            .map(|text| Self::parse(&text))
+            .map_err(|err| FileError::new(path, err))
+    }
+
+    /// Returns the record of `input`, which is relative to the input root of the run.
+    #[must_use]
+    pub fn get(&self, input: &Path) -> Option<&ManifestRecord> {
+        self.records.get(input)
+    }
+
+    /// Adds `record`, and returns the previous record of the same input, if any.
+    pub fn insert(&mut self, record: ManifestRecord) -> Option<ManifestRecord> {
+        self.records.insert(record.input.clone(), record)
+    }
+
+    /// Returns all records, in no particular order.
+    pub fn records(&self) -> impl Iterator<Item = &ManifestRecord> {
+        self.records.values()
+    }
+
+    /// Returns the number of records.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns whether there are no records.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Appends [`ManifestRecord`]s to a manifest file, one line at a time, from any number of threads.
+#[derive(Debug)]
+pub(crate) struct ManifestWriter {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl ManifestWriter {
+    /// Opens manifest at `path` for appending, creating it if it doesn't exist.
+    pub(crate) fn open(path: &Path) -> Result<Self, FileError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| FileError::new(path, err))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Appends `record` and flushes it, so it survives the process being killed right after.
+    pub(crate) fn append(
+        &self,
+        record: &ManifestRecord,
+    ) -> Result<(), FileError> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+
+        file.write_all(record.to_json_line().as_bytes())
+            .and_then(|()| file.flush())
+            .map_err(|err: io::Error| FileError::new(&self.path, err))
+    }
+}
+
+/// Returns the hash of `data` recorded in [`ManifestRecord::hash`].
+///
+/// The hash is FNV-1a, which is fast and stable across versions and platforms, but not cryptographic: it detects incomplete or changed outputs, not tampering.
+/// It's also the hash of [`crate::patch`] and [`crate::key_store::content_hash`].
+#[must_use]
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Round constants of SHA-256.
+#[rustfmt::skip]
+const SHA256_K: [u32; 64] = [
+    0x428a_2f98, 0x7137_4491, 0xb5c0_fbcf, 0xe9b5_dba5,
+    0x3956_c25b, 0x59f1_11f1, 0x923f_82a4, 0xab1c_5ed5,
+    0xd807_aa98, 0x1283_5b01, 0x2431_85be, 0x550c_7dc3,
+    0x72be_5d74, 0x80de_b1fe, 0x9bdc_06a7, 0xc19b_f174,
+    0xe49b_69c1, 0xefbe_4786, 0x0fc1_9dc6, 0x240c_a1cc,
+    0x2de9_2c6f, 0x4a74_84aa, 0x5cb0_a9dc, 0x76f9_88da,
+    0x983e_5152, 0xa831_c66d, 0xb003_27c8, 0xbf59_7fc7,
+    0xc6e0_0bf3, 0xd5a7_9147, 0x06ca_6351, 0x1429_2967,
+    0x27b7_0a85, 0x2e1b_2138, 0x4d2c_6dfc, 0x5338_0d13,
+    0x650a_7354, 0x766a_0abb, 0x81c2_c92e, 0x9272_2c85,
+    0xa2bf_e8a1, 0xa81a_664b, 0xc24b_8b70, 0xc76c_51a3,
+    0xd192_e819, 0xd699_0624, 0xf40e_3585, 0x106a_a070,
+    0x19a4_c116, 0x1e37_6c08, 0x2748_774c, 0x34b0_bcb5,
+    0x391c_0cb3, 0x4ed8_aa4a, 0x5b9c_ca4f, 0x682e_6ff3,
+    0x748f_82ee, 0x78a5_636f, 0x84c8_7814, 0x8cc7_0208,
+    0x90be_fffa, 0xa450_6ceb, 0xbef9_a3f7, 0xc671_78f2,
+];
+
+/// Returns SHA-256 of `data`, recorded in [`ManifestRecord::input_sha256`] and [`ManifestRecord::output_sha256`].
+#[must_use]
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09_e667,
+        0xbb67_ae85,
+        0x3c6e_f372,
+        0xa54f_f53a,
+        0x510e_527f,
+        0x9b05_688c,
+        0x1f83_d9ab,
+        0x5be0_cd19,
+    ];
+
+    // Padding: a single `1` bit, zeros up to 56 bytes modulo 64, and the bit length as big-endian u64.
+    let bit_length = (data.len() as u64).wrapping_mul(8);
+    let tail_start = data.len() - data.len() % 64;
+    let mut tail = data[tail_start..].to_vec();
+    tail.push(0x80);
+
+    while tail.len() % 64 != 56 {
+        tail.push(0);
+    }
+
+    tail.extend_from_slice(&bit_length.to_be_bytes());
+
+    for block in data[..tail_start]
+        .chunks_exact(64)
+        .chain(tail.chunks_exact(64))
+    {
+        sha256_block(&mut state, block);
+    }
+
+    let mut digest = [0; 32];
+
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+// Names follow FIPS 180-4, which makes the code easy to check against it.
+#[allow(clippy::many_single_char_names)]
+fn sha256_block(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+
+    for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7)
+            ^ w[i - 15].rotate_right(18)
+            ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17)
+            ^ w[i - 2].rotate_right(19)
+            ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    for (k, w) in SHA256_K.iter().zip(w) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(*k)
+            .wrapping_add(w);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+
+    for (state, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+/// Formats `bytes` as lowercase hex string.
+#[must_use]
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+
+    Some(bytes)
+}
+
+pub(crate) fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+
+    for char in value.chars() {
+        match char {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            char if char.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(char));
+            }
+            char => out.push(char),
+        }
+    }
+
+    out.push('"');
+}
+
+/// Parser of the flat JSON objects written by [`ManifestRecord::to_json_line`].
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<u8> {
+        while self.data.get(self.pos)?.is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        let byte = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Option<()> {
+        (self.next()? == expected).then_some(())
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        self.next()?;
+        let start = self.pos - 1;
+
+        while self.data.get(self.pos).map_or(false, u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        json::read_string(self.data, &mut self.pos)
+    }
+}
@@ -0,0 +1,132 @@
+//! Memory-mapped decryption of encrypted files.
+//!
+//! Enabled with the `mmap` feature. Only the first 16 bytes of payload are encrypted, so they are decrypted into a small shadow buffer, and the rest of the file is served straight from the mapping, without reading it into memory.
+
+use crate::{Decrypter, Error, FileError, FileType, HEADER_LENGTH};
+use memmap2::Mmap;
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Decrypted view of a memory-mapped encrypted file, returned by [`decrypt_mmap`].
+#[derive(Debug)]
+pub struct DecryptedMmap {
+    mmap: Mmap,
+    header: [u8; HEADER_LENGTH],
+}
+
+impl DecryptedMmap {
+    /// Returns the length of decrypted data.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.mmap.len() - HEADER_LENGTH
+    }
+
+    /// Returns whether decrypted data is empty. Always `false`, since mapped files must have at least 16 bytes of payload.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the first 16 bytes of decrypted data.
+    #[must_use]
+    pub fn header(&self) -> &[u8; HEADER_LENGTH] {
+        &self.header
+    }
+
+    /// Returns the rest of decrypted data after [`DecryptedMmap::header`], borrowed from the mapping.
+    #[must_use]
+    pub fn body(&self) -> &[u8] {
+        &self.mmap[HEADER_LENGTH * 2..]
+    }
+
+    /// Returns a reader over the whole decrypted data.
+    #[must_use]
+    pub fn reader(&self) -> io::Chain<&[u8], &[u8]> {
+        self.header.as_slice().chain(self.body())
+    }
+
+    /// Copies decrypted data starting at `offset` into `buf`, and returns the number of copied bytes.
+    #[must_use]
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let mut copied = 0;
+
+        if let Some(header) = self.header.get(offset..) {
+            copied = header.len().min(buf.len());
+            buf[..copied].copy_from_slice(&header[..copied]);
+        }
+
+        let body_offset = (offset + copied).saturating_sub(HEADER_LENGTH);
+
+        if let Some(body) = self.body().get(body_offset..) {
+            let remaining = body.len().min(buf.len() - copied);
+            buf[copied..copied + remaining].copy_from_slice(&body[..remaining]);
+            copied += remaining;
+        }
+
+        copied
+    }
+
+    /// Copies the whole decrypted data into a [`Vec<u8>`].
+    #[must_use]
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.len());
+        result.extend_from_slice(&self.header);
+        result.extend_from_slice(self.body());
+        result
+    }
+}
+
+/// Memory-maps the encrypted file at `path` and returns its decrypted view.
+///
+/// File type is determined by the file's extension, and the key is determined from the file itself.
+/// To decrypt many files with a known key, use [`decrypt_mmap_with`].
+///
+/// # Errors
+///
+/// - [`Error::UnsupportedFileType`] - if `path` doesn't have an encrypted asset extension.
+/// - Any error of [`decrypt_mmap_with`].
+pub fn decrypt_mmap(path: &Path) -> Result<DecryptedMmap, FileError> {
+    decrypt_mmap_with(path, &mut Decrypter::new())
+}
+
+/// Memory-maps the encrypted file at `path` and returns its decrypted view, using `decrypter`'s key.
+///
+/// If `decrypter` has no key set, it's determined from the file.
+///
+/// # Note
+///
+/// As with any memory mapping, the file must not be truncated while the view is alive, otherwise accessing it may crash the process.
+///
+/// # Errors
+///
+/// - [`Error::UnsupportedFileType`] - if `path` doesn't have an encrypted asset extension.
+/// - [`Error::InvalidMagic`] - if the file doesn't start with the RPG Maker header.
+/// - [`Error::InvalidHeader`] - if the file is too short to contain the header.
+/// - [`Error::AlreadyDecrypted`] - if the file is a plain, not encrypted asset.
+/// - [`Error::UnexpectedEOF`] - if the file is shorter than 32 bytes.
+/// - [`Error::Io`] - if opening or mapping the file fails.
+pub fn decrypt_mmap_with(
+    path: &Path,
+    decrypter: &mut Decrypter,
+) -> Result<DecryptedMmap, FileError> {
+    let file_type = path
+        .extension()
+        .and_then(|ext| FileType::try_from(ext).ok())
+        .ok_or_else(|| FileError::new(path, Error::UnsupportedFileType))?;
+
+    let file = File::open(path).map_err(|err| FileError::new(path, err))?;
+
+    // SAFETY: the mapping is read-only. Concurrent truncation of the file is the caller's responsibility, as documented above.
+    let mmap =
+        unsafe { Mmap::map(&file) }.map_err(|err| FileError::new(path, err))?;
+
+    let header = decrypter
+        .decrypt_header_only(&mmap, file_type)
+        .map_err(|err| FileError::new(path, err))?;
+
+    Ok(DecryptedMmap { mmap, header })
+}
@@ -0,0 +1,265 @@
+//! Binary patches between original encrypted assets and their modified replacements.
+//!
+//! Translation groups can't redistribute copyrighted assets, so instead of shipping replaced files, they can ship patches, which only contain the changed bytes, and apply them on the player's side.
+//!
+//! Patch format, all integers are LEB128-encoded:
+//!
+//! - [`PATCH_MAGIC`].
+//! - Length and FNV-1a hash (see [`crate::manifest::hash`]) of the original encrypted file.
+//! - Length and FNV-1a hash of the resulting encrypted file.
+//! - Sequence of operations until the end of data:
+//!   - `0`, offset, length - copy bytes from the original file.
+//!   - `1`, length, bytes - insert new bytes.
+
+use crate::{Decrypter, Encrypter, Error, FileType, manifest};
+use std::{collections::HashMap, convert::TryFrom};
+
+/// Magic every patch starts with, including the format version.
+pub const PATCH_MAGIC: &[u8] = b"RPGMPATCH\x01";
+
+/// Length of blocks matched between the original and the replacement.
+const BLOCK_SIZE: usize = 32;
+/// Base of the rolling hash.
+const HASH_BASE: u32 = 257;
+
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+fn block_hash(block: &[u8]) -> u32 {
+    block.iter().fold(0, |hash, &byte| {
+        hash.wrapping_mul(HASH_BASE).wrapping_add(u32::from(byte))
+    })
+}
+
+// Truncation is intended, only the lowest 7 bits are written at a time.
+#[allow(clippy::cast_possible_truncation)]
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    output.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let byte = *data.get(*pos).ok_or(Error::UnexpectedEOF)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(Error::InvalidHeader)
+}
+
+fn read_usize(data: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    usize::try_from(read_varint(data, pos)?).map_err(|_| Error::InvalidHeader)
+}
+
+fn push_insert(output: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        output.push(OP_INSERT);
+        write_varint(output, bytes.len() as u64);
+        output.extend_from_slice(bytes);
+    }
+}
+
+/// Encodes operations turning `original` into `target`.
+fn diff(original: &[u8], target: &[u8], output: &mut Vec<u8>) {
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+
+    for offset in
+        (0..original.len().saturating_sub(BLOCK_SIZE - 1)).step_by(BLOCK_SIZE)
+    {
+        index
+            .entry(block_hash(&original[offset..offset + BLOCK_SIZE]))
+            .or_default()
+            .push(offset);
+    }
+
+    // Multiplier of the byte leaving the rolling window.
+    let leaving_factor =
+        (1..BLOCK_SIZE).fold(1u32, |factor, _| factor.wrapping_mul(HASH_BASE));
+
+    let mut literal_start = 0;
+    let mut pos = 0;
+    let mut hash = target.get(..BLOCK_SIZE).map(block_hash);
+
+    while let Some(current_hash) = hash {
+        let window = &target[pos..pos + BLOCK_SIZE];
+        let matched = index.get(&current_hash).and_then(|offsets| {
+            offsets.iter().copied().find(|&offset| {
+                &original[offset..offset + BLOCK_SIZE] == window
+            })
+        });
+
+        if let Some(offset) = matched {
+            let length = original[offset..]
+                .iter()
+                .zip(&target[pos..])
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            push_insert(output, &target[literal_start..pos]);
+            output.push(OP_COPY);
+            write_varint(output, offset as u64);
+            write_varint(output, length as u64);
+
+            pos += length;
+            literal_start = pos;
+            hash = target.get(pos..pos + BLOCK_SIZE).map(block_hash);
+            continue;
+        }
+
+        hash = target.get(pos + BLOCK_SIZE).map(|&entering| {
+            current_hash
+                .wrapping_sub(
+                    u32::from(target[pos]).wrapping_mul(leaving_factor),
+                )
+                .wrapping_mul(HASH_BASE)
+                .wrapping_add(u32::from(entering))
+        });
+        pos += 1;
+    }
+
+    push_insert(output, &target[literal_start..]);
+}
+
+/// Creates a patch turning `original` encrypted asset into the encrypted `replacement`.
+///
+/// `replacement` is encrypted with the key of `original`, so the patched file can be loaded by the game as is.
+///
+/// # Parameters
+///
+/// - `original` - The data of the original encrypted RPG Maker file.
+/// - `replacement` - The data of the modified decrypted asset.
+/// - `file_type` - [`FileType`] of `original`, used to determine its key.
+///
+/// # Returns
+///
+/// - Patch data, which can be applied with [`apply_patch`].
+/// - [`Error`] if the key can't be determined from `original`.
+///
+/// # Errors
+///
+/// - Any error of [`Decrypter::set_key_from_file`].
+pub fn create_patch(
+    original: &[u8],
+    replacement: &[u8],
+    file_type: FileType,
+) -> Result<Vec<u8>, Error> {
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_file(original, file_type)?;
+
+    let Some(key) = decrypter.encryption_key() else {
+        return Err(Error::KeyNotSet);
+    };
+    let target = Encrypter::with_key(key).encrypt(replacement);
+
+    let mut patch = PATCH_MAGIC.to_vec();
+    write_varint(&mut patch, original.len() as u64);
+    patch.extend_from_slice(&manifest::hash(original).to_le_bytes());
+    write_varint(&mut patch, target.len() as u64);
+    patch.extend_from_slice(&manifest::hash(&target).to_le_bytes());
+
+    diff(original, &target, &mut patch);
+    Ok(patch)
+}
+
+/// Applies `patch`, created with [`create_patch`], to `original` encrypted asset.
+///
+/// # Returns
+///
+/// - Patched encrypted file.
+/// - [`Error`] if `patch` is malformed or doesn't match `original`.
+///
+/// # Errors
+///
+/// - [`Error::InvalidHeader`] - if `patch` doesn't start with [`PATCH_MAGIC`], or contains unknown operations.
+/// - [`Error::UnexpectedEOF`] - if `patch` ends unexpectedly.
+/// - [`Error::PatchMismatch`] - if `original` is not the file the patch was created for, or the patched result is corrupted or exceeds its declared length.
+pub fn apply_patch(original: &[u8], patch: &[u8]) -> Result<Vec<u8>, Error> {
+    fn read_hash(patch: &[u8], pos: &mut usize) -> Result<u64, Error> {
+        let bytes = patch.get(*pos..*pos + 8).ok_or(Error::UnexpectedEOF)?;
+        *pos += 8;
+
+        let mut hash = [0; 8];
+        hash.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(hash))
+    }
+
+    if !patch.starts_with(PATCH_MAGIC) {
+        return Err(Error::InvalidHeader);
+    }
+
+    let mut pos = PATCH_MAGIC.len();
+
+    let original_length = read_usize(patch, &mut pos)?;
+    let original_hash = read_hash(patch, &mut pos)?;
+
+    if original.len() != original_length
+        || manifest::hash(original) != original_hash
+    {
+        return Err(Error::PatchMismatch);
+    }
+
+    let target_length = read_usize(patch, &mut pos)?;
+    let target_hash = read_hash(patch, &mut pos)?;
+
+    let mut target = Vec::with_capacity(target_length.min(patch.len() * 64));
+
+    // Ops may not grow the target past its declared length, so a small patch can't repeat large copies without bound.
+    let fits = |target: &[u8], length: usize| {
+        target
+            .len()
+            .checked_add(length)
+            .map_or(false, |len| len <= target_length)
+    };
+
+    while pos < patch.len() {
+        let op = patch[pos];
+        pos += 1;
+
+        match op {
+            OP_COPY => {
+                let offset = read_usize(patch, &mut pos)?;
+                let length = read_usize(patch, &mut pos)?;
+                let bytes = original
+                    .get(offset..offset.saturating_add(length))
+                    .ok_or(Error::PatchMismatch)?;
+
+                if !fits(&target, length) {
+                    return Err(Error::PatchMismatch);
+                }
+
+                target.extend_from_slice(bytes);
+            }
+            OP_INSERT => {
+                let length = read_usize(patch, &mut pos)?;
+                let bytes = patch
+                    .get(pos..pos.saturating_add(length))
+                    .ok_or(Error::UnexpectedEOF)?;
+                pos += length;
+
+                if !fits(&target, length) {
+                    return Err(Error::PatchMismatch);
+                }
+
+                target.extend_from_slice(bytes);
+            }
+            _ => return Err(Error::InvalidHeader),
+        }
+    }
+
+    if target.len() != target_length || manifest::hash(&target) != target_hash {
+        return Err(Error::PatchMismatch);
+    }
+
+    Ok(target)
+}
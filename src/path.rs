@@ -0,0 +1,185 @@
+//! Mapping between encrypted and decrypted asset paths, and matching them against glob patterns.
+
+use crate::{
+    Engine, FileType, OTHER_ENCRYPTED_EXT, OTHER_EXT, encrypted_extension_for,
+};
+use std::{
+    convert::TryFrom,
+    path::{Component, Path, PathBuf},
+};
+
+/// Defines how decrypted outputs are named.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputNaming {
+    /// Replaces the encrypted extension with the decrypted one.
+    ///
+    /// `img/pictures/image.rpgmvp` -> `img/pictures/image.png`
+    #[default]
+    ReplaceExtension,
+    /// Inserts the suffix between the file stem and the decrypted extension, so decrypted copies can live next to the encrypted ones, even if the game also ships unencrypted assets with the same name.
+    ///
+    /// `img/pictures/image.rpgmvp` with `dec` suffix -> `img/pictures/image.dec.png`
+    Suffix(String),
+    /// Keeps the original file name, and places the output into a sibling folder with the given name.
+    ///
+    /// `img/pictures/image.rpgmvp` with `decrypted` folder -> `img/pictures/decrypted/image.png`
+    SiblingDir(String),
+}
+
+/// Returns the path decrypted `path` should be written to, according to `naming`.
+///
+/// # Parameters
+///
+/// - `path` - Path to the encrypted file.
+/// - `file_type` - [`FileType`] of the decrypted file, which determines its extension.
+/// - `naming` - [`OutputNaming`] style.
+#[must_use]
+pub fn decrypted_path(
+    path: &Path,
+    file_type: FileType,
+    naming: &OutputNaming,
+) -> PathBuf {
+    decrypted_path_with_extension(path, &file_type.to_string(), naming)
+}
+
+/// Same as [`decrypted_path`], but with an explicit decrypted extension, e.g. one from [`crate::registry::SignatureRegistry::decrypted_extension`].
+#[must_use]
+pub fn decrypted_path_with_extension(
+    path: &Path,
+    ext: &str,
+    naming: &OutputNaming,
+) -> PathBuf {
+    match naming {
+        OutputNaming::ReplaceExtension => path.with_extension(ext),
+        OutputNaming::Suffix(suffix) => {
+            path.with_extension(format!("{suffix}.{ext}"))
+        }
+        OutputNaming::SiblingDir(dir) => {
+            let file_name = Path::new(path.file_name().unwrap_or_default())
+                .with_extension(ext);
+
+            match path.parent() {
+                Some(parent) => parent.join(dir).join(file_name),
+                None => Path::new(dir).join(file_name),
+            }
+        }
+    }
+}
+
+/// Returns the extension of `path` in lower case, or [`None`] if it has no extension, or it's not valid UTF-8.
+fn lowercase_extension(path: &Path) -> Option<String> {
+    path.extension()?.to_str().map(str::to_ascii_lowercase)
+}
+
+/// Returns the name encrypted asset at `path` has once decrypted, e.g. `img/pictures/image.png` for both `img/pictures/image.rpgmvp` and `img/pictures/image.png_`.
+///
+/// Only the last extension is replaced, so `image.png.rpgmvp` becomes `image.png.png`, the same as the file RPG Maker would have encrypted into it. Extensions are matched regardless of case, since some deployments upper-case them, and the rest of the name is kept byte for byte, even if it's not valid UTF-8.
+///
+/// Paths without an encrypted asset extension (see [`crate::ENCRYPTED_ASSET_EXTS`]) are returned as is.
+#[must_use]
+pub fn decrypted_name(path: &Path) -> PathBuf {
+    let ext = lowercase_extension(path).and_then(|ext| {
+        if ext == OTHER_ENCRYPTED_EXT {
+            Some(OTHER_EXT.to_owned())
+        } else {
+            FileType::try_from(ext.as_str())
+                .ok()
+                .map(|file_type| file_type.to_string())
+        }
+    });
+
+    match ext {
+        Some(ext) => path.with_extension(ext),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Returns the name decrypted asset at `path` has once encrypted for `engine`, e.g. `img/pictures/image.rpgmvp` for [`Engine::Mv`], and `img/pictures/image.png_` for [`Engine::Mz`].
+///
+/// This is the inverse of [`decrypted_name`], with the same rules: only the last extension is replaced, it's matched regardless of case, and the rest of the name is kept byte for byte.
+///
+/// Paths without a decrypted asset extension, e.g. already encrypted ones, are returned as is.
+#[must_use]
+pub fn encrypted_name(path: &Path, engine: Engine) -> PathBuf {
+    let ext = lowercase_extension(path).and_then(|ext| {
+        if ext == OTHER_EXT {
+            Some(OTHER_ENCRYPTED_EXT)
+        } else {
+            FileType::from_decrypted_extension(&ext)
+                .map(|file_type| encrypted_extension_for(file_type, engine))
+        }
+    });
+
+    match ext {
+        Some(ext) => path.with_extension(ext),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Returns whether wildcard `pattern`, which may contain `*` and `?`, matches a single path segment.
+fn matches_segment(pattern: &[char], segment: &[char]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Position after the last `*`, and the segment position it was tried at.
+    let mut backtrack = None;
+
+    while s < segment.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, s));
+            }
+            Some(&c) if c == '?' || c == segment[s] => {
+                p += 1;
+                s += 1;
+            }
+            _ => match backtrack {
+                Some((after_star, tried)) => {
+                    p = after_star;
+                    s = tried + 1;
+                    backtrack = Some((after_star, s));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn matches_segments(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first.as_slice() == ['*', '*'] => {
+            (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => {
+            path.split_first().map_or(false, |(head, tail)| {
+                matches_segment(first, head) && matches_segments(rest, tail)
+            })
+        }
+    }
+}
+
+/// Returns whether relative `path` matches glob `pattern`, e.g. `img/pictures/**` or `audio/*/battle?.ogg_`.
+///
+/// Patterns use `/` as a separator. `*` matches any run of characters within a segment, `?` matches a single one, and a `**` segment matches any number of segments, including none.
+/// A pattern that matches a directory also matches everything under it, so `img/pictures` is the same as `img/pictures/**`.
+#[must_use]
+pub fn matches_glob(pattern: &str, path: &Path) -> bool {
+    let pattern: Vec<Vec<char>> = pattern
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .map(|segment| segment.chars().collect())
+        .collect();
+    let path: Vec<Vec<char>> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(segment) => {
+                Some(segment.to_string_lossy().chars().collect())
+            }
+            _ => None,
+        })
+        .collect();
+
+    (1..=path.len()).any(|length| matches_segments(&pattern, &path[..length]))
+}
@@ -0,0 +1,276 @@
+//! Decrypting and probing assets in one step, for asset browsers, music rippers and cataloguing tools.
+//!
+//! [`ogg_info`] reads metadata of OGG audio without any audio stack. With `image` feature, [`decode_png`] decodes images into RGBA pixels, e.g. to thumbnail encrypted sprites, using [image](https://docs.rs/image).
+
+use crate::{Decrypter, Error, FileType, HEADER_LENGTH, verify::le_u32};
+#[cfg(feature = "image")]
+use image::{DynamicImage, ImageFormat, load_from_memory_with_format};
+#[cfg(feature = "image")]
+use std::io;
+use std::{convert::TryFrom, time::Duration};
+
+#[cfg(feature = "image")]
+pub use image::RgbaImage;
+
+/// Decrypts PNG image `data` in place, and decodes it into RGBA pixels.
+///
+/// This is a convenience wrapper around [`decode_png_with`], with a temporary [`Decrypter`], whose key is determined from `data`.
+///
+/// # Errors
+///
+/// See [`decode_png_with`].
+#[cfg(feature = "image")]
+pub fn decode_png(data: &mut [u8]) -> Result<RgbaImage, Error> {
+    decode_png_with(&mut Decrypter::new(), data)
+}
+
+/// Decrypts PNG image `data` in place with `decrypter`, and decodes it into RGBA pixels.
+///
+/// Only the first 16 bytes past the header are touched by decryption, so the image is decoded straight from `data`, without copying it.
+/// Plain, not encrypted PNG images are decoded as is, so assets can be previewed regardless of whether the game was encrypted.
+///
+/// # Parameters
+///
+/// - `decrypter` - [`Decrypter`] whose key is used. If its key is not set, it's determined from `data`.
+/// - `data` - The data of encrypted or plain PNG image.
+///
+/// # Returns
+///
+/// - [`RgbaImage`] with decoded pixels.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if decrypted data is not a valid PNG image, e.g. because it's of another type, or the key is wrong.
+/// - Any error of [`Decrypter::decrypt_in_place`], besides [`Error::AlreadyDecrypted`] of PNG.
+#[cfg(feature = "image")]
+pub fn decode_png_with(
+    decrypter: &mut Decrypter,
+    data: &mut [u8],
+) -> Result<RgbaImage, Error> {
+    let png = match decrypter.decrypt_in_place(data, FileType::PNG) {
+        Ok(plain) => plain,
+        Err(Error::AlreadyDecrypted(FileType::PNG)) => data,
+        Err(err) => return Err(err),
+    };
+
+    load_from_memory_with_format(png, ImageFormat::Png)
+        .map(DynamicImage::into_rgba8)
+        .map_err(|err| {
+            Error::Io(io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+}
+
+/// Codec of OGG audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OggCodec {
+    /// Vorbis audio.
+    Vorbis,
+    /// Opus audio, which is always decoded at 48 kHz.
+    Opus,
+}
+
+/// Metadata of OGG audio, returned by [`ogg_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OggInfo {
+    /// Codec of the audio.
+    pub codec: OggCodec,
+    /// Number of channels.
+    pub channels: u8,
+    /// Sample rate in Hz. For Opus, it's the rate of the original input, since Opus is always decoded at 48 kHz.
+    pub sample_rate: u32,
+    /// Duration of the audio, determined from the granule position of the last page, or [`None`] if there's no complete page past the headers.
+    pub duration: Option<Duration>,
+    /// Vendor string of the comment header, which names the encoder, e.g. `Lavf62.3.100`, or [`None`] if the header is absent or truncated.
+    pub vendor: Option<String>,
+}
+
+/// OGG page, whose header is 27 bytes long, followed by the segment table and the body.
+struct Page<'a> {
+    granule_position: u64,
+    body: &'a [u8],
+    length: usize,
+}
+
+/// Parses OGG page at the start of `data`.
+fn page(data: &[u8]) -> Option<Page<'_>> {
+    const HEADER_SIZE: usize = 27;
+
+    let segment_count = usize::from(*data.get(HEADER_SIZE - 1)?);
+    let segment_table = data.get(HEADER_SIZE..HEADER_SIZE + segment_count)?;
+    let body_start = HEADER_SIZE + segment_count;
+    let length = body_start
+        + segment_table
+            .iter()
+            .map(|&size| usize::from(size))
+            .sum::<usize>();
+
+    let mut granule_position = [0; 8];
+    granule_position.copy_from_slice(data.get(6..14)?);
+
+    Some(Page {
+        granule_position: u64::from_le_bytes(granule_position),
+        body: data.get(body_start..length)?,
+        length,
+    })
+}
+
+/// Returns the vendor string of Vorbis or Opus comment header `packet`, which starts with `magic`.
+fn vendor(packet: &[u8], magic: &[u8]) -> Option<String> {
+    let packet = packet.strip_prefix(magic)?;
+    let length = usize::try_from(le_u32(packet, 0)?).ok()?;
+    let vendor = packet.get(4..4usize.checked_add(length)?)?;
+    Some(String::from_utf8_lossy(vendor).into_owned())
+}
+
+/// Decrypts OGG audio `data`, and reads its metadata from Vorbis or Opus identification and comment headers.
+///
+/// This is a convenience wrapper around [`ogg_info_with`], with a temporary [`Decrypter`], whose key is determined from `data`.
+///
+/// # Errors
+///
+/// See [`ogg_info_with`].
+pub fn ogg_info(data: &[u8]) -> Result<OggInfo, Error> {
+    ogg_info_with(&mut Decrypter::new(), data)
+}
+
+/// Decrypts OGG audio `data` with `decrypter`, and reads its metadata from Vorbis or Opus identification and comment headers.
+///
+/// Only the first block of `data` is decrypted, into a copy of the first page, so nothing else is copied. Plain, not encrypted OGG audio is probed as is.
+///
+/// # Parameters
+///
+/// - `decrypter` - [`Decrypter`] whose key is used. If its key is not set, it's determined from `data`.
+/// - `data` - The data of encrypted or plain OGG audio.
+///
+/// # Returns
+///
+/// - [`OggInfo`] of the audio.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::UnexpectedEOF`] - if the first page is truncated.
+/// - [`Error::Malformed`] - if the first page doesn't contain Vorbis or Opus identification header. Offset is relative to decrypted data.
+/// - Any error of [`Decrypter::decrypt_header_only`], besides [`Error::AlreadyDecrypted`] of OGG.
+pub fn ogg_info_with(
+    decrypter: &mut Decrypter,
+    data: &[u8],
+) -> Result<OggInfo, Error> {
+    let (block, body) = match decrypter.decrypt_header_only(data, FileType::OGG)
+    {
+        Ok(block) => (Some(block), &data[HEADER_LENGTH..]),
+        Err(Error::AlreadyDecrypted(FileType::OGG)) => (None, data),
+        Err(err) => return Err(err),
+    };
+
+    // Only the first page is affected by decryption, so only it is copied.
+    let first_length = page(body).ok_or(Error::UnexpectedEOF)?.length;
+    let mut first = body[..first_length].to_vec();
+
+    if let Some(block) = block {
+        first[..HEADER_LENGTH].copy_from_slice(&block);
+    }
+
+    let identification =
+        identification(page(&first).ok_or(Error::UnexpectedEOF)?.body)?;
+
+    let rest = &body[first_length..];
+    let vendor = page(rest).and_then(|page| match identification.codec {
+        OggCodec::Vorbis => vendor(page.body, b"\x03vorbis"),
+        OggCodec::Opus => vendor(page.body, b"OpusTags"),
+    });
+
+    Ok(OggInfo {
+        codec: identification.codec,
+        channels: identification.channels,
+        sample_rate: identification.sample_rate,
+        duration: last_page_duration(&identification, rest),
+        vendor,
+    })
+}
+
+/// Vorbis or Opus identification header, which is the body of the first OGG page.
+struct Identification {
+    codec: OggCodec,
+    channels: u8,
+    sample_rate: u32,
+    pre_skip: u64,
+}
+
+/// Parses identification header `packet`.
+///
+/// # Errors
+///
+/// - [`Error::Malformed`] - if `packet` is not Vorbis or Opus identification header.
+fn identification(packet: &[u8]) -> Result<Identification, Error> {
+    let malformed = Error::Malformed {
+        offset: 0,
+        reason: "expected Vorbis or Opus identification header",
+    };
+
+    if let Some(header) = packet.strip_prefix(b"\x01vorbis") {
+        let (Some(&channels), Some(sample_rate)) =
+            (header.get(4), le_u32(header, 5))
+        else {
+            return Err(malformed);
+        };
+
+        Ok(Identification {
+            codec: OggCodec::Vorbis,
+            channels,
+            sample_rate,
+            pre_skip: 0,
+        })
+    } else if let Some(header) = packet.strip_prefix(b"OpusHead") {
+        let (Some(&channels), Some(pre_skip), Some(sample_rate)) =
+            (header.get(1), header.get(2..4), le_u32(header, 4))
+        else {
+            return Err(malformed);
+        };
+
+        Ok(Identification {
+            codec: OggCodec::Opus,
+            channels,
+            sample_rate: if sample_rate == 0 { 48000 } else { sample_rate },
+            pre_skip: u64::from(u16::from_le_bytes([pre_skip[0], pre_skip[1]])),
+        })
+    } else {
+        Err(malformed)
+    }
+}
+
+/// Returns duration of audio with `identification` header from the granule position of its last page, which must end exactly at the end of `tail`, or [`None`] if there's no such page past the headers.
+fn last_page_duration(
+    identification: &Identification,
+    tail: &[u8],
+) -> Option<Duration> {
+    // Granule position of the last page is the number of samples, at 48 kHz for Opus. Headers' pages have zero granule position.
+    let rate = match identification.codec {
+        OggCodec::Vorbis => u64::from(identification.sample_rate),
+        OggCodec::Opus => 48000,
+    };
+
+    (0..tail.len().saturating_sub(3))
+        .rev()
+        .filter(|&start| &tail[start..start + 4] == b"OggS")
+        .find_map(|start| {
+            page(&tail[start..])
+                .filter(|page| start + page.length == tail.len())
+        })
+        .map(|page| page.granule_position)
+        .filter(|&samples| samples != 0 && samples != u64::MAX && rate != 0)
+        .map(|samples| {
+            let samples = samples.saturating_sub(identification.pre_skip);
+            Duration::from_secs(samples / rate)
+                + Duration::from_nanos(samples % rate * 1_000_000_000 / rate)
+        })
+}
+
+/// Returns duration of plain OGG audio, whose data starts with `ogg` and ends with `tail`, which may overlap.
+///
+/// It's determined the same way as [`OggInfo::duration`], so listings agree with [`ogg_info`].
+pub(crate) fn ogg_duration(ogg: &[u8], tail: &[u8]) -> Option<Duration> {
+    let identification = identification(page(ogg)?.body).ok()?;
+    last_page_duration(&identification, tail)
+}
@@ -0,0 +1,169 @@
+//! Helpers for RPG Maker MV/MZ project layout.
+
+use crate::{
+    Engine,
+    fs::encrypted_files,
+    system_json::{EncryptionInfo, SYSTEM_JSON_NAMES, find_key_in_dir},
+};
+use std::{
+    ffi::OsStr,
+    fs::{read, read_dir},
+    path::{Path, PathBuf},
+};
+
+/// Extension of MV project files, e.g. `Game.rpgproject`.
+const MV_PROJECT_EXT: &str = "rpgproject";
+/// Extension of MZ project files, e.g. `game.rmmzproject`.
+const MZ_PROJECT_EXT: &str = "rmmzproject";
+
+/// Detection of [`Engine`] from project layout.
+pub trait DetectEngine {
+    /// Detects the engine of an editor project or deployed game at `project_root`.
+    ///
+    /// Checks, in order:
+    ///
+    /// - Editor project files: `*.rpgproject` for MV, `*.rmmzproject` for MZ.
+    /// - `www` folder, which only MV games have.
+    /// - `main` field of `package.json`, which points into `www` for MV.
+    /// - Core scripts: `rpg_core.js` for MV, `rmmz_core.js` for MZ.
+    /// - Extension style of encrypted assets: `rpgmvp` for MV, `png_` for MZ.
+    /// - `data` folder at the root, which only MZ games have when deployed.
+    ///
+    /// # Returns
+    ///
+    /// - Detected [`Engine`].
+    /// - [`None`] if `project_root` doesn't look like an RPG Maker MV/MZ project.
+    fn detect(project_root: &Path) -> Option<Engine>;
+}
+
+impl DetectEngine for Engine {
+    fn detect(project_root: &Path) -> Option<Engine> {
+        if let Ok(entries) = read_dir(project_root) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+
+                match path.extension().and_then(OsStr::to_str) {
+                    Some(MV_PROJECT_EXT) => return Some(Engine::Mv),
+                    Some(MZ_PROJECT_EXT) => return Some(Engine::Mz),
+                    _ => {}
+                }
+            }
+        }
+
+        if project_root.join("www").is_dir() {
+            return Some(Engine::Mv);
+        }
+
+        if let Ok(package_json) = read(project_root.join("package.json")) {
+            if package_json.windows(4).any(|window| window == b"www/") {
+                return Some(Engine::Mv);
+            }
+        }
+
+        if project_root.join("js/rpg_core.js").is_file() {
+            return Some(Engine::Mv);
+        }
+
+        if project_root.join("js/rmmz_core.js").is_file() {
+            return Some(Engine::Mz);
+        }
+
+        if let Some(asset) = encrypted_files(project_root)
+            .ok()
+            .and_then(|files| files.into_iter().next())
+        {
+            return asset
+                .extension()
+                .and_then(OsStr::to_str)
+                .and_then(Engine::from_encrypted_extension);
+        }
+
+        if project_root.join("data").is_dir() {
+            return Some(Engine::Mz);
+        }
+
+        None
+    }
+}
+
+/// Returns the root of game files, which contains `data`, `img` and `audio` folders: `www` for deployed MV games, `project_root` itself otherwise.
+#[must_use]
+pub fn content_root(project_root: &Path, engine: Engine) -> PathBuf {
+    let www = project_root.join("www");
+
+    if engine == Engine::Mv && www.is_dir() {
+        www
+    } else {
+        project_root.to_path_buf()
+    }
+}
+
+/// Returns the path to `System.json` of the project at `project_root`.
+#[must_use]
+pub fn system_json_path(project_root: &Path, engine: Engine) -> PathBuf {
+    content_root(project_root, engine).join("data/System.json")
+}
+
+/// Folders of game content root, which contains `data/System.json`.
+const CONTENT_DIRS: &[&str] = &[
+    "audio", "data", "effects", "fonts", "icon", "img", "js", "movies", "www",
+];
+
+/// Returns `data` folders of the games `path` may belong to, nearest first: `data` and `www/data` of `path` itself, and of those of its ancestors, from which `path` is reached through a content folder, such as `img` or `www`.
+fn data_dirs(path: &Path) -> Vec<PathBuf> {
+    let is_content_dir = |dir: &Path| {
+        dir.file_name()
+            .and_then(OsStr::to_str)
+            .map_or(false, |name| CONTENT_DIRS.contains(&name))
+    };
+
+    let mut dirs = Vec::new();
+    let mut child: Option<&Path> = None;
+
+    for dir in path.ancestors() {
+        if child.map_or(true, is_content_dir) {
+            dirs.extend(
+                [dir.join("data"), dir.join("www/data")]
+                    .iter()
+                    .filter(|data| data.is_dir())
+                    .cloned(),
+            );
+        }
+
+        child = Some(dir);
+    }
+
+    dirs
+}
+
+/// Locates `System.json` of the game `path` belongs to, whether `path` is the game root, the `www` folder of deployed MV games, or any folder under them, e.g. `www/img/pictures`.
+///
+/// `path` itself is checked for `data/System.json` and `www/data/System.json`, and so are those of its ancestors, from which `path` is reached through a content folder, such as `img` or `www`. Other ancestors are skipped, so `System.json` of an unrelated game higher up isn't picked up.
+/// Alternate names of `System.json` (see [`SYSTEM_JSON_NAMES`]) are accepted too.
+///
+/// # Returns
+///
+/// - Path to `System.json`.
+/// - [`None`] if there's none.
+#[must_use]
+pub fn find_system_json(path: &Path) -> Option<PathBuf> {
+    data_dirs(path).into_iter().find_map(|data| {
+        SYSTEM_JSON_NAMES
+            .iter()
+            .map(|name| data.join(name))
+            .find(|system_json| system_json.is_file())
+    })
+}
+
+/// Finds the key of the game `path` belongs to, in `data` folders [`find_system_json`] looks into, with [`find_key_in_dir`], so renamed and obfuscated `System.json` files, and keys in other data files are found too.
+///
+/// # Returns
+///
+/// - Path to the file the key was found in, and [`EncryptionInfo`] read from it.
+/// - [`None`] if there's no key.
+#[must_use]
+pub fn find_encryption_info(path: &Path) -> Option<(PathBuf, EncryptionInfo)> {
+    data_dirs(path)
+        .into_iter()
+        .find_map(|data| find_key_in_dir(&data))
+}
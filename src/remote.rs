@@ -0,0 +1,127 @@
+//! Decrypting assets of hosted games over HTTP range requests, without downloading whole files.
+//!
+//! Only the first 32 bytes of an asset are fetched upfront, which is enough to validate its header, and to derive the key from PNG images. OGG and M4A audio need more data to derive the key from, so up to 4 KiB are fetched for them, unless the key is already known. The rest of the asset is streamed as it's read.
+//!
+//! HTTP itself is left to the caller, who implements [`RangeClient`] over a client of their choice.
+
+use crate::{Decrypter, Error, FileType, HEADER_LENGTH, stream::DecryptReader};
+use std::io::{self, Cursor, Read};
+
+/// Length of the RPG Maker header and the encrypted first block.
+const HEAD_LENGTH: u64 = HEADER_LENGTH as u64 * 2;
+/// Length of the prefix to derive keys of OGG and M4A audio from, same as [`DecryptReader`] reads upfront.
+const PREFIX_LENGTH: u64 = 4096;
+
+/// HTTP client capable of range requests.
+pub trait RangeClient {
+    /// Response body.
+    type Body: Read;
+
+    /// Requests bytes of resource at `url` from `start` up to `end`, exclusive, or up to the end of the resource if `end` is [`None`], i.e. sends `Range: bytes={start}-{end - 1}` header.
+    ///
+    /// The returned body must contain only the requested bytes, so responses of servers that ignore the range (`200 OK` instead of `206 Partial Content`) must be rejected. It may be shorter if the resource ends earlier.
+    ///
+    /// # Errors
+    ///
+    /// Any I/O or HTTP error.
+    fn get_range(
+        &mut self,
+        url: &str,
+        start: u64,
+        end: Option<u64>,
+    ) -> io::Result<Self::Body>;
+}
+
+/// [`Read`] adapter over a remote asset, returned from [`decrypt_remote`], which yields its decrypted data, without the RPG Maker header.
+#[derive(Debug)]
+pub struct RemoteReader<B> {
+    prefix: DecryptReader<Cursor<Vec<u8>>>,
+    /// Rest of the asset past the prefix, or [`None`] if the prefix already contains the whole asset.
+    body: Option<B>,
+}
+
+impl<B: Read> Read for RemoteReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.prefix.read(buf)?;
+
+        if read != 0 || buf.is_empty() {
+            return Ok(read);
+        }
+
+        match &mut self.body {
+            Some(body) => body.read(buf),
+            None => Ok(0),
+        }
+    }
+}
+
+/// Fetches bytes `start..end` of resource at `url`.
+fn fetch<C: RangeClient>(
+    client: &mut C,
+    url: &str,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    client
+        .get_range(url, start, Some(end))?
+        .take(end - start)
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Decrypts remote asset at `url`, fetching only its prefix upfront, and streaming the rest.
+///
+/// # Parameters
+///
+/// - `client` - HTTP client to fetch the asset with.
+/// - `url` - URL of the encrypted asset.
+/// - `decrypter` - [`Decrypter`] whose key is used. If its key is not set, it's determined from the asset, so reusing `decrypter` for other assets of the same game saves fetching their 4 KiB prefixes.
+/// - `file_type` - [`FileType`], representing the type of the asset.
+///
+/// # Returns
+///
+/// - [`RemoteReader`] yielding decrypted data.
+/// - [`Error`] otherwise.
+///
+/// # Errors
+///
+/// - [`Error::KeyNotSet`] - if `decrypter`'s key is not set, and `file_type` is WAV, WEBP or JSON, whose keys can't be derived from a prefix, since they depend on the size of the whole asset, or have no known plaintext.
+/// - [`Error::Io`] - if fetching fails.
+/// - Any error of [`DecryptReader::new`].
+pub fn decrypt_remote<C: RangeClient>(
+    client: &mut C,
+    url: &str,
+    decrypter: &mut Decrypter,
+    file_type: FileType,
+) -> Result<RemoteReader<C::Body>, Error> {
+    let has_key = decrypter.encryption_key().is_some();
+
+    if !has_key
+        && (file_type.is_wav() || file_type.is_webp() || file_type.is_json())
+    {
+        return Err(Error::KeyNotSet);
+    }
+
+    let mut length = HEAD_LENGTH;
+    let mut prefix = fetch(client, url, 0, length)?;
+
+    if !has_key
+        && (file_type.is_ogg() || file_type.is_m4a())
+        && prefix.len() as u64 == length
+    {
+        prefix.extend(fetch(client, url, length, PREFIX_LENGTH)?);
+        length = PREFIX_LENGTH;
+    }
+
+    let complete = (prefix.len() as u64) < length;
+    let prefix = DecryptReader::new(Cursor::new(prefix), decrypter, file_type)?;
+
+    let body = if complete {
+        None
+    } else {
+        Some(client.get_range(url, length, None)?)
+    };
+
+    Ok(RemoteReader { prefix, body })
+}
@@ -0,0 +1,243 @@
+//! Reading of RPG Maker XP/VX/VX Ace encrypted archives: `Game.rgssad`, `Game.rgss2a` and `Game.rgss3a`.
+//!
+//! Older engines don't encrypt files one by one, but pack the whole project into a single archive, where file names, sizes and contents are xor'd with a rolling 32-bit key.
+
+use crate::{Error, FileError};
+use std::{
+    fs::{create_dir_all, read},
+    path::{Component, Path, PathBuf},
+};
+
+/// Magic every archive starts with, followed by the version byte.
+pub const RGSS_MAGIC: &[u8] = b"RGSSAD\0";
+
+/// Extension of RPG Maker XP archives.
+pub const RGSSAD_EXT: &str = "rgssad";
+/// Extension of RPG Maker VX archives.
+pub const RGSS2A_EXT: &str = "rgss2a";
+/// Extension of RPG Maker VX Ace archives.
+pub const RGSS3A_EXT: &str = "rgss3a";
+
+// Initial key of XP/VX archives.
+const V1_KEY: u32 = 0xDEAD_CAFE;
+
+/// Archive format version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RgssVersion {
+    /// `rgssad` (XP) and `rgss2a` (VX) archives.
+    V1,
+    /// `rgss3a` (VX Ace) archives.
+    V3,
+}
+
+/// Single file entry of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgssEntry {
+    /// Path of the file inside the archive, with `\` separators replaced by `/`, e.g. `Graphics/Pictures/title.png`.
+    pub name: String,
+    /// Offset of the file data in the archive.
+    pub offset: usize,
+    /// Size of the file data.
+    pub size: usize,
+    key: u32,
+}
+
+#[inline]
+fn advance(key: u32) -> u32 {
+    key.wrapping_mul(7).wrapping_add(3)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], Error> {
+        let end = self.pos.checked_add(len).ok_or(Error::UnexpectedEOF)?;
+        let bytes = self.data.get(self.pos..end).ok_or(Error::UnexpectedEOF)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+fn entry_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).replace('\\', "/")
+}
+
+/// Returns the version of the archive.
+///
+/// # Errors
+///
+/// - [`Error::InvalidHeader`] - if `data` is not an RGSS archive, or its version is unknown.
+pub fn version(data: &[u8]) -> Result<RgssVersion, Error> {
+    if !data.starts_with(RGSS_MAGIC) {
+        return Err(Error::InvalidHeader);
+    }
+
+    match data.get(RGSS_MAGIC.len()) {
+        Some(1) => Ok(RgssVersion::V1),
+        Some(3) => Ok(RgssVersion::V3),
+        _ => Err(Error::InvalidHeader),
+    }
+}
+
+/// Lists all file entries of the archive.
+///
+/// # Parameters
+///
+/// - `data` - Contents of the archive.
+///
+/// # Errors
+///
+/// - [`Error::InvalidHeader`] - if `data` is not an RGSS archive, or its version is unknown.
+/// - [`Error::UnexpectedEOF`] - if `data` ends unexpectedly.
+pub fn list(data: &[u8]) -> Result<Vec<RgssEntry>, Error> {
+    let version = version(data)?;
+    let mut reader = Reader {
+        data,
+        pos: RGSS_MAGIC.len() + 1,
+    };
+    let mut entries = Vec::new();
+
+    match version {
+        RgssVersion::V1 => {
+            let mut key = V1_KEY;
+
+            while reader.pos < data.len() {
+                let name_len = (reader.u32()? ^ key) as usize;
+                key = advance(key);
+
+                let mut name = reader.bytes(name_len)?.to_vec();
+                for byte in &mut name {
+                    *byte ^= key.to_le_bytes()[0];
+                    key = advance(key);
+                }
+
+                let size = (reader.u32()? ^ key) as usize;
+                key = advance(key);
+
+                entries.push(RgssEntry {
+                    name: entry_name(&name),
+                    offset: reader.pos,
+                    size,
+                    key,
+                });
+
+                reader.bytes(size)?;
+            }
+        }
+        RgssVersion::V3 => {
+            let key = reader.u32()?.wrapping_mul(9).wrapping_add(3);
+
+            loop {
+                let offset = (reader.u32()? ^ key) as usize;
+
+                if offset == 0 {
+                    break;
+                }
+
+                let size = (reader.u32()? ^ key) as usize;
+                let file_key = reader.u32()? ^ key;
+                let name_len = (reader.u32()? ^ key) as usize;
+
+                let key_bytes = key.to_le_bytes();
+                let name: Vec<u8> = reader
+                    .bytes(name_len)?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ key_bytes[i % 4])
+                    .collect();
+
+                entries.push(RgssEntry {
+                    name: entry_name(&name),
+                    offset,
+                    size,
+                    key: file_key,
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Extracts and decrypts a single file from the archive.
+///
+/// # Parameters
+///
+/// - `data` - Contents of the archive.
+/// - `entry` - Entry returned by [`list`] for the same archive.
+///
+/// # Errors
+///
+/// - [`Error::UnexpectedEOF`] - if file data is out of `data` bounds.
+pub fn extract(data: &[u8], entry: &RgssEntry) -> Result<Vec<u8>, Error> {
+    let end = entry
+        .offset
+        .checked_add(entry.size)
+        .ok_or(Error::UnexpectedEOF)?;
+    let mut file = data
+        .get(entry.offset..end)
+        .ok_or(Error::UnexpectedEOF)?
+        .to_vec();
+    let mut key = entry.key;
+
+    for chunk in file.chunks_mut(4) {
+        for (byte, key_byte) in chunk.iter_mut().zip(key.to_le_bytes()) {
+            *byte ^= key_byte;
+        }
+
+        key = advance(key);
+    }
+
+    Ok(file)
+}
+
+/// Extracts all files from the archive at `path` into `output`, preserving the archive's directory structure.
+///
+/// Path components that could escape `output`, like `..`, are dropped from entry names.
+///
+/// # Returns
+///
+/// - Paths of the written files, in archive order.
+/// - [`FileError`] otherwise.
+///
+/// # Errors
+///
+/// - Any error of [`list`] or [`extract`].
+/// - [`Error::Io`] - if reading the archive or writing a file fails.
+pub fn extract_all(
+    path: &Path,
+    output: &Path,
+) -> Result<Vec<PathBuf>, FileError> {
+    let data = read(path).map_err(|err| FileError::new(path, err))?;
+    let entries = list(&data).map_err(|err| FileError::new(path, err))?;
+    let mut written = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let relative: PathBuf = Path::new(&entry.name)
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+        let output_path = output.join(relative);
+
+        let file =
+            extract(&data, &entry).map_err(|err| FileError::new(path, err))?;
+
+        if let Some(parent) = output_path.parent() {
+            create_dir_all(parent)
+                .map_err(|err| FileError::new(parent, err))?;
+        }
+
+        crate::fs::write_atomic(&output_path, &file)?;
+        written.push(output_path);
+    }
+
+    Ok(written)
+}
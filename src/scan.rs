@@ -0,0 +1,633 @@
+//! Lazy scanning of directories for encrypted assets.
+//!
+//! [`find_duplicates`] additionally hashes decrypted contents of scanned assets, to find the same asset stored under multiple names.
+//! [`inventory`] lists both encrypted and decrypted assets with their metadata, and [`write_csv`] and [`write_json`] export the list, e.g. to divide work between translators.
+
+use crate::{
+    AssetState, DECRYPTED_ASSETS_EXTS, Decrypter, ENCRYPTED_ASSET_EXTS, Engine,
+    Error, FileError, FileType, HEADER_LENGTH,
+    container::{AsarContainer, is_asar},
+    fs::walk,
+    inspect,
+    manifest::{push_json_string, sha256},
+    preview::ogg_duration,
+    project::find_encryption_info,
+    verify::le_u32,
+    xor_header,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    ffi::OsStr,
+    fmt::Write as _,
+    fs::{self, DirEntry, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Encrypted asset found by [`encrypted_assets`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedAsset {
+    /// Full path of the asset.
+    pub path: PathBuf,
+    /// Path of the asset relative to the scanned root.
+    pub relative_path: PathBuf,
+    /// Type of the asset, determined by its extension.
+    pub file_type: FileType,
+    /// Engine whose extension the asset uses.
+    pub engine: Engine,
+    /// Size of the asset's file in bytes, including the RPG Maker header.
+    pub size: u64,
+}
+
+/// Iterator returned by [`encrypted_assets`].
+#[derive(Debug)]
+pub struct EncryptedAssets {
+    root: PathBuf,
+    /// Entries of directories being walked, each sorted in reverse, so the next entry is popped from the end.
+    stack: Vec<Vec<DirEntry>>,
+    /// Directory to read before taking the next entry.
+    pending: Option<PathBuf>,
+    /// Results listed upfront, in reverse, when the root is an asar archive.
+    listed: Vec<Result<ScannedAsset, FileError>>,
+}
+
+impl EncryptedAssets {
+    fn read_dir(dir: &Path) -> Result<Vec<DirEntry>, FileError> {
+        let mut entries = fs::read_dir(dir)
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|err| FileError::new(dir, err))?;
+
+        entries.sort_unstable_by_key(|entry| std::cmp::Reverse(entry.path()));
+        Ok(entries)
+    }
+
+    fn scan_entry(
+        &self,
+        entry: &DirEntry,
+    ) -> Result<Option<ScannedAsset>, FileError> {
+        let path = entry.path();
+
+        let Some((file_type, engine)) =
+            path.extension().and_then(OsStr::to_str).and_then(|ext| {
+                Some((
+                    FileType::try_from(ext).ok()?,
+                    Engine::from_encrypted_extension(ext)?,
+                ))
+            })
+        else {
+            return Ok(None);
+        };
+
+        let size = entry
+            .metadata()
+            .map_err(|err| FileError::new(&path, err))?
+            .len();
+
+        Ok(Some(ScannedAsset {
+            relative_path: path
+                .strip_prefix(&self.root)
+                .unwrap_or(&path)
+                .to_path_buf(),
+            path,
+            file_type,
+            engine,
+            size,
+        }))
+    }
+}
+
+impl Iterator for EncryptedAssets {
+    type Item = Result<ScannedAsset, FileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(result) = self.listed.pop() {
+            return Some(result);
+        }
+
+        loop {
+            if let Some(dir) = self.pending.take() {
+                match Self::read_dir(&dir) {
+                    Ok(entries) => self.stack.push(entries),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            let entries = self.stack.last_mut()?;
+
+            let Some(entry) = entries.pop() else {
+                self.stack.pop();
+                continue;
+            };
+
+            let is_dir = match entry.file_type() {
+                Ok(file_type) => file_type.is_dir(),
+                Err(err) => {
+                    return Some(Err(FileError::new(entry.path(), err)));
+                }
+            };
+
+            if is_dir {
+                self.pending = Some(entry.path());
+                continue;
+            }
+
+            match self.scan_entry(&entry) {
+                Ok(Some(asset)) => return Some(Ok(asset)),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Recursively scans `root` for encrypted assets (files with any of [`crate::ENCRYPTED_ASSET_EXTS`] extensions).
+///
+/// Directories are read lazily, one at a time, so scanning can be stopped early, and the results can be filtered, sharded or fed into custom pipelines without collecting them first.
+/// Assets are yielded in the order of their paths, like in [`crate::fs::encrypted_files`].
+///
+/// If `root` is an Electron `app.asar` archive, it's scanned as a virtual directory (see [`AsarContainer`]): its header is read at once, and yielded paths are `root` joined with entry paths.
+///
+/// # Returns
+///
+/// - Iterator of [`ScannedAsset`]s. [`crate::Error::Io`] errors of reading a directory or a file's metadata are yielded in place, and scanning continues after them.
+#[must_use]
+pub fn encrypted_assets(root: &Path) -> EncryptedAssets {
+    if is_asar(root) {
+        let mut listed: Vec<_> = match AsarContainer::open(root) {
+            Ok(container) => container.assets().into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+        listed.reverse();
+
+        return EncryptedAssets {
+            root: root.to_path_buf(),
+            stack: Vec::new(),
+            pending: None,
+            listed,
+        };
+    }
+
+    EncryptedAssets {
+        root: root.to_path_buf(),
+        stack: Vec::new(),
+        pending: Some(root.to_path_buf()),
+        listed: Vec::new(),
+    }
+}
+
+/// Assets with identical decrypted content, found by [`find_duplicates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// SHA-256 of the decrypted content.
+    pub sha256: [u8; 32],
+    /// Assets with this content, sorted by path. Always at least two.
+    pub assets: Vec<ScannedAsset>,
+}
+
+impl DuplicateGroup {
+    /// Returns the size of the decrypted content, without the RPG Maker header.
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.assets[0]
+            .size
+            .saturating_sub(crate::HEADER_LENGTH as u64)
+    }
+}
+
+/// Result of [`find_duplicates`].
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// Groups of duplicate assets, sorted by the path of their first asset.
+    pub groups: Vec<DuplicateGroup>,
+    /// Errors of scanning, reading or decrypting assets, which were left out.
+    pub errors: Vec<FileError>,
+}
+
+impl Duplicates {
+    /// Returns the number of bytes taken by redundant copies, i.e. by all assets of every group but one.
+    #[must_use]
+    pub fn redundant_bytes(&self) -> u64 {
+        self.groups
+            .iter()
+            .map(|group| {
+                group.assets[1..]
+                    .iter()
+                    .map(|asset| asset.size)
+                    .sum::<u64>()
+            })
+            .sum()
+    }
+}
+
+/// Reads up to `limit` first bytes of `asset`, from `asar` archive it was scanned from, if it's set, or from its file otherwise.
+pub(crate) fn read_asset(
+    asar: Option<&mut AsarContainer<File>>,
+    asset: &ScannedAsset,
+    limit: u64,
+) -> Result<Vec<u8>, Error> {
+    let Some(container) = asar else {
+        let mut data = Vec::new();
+        File::open(&asset.path)?
+            .take(limit)
+            .read_to_end(&mut data)?;
+        return Ok(data);
+    };
+
+    let name = asset
+        .relative_path
+        .to_str()
+        .ok_or(Error::UnsupportedFileType)?;
+    let mut data = container.read(name)?;
+    data.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+    Ok(data)
+}
+
+/// Returns SHA-256 of decrypted `data` of type `file_type`, decrypting it in-place.
+///
+/// `decrypter` is tried first, falling back to the key derived from `data` itself, if `decrypter`'s key doesn't match.
+fn payload_sha256(
+    decrypter: &Decrypter,
+    data: &mut [u8],
+    file_type: FileType,
+) -> Result<[u8; 32], Error> {
+    match decrypter.clone().decrypt_in_place(data, file_type) {
+        Ok(payload) => return Ok(sha256(payload)),
+        Err(Error::KeyMismatch { .. }) => {}
+        Err(err) => return Err(err),
+    }
+
+    Decrypter::new()
+        .decrypt_in_place(data, file_type)
+        .map(sha256)
+}
+
+/// Recursively scans `root` for encrypted assets, like [`encrypted_assets`], and finds the ones with identical decrypted content, e.g. the same audio under multiple names, so editing one of them can be applied to all.
+///
+/// Only assets whose sizes match any other asset are read. Contents are decrypted with the key of the project's `System.json` (see [`find_encryption_info`]), or, for assets it doesn't match, or if there's none, with the key derived from each asset, and hashed with SHA-256.
+/// Assets of `app.asar` archives are read from the archive.
+///
+/// # Returns
+///
+/// - [`Duplicates`] with groups of duplicates, and errors of assets that couldn't be scanned, read or decrypted, e.g. encrypted JSON without a project key.
+#[must_use]
+pub fn find_duplicates(root: &Path) -> Duplicates {
+    let mut duplicates = Duplicates::default();
+    let mut by_size: BTreeMap<u64, Vec<ScannedAsset>> = BTreeMap::new();
+
+    for asset in encrypted_assets(root) {
+        match asset {
+            Ok(asset) => by_size.entry(asset.size).or_default().push(asset),
+            Err(err) => duplicates.errors.push(err),
+        }
+    }
+
+    let mut decrypter = Decrypter::new();
+    if let Some((_, info)) = find_encryption_info(root) {
+        let _ = decrypter.set_key_from_str(&info.key);
+    }
+
+    let mut asar = if is_asar(root) {
+        match AsarContainer::open(root) {
+            Ok(container) => Some(container),
+            Err(err) => {
+                duplicates.errors.push(err);
+                return duplicates;
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut by_hash: BTreeMap<[u8; 32], Vec<ScannedAsset>> = BTreeMap::new();
+
+    for asset in by_size
+        .into_values()
+        .filter(|assets| assets.len() > 1)
+        .flatten()
+    {
+        let hash = read_asset(asar.as_mut(), &asset, u64::MAX)
+            .and_then(|mut data| {
+                payload_sha256(&decrypter, &mut data, asset.file_type)
+            })
+            .map_err(|err| FileError::new(&asset.path, err));
+
+        match hash {
+            Ok(hash) => by_hash.entry(hash).or_default().push(asset),
+            Err(err) => duplicates.errors.push(err),
+        }
+    }
+
+    duplicates.groups = by_hash
+        .into_iter()
+        .filter(|(_, assets)| assets.len() > 1)
+        .map(|(sha256, mut assets)| {
+            assets.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+            DuplicateGroup { sha256, assets }
+        })
+        .collect();
+    duplicates
+        .groups
+        .sort_unstable_by(|a, b| a.assets[0].path.cmp(&b.assets[0].path));
+    duplicates
+}
+
+/// Number of bytes [`inventory`] and [`crate::audit::audit`] read from the start of every asset. It's enough for headers of all supported types.
+pub(crate) const HEAD_LENGTH: u64 = 64 * 1024;
+
+/// Number of bytes [`inventory`] reads from the end of OGG assets, which is the maximum size of an OGG page.
+const OGG_TAIL_LENGTH: u64 = 65_307;
+
+/// Asset listed by [`inventory`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct AssetRecord {
+    /// Path of the asset relative to the scanned root.
+    pub path: PathBuf,
+    /// Type of the asset, determined by its extension.
+    pub file_type: FileType,
+    /// Size of the asset's file in bytes.
+    pub size: u64,
+    /// Whether the asset's data starts with the RPG Maker header.
+    pub encrypted: bool,
+    /// [`crate::EncryptionKey::fingerprint`] of the key the asset is encrypted with, if it could be derived from the asset.
+    pub key_fingerprint: Option<String>,
+    /// Width and height of PNG and WebP images.
+    pub dimensions: Option<(u32, u32)>,
+    /// Duration of OGG (Vorbis and Opus) and WAV audio, in seconds.
+    pub duration: Option<f64>,
+}
+
+impl AssetRecord {
+    /// Reads the record of asset at `path` of type `file_type`, found under `root`.
+    fn read(
+        root: &Path,
+        path: &Path,
+        file_type: FileType,
+    ) -> Result<Self, FileError> {
+        let (size, head, tail) = read_head_and_tail(path, file_type)
+            .map_err(|err| FileError::new(path, err))?;
+
+        let encrypted = AssetState::detect(&head) == AssetState::Encrypted;
+        let info = inspect(&head).ok();
+        let key = info.and_then(|info| info.key);
+
+        let mut record = Self {
+            path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            file_type,
+            size,
+            encrypted,
+            key_fingerprint: key.map(|key| key.fingerprint()),
+            dimensions: info.and_then(|info| info.dimensions),
+            duration: None,
+        };
+
+        // The asset's own data, with the first block decrypted.
+        let payload = if encrypted {
+            let (Some(key), Some(payload)) = (key, head.get(HEADER_LENGTH..))
+            else {
+                return Ok(record);
+            };
+
+            let mut payload = payload.to_vec();
+            let length = payload.len().min(HEADER_LENGTH);
+            let mut block = [0; HEADER_LENGTH];
+            block[..length].copy_from_slice(&payload[..length]);
+            xor_header(&key, &mut block);
+            payload[..length].copy_from_slice(&block[..length]);
+            payload
+        } else {
+            head
+        };
+
+        match file_type {
+            FileType::WEBP => record.dimensions = webp_dimensions(&payload),
+            FileType::WAV => record.duration = wav_duration(&payload),
+            FileType::OGG => {
+                let tail = if tail.is_empty() { &payload } else { &tail };
+                record.duration = ogg_duration(&payload, tail)
+                    .map(|duration| duration.as_secs_f64());
+            }
+            _ => {}
+        }
+
+        Ok(record)
+    }
+
+    /// Appends the record to `csv` as a row of [`write_csv`].
+    fn push_csv_row(&self, csv: &mut String) {
+        let path = self.path.to_string_lossy();
+
+        if path.contains(['"', ',', '\n', '\r']) {
+            let _ = write!(csv, "\"{}\"", path.replace('"', "\"\""));
+        } else {
+            csv.push_str(&path);
+        }
+
+        let _ = write!(
+            csv,
+            ",{},{},{},{}",
+            self.file_type,
+            self.size,
+            self.encrypted,
+            self.key_fingerprint.as_deref().unwrap_or_default()
+        );
+
+        match self.dimensions {
+            Some((width, height)) => {
+                let _ = write!(csv, ",{width},{height}");
+            }
+            None => csv.push_str(",,"),
+        }
+
+        match self.duration {
+            Some(duration) => {
+                let _ = write!(csv, ",{duration:?}");
+            }
+            None => csv.push(','),
+        }
+
+        csv.push_str("\r\n");
+    }
+
+    /// Appends the record to `json` as an object of [`write_json`].
+    fn push_json_object(&self, json: &mut String) {
+        json.push_str("{\"path\":");
+        push_json_string(json, &self.path.to_string_lossy());
+        let _ = write!(
+            json,
+            ",\"file_type\":\"{}\",\"size\":{},\"encrypted\":{},\"key_fingerprint\":",
+            self.file_type, self.size, self.encrypted
+        );
+
+        match &self.key_fingerprint {
+            Some(fingerprint) => push_json_string(json, fingerprint),
+            None => json.push_str("null"),
+        }
+
+        match self.dimensions {
+            Some((width, height)) => {
+                let _ = write!(json, ",\"dimensions\":[{width},{height}]");
+            }
+            None => json.push_str(",\"dimensions\":null"),
+        }
+
+        match self.duration {
+            Some(duration) => {
+                let _ = write!(json, ",\"duration\":{duration:?}}}");
+            }
+            None => json.push_str(",\"duration\":null}"),
+        }
+    }
+}
+
+/// Reads the size of file at `path`, its first [`HEAD_LENGTH`] bytes, and, for OGG files that are longer, its last [`OGG_TAIL_LENGTH`] bytes.
+fn read_head_and_tail(
+    path: &Path,
+    file_type: FileType,
+) -> io::Result<(u64, Vec<u8>, Vec<u8>)> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut head = Vec::new();
+    (&mut file).take(HEAD_LENGTH).read_to_end(&mut head)?;
+
+    let mut tail = Vec::new();
+
+    if file_type == FileType::OGG && size > HEAD_LENGTH {
+        file.seek(SeekFrom::Start(size.saturating_sub(OGG_TAIL_LENGTH)))?;
+        file.read_to_end(&mut tail)?;
+    }
+
+    Ok((size, head, tail))
+}
+
+/// Returns dimensions of WebP image from its `VP8X`, `VP8L` or `VP8 ` chunk.
+fn webp_dimensions(webp: &[u8]) -> Option<(u32, u32)> {
+    let chunk = webp.get(20..30)?;
+
+    match webp.get(12..16)? {
+        b"VP8X" => {
+            let width = le_u32(&[chunk[4], chunk[5], chunk[6], 0], 0)?;
+            let height = le_u32(&[chunk[7], chunk[8], chunk[9], 0], 0)?;
+            Some((width + 1, height + 1))
+        }
+        b"VP8L" if chunk[0] == 0x2F => {
+            let bits = le_u32(chunk, 1)?;
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8 " if chunk[3..6] == [0x9D, 0x01, 0x2A] => {
+            let width = u16::from_le_bytes([chunk[6], chunk[7]]) & 0x3FFF;
+            let height = u16::from_le_bytes([chunk[8], chunk[9]]) & 0x3FFF;
+            Some((u32::from(width), u32::from(height)))
+        }
+        _ => None,
+    }
+}
+
+/// Returns duration of WAV audio from its `fmt ` and `data` chunks, which must precede any sample data.
+fn wav_duration(wav: &[u8]) -> Option<f64> {
+    let mut offset: usize = 12;
+    let mut byte_rate = None;
+
+    loop {
+        let id = wav.get(offset..offset.checked_add(4)?)?;
+        let size = le_u32(wav, offset.checked_add(4)?)?;
+
+        match id {
+            b"fmt " => byte_rate = le_u32(wav, offset.checked_add(16)?),
+            b"data" => {
+                let byte_rate = byte_rate.filter(|&rate| rate != 0)?;
+                return Some(f64::from(size) / f64::from(byte_rate));
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to even sizes.
+        let size = usize::try_from(size).ok()?;
+        offset = offset
+            .checked_add(8)?
+            .checked_add(size)?
+            .checked_add(size % 2)?;
+    }
+}
+
+/// Returns the type of asset at `path`, determined by its encrypted or decrypted extension.
+fn asset_type(path: &Path) -> Option<FileType> {
+    let ext = path.extension().and_then(OsStr::to_str)?;
+
+    if ENCRYPTED_ASSET_EXTS.contains(&ext) {
+        FileType::try_from(ext).ok()
+    } else if DECRYPTED_ASSETS_EXTS.contains(&ext) {
+        FileType::from_decrypted_extension(ext)
+    } else {
+        None
+    }
+}
+
+/// Recursively lists both encrypted (see [`crate::ENCRYPTED_ASSET_EXTS`]) and decrypted (see [`crate::DECRYPTED_ASSETS_EXTS`]) assets under `root`, sorted by path.
+///
+/// Only the start of every asset is read, and the end of OGG audio, so listing is cheap even for large games. Key fingerprints are derived from encrypted assets themselves, so assets encrypted with different keys stand out.
+///
+/// # Returns
+///
+/// - [`AssetRecord`] of every asset.
+/// - [`FileError`] if reading a directory or an asset fails.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading a directory or an asset fails.
+pub fn inventory(root: &Path) -> Result<Vec<AssetRecord>, FileError> {
+    walk(root, |path| asset_type(path).is_some())?
+        .iter()
+        .filter_map(|path| Some((path, asset_type(path)?)))
+        .map(|(path, file_type)| AssetRecord::read(root, path, file_type))
+        .collect()
+}
+
+/// Writes `records` to `writer` as CSV ([RFC 4180](https://www.rfc-editor.org/rfc/rfc4180)), with a header row.
+///
+/// Columns are `path`, `file_type`, `size`, `encrypted`, `key_fingerprint`, `width`, `height` and `duration`. Missing values are empty.
+///
+/// # Errors
+///
+/// - Any error of `writer`.
+pub fn write_csv(
+    records: &[AssetRecord],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut csv = String::from(
+        "path,file_type,size,encrypted,key_fingerprint,width,height,duration\r\n",
+    );
+
+    for record in records {
+        record.push_csv_row(&mut csv);
+    }
+
+    writer.write_all(csv.as_bytes())
+}
+
+/// Writes `records` to `writer` as a JSON array of objects, with the same fields as [`AssetRecord`]. Missing values are `null`, and dimensions are `[width, height]` arrays.
+///
+/// # Errors
+///
+/// - Any error of `writer`.
+pub fn write_json(
+    records: &[AssetRecord],
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut json = String::from("[");
+
+    for (i, record) in records.iter().enumerate() {
+        if i != 0 {
+            json.push(',');
+        }
+
+        record.push_json_object(&mut json);
+    }
+
+    json.push(']');
+    writer.write_all(json.as_bytes())
+}
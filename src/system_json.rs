@@ -0,0 +1,80 @@
+//! Helpers for RPG Maker MV/MZ `System.json` file.
+//!
+//! Parsing itself lives in the core crate and is re-exported here; this module adds filesystem helpers on top.
+
+pub use rpgm_asset_decrypter_core::system_json::*;
+
+use crate::{Error, FileError};
+use std::{
+    fs::{read, read_dir},
+    path::{Path, PathBuf},
+};
+
+/// Names `System.json` is found under: the original one first, then ones it's renamed to by plugins and repackers, or by case-insensitive filesystems of developers.
+pub const SYSTEM_JSON_NAMES: &[&str] = &[
+    "System.json",
+    "system.json",
+    "SYSTEM.JSON",
+    "System.txt",
+    "System.dat",
+    "System",
+];
+
+/// Maximum size of files [`find_key_in_dir`] searches for the key by field name, so huge map or media files aren't read into memory.
+const MAX_SEARCHED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Reads MV/MZ `System.json` file at `path` and returns its encryption-related fields.
+///
+/// See [`extract_key_from_system_json`].
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if reading the file fails.
+/// - Any error of [`extract_key_from_system_json`].
+pub fn extract_key_from_system_json_file(
+    path: &Path,
+) -> Result<EncryptionInfo, FileError> {
+    read(path)
+        .map_err(Error::from)
+        .and_then(|data| extract_key_from_system_json(&data))
+        .map_err(|err| FileError::new(path, err))
+}
+
+/// Finds the key in game's `data` folder at `dir`, even if `System.json` is renamed or obfuscated.
+///
+/// Files named as [`SYSTEM_JSON_NAMES`] are tried first, in order, with [`extract_key_from_system_json`]. If none of them contains the key, other files of `dir`, up to 16 MiB, are searched for `encryptionKey` field with [`search_encryption_info`], in the order of their names.
+///
+/// # Returns
+///
+/// - Path to the file the key was found in, and [`EncryptionInfo`] read from it.
+/// - [`None`] if there's no key in `dir`, or it can't be read.
+#[must_use]
+pub fn find_key_in_dir(dir: &Path) -> Option<(PathBuf, EncryptionInfo)> {
+    let named = SYSTEM_JSON_NAMES.iter().find_map(|name| {
+        let path = dir.join(name);
+        extract_key_from_system_json_file(&path)
+            .ok()
+            .map(|info| (path, info))
+    });
+
+    if named.is_some() {
+        return named;
+    }
+
+    let mut files: Vec<PathBuf> = read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry.metadata().map_or(false, |meta| {
+                meta.is_file() && meta.len() <= MAX_SEARCHED_SIZE
+            })
+        })
+        .map(|entry| entry.path())
+        .collect();
+    files.sort_unstable();
+
+    files.into_iter().find_map(|path| {
+        let info = search_encryption_info(&read(&path).ok()?).ok()?;
+        Some((path, info))
+    })
+}
@@ -0,0 +1,98 @@
+//! Golden-file helpers for testing pipelines built on this crate, without shipping copyrighted game assets.
+//!
+//! Encryption is deterministic, so [`encrypt_fixture`] turns any plain asset into the exact file RPG Maker would produce with the same key, and such synthetic files can be committed instead of real game assets.
+//! [`make_fixture`] generates such assets from scratch, for every supported type.
+//! Assertions panic with the offset of the first differing byte instead of dumping whole buffers, so failures stay readable for large assets.
+
+mod fixture;
+
+pub use self::fixture::{Fixture, make_fixture};
+
+use crate::{Decrypter, Encrypter, EncryptionKey, FileType};
+
+/// Encrypts `plain` asset with `key`, producing the same file RPG Maker would, including the standard header.
+#[must_use]
+pub fn encrypt_fixture(plain: &[u8], key: &EncryptionKey) -> Vec<u8> {
+    Encrypter::with_key(*key).encrypt(plain)
+}
+
+/// Panics if `actual` differs from `expected`, naming the first differing byte.
+#[track_caller]
+fn assert_bytes_eq(actual: &[u8], expected: &[u8], context: &str) {
+    if let Some(offset) = actual
+        .iter()
+        .zip(expected)
+        .position(|(left, right)| left != right)
+    {
+        panic!(
+            "{}: data differs at offset {}: {:#04x} != {:#04x}",
+            context, offset, actual[offset], expected[offset]
+        );
+    }
+
+    assert!(
+        actual.len() == expected.len(),
+        "{}: length differs: {} != {}",
+        context,
+        actual.len(),
+        expected.len()
+    );
+}
+
+/// Asserts that `plain` asset survives encryption with `key` and decryption losslessly, and returns the encrypted asset.
+///
+/// Besides decrypting with `key`, it checks that:
+///
+/// - Re-encrypting decrypted data reproduces the encrypted asset byte for byte.
+/// - If `plain` is a recognized asset of `file_type`, the key is derived from the encrypted asset alone, as it would be for real game files. JSON has no known plaintext to derive the key from, so it's never checked.
+///
+/// # Panics
+///
+/// - If any of the checks fails.
+#[track_caller]
+#[must_use = "returned encrypted asset can be compared against a golden file"]
+pub fn assert_round_trip(
+    plain: &[u8],
+    key: &EncryptionKey,
+    file_type: FileType,
+) -> Vec<u8> {
+    let encrypted = encrypt_fixture(plain, key);
+
+    let mut cipher = Decrypter::new();
+    cipher.set_key(*key);
+    let decrypted =
+        cipher.decrypt(&encrypted, file_type).unwrap_or_else(|err| {
+            panic!("decryption with the key failed: {}", err)
+        });
+    assert_bytes_eq(&decrypted, plain, "decrypted asset");
+
+    let reencrypted = cipher
+        .encrypt(&decrypted)
+        .unwrap_or_else(|err| panic!("re-encryption failed: {}", err));
+    assert_bytes_eq(&reencrypted, &encrypted, "re-encrypted asset");
+
+    if !file_type.is_json() && FileType::detect(plain) == Some(file_type) {
+        assert_decrypts_to(&encrypted, file_type, plain);
+    }
+
+    encrypted
+}
+
+/// Asserts that `encrypted` asset decrypts to `expected`, with the key derived from `encrypted` itself.
+///
+/// # Panics
+///
+/// - If the key can't be derived, or decrypted data differs from `expected`.
+#[track_caller]
+pub fn assert_decrypts_to(
+    encrypted: &[u8],
+    file_type: FileType,
+    expected: &[u8],
+) {
+    let decrypted = Decrypter::new()
+        .decrypt(encrypted, file_type)
+        .unwrap_or_else(|err| {
+            panic!("decryption with derived key failed: {}", err)
+        });
+    assert_bytes_eq(&decrypted, expected, "decrypted asset");
+}
@@ -0,0 +1,335 @@
+use super::encrypt_fixture;
+use crate::{EncryptionKey, FileType};
+use std::convert::TryFrom;
+
+/// Sample rate of OGG fixtures, whose last page claims one second of audio.
+const OGG_SAMPLE_RATE: u32 = 44100;
+/// Serial number of OGG fixtures' stream.
+const OGG_SERIAL: u32 = 0x4d47_5052;
+/// Vendor string of OGG fixtures' comment header.
+const OGG_VENDOR: &[u8] = b"rpgm-asset-decrypter";
+/// Length of OGG page header, without the segment table.
+const OGG_PAGE_HEADER_SIZE: usize = 27;
+/// Maximum length of OGG page, whose packets are all terminated within it.
+const OGG_MAX_PAGE_SIZE: usize = OGG_PAGE_HEADER_SIZE + 255 * 256 - 1;
+
+/// Plain asset and its encrypted form, produced by [`make_fixture`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// Plain asset.
+    pub plain: Vec<u8>,
+    /// Plain asset, encrypted with the fixture's key, including the standard header.
+    pub encrypted: Vec<u8>,
+}
+
+/// CRC-32 of PNG chunks.
+fn png_crc(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+
+    for &byte in data {
+        crc ^= u32::from(byte);
+
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xedb8_8320
+            };
+        }
+    }
+
+    !crc
+}
+
+/// CRC of OGG pages, which uses the same polynomial as PNG, but in normal bit order and without final XOR.
+fn ogg_crc(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+
+    for &byte in data {
+        crc ^= u32::from(byte) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x04c1_1db7
+            };
+        }
+    }
+
+    crc
+}
+
+/// Adler-32 checksum of zlib streams.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// Returns `length` as a 32-bit field of a binary format.
+fn u32_field(length: usize) -> u32 {
+    u32::try_from(length).expect("fixture size exceeds 4 GiB")
+}
+
+fn push_png_chunk(png: &mut Vec<u8>, chunk_type: [u8; 4], data: &[u8]) {
+    png.extend_from_slice(&u32_field(data.len()).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(&chunk_type);
+    png.extend_from_slice(data);
+
+    let crc = png_crc(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// 1x1 transparent RGBA image, padded with `tEXt` chunk.
+fn png(size: usize) -> Vec<u8> {
+    const MIN_SIZE: usize = 93;
+    const KEYWORD: &[u8] = b"Comment\0";
+
+    // Width, height, bit depth, color type (RGBA), compression, filter and interlace methods.
+    let ihdr = [0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0];
+
+    // Filter type byte and a single transparent pixel, stored uncompressed.
+    let scanline = [0; 5];
+    let mut idat = vec![0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff];
+    idat.extend_from_slice(&scanline);
+    idat.extend_from_slice(&adler32(&scanline).to_be_bytes());
+
+    let mut text = KEYWORD.to_vec();
+    text.resize(KEYWORD.len() + size.saturating_sub(MIN_SIZE), b'.');
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    push_png_chunk(&mut png, *b"IHDR", &ihdr);
+    push_png_chunk(&mut png, *b"tEXt", &text);
+    push_png_chunk(&mut png, *b"IDAT", &idat);
+    push_png_chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+fn push_ogg_page(
+    ogg: &mut Vec<u8>,
+    header_type: u8,
+    granule_position: u64,
+    sequence: u32,
+    lacing: &[u8],
+    body: &[u8],
+) {
+    const CRC_POS: usize = 22;
+
+    let start = ogg.len();
+    ogg.extend_from_slice(b"OggS\0");
+    ogg.push(header_type);
+    ogg.extend_from_slice(&granule_position.to_le_bytes());
+    ogg.extend_from_slice(&OGG_SERIAL.to_le_bytes());
+    ogg.extend_from_slice(&sequence.to_le_bytes());
+    ogg.extend_from_slice(&[0; 4]);
+    ogg.push(u8::try_from(lacing.len()).expect("too many OGG segments"));
+    ogg.extend_from_slice(lacing);
+    ogg.extend_from_slice(body);
+
+    let crc = ogg_crc(&ogg[start..]);
+    ogg[start + CRC_POS..start + CRC_POS + 4]
+        .copy_from_slice(&crc.to_le_bytes());
+}
+
+/// Vorbis identification and comment headers, followed by zeroed pages, the last of which claims one second of audio.
+///
+/// There's no setup header nor actual audio, so the stream is structurally valid, but not playable.
+fn ogg(size: usize) -> Vec<u8> {
+    let mut identification = b"\x01vorbis".to_vec();
+    identification.extend_from_slice(&0u32.to_le_bytes());
+    identification.push(1);
+    identification.extend_from_slice(&OGG_SAMPLE_RATE.to_le_bytes());
+    // Maximum, nominal and minimum bitrates are unset.
+    identification.extend_from_slice(&[0; 12]);
+    // Block sizes of 256 and 2048 samples, and framing bit.
+    identification.extend_from_slice(&[0xb8, 1]);
+
+    let mut comment = b"\x03vorbis".to_vec();
+    comment.extend_from_slice(&u32_field(OGG_VENDOR.len()).to_le_bytes());
+    comment.extend_from_slice(OGG_VENDOR);
+    comment.extend_from_slice(&0u32.to_le_bytes());
+    comment.push(1);
+
+    let mut ogg = Vec::new();
+    let mut sequence = 0;
+
+    for &(header_type, packet) in &[(0x02, &identification), (0x00, &comment)] {
+        let lacing = [u8::try_from(packet.len()).unwrap_or_default()];
+        push_ogg_page(&mut ogg, header_type, 0, sequence, &lacing, packet);
+        sequence += 1;
+    }
+
+    let mut remaining =
+        size.saturating_sub(ogg.len()).max(OGG_PAGE_HEADER_SIZE + 1);
+
+    while remaining != 0 {
+        let mut page_size = remaining.min(OGG_MAX_PAGE_SIZE);
+
+        // Every page needs a header and at least one segment.
+        if (1..=OGG_PAGE_HEADER_SIZE).contains(&(remaining - page_size)) {
+            page_size = remaining - OGG_PAGE_HEADER_SIZE - 1;
+        }
+
+        remaining -= page_size;
+
+        // Segments are laced so that the last one is shorter than 255 bytes, and terminates its packet.
+        let segments_and_body = page_size - OGG_PAGE_HEADER_SIZE;
+        let segment_count = segments_and_body / 256 + 1;
+        let mut body_length = segments_and_body - segment_count;
+
+        let lacing: Vec<u8> = (0..segment_count)
+            .map(|_| {
+                let segment = body_length.min(255);
+                body_length -= segment;
+                u8::try_from(segment).unwrap_or_default()
+            })
+            .collect();
+
+        let body = vec![0; segments_and_body - segment_count];
+        let header_type = if remaining == 0 { 0x04 } else { 0x00 };
+
+        push_ogg_page(
+            &mut ogg,
+            header_type,
+            u64::from(OGG_SAMPLE_RATE),
+            sequence,
+            &lacing,
+            &body,
+        );
+        sequence += 1;
+    }
+
+    ogg
+}
+
+fn push_m4a_box(m4a: &mut Vec<u8>, box_type: [u8; 4], data: &[u8]) {
+    m4a.extend_from_slice(&u32_field(8 + data.len()).to_be_bytes());
+    m4a.extend_from_slice(&box_type);
+    m4a.extend_from_slice(data);
+}
+
+/// `ftyp` box written by `FFmpeg`, padded with `free` box, and followed by empty `mdat` box.
+///
+/// There's no `moov` box, so the file is structurally valid, but not playable.
+fn m4a(size: usize) -> Vec<u8> {
+    // Key derivation looks for the box after `ftyp` within the first 64 bytes.
+    const MIN_SIZE: usize = 64;
+    const FTYP_AND_MDAT_SIZE: usize = 36;
+
+    let mut m4a = Vec::new();
+    push_m4a_box(&mut m4a, *b"ftyp", b"M4A \0\0\x02\0M4A isomiso2");
+    push_m4a_box(
+        &mut m4a,
+        *b"free",
+        &vec![0; size.max(MIN_SIZE) - FTYP_AND_MDAT_SIZE - 8],
+    );
+    push_m4a_box(&mut m4a, *b"mdat", &[]);
+    m4a
+}
+
+/// RIFF file of `form_type`, containing `chunks`, each of which is padded to even length.
+fn riff(form_type: [u8; 4], chunks: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let mut riff = b"RIFF\0\0\0\0".to_vec();
+    riff.extend_from_slice(&form_type);
+
+    for (chunk_type, data) in chunks {
+        riff.extend_from_slice(chunk_type);
+        riff.extend_from_slice(&u32_field(data.len()).to_le_bytes());
+        riff.extend_from_slice(data);
+
+        if data.len() % 2 == 1 {
+            riff.push(0);
+        }
+    }
+
+    let riff_size = u32_field(riff.len() - 8).to_le_bytes();
+    riff[4..8].copy_from_slice(&riff_size);
+    riff
+}
+
+/// 1x1 transparent lossless image, whose `VP8L` chunk is padded with zeros.
+fn webp(size: usize) -> Vec<u8> {
+    const MIN_SIZE: usize = 34;
+    const VP8L: &[u8] = &[
+        0x2f, 0x00, 0x00, 0x00, 0x10, 0x07, 0x10, 0x11, 0x11, 0x88, 0x88, 0xfe,
+        0x07,
+    ];
+
+    // RIFF header and `VP8L` chunk header.
+    const HEADERS_SIZE: usize = 20;
+
+    let mut vp8l = VP8L.to_vec();
+    vp8l.resize(size.max(MIN_SIZE) - HEADERS_SIZE, 0);
+    riff(*b"WEBP", &[(*b"VP8L", &vp8l)])
+}
+
+/// 8-bit mono PCM audio at 8 kHz, whose samples are silence.
+fn wav(size: usize) -> Vec<u8> {
+    const MIN_SIZE: usize = 44;
+
+    // PCM format, 1 channel, 8000 Hz sample rate, 8000 bytes per second, 1-byte blocks and 8 bits per sample.
+    let fmt = [1, 0, 1, 0, 0x40, 0x1f, 0, 0, 0x40, 0x1f, 0, 0, 1, 0, 8, 0];
+    let samples = vec![0x80; size.saturating_sub(MIN_SIZE)];
+    riff(*b"WAVE", &[(*b"fmt ", &fmt), (*b"data", &samples)])
+}
+
+/// Generates a minimal valid plain asset of `file_type`, padded to `size` bytes, and encrypts it with `key`.
+///
+/// Fixtures are deterministic, and each of them passes [`crate::verify::validate`], and derives `key` from its encrypted form, just as real assets do, so they can reproduce bug reports without the original files:
+///
+/// - PNG - 1x1 transparent image, padded with `tEXt` chunk. At least 93 bytes.
+/// - OGG - Vorbis headers, padded with empty pages, without any actual audio. At least 150 bytes.
+/// - M4A - `ftyp` box, padded with `free` box, without any actual audio. At least 64 bytes.
+/// - WEBP - 1x1 transparent lossless image, with padded image data. At least 34 bytes.
+/// - WAV - Silent 8-bit mono PCM audio. At least 44 bytes.
+/// - JSON - Empty object, padded with whitespace. At least 2 bytes.
+/// - [`FileType::Other`] - The signature, padded with zeros. At least 16 bytes.
+///
+/// # Parameters
+///
+/// - `file_type` - Type of the fixture.
+/// - `key` - Key to encrypt the fixture with.
+/// - `size` - Length of the plain asset. Smaller lengths are raised to the minimum of `file_type`, and RIFF formats (WEBP and WAV) are rounded up to even length, since their chunks are padded to it.
+///
+/// # Panics
+///
+/// - If `size` exceeds 4 GiB, which 32-bit size fields of the formats can't describe.
+#[must_use]
+pub fn make_fixture(
+    file_type: FileType,
+    key: &EncryptionKey,
+    size: usize,
+) -> Fixture {
+    let plain = match file_type {
+        FileType::PNG => png(size),
+        FileType::OGG => ogg(size),
+        FileType::M4A => m4a(size),
+        FileType::WEBP => webp(size),
+        FileType::WAV => wav(size),
+        FileType::JSON => {
+            let mut json = vec![b' '; size.max(2)];
+            let last = json.len() - 1;
+            json[0] = b'{';
+            json[last] = b'}';
+            json
+        }
+        FileType::Other(signature) => {
+            let mut data = signature.to_vec();
+            data.resize(size.max(signature.len()), 0);
+            data
+        }
+    };
+
+    Fixture {
+        encrypted: encrypt_fixture(&plain, key),
+        plain,
+    }
+}
@@ -0,0 +1,123 @@
+//! Virtual filesystem abstraction over sources and destinations of batch runs.
+//!
+//! [`crate::batch::decrypt_vfs`] only talks to [`VirtualFs`], so any container, e.g. [`crate::container::AsarContainer`], can be decrypted from, or written into, by implementing it.
+
+use crate::{Error, FileError, fs::write_atomic};
+use std::{
+    fs::{create_dir_all, read},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Tree of files addressed by relative paths, e.g. `img/pictures/image.rpgmvp`.
+pub trait VirtualFs {
+    /// Returns relative paths of all files, sorted.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if listing fails.
+    fn list(&mut self) -> Result<Vec<PathBuf>, FileError>;
+
+    /// Returns the data of file at relative `path`.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if there's no such file, or reading it fails.
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>, FileError>;
+
+    /// Writes `data` to file at relative `path`, creating missing directories.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::Io`] - if writing fails, or the filesystem is read-only.
+    fn write(&mut self, path: &Path, data: &[u8]) -> Result<(), FileError>;
+
+    /// Returns the path relative `path` is reported with, e.g. in [`FileError`]s and [`crate::batch::FileReport`]s.
+    fn full_path(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
+
+/// Returns the error [`VirtualFs::write`] of read-only filesystems fails with.
+pub(crate) fn read_only(path: PathBuf) -> FileError {
+    FileError::new(
+        path,
+        Error::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "filesystem is read-only",
+        )),
+    )
+}
+
+/// Returns whether relative `path` stays inside the root it's resolved against, e.g. `../System.json` and `/etc/passwd` don't.
+pub(crate) fn is_enclosed(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Directory of the real filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirFs {
+    root: PathBuf,
+}
+
+impl DirFs {
+    /// Creates a filesystem rooted at directory `root`.
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the root directory.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn resolve(&self, path: &Path) -> Result<PathBuf, FileError> {
+        if is_enclosed(path) {
+            Ok(self.root.join(path))
+        } else {
+            Err(FileError::new(
+                self.root.join(path),
+                Error::Io(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "path escapes the root",
+                )),
+            ))
+        }
+    }
+}
+
+impl VirtualFs for DirFs {
+    fn list(&mut self) -> Result<Vec<PathBuf>, FileError> {
+        let files = crate::fs::walk(&self.root, |_| true)?;
+
+        Ok(files
+            .into_iter()
+            .filter_map(|path| {
+                path.strip_prefix(&self.root).ok().map(Path::to_path_buf)
+            })
+            .collect())
+    }
+
+    fn read(&mut self, path: &Path) -> Result<Vec<u8>, FileError> {
+        let path = self.resolve(path)?;
+        read(&path).map_err(|err| FileError::new(path, err))
+    }
+
+    fn write(&mut self, path: &Path, data: &[u8]) -> Result<(), FileError> {
+        let path = self.resolve(path)?;
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)
+                .map_err(|err| FileError::new(parent, err))?;
+        }
+
+        write_atomic(&path, data)
+    }
+
+    fn full_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
@@ -0,0 +1,63 @@
+//! `wasm-bindgen` bindings for browser-based tools.
+//!
+//! Enabled with the `wasm` feature. Errors are thrown as JS `Error` objects with a human-readable message.
+//!
+//! File types are passed as strings: either decrypted (`png`, `ogg`, `m4a`, `webp`, `wav`) or encrypted (`rpgmvp`, `png_`, etc.) extension.
+
+use crate::{Decrypter, FileType};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+fn parse_file_type(file_type: &str) -> Result<FileType, JsError> {
+    match FileType::from_decrypted_extension(file_type) {
+        Some(file_type) => Ok(file_type),
+        None => FileType::try_from(file_type).map_err(JsError::new),
+    }
+}
+
+/// Decrypts RPG Maker file data.
+///
+/// If `key` is not passed, it's determined from the data.
+///
+/// # Errors
+///
+/// Throws if `file_type` is not supported, `key` is invalid, or the data has invalid header or ends unexpectedly.
+#[wasm_bindgen(js_name = decryptBytes)]
+pub fn decrypt_bytes(
+    data: &[u8],
+    file_type: &str,
+    key: Option<String>,
+) -> Result<Vec<u8>, JsError> {
+    let file_type = parse_file_type(file_type)?;
+    let mut decrypter = Decrypter::new();
+
+    if let Some(key) = key {
+        decrypter.set_key_from_str(&key)?;
+    }
+
+    Ok(decrypter.decrypt(data, file_type)?)
+}
+
+/// Encrypts `.png`, `.ogg` or `.m4a` file data with `key`, and prepends the RPG Maker header.
+///
+/// # Errors
+///
+/// Throws if `key` is invalid.
+#[wasm_bindgen(js_name = encryptBytes)]
+pub fn encrypt_bytes(data: &[u8], key: &str) -> Result<Vec<u8>, JsError> {
+    Ok(crate::encrypt(data, key)?)
+}
+
+/// Determines the encryption key from RPG Maker file data.
+///
+/// # Errors
+///
+/// Throws if `file_type` is not supported, or the data has invalid header or ends unexpectedly.
+#[wasm_bindgen(js_name = detectKey)]
+pub fn detect_key(data: &[u8], file_type: &str) -> Result<String, JsError> {
+    let file_type = parse_file_type(file_type)?;
+
+    Ok(Decrypter::new()
+        .set_key_from_file(data, file_type)?
+        .to_owned())
+}
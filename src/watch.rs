@@ -0,0 +1,259 @@
+//! Watch mode: decrypting assets of a game folder as they appear or change, so edited files materialize without rerunning anything.
+//!
+//! [`decrypt_on_change`] decrypts assets of a game as they change, and [`encrypt_on_change`] does the reverse, encrypting edited assets back into the game, so e.g. translated images can be previewed in the running game right away.
+//!
+//! Changes are picked up through the native file notification API of the platform, see [notify](https://docs.rs/notify).
+
+use crate::{
+    DECRYPTED_ASSETS_EXTS, Error, FileError,
+    batch::{
+        BatchOptions, BatchReport, RepackOptions, decrypt_files, encrypt_files,
+        is_decryptable,
+    },
+};
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode},
+};
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Time without new events after which collected changes are processed.
+///
+/// Writers often produce several events per file, e.g. create, then a few writes, so waiting for them to settle avoids decrypting half-written files.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Converts an error of the watcher of `path` into [`FileError`].
+fn watch_error(path: &Path, err: notify::Error) -> FileError {
+    let err = match err.kind {
+        notify::ErrorKind::Io(err) => err,
+        kind => io::Error::new(
+            io::ErrorKind::Other,
+            notify::Error::new(kind).to_string(),
+        ),
+    };
+
+    FileError::new(path, Error::Io(err))
+}
+
+/// Returns paths of files `event` writes to, or none if it doesn't.
+fn written_paths(event: Event) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(_)
+        | EventKind::Access(AccessKind::Close(AccessMode::Write)) => event
+            .paths
+            .into_iter()
+            .filter(|path| path.is_file())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Handle of a running watch, see [`decrypt_on_change`] and [`encrypt_on_change`].
+///
+/// Watching stops when the handle is dropped, or [`WatchHandle::stop`] is called.
+pub struct WatchHandle {
+    watcher: Option<RecommendedWatcher>,
+    worker: Option<JoinHandle<()>>,
+    reports: Receiver<Result<BatchReport, FileError>>,
+}
+
+impl WatchHandle {
+    /// Starts watching `root` recursively, and calls `process` with sorted paths of files changed under it, after they settle for [`DEBOUNCE`].
+    fn spawn<F>(root: &Path, mut process: F) -> Result<Self, FileError>
+    where
+        F: FnMut(Vec<PathBuf>) -> Option<Result<BatchReport, FileError>>
+            + Send
+            + 'static,
+    {
+        let (events_tx, events) = channel::<notify::Result<Event>>();
+        let (reports_tx, reports) = channel();
+
+        let mut watcher = notify::recommended_watcher(events_tx)
+            .map_err(|err| watch_error(root, err))?;
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .map_err(|err| watch_error(root, err))?;
+
+        let error_root = root.to_path_buf();
+        let worker = thread::spawn(move || {
+            // Ends when the watcher, and its sender with it, is dropped.
+            while let Ok(first) = events.recv() {
+                let mut changed = BTreeSet::new();
+                let mut next = Some(first);
+
+                while let Some(event) = next.take() {
+                    match event {
+                        Ok(event) => changed.extend(written_paths(event)),
+                        Err(err) => {
+                            let _ = reports_tx
+                                .send(Err(watch_error(&error_root, err)));
+                        }
+                    }
+
+                    match events.recv_timeout(DEBOUNCE) {
+                        Ok(event) => next = Some(event),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                if let Some(report) = process(changed.into_iter().collect()) {
+                    if reports_tx.send(report).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            watcher: Some(watcher),
+            worker: Some(worker),
+            reports,
+        })
+    }
+
+    /// Waits up to `timeout` for the next report.
+    ///
+    /// # Returns
+    ///
+    /// - [`BatchReport`] of the next processed group of changes, or [`FileError`] if they couldn't be processed at all, or the watcher failed.
+    /// - [`None`] if nothing was processed in time.
+    #[must_use]
+    pub fn next_report(
+        &self,
+        timeout: Duration,
+    ) -> Option<Result<BatchReport, FileError>> {
+        self.reports.recv_timeout(timeout).ok()
+    }
+
+    /// Stops watching, and waits for changes being processed to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        drop(self.watcher.take());
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl std::fmt::Debug for WatchHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchHandle")
+            .field("running", &self.worker.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Watches `src` recursively, and decrypts encrypted assets into `dst` as they appear or change, like [`crate::batch::decrypt_dir`] does for the whole directory.
+///
+/// Only changes made after the call are picked up, so run [`crate::batch::decrypt_dir`] first to decrypt existing assets.
+/// Changes are processed in groups, once no new events arrive for [`DEBOUNCE`]. Every group produces a [`BatchReport`], available from [`WatchHandle::next_report`]; groups without selected encrypted assets produce nothing.
+/// The key is determined for every group, the same way [`crate::batch::decrypt_dir`] determines it, so set [`BatchOptions::key`] to avoid reading `System.json` each time.
+///
+/// # Parameters
+///
+/// - `src` - Root directory to watch, e.g. game's root or `www` folder.
+/// - `dst` - Root directory to write decrypted assets to.
+/// - `options` - [`BatchOptions`].
+///
+/// # Returns
+///
+/// - [`WatchHandle`] that keeps watching until dropped.
+/// - [`FileError`] if watching couldn't start.
+///
+/// # Errors
+///
+/// - [`Error::Io`] - if `src` can't be resolved or watched.
+pub fn decrypt_on_change(
+    src: &Path,
+    dst: &Path,
+    options: &BatchOptions,
+) -> Result<WatchHandle, FileError> {
+    // Some platforms report canonical paths, which must be relative to the watched root.
+    let src = src.canonicalize().map_err(|err| FileError::new(src, err))?;
+    let dst = dst.to_path_buf();
+    let options = options.clone();
+
+    WatchHandle::spawn(&src.clone(), move |changed| {
+        let files: Vec<PathBuf> = changed
+            .into_iter()
+            .filter(|path| is_decryptable(&src, path, &options))
+            .collect();
+
+        if files.is_empty() {
+            return None;
+        }
+
+        Some(decrypt_files(&src, &dst, files, &options))
+    })
+}
+
+/// Watches `src` recursively, and encrypts decrypted assets (see [`DECRYPTED_ASSETS_EXTS`]) into `dst` as they appear or change, like [`crate::batch::encrypt_dir`] does for the whole directory.
+///
+/// This is the reverse of [`decrypt_on_change`]: point `src` at the folder of edited assets, and `dst` at the game's root or `www` folder, and edited files are written into its `img`/`audio` tree with encrypted extensions of [`RepackOptions::engine`], or of the engine detected from `dst`, so the running game picks them up.
+/// Changes are processed the same way as by [`decrypt_on_change`]. The key is read from [`RepackOptions::system_json`] for every group, unless [`RepackOptions::key`] is set.
+///
+/// # Parameters
+///
+/// - `src` - Root directory of decrypted assets to watch.
+/// - `dst` - Root directory to write encrypted assets to, e.g. game's `www` folder.
+/// - `options` - [`RepackOptions`].
+///
+/// # Returns
+///
+/// - [`WatchHandle`] that keeps watching until dropped.
+/// - [`FileError`] if watching couldn't start.
+///
+/// # Errors
+///
+/// - [`Error::KeyNotSet`] - if neither [`RepackOptions::key`] nor [`RepackOptions::system_json`] is set.
+/// - [`Error::Io`] - if `src` can't be resolved or watched.
+pub fn encrypt_on_change(
+    src: &Path,
+    dst: &Path,
+    options: &RepackOptions,
+) -> Result<WatchHandle, FileError> {
+    if options.key.is_none() && options.system_json.is_none() {
+        return Err(FileError::new(src, Error::KeyNotSet));
+    }
+
+    let src = src.canonicalize().map_err(|err| FileError::new(src, err))?;
+    let dst = dst.to_path_buf();
+    let options = options.clone();
+
+    WatchHandle::spawn(&src.clone(), move |changed| {
+        let files: Vec<PathBuf> = changed
+            .into_iter()
+            .filter(|path| {
+                path.extension()
+                    .and_then(OsStr::to_str)
+                    .map_or(false, |ext| DECRYPTED_ASSETS_EXTS.contains(&ext))
+            })
+            .collect();
+
+        if files.is_empty() {
+            return None;
+        }
+
+        Some(encrypt_files(&src, &dst, files, &options))
+    })
+}
@@ -1,5 +1,11 @@
-use rpgm_asset_decrypter_lib::{Decrypter, FileType, RPGM_HEADER};
-use std::fs::read;
+use rpgm_asset_decrypter_lib::{
+    Decrypter, FileType, HEADER_LENGTH, RPGM_HEADER,
+    stream::{DecryptReader, EncryptWriter},
+};
+use std::{
+    fs::read,
+    io::{Read, Write},
+};
 
 fn is_valid_ogg(buf: &[u8]) -> bool {
     buf.starts_with(b"OggS")
@@ -141,6 +147,101 @@ fn run_encrypt_in_place_roundtrip(case: &TestCase) {
     assert!((case.validator)(&decrypted));
 }
 
+fn run_detect(case: &TestCase) {
+    let decrypted = Decrypter::new()
+        .decrypt(&read(case.path).unwrap(), case.file_type)
+        .unwrap();
+
+    assert_eq!(FileType::detect(&decrypted), Some(case.file_type));
+}
+
+fn run_decrypt_header_only(case: &TestCase) {
+    let data = read(case.path).unwrap();
+
+    let mut d = Decrypter::new();
+    let decrypted = d.decrypt(&data, case.file_type).unwrap();
+
+    // Key is already set, so the block is decrypted with it instead of being derived again.
+    let block = d.decrypt_header_only(&data, case.file_type).unwrap();
+
+    assert_eq!(block, decrypted[..HEADER_LENGTH]);
+    assert!((case.validator)(&block));
+}
+
+#[test]
+fn decrypter_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Decrypter>();
+}
+
+#[test]
+fn parallel_key_derivation() {
+    let handles: Vec<_> = ogg_cases()
+        .into_iter()
+        .chain(m4a_cases())
+        .chain(png_cases())
+        .map(|case| {
+            std::thread::spawn(move || {
+                for _ in 0..32 {
+                    run_decrypt(&case);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(feature = "md5")]
+#[test]
+fn set_key_from_plaintext() {
+    let mut d = Decrypter::new();
+
+    assert_eq!(
+        d.set_key_from_plaintext(""),
+        rpgm_asset_decrypter_lib::DEFAULT_KEY
+    );
+    // Test assets named `aboba` were encrypted with `aboba` passphrase.
+    assert_eq!(
+        d.set_key_from_plaintext("aboba"),
+        "150f15e73422e0a5ba5b59f997fc2350"
+    );
+
+    let decrypted = d
+        .decrypt(&read(MV_PNG_ABOBA).unwrap(), FileType::PNG)
+        .unwrap();
+    assert!(is_valid_png(&decrypted));
+
+    // Passphrases longer than a single MD5 block.
+    assert_eq!(
+        d.set_key_from_plaintext(&"a".repeat(100)),
+        "36a92cc94a9e0fa21f625f8bfb007adf"
+    );
+}
+
+fn run_stream_roundtrip(case: &TestCase) {
+    let data = read(case.path).unwrap();
+
+    let mut d = Decrypter::new();
+    let mut reader =
+        DecryptReader::new(data.as_slice(), &mut d, case.file_type).unwrap();
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted).unwrap();
+
+    assert!((case.validator)(&decrypted));
+    assert_eq!(decrypted, d.decrypt(&data, case.file_type).unwrap());
+
+    let mut writer = EncryptWriter::new(Vec::new(), &d).unwrap();
+    // Write in small pieces to cross the encrypted block boundary.
+    for chunk in decrypted.chunks(7) {
+        writer.write_all(chunk).unwrap();
+    }
+
+    assert_eq!(writer.into_inner(), data);
+}
+
 macro_rules! generate_tests {
     ($group:ident, $cases_fn:ident) => {
         mod $group {
@@ -173,6 +274,27 @@ macro_rules! generate_tests {
                     run_encrypt_in_place_roundtrip(&c);
                 }
             }
+
+            #[test]
+            fn stream_roundtrip() {
+                for c in $cases_fn() {
+                    run_stream_roundtrip(&c);
+                }
+            }
+
+            #[test]
+            fn detect() {
+                for c in $cases_fn() {
+                    run_detect(&c);
+                }
+            }
+
+            #[test]
+            fn decrypt_header_only() {
+                for c in $cases_fn() {
+                    run_decrypt_header_only(&c);
+                }
+            }
         }
     };
 }
@@ -180,3 +302,4486 @@ macro_rules! generate_tests {
 generate_tests!(ogg, ogg_cases);
 generate_tests!(m4a, m4a_cases);
 generate_tests!(png, png_cases);
+
+#[test]
+fn encryption_key() {
+    use rpgm_asset_decrypter_lib::{EncryptionKey, Error};
+
+    let key: EncryptionKey =
+        "150F15E73422E0A5BA5B59F997FC2350".parse().unwrap();
+    assert_eq!(key.to_string(), "150f15e73422e0a5ba5b59f997fc2350");
+    assert_eq!(EncryptionKey::from_bytes(*key.as_bytes()), key);
+
+    assert!(matches!(
+        EncryptionKey::from_hex_str("aboba"),
+        Err(Error::InvalidKeyLength)
+    ));
+    assert!(matches!(
+        EncryptionKey::from_hex_str("zz0f15e73422e0a5ba5b59f997fc2350"),
+        Err(Error::InvalidKey)
+    ));
+    assert_eq!(
+        EncryptionKey::from_system_json(
+            br#"{"encryptionKey": "150f15e73422e0a5ba5b59f997fc2350"}"#
+        )
+        .unwrap(),
+        key
+    );
+
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key(key);
+    assert_eq!(decrypter.encryption_key(), Some(key));
+    assert_eq!(decrypter.key(), Some("150f15e73422e0a5ba5b59f997fc2350"));
+}
+
+#[test]
+fn parse_key_lossy() {
+    use rpgm_asset_decrypter_lib::{EncryptionKey, Error};
+
+    let key = EncryptionKey::from_hex_str("150f15e73422e0a5ba5b59f997fc2350")
+        .unwrap();
+
+    for input in [
+        "150F15E73422E0A5BA5B59F997FC2350",
+        " \"150f15e73422e0a5ba5b59f997fc2350\"\n",
+        "'  150f15e73422e0a5ba5b59f997fc2350 '\r\n",
+    ] {
+        assert_eq!(EncryptionKey::parse_lossy(input).unwrap(), key);
+    }
+
+    let position = |input| match EncryptionKey::parse_lossy(input) {
+        Err(Error::MalformedKey { position, .. }) => position,
+        result => panic!("unexpected {:?}", result),
+    };
+
+    assert_eq!(position("  \"150f15e73422e0a5ba5b59f997fc2350"), 2);
+    assert_eq!(position("150f15e73422e0a5ba5b59f997fc2350'"), 32);
+    assert_eq!(position("\"150f15e73422e0a5bx5b59f997fc2350\""), 18);
+    assert_eq!(position(" 150f15e7"), 9);
+    assert_eq!(position("150f15e73422e0a5ba5b59f997fc23500"), 32);
+    assert_eq!(position("\"  \""), 1);
+}
+
+#[test]
+fn encrypt_file() {
+    use rpgm_asset_decrypter_lib::{
+        EncryptionKey, Engine, Error, encrypt_file, encrypted_extension_for,
+    };
+    use std::{env::temp_dir, fs};
+
+    assert_eq!(encrypted_extension_for(FileType::PNG, Engine::Mv), "rpgmvp");
+    assert_eq!(encrypted_extension_for(FileType::OGG, Engine::Mz), "ogg_");
+
+    let dir = temp_dir().join("rpgm-asset-decrypter-encrypt-file");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let plain = Decrypter::new()
+        .decrypt(&read(MV_PNG_ABOBA).unwrap(), FileType::PNG)
+        .unwrap();
+    let input = dir.join("image.png");
+    fs::write(&input, &plain).unwrap();
+
+    let key: EncryptionKey =
+        "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+
+    let output = encrypt_file(&input, Engine::Mv, key).unwrap();
+    assert_eq!(output, dir.join("image.rpgmvp"));
+    assert_eq!(read(&output).unwrap(), read(MV_PNG_ABOBA).unwrap());
+
+    let output = encrypt_file(&input, Engine::Mz, key).unwrap();
+    assert_eq!(output, dir.join("image.png_"));
+
+    let text = dir.join("notes.txt");
+    fs::write(&text, b"aboba").unwrap();
+    assert!(matches!(
+        encrypt_file(&text, Engine::Mv, key).map_err(|err| err.source),
+        Err(Error::UnsupportedFileType)
+    ));
+
+    let json = dir.join("System.json");
+    fs::write(&json, b"{}").unwrap();
+    assert!(matches!(
+        encrypt_file(&json, Engine::Mv, key).map_err(|err| err.source),
+        Err(Error::UnsupportedFileType)
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn decrypt_file_and_encrypt_file_to() {
+    use rpgm_asset_decrypter_lib::{
+        EncryptionKey, Error, decrypt_file, encrypt_file_to,
+    };
+    use std::{env::temp_dir, fs, path::Path};
+
+    let dir = temp_dir().join("rpgm-asset-decrypter-file-helpers");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let decrypted = dir.join("audio.ogg");
+    decrypt_file(Path::new(MZ_OGG_ABOBA), &decrypted).unwrap();
+    assert!(is_valid_ogg(&read(&decrypted).unwrap()));
+
+    let key: EncryptionKey =
+        "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+    let encrypted = dir.join("nested/audio.ogg_");
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    encrypt_file_to(&decrypted, &encrypted, key).unwrap();
+    assert_eq!(read(&encrypted).unwrap(), read(MZ_OGG_ABOBA).unwrap());
+
+    assert!(matches!(
+        decrypt_file(&decrypted, &dir.join("out")).map_err(|err| err.source),
+        Err(Error::UnsupportedFileType)
+    ));
+    assert!(matches!(
+        encrypt_file_to(&encrypted, &dir.join("out"), key)
+            .map_err(|err| err.source),
+        Err(Error::UnsupportedFileType)
+    ));
+    let json = dir.join("System.json");
+    fs::write(&json, b"{}").unwrap();
+    assert!(matches!(
+        encrypt_file_to(&json, &dir.join("out"), key).map_err(|err| err.source),
+        Err(Error::UnsupportedFileType)
+    ));
+    assert!(!dir.join("out").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn asset_state() {
+    use rpgm_asset_decrypter_lib::{AssetState, Error};
+
+    let encrypted = read(MV_PNG_DEFAULT).unwrap();
+    assert_eq!(AssetState::detect(&encrypted), AssetState::Encrypted);
+
+    let plain = Decrypter::new().decrypt(&encrypted, FileType::PNG).unwrap();
+    assert_eq!(
+        AssetState::detect(&plain),
+        AssetState::Decrypted(FileType::PNG)
+    );
+    assert!(matches!(
+        Decrypter::new().decrypt(&plain, FileType::PNG),
+        Err(Error::AlreadyDecrypted(FileType::PNG))
+    ));
+
+    assert_eq!(AssetState::detect(b"aboba"), AssetState::Unknown);
+    assert!(matches!(
+        Decrypter::new().decrypt(b"aboba", FileType::PNG),
+        Err(Error::InvalidHeader)
+    ));
+}
+
+#[test]
+fn decrypt_cow() {
+    use rpgm_asset_decrypter_lib::EncryptionKey;
+    use std::borrow::Cow;
+
+    let encrypted = read(MV_PNG_ABOBA).unwrap();
+    let plain = Decrypter::new().decrypt(&encrypted, FileType::PNG).unwrap();
+
+    let cow = Decrypter::new()
+        .decrypt_cow(&encrypted, FileType::PNG)
+        .unwrap();
+    assert!(matches!(cow, Cow::Owned(_)));
+    assert_eq!(cow, plain);
+
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key(EncryptionKey::from_bytes([0; 16]));
+    let zero_key_encrypted = decrypter.encrypt(&plain).unwrap();
+
+    let cow = decrypter
+        .decrypt_cow(&zero_key_encrypted, FileType::PNG)
+        .unwrap();
+    assert!(matches!(cow, Cow::Borrowed(_)));
+    assert_eq!(cow, plain);
+}
+
+#[test]
+fn decrypt_many() {
+    use rpgm_asset_decrypter_lib::{Encrypter, EncryptionKey, Error};
+
+    let files = [
+        read(MV_OGG_ABOBA).unwrap(),
+        read(MZ_M4A_ABOBA).unwrap(),
+        read(MV_PNG_DEFAULT).unwrap(),
+        read(MV_PNG_ABOBA).unwrap(),
+    ];
+    let types = [FileType::OGG, FileType::M4A, FileType::PNG, FileType::PNG];
+
+    let mut decrypter = Decrypter::new();
+    let results = decrypter.decrypt_many(&files, |i, _| types[i]);
+
+    assert_eq!(results.len(), files.len());
+    assert!(is_valid_ogg(results[0].as_ref().unwrap()));
+    assert!(is_valid_m4a(results[1].as_ref().unwrap()));
+    assert!(matches!(results[2], Err(Error::KeyMismatch { .. })));
+    assert!(is_valid_png(results[3].as_ref().unwrap()));
+    assert_eq!(decrypter.key(), Some("150f15e73422e0a5ba5b59f997fc2350"));
+
+    let plain: Vec<Vec<u8>> = results.into_iter().flatten().collect();
+    let key: EncryptionKey =
+        "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+    assert_eq!(
+        decrypter.encrypt_many(&plain).unwrap(),
+        Encrypter::with_key(key).encrypt_many(plain.iter())
+    );
+    assert!(matches!(
+        Decrypter::new().encrypt_many(&plain),
+        Err(Error::KeyNotSet)
+    ));
+}
+
+#[test]
+fn buffer_pool() {
+    use rpgm_asset_decrypter_lib::pool::{BufferPool, VecPool};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let encrypted = read(MV_PNG_ABOBA).unwrap();
+    let plain = Decrypter::new().decrypt(&encrypted, FileType::PNG).unwrap();
+
+    let pool = VecPool::new().max_buffers(2);
+    let mut decrypter = Decrypter::new();
+
+    let first = decrypter
+        .decrypt_pooled(&encrypted, FileType::PNG, &pool)
+        .unwrap();
+    assert_eq!(first, plain);
+    let address = first.as_ptr();
+    pool.release(first);
+
+    let second = decrypter
+        .decrypt_pooled(&encrypted, FileType::PNG, &pool)
+        .unwrap();
+    assert_eq!(second, plain);
+    assert_eq!(second.as_ptr(), address);
+    assert!(pool.is_empty());
+
+    for _ in 0..3 {
+        pool.release(Vec::with_capacity(8));
+    }
+    assert_eq!(pool.len(), 2);
+
+    // Failing files don't take buffers from custom pools.
+    struct Counting(AtomicUsize);
+
+    impl BufferPool for Counting {
+        fn acquire(&self, capacity: usize) -> Vec<u8> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Vec::with_capacity(capacity)
+        }
+
+        fn release(&self, _: Vec<u8>) {}
+    }
+
+    let counting = Counting(AtomicUsize::new(0));
+    let pool: &dyn BufferPool = &counting;
+    assert!(
+        decrypter
+            .decrypt_pooled(&encrypted, FileType::OGG, pool)
+            .is_err()
+    );
+    assert!(
+        decrypter
+            .decrypt_pooled(b"aboba", FileType::PNG, pool)
+            .is_err()
+    );
+    assert_eq!(
+        decrypter
+            .decrypt_pooled(&encrypted, FileType::PNG, pool)
+            .unwrap(),
+        plain
+    );
+    assert_eq!(counting.0.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn double_encryption() {
+    use rpgm_asset_decrypter_lib::{
+        Encrypter, EncryptionKey, Error, MAX_LAYERS, decrypt_recursive, inspect,
+    };
+
+    let key = EncryptionKey::from_hex_str("150f15e73422e0a5ba5b59f997fc2350")
+        .unwrap();
+    let encrypter = Encrypter::with_key(key);
+
+    for &(path, file_type) in
+        &[(MV_PNG_ABOBA, FileType::PNG), (MZ_OGG_ABOBA, FileType::OGG)]
+    {
+        let once = read(path).unwrap();
+        let plain = Decrypter::new().decrypt(&once, file_type).unwrap();
+        let twice = encrypter.encrypt(&once);
+        let thrice = encrypter.encrypt(&twice);
+
+        assert_eq!(decrypt_recursive(&once, file_type).unwrap(), plain);
+        assert_eq!(inspect(&once).unwrap().layers, 1);
+        assert_eq!(inspect(&plain).unwrap().layers, 0);
+
+        for (layers, data) in [(2, &twice), (3, &thrice)] {
+            assert!(matches!(
+                Decrypter::new().decrypt(data, file_type),
+                Err(Error::DoubleEncrypted)
+            ));
+
+            let mut decrypter = Decrypter::new();
+            decrypter.set_key(key);
+            assert!(matches!(
+                decrypter.decrypt(data, file_type),
+                Err(Error::DoubleEncrypted)
+            ));
+            assert_eq!(
+                decrypter.decrypt_recursive(data, file_type).unwrap(),
+                plain
+            );
+
+            let mut decrypter = Decrypter::new();
+            assert_eq!(
+                decrypter.decrypt_recursive(data, file_type).unwrap(),
+                plain
+            );
+            assert_eq!(decrypter.encryption_key(), Some(key));
+
+            let info = inspect(data).unwrap();
+            assert_eq!(info.layers, layers);
+            assert_eq!(info.file_type, Some(file_type));
+            assert_eq!(info.key, Some(key));
+            assert_eq!(info.payload_length, plain.len());
+        }
+    }
+
+    let png = inspect(&encrypter.encrypt(&read(MV_PNG_ABOBA).unwrap()));
+    assert_eq!(png.unwrap().dimensions, Some((1920, 1080)));
+
+    let once = read(MV_PNG_ABOBA).unwrap();
+    let plain = Decrypter::new().decrypt(&once, FileType::PNG).unwrap();
+    let mut layered = once;
+
+    for _ in 1..MAX_LAYERS {
+        layered = encrypter.encrypt(&layered);
+    }
+
+    assert_eq!(inspect(&layered).unwrap().layers, MAX_LAYERS);
+    assert_eq!(decrypt_recursive(&layered, FileType::PNG).unwrap(), plain);
+
+    let layered = encrypter.encrypt(&layered);
+    assert!(matches!(inspect(&layered), Err(Error::TooManyLayers)));
+    assert!(matches!(
+        decrypt_recursive(&layered, FileType::PNG),
+        Err(Error::TooManyLayers)
+    ));
+
+    // Every zeroed block looks like another layer.
+    let mut zeros = RPGM_HEADER.to_vec();
+    zeros.resize(HEADER_LENGTH + 4 * 1024 * 1024, 0);
+
+    assert!(matches!(
+        Decrypter::new().decrypt(&zeros, FileType::PNG),
+        Err(Error::TooManyLayers)
+    ));
+    assert!(matches!(
+        decrypt_recursive(&zeros, FileType::PNG),
+        Err(Error::TooManyLayers)
+    ));
+    assert!(matches!(inspect(&zeros), Err(Error::TooManyLayers)));
+}
+
+fn webp_fixture(chunk_type: u8) -> Vec<u8> {
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8".to_vec();
+    webp.push(chunk_type);
+
+    match chunk_type {
+        b'X' => webp.extend_from_slice(&[10, 0, 0, 0, 0]),
+        b'L' => webp.extend_from_slice(&[100, 0, 0, 0, 0x2f]),
+        _ => webp.extend_from_slice(&[100, 0, 0, 0, 0x30, 0, 0, 0x9d, 1, 0x2a]),
+    }
+
+    webp.resize(128, 0xaa);
+    let riff_size = (webp.len() - 8) as u32;
+    webp[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    webp
+}
+
+#[test]
+fn webp_and_other_key() {
+    use rpgm_asset_decrypter_lib::{Engine, encrypted_extension_for};
+    use std::convert::TryFrom;
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    let mut encrypter = Decrypter::new();
+    encrypter.set_key_from_str(KEY).unwrap();
+
+    for chunk_type in [b' ', b'L', b'X'] {
+        let plain = webp_fixture(chunk_type);
+        assert_eq!(FileType::detect(&plain), Some(FileType::WEBP));
+
+        let encrypted = encrypter.encrypt(&plain).unwrap();
+        let mut decrypter = Decrypter::new();
+        assert_eq!(
+            decrypter
+                .set_key_from_file(&encrypted, FileType::WEBP)
+                .unwrap(),
+            KEY
+        );
+    }
+
+    assert_eq!(FileType::try_from("rpgmvw"), Ok(FileType::WEBP));
+    assert_eq!(FileType::try_from("webp_"), Ok(FileType::WEBP));
+    assert_eq!(encrypted_extension_for(FileType::WEBP, Engine::Mz), "webp_");
+
+    let mut signature = [0; 16];
+    signature.copy_from_slice(b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01");
+    let mut plain = signature.to_vec();
+    plain.resize(64, 0);
+
+    let encrypted = encrypter.encrypt(&plain).unwrap();
+    assert_eq!(
+        Decrypter::new()
+            .set_key_from_file(&encrypted, FileType::Other(signature))
+            .unwrap(),
+        KEY
+    );
+}
+
+#[test]
+fn wav_key() {
+    use rpgm_asset_decrypter_lib::{
+        Engine, encrypted_extension_for, inspect,
+        verify::{ValidationResult, validate},
+    };
+    use std::convert::TryFrom;
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&[
+        1, 0, 2, 0, 0x44, 0xac, 0, 0, 0x10, 0xb1, 2, 0, 4, 0, 16, 0,
+    ]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&64u32.to_le_bytes());
+    wav.resize(wav.len() + 64, 0x55);
+    let riff_size = (wav.len() - 8) as u32;
+    wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    assert_eq!(FileType::detect(&wav), Some(FileType::WAV));
+    assert_eq!(validate(&wav, FileType::WAV), ValidationResult::Valid);
+
+    let mut encrypter = Decrypter::new();
+    encrypter.set_key_from_str(KEY).unwrap();
+    let encrypted = encrypter.encrypt(&wav).unwrap();
+
+    let info = inspect(&encrypted).unwrap();
+    assert_eq!(info.file_type, Some(FileType::WAV));
+
+    let mut decrypter = Decrypter::new();
+    assert_eq!(
+        decrypter
+            .set_key_from_file(&encrypted, FileType::WAV)
+            .unwrap(),
+        KEY
+    );
+    assert_eq!(decrypter.decrypt(&encrypted, FileType::WAV).unwrap(), wav);
+
+    assert_eq!(FileType::try_from("wav_"), Ok(FileType::WAV));
+    assert_eq!(FileType::try_from("rpgmvwav"), Ok(FileType::WAV));
+    assert_eq!(FileType::try_from("rpgmvw"), Ok(FileType::WEBP));
+    assert_eq!(encrypted_extension_for(FileType::WAV, Engine::Mz), "wav_");
+    assert_eq!(
+        Engine::from_encrypted_extension("rpgmvwav"),
+        Some(Engine::Mv)
+    );
+}
+
+#[test]
+fn json_requires_key() {
+    use rpgm_asset_decrypter_lib::{Engine, Error, encrypted_extension_for};
+    use std::convert::TryFrom;
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    let plain = b"{\"gameTitle\":\"Test\",\"locale\":\"en_US\"}".to_vec();
+    let mut encrypter = Decrypter::new();
+    encrypter.set_key_from_str(KEY).unwrap();
+    let encrypted = encrypter.encrypt(&plain).unwrap();
+
+    assert!(matches!(
+        Decrypter::new().decrypt(&encrypted, FileType::JSON),
+        Err(Error::KeyNotSet)
+    ));
+
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_str(KEY).unwrap();
+    assert_eq!(
+        decrypter.decrypt(&encrypted, FileType::JSON).unwrap(),
+        plain
+    );
+
+    assert_eq!(FileType::try_from("rpgmvj"), Ok(FileType::JSON));
+    assert_eq!(FileType::try_from("json_"), Ok(FileType::JSON));
+    assert_eq!(FileType::JSON.to_string(), "json");
+    assert_eq!(
+        encrypted_extension_for(FileType::JSON, Engine::Mv),
+        "rpgmvj"
+    );
+}
+
+#[test]
+fn is_encrypted() {
+    use rpgm_asset_decrypter_lib::{is_encrypted_data, is_encrypted_path};
+    use std::{env::temp_dir, path::Path};
+
+    let data = read(MV_PNG_DEFAULT).unwrap();
+    assert!(is_encrypted_data(&data));
+    assert!(!is_encrypted_data(&data[..HEADER_LENGTH - 1]));
+    assert!(!is_encrypted_data(b"\x89PNG\r\n\x1a\n"));
+
+    assert!(is_encrypted_path(Path::new(MV_PNG_DEFAULT)));
+    assert!(is_encrypted_path(Path::new(MZ_OGG_ABOBA)));
+    assert!(!is_encrypted_path(Path::new(
+        "./tests/assets/mz/missing.png_"
+    )));
+
+    // Encrypted data with a plain extension isn't an encrypted asset.
+    let renamed = temp_dir().join("rpgm-asset-decrypter-is-encrypted.png");
+    std::fs::write(&renamed, &data).unwrap();
+    assert!(!is_encrypted_path(&renamed));
+    std::fs::remove_file(renamed).unwrap();
+}
+
+#[test]
+fn clear_key_and_reset() {
+    let mut decrypter = Decrypter::new();
+    decrypter.set_expected_magic([0; HEADER_LENGTH]);
+    decrypter
+        .set_key_from_str("150f15e73422e0a5ba5b59f997fc2350")
+        .unwrap();
+
+    decrypter.clear_key();
+    assert_eq!(decrypter.key(), None);
+    assert_eq!(decrypter.last_header_mask(), None);
+    assert_eq!(decrypter.expected_magic(), Some([0; HEADER_LENGTH]));
+
+    decrypter.reset();
+    assert!(decrypter.expected_magic().unwrap().starts_with(RPGM_HEADER));
+
+    // Stale key of another game isn't used after reset.
+    decrypter
+        .decrypt(&read(MZ_PNG_ABOBA).unwrap(), FileType::PNG)
+        .unwrap();
+    decrypter.reset();
+    let decrypted = decrypter
+        .decrypt(&read(MZ_PNG_DEFAULT).unwrap(), FileType::PNG)
+        .unwrap();
+    assert!(is_valid_png(&decrypted));
+    assert_eq!(decrypter.key(), Some(rpgm_asset_decrypter_lib::DEFAULT_KEY));
+}
+
+#[test]
+fn debug_redacts_keys() {
+    use rpgm_asset_decrypter_lib::EncryptionKey;
+    use std::collections::HashSet;
+
+    let key = "150f15e73422e0a5ba5b59f997fc2350";
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_str(key).unwrap();
+    let parsed: EncryptionKey = key.parse().unwrap();
+
+    for debug in [
+        format!("{decrypter:?}"),
+        format!("{parsed:?}"),
+        format!(
+            "{:?}",
+            rpgm_asset_decrypter_lib::batch::BatchOptions::new()
+                .key(parsed)
+                .key_override("audio/**", parsed)
+        ),
+    ] {
+        assert!(!debug.contains(key), "{}", debug);
+        assert!(debug.contains(&parsed.fingerprint()), "{}", debug);
+    }
+
+    assert_eq!(parsed.fingerprint().len(), 6);
+    assert_ne!(
+        parsed.fingerprint(),
+        rpgm_asset_decrypter_lib::DEFAULT_KEY
+            .parse::<EncryptionKey>()
+            .unwrap()
+            .fingerprint()
+    );
+
+    assert_eq!(decrypter.clone(), decrypter);
+    assert_ne!(decrypter, Decrypter::new());
+
+    let types: HashSet<_> = vec![FileType::PNG, FileType::OGG, FileType::PNG]
+        .into_iter()
+        .collect();
+    assert_eq!(types.len(), 2);
+}
+
+#[test]
+fn malformed_inputs() {
+    use rpgm_asset_decrypter_lib::{Error, inspect, verify::validate};
+
+    let file_types = [
+        FileType::PNG,
+        FileType::OGG,
+        FileType::M4A,
+        FileType::WEBP,
+        FileType::WAV,
+    ];
+
+    // Truncated and garbage inputs must fail gracefully, never panic.
+    for path in [MZ_OGG_DEFAULT, MZ_M4A_DEFAULT, MZ_PNG_DEFAULT] {
+        let data = read(path).unwrap();
+
+        for length in 0..data.len().min(512) {
+            let truncated = &data[..length];
+            let _ = inspect(truncated);
+
+            for &file_type in &file_types {
+                let _ = Decrypter::new().decrypt(truncated, file_type);
+                let _ = validate(truncated, file_type);
+            }
+        }
+    }
+
+    let mut garbage = RPGM_HEADER.to_vec();
+    garbage.extend((0..=255).cycle().take(300).map(|byte: u8| !byte));
+
+    for &file_type in &file_types {
+        let _ = Decrypter::new().decrypt(&garbage, file_type);
+        let _ = validate(&garbage[HEADER_LENGTH..], file_type);
+    }
+
+    let truncated_ogg = &read(MZ_OGG_DEFAULT).unwrap()[..HEADER_LENGTH + 40];
+    assert!(matches!(
+        Decrypter::new().decrypt(truncated_ogg, FileType::OGG),
+        Err(Error::UnexpectedEOF)
+    ));
+}
+
+#[test]
+fn m4a_brand() {
+    use rpgm_asset_decrypter_lib::{Encrypter, EncryptionKey, inspect};
+
+    let key: EncryptionKey =
+        "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+
+    // Header written by Apple tools, unlike the FFmpeg one of test assets.
+    let mut m4a = b"\0\0\0\x20ftypM4A \0\0\0\0M4A mp42isom\0\0\0\0".to_vec();
+    m4a.extend(b"\0\0\0\x08free\0\0\0\x10mdat");
+    m4a.extend([0xAB; 64].iter());
+
+    let encrypted = Encrypter::with_key(key).encrypt(&m4a);
+    let mut decrypter = Decrypter::new();
+
+    assert_eq!(decrypter.decrypt(&encrypted, FileType::M4A).unwrap(), m4a);
+    assert_eq!(decrypter.encryption_key(), Some(key));
+    assert_eq!(inspect(&encrypted).unwrap().brand, Some(*b"M4A "));
+    assert_eq!(inspect(&m4a).unwrap().brand, Some(*b"M4A "));
+
+    // Box size is determined by the first box after `ftyp`.
+    let decrypted = Decrypter::new()
+        .decrypt(&read(MZ_M4A_DEFAULT).unwrap(), FileType::M4A)
+        .unwrap();
+    assert_eq!(&decrypted[..16], b"\0\0\0\x1cftypM4A \0\0\x02\0");
+}
+
+#[test]
+fn ogg_crc() {
+    use rpgm_asset_decrypter_lib::verify::validate;
+
+    for path in [MZ_OGG_DEFAULT, MZ_OGG_ABOBA, MV_OGG_DEFAULT, MV_OGG_ABOBA] {
+        let decrypted = Decrypter::new()
+            .decrypt(&read(path).unwrap(), FileType::OGG)
+            .unwrap();
+        assert!(validate(&decrypted, FileType::OGG).is_valid());
+    }
+
+    // Serial number is recovered from the first page alone.
+    let encrypted = read(MZ_OGG_DEFAULT).unwrap();
+    let first_page_end = HEADER_LENGTH
+        + 27
+        + encrypted[HEADER_LENGTH + 26] as usize
+        + encrypted[HEADER_LENGTH + 27
+            ..HEADER_LENGTH + 27 + encrypted[HEADER_LENGTH + 26] as usize]
+            .iter()
+            .map(|&size| size as usize)
+            .sum::<usize>();
+
+    let mut decrypter = Decrypter::new();
+    decrypter
+        .set_key_from_file(&encrypted[..first_page_end], FileType::OGG)
+        .unwrap();
+    assert_eq!(decrypter.key(), Some(rpgm_asset_decrypter_lib::DEFAULT_KEY));
+}
+
+#[test]
+fn xor_header() {
+    use rpgm_asset_decrypter_lib::{EncryptionKey, xor_header};
+
+    let data = read(MZ_PNG_ABOBA).unwrap();
+    let key: EncryptionKey =
+        "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+
+    let mut block = [0; HEADER_LENGTH];
+    block.copy_from_slice(&data[HEADER_LENGTH..HEADER_LENGTH * 2]);
+    xor_header(&key, &mut block);
+
+    let decrypted = Decrypter::new().decrypt(&data, FileType::PNG).unwrap();
+    assert_eq!(block, decrypted[..HEADER_LENGTH]);
+
+    xor_header(&key, &mut block);
+    assert_eq!(block, data[HEADER_LENGTH..HEADER_LENGTH * 2]);
+}
+
+#[test]
+fn expected_magic() {
+    use rpgm_asset_decrypter_lib::Error;
+
+    const MAGIC: [u8; HEADER_LENGTH] = *b"PGMKR\0\0\0\0\x03\x01\0\0\0\0\0";
+
+    let mut encrypted = read(MZ_PNG_ABOBA).unwrap();
+    encrypted[..HEADER_LENGTH].copy_from_slice(&MAGIC);
+
+    assert!(matches!(
+        Decrypter::new().decrypt(&encrypted, FileType::PNG),
+        Err(Error::InvalidMagic { got: MAGIC, .. })
+    ));
+
+    let mut decrypter = Decrypter::new();
+    decrypter.set_expected_magic(MAGIC);
+    assert_eq!(decrypter.expected_magic(), Some(MAGIC));
+
+    let decrypted = decrypter.decrypt(&encrypted, FileType::PNG).unwrap();
+    assert!(is_valid_png(&decrypted));
+    assert_eq!(decrypter.encrypt(&decrypted).unwrap(), encrypted);
+    assert!(matches!(
+        decrypter.decrypt(&read(MZ_PNG_DEFAULT).unwrap(), FileType::PNG),
+        Err(Error::InvalidMagic { .. })
+    ));
+
+    let mut decrypter = Decrypter::new();
+    decrypter.accept_any_magic();
+    assert_eq!(decrypter.expected_magic(), None);
+    assert!(is_valid_png(
+        &decrypter.decrypt(&encrypted, FileType::PNG).unwrap()
+    ));
+    assert!(matches!(
+        decrypter.decrypt(&decrypted, FileType::PNG),
+        Err(Error::AlreadyDecrypted(FileType::PNG))
+    ));
+}
+
+#[test]
+fn variant_cipher() {
+    use rpgm_asset_decrypter_lib::{
+        Error,
+        variant::{Variant, VariantCipher},
+    };
+
+    let encrypted = read(MZ_PNG_ABOBA).unwrap();
+    let standard = VariantCipher::from_hex_str(
+        Variant::Standard16,
+        "150f15e73422e0a5ba5b59f997fc2350",
+    )
+    .unwrap();
+
+    let plain = standard.decrypt(&encrypted).unwrap();
+    assert_eq!(
+        plain,
+        Decrypter::new().decrypt(&encrypted, FileType::PNG).unwrap()
+    );
+    assert_eq!(standard.encrypt(&plain), encrypted);
+
+    let extended =
+        VariantCipher::new(Variant::Extended32, &[0xAB; 32]).unwrap();
+    let encrypted = extended.encrypt(&plain);
+
+    assert_eq!(encrypted.len(), plain.len() + 32);
+    assert!(encrypted.starts_with(RPGM_HEADER));
+    assert_ne!(&encrypted[32..64], &plain[..32]);
+    assert_eq!(&encrypted[64..], &plain[32..]);
+    assert_eq!(extended.decrypt(&encrypted).unwrap(), plain);
+    assert!(matches!(
+        extended.decrypt(&plain),
+        Err(Error::AlreadyDecrypted(FileType::PNG))
+    ));
+
+    let custom = Variant::Custom {
+        header_length: 8,
+        key_length: 4,
+    };
+    assert!(matches!(
+        VariantCipher::new(custom, &[1; 16]),
+        Err(Error::InvalidKeyLength)
+    ));
+    assert_eq!(
+        VariantCipher::from_hex_str(custom, "01020304")
+            .unwrap()
+            .key(),
+        &[1, 2, 3, 4]
+    );
+}
+
+#[test]
+fn verify() {
+    use rpgm_asset_decrypter_lib::{
+        EncryptionKey,
+        verify::{ValidationResult, validate},
+    };
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let encrypted = read(case.path).unwrap();
+        let plain = Decrypter::new()
+            .decrypt(&encrypted, case.file_type)
+            .unwrap();
+        assert_eq!(validate(&plain, case.file_type), ValidationResult::Valid);
+
+        let mut decrypter = Decrypter::new();
+        decrypter.set_key(EncryptionKey::from_bytes([0xAB; 16]));
+        let mut garbage = plain.clone();
+        garbage[..HEADER_LENGTH].copy_from_slice(
+            &decrypter
+                .decrypt_header_only(&encrypted, case.file_type)
+                .unwrap(),
+        );
+        assert!(!validate(&garbage, case.file_type).is_valid());
+    }
+
+    let mut plain = Decrypter::new()
+        .decrypt(&read(MV_PNG_ABOBA).unwrap(), FileType::PNG)
+        .unwrap();
+    let last = plain.len() - 20;
+    plain[last] ^= 0xFF;
+    assert!(matches!(
+        validate(&plain, FileType::PNG),
+        ValidationResult::Invalid { .. }
+    ));
+
+    // Fixture is padded with garbage after VP8L chunk, so cut it off.
+    let mut webp = webp_fixture(b'L');
+    webp.truncate(120);
+    webp[4..8].copy_from_slice(&112u32.to_le_bytes());
+    assert_eq!(validate(&webp, FileType::WEBP), ValidationResult::Valid);
+    assert!(!validate(&webp_fixture(b'L'), FileType::WEBP).is_valid());
+    assert_eq!(
+        validate(&plain, FileType::Other([0; 16])),
+        ValidationResult::Unsupported
+    );
+}
+
+#[test]
+fn key_mismatch() {
+    use rpgm_asset_decrypter_lib::{EncryptionKey, Error};
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let mut encrypted = read(case.path).unwrap();
+        let key = Decrypter::new()
+            .set_key_from_file(&encrypted, case.file_type)
+            .unwrap()
+            .to_owned();
+
+        let mut decrypter = Decrypter::new();
+        decrypter.set_key_from_str(&key).unwrap();
+        let plain = decrypter.decrypt(&encrypted, case.file_type).unwrap();
+        assert!((case.validator)(&plain));
+
+        decrypter.set_key(EncryptionKey::from_bytes([0xAB; 16]));
+        let original = encrypted.clone();
+        let err = decrypter
+            .decrypt_in_place(&mut encrypted, case.file_type)
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::KeyMismatch { got, .. } if got[..] != plain[..HEADER_LENGTH])
+        );
+        assert_eq!(encrypted, original);
+    }
+
+    // OGG content shipped as PNG is reported too.
+    let ogg = read(MV_OGG_ABOBA).unwrap();
+    let mut decrypter = Decrypter::new();
+    decrypter.set_key_from_file(&ogg, FileType::OGG).unwrap();
+    assert!(matches!(
+        decrypter.decrypt(&ogg, FileType::PNG),
+        Err(Error::KeyMismatch { .. })
+    ));
+}
+
+#[test]
+fn inspect() {
+    use rpgm_asset_decrypter_lib::{AssetState, inspect};
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let encrypted = read(case.path).unwrap();
+        let mut decrypter = Decrypter::new();
+        let plain = decrypter.decrypt(&encrypted, case.file_type).unwrap();
+
+        let info = inspect(&encrypted).unwrap();
+        assert_eq!(info.state, AssetState::Encrypted);
+        assert_eq!(info.file_type, Some(case.file_type));
+        assert_eq!(info.key, decrypter.encryption_key());
+        assert_eq!(info.payload_length, plain.len());
+
+        let plain_info = inspect(&plain).unwrap();
+        assert_eq!(plain_info.state, AssetState::Decrypted(case.file_type));
+        assert_eq!(plain_info.key, None);
+        assert_eq!(plain_info.dimensions, info.dimensions);
+        assert_eq!(plain_info.serial_number, info.serial_number);
+
+        if case.file_type.is_png() {
+            let (width, height) = info.dimensions.unwrap();
+            assert_eq!(&plain[16..20], &width.to_be_bytes());
+            assert_eq!(&plain[20..24], &height.to_be_bytes());
+        } else if case.file_type.is_ogg() {
+            assert_eq!(
+                &plain[14..18],
+                &info.serial_number.unwrap().to_le_bytes()
+            );
+        }
+    }
+
+    assert_eq!(inspect(b"garbage").unwrap().state, AssetState::Unknown);
+    assert!(inspect(RPGM_HEADER).is_err());
+}
+
+#[test]
+fn encrypt_options() {
+    use rpgm_asset_decrypter_lib::{EncryptOptions, Error};
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let encrypted = read(case.path).unwrap();
+        let mut decrypter = Decrypter::new();
+        let plain = decrypter.decrypt(&encrypted, case.file_type).unwrap();
+
+        let default = EncryptOptions::new();
+        assert_eq!(
+            decrypter.encrypt_with_options(&plain, &default).unwrap(),
+            encrypted
+        );
+
+        let options = EncryptOptions::new()
+            .custom_magic(*b"NOT AN RPGM FILE")
+            .fake_signature(*b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        let obfuscated =
+            decrypter.encrypt_with_options(&plain, &options).unwrap();
+        assert!(obfuscated.starts_with(b"NOT AN RPGM FILE"));
+
+        assert!(matches!(
+            Decrypter::new().decrypt(&obfuscated, case.file_type),
+            Err(Error::InvalidMagic { got, .. }) if &got == b"NOT AN RPGM FILE"
+        ));
+        // Without stripping the fake signature, the derived key is wrong.
+        let unstripped = Decrypter::new()
+            .decrypt_with_options(
+                &obfuscated,
+                case.file_type,
+                &EncryptOptions::new().custom_magic(*b"NOT AN RPGM FILE"),
+            )
+            .unwrap_or_default();
+        assert_ne!(unstripped, plain);
+
+        let decrypted = Decrypter::new()
+            .decrypt_with_options(&obfuscated, case.file_type, &options)
+            .unwrap();
+        assert_eq!(decrypted, plain);
+    }
+}
+
+#[test]
+fn encrypter() {
+    use rpgm_asset_decrypter_lib::{EncryptOptions, Encrypter, Engine};
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let encrypted = read(case.path).unwrap();
+        let mut decrypter = Decrypter::new();
+        let plain = decrypter.decrypt(&encrypted, case.file_type).unwrap();
+        let key = decrypter.encryption_key().unwrap();
+
+        let encrypter = Encrypter::with_key(key);
+        assert_eq!(encrypter.key(), key);
+        assert_eq!(encrypter.encrypt(&plain), encrypted);
+
+        let mut in_place = plain.clone();
+        encrypter.encrypt_in_place(&mut in_place);
+        assert_eq!(in_place, &encrypted[HEADER_LENGTH..]);
+
+        let options = EncryptOptions::new().custom_magic([0xFF; 16]);
+        assert_eq!(
+            encrypter.options(options).encrypt(&plain),
+            decrypter.encrypt_with_options(&plain, &options).unwrap()
+        );
+    }
+
+    let key = "d41d8cd98f00b204e9800998ecf8427e".parse().unwrap();
+    let encrypter = Encrypter::with_key(key);
+    assert_eq!(encrypter.encrypted_extension(FileType::OGG), "rpgmvo");
+    assert_eq!(
+        encrypter
+            .engine(Engine::Mz)
+            .encrypted_extension(FileType::OGG),
+        "ogg_"
+    );
+}
+
+#[test]
+fn original_header_roundtrip() {
+    use rpgm_asset_decrypter_lib::{
+        decrypt_with_original_header, encrypt_with_original_header,
+    };
+
+    for case in ogg_cases().iter().chain(&m4a_cases()).chain(&png_cases()) {
+        let encrypted = read(case.path).unwrap();
+        let (plain, original_header) =
+            decrypt_with_original_header(&encrypted, case.file_type).unwrap();
+        assert!((case.validator)(&plain));
+        assert_eq!(
+            &original_header,
+            &encrypted[HEADER_LENGTH..HEADER_LENGTH * 2]
+        );
+
+        assert_eq!(
+            encrypt_with_original_header(&plain, &original_header),
+            encrypted
+        );
+
+        let mut modified = plain.clone();
+        let last = modified.len() - 1;
+        modified[last] ^= 0xFF;
+
+        let reencrypted =
+            encrypt_with_original_header(&modified, &original_header);
+        assert_eq!(
+            &reencrypted[..last + HEADER_LENGTH],
+            &encrypted[..last + HEADER_LENGTH]
+        );
+        assert_ne!(reencrypted, encrypted);
+    }
+}
+
+#[test]
+fn last_header_mask() {
+    let encrypted = read(MZ_OGG_ABOBA).unwrap();
+    let mask = &encrypted[HEADER_LENGTH..HEADER_LENGTH * 2];
+
+    let mut decrypter = Decrypter::new();
+    assert_eq!(decrypter.last_header_mask(), None);
+
+    decrypter.decrypt(&encrypted, FileType::OGG).unwrap();
+    assert_eq!(&decrypter.last_header_mask().unwrap(), mask);
+
+    let mut decrypter = Decrypter::new();
+    decrypter
+        .decrypt_in_place(&mut encrypted.clone(), FileType::OGG)
+        .unwrap();
+    assert_eq!(&decrypter.last_header_mask().unwrap(), mask);
+
+    let mut decrypter = Decrypter::new();
+    DecryptReader::new(encrypted.as_slice(), &mut decrypter, FileType::OGG)
+        .unwrap();
+    assert_eq!(&decrypter.last_header_mask().unwrap(), mask);
+
+    // Failed decryption keeps the mask of the last successful one.
+    assert!(decrypter.decrypt(&encrypted[..8], FileType::OGG).is_err());
+    assert_eq!(&decrypter.last_header_mask().unwrap(), mask);
+}
+
+#[test]
+fn decrypt_auto_renamed() {
+    use rpgm_asset_decrypter_lib::{Error, decrypt_auto};
+
+    // OGG renamed to `.png_`.
+    let encrypted = read(MZ_OGG_ABOBA).unwrap();
+    let decrypted = decrypt_auto(&encrypted, Some(FileType::PNG)).unwrap();
+    assert_eq!(decrypted.file_type, FileType::OGG);
+    assert!(is_valid_ogg(&decrypted.data));
+    assert!(matches!(
+        decrypted.warning,
+        Some(Error::TypeMismatch {
+            declared: FileType::PNG,
+            detected: FileType::OGG
+        })
+    ));
+
+    for case in m4a_cases().iter().chain(&png_cases()) {
+        let mut decrypter = Decrypter::new();
+        let decrypted = decrypter
+            .decrypt_auto(&read(case.path).unwrap(), Some(case.file_type))
+            .unwrap();
+        assert_eq!(decrypted.file_type, case.file_type);
+        assert!((case.validator)(&decrypted.data));
+        assert!(decrypted.warning.is_none());
+        assert!(decrypter.key().is_some());
+    }
+
+    // Key set beforehand is kept.
+    let mut decrypter = Decrypter::new();
+    decrypter
+        .set_key_from_str("150f15e73422e0a5ba5b59f997fc2350")
+        .unwrap();
+    let decrypted = decrypter.decrypt_auto(&encrypted, None).unwrap();
+    assert_eq!(decrypted.file_type, FileType::OGG);
+    assert!(decrypted.warning.is_none());
+}
+
+#[test]
+fn error_context() {
+    use rpgm_asset_decrypter_lib::Error;
+    use std::io;
+
+    let data = read(MV_OGG_ABOBA).unwrap();
+
+    let mut garbage = data.clone();
+    garbage[..4].copy_from_slice(b"RIFF");
+    let err = Decrypter::new()
+        .decrypt(&garbage, FileType::OGG)
+        .unwrap_err();
+    assert!(
+        matches!(&err, Error::InvalidMagic { expected, got } if expected.as_slice() == RPGM_HEADER && got[..4] == *b"RIFF")
+    );
+    assert_eq!(err.offset(), Some(0));
+    assert!(!err.is_recoverable());
+
+    // Capture pattern of the second page is past the first page, which is at least 27 + 1 bytes long.
+    let second_page = (HEADER_LENGTH + 28..data.len() - 4)
+        .find(|&i| &data[i..i + 4] == b"OggS")
+        .unwrap();
+    let mut corrupted = data.clone();
+    corrupted[second_page] = b'X';
+    // With intact CRC of the first page, the second one isn't read at all.
+    corrupted[HEADER_LENGTH + 22] ^= 0xFF;
+    let err = Decrypter::new()
+        .set_key_from_file(&corrupted, FileType::OGG)
+        .unwrap_err();
+    assert!(matches!(err, Error::Malformed { .. }));
+    assert_eq!(err.offset(), Some(second_page));
+    assert!(!err.is_recoverable());
+
+    let mut decrypter = Decrypter::new();
+    decrypter
+        .set_key_from_file(&read(MV_OGG_DEFAULT).unwrap(), FileType::OGG)
+        .unwrap();
+    let err = decrypter.decrypt(&data, FileType::OGG).unwrap_err();
+    assert!(matches!(err, Error::KeyMismatch { .. }));
+    assert_eq!(err.offset(), Some(HEADER_LENGTH));
+    assert!(err.is_recoverable());
+
+    assert!(Error::Timeout.is_recoverable());
+    assert!(
+        Error::from(io::Error::from(io::ErrorKind::Interrupted))
+            .is_recoverable()
+    );
+    assert!(
+        !Error::from(io::Error::from(io::ErrorKind::NotFound)).is_recoverable()
+    );
+}
+
+mod path {
+    use rpgm_asset_decrypter_lib::{
+        Engine, FileType,
+        path::{
+            OutputNaming, decrypted_name, decrypted_path, encrypted_name,
+            matches_glob,
+        },
+    };
+    use std::path::Path;
+
+    #[test]
+    fn glob_patterns() {
+        let path = Path::new("img/pictures/Actor1_face.png_");
+
+        for pattern in [
+            "img/pictures/**",
+            "img/pictures",
+            "img/**/*.png_",
+            "**/Actor?_*",
+            "./img/*/*face.png_",
+            "**",
+        ] {
+            assert!(matches_glob(pattern, path), "{}", pattern);
+        }
+
+        for pattern in ["img/*.png_", "audio/**", "img/pic", "**/*.ogg_"] {
+            assert!(!matches_glob(pattern, path), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn decrypted_path_naming() {
+        let input = Path::new("img/pictures/image.rpgmvp");
+
+        assert_eq!(
+            decrypted_path(
+                input,
+                FileType::PNG,
+                &OutputNaming::ReplaceExtension
+            ),
+            Path::new("img/pictures/image.png")
+        );
+        assert_eq!(
+            decrypted_path(
+                input,
+                FileType::PNG,
+                &OutputNaming::Suffix("dec".to_owned())
+            ),
+            Path::new("img/pictures/image.dec.png")
+        );
+        assert_eq!(
+            decrypted_path(
+                input,
+                FileType::PNG,
+                &OutputNaming::SiblingDir("decrypted".to_owned())
+            ),
+            Path::new("img/pictures/decrypted/image.png")
+        );
+    }
+
+    #[test]
+    fn asset_names() {
+        for (encrypted, engine, decrypted) in [
+            (
+                "img/pictures/image.rpgmvp",
+                Engine::Mv,
+                "img/pictures/image.png",
+            ),
+            (
+                "img/pictures/image.png_",
+                Engine::Mz,
+                "img/pictures/image.png",
+            ),
+            ("audio/bgm/Theme.rpgmvo", Engine::Mv, "audio/bgm/Theme.ogg"),
+            ("audio/se/Jump.m4a_", Engine::Mz, "audio/se/Jump.m4a"),
+            ("data/Map001.json_", Engine::Mz, "data/Map001.json"),
+            ("Sound.rpgmvwav", Engine::Mv, "Sound.wav"),
+            ("blob.bin_", Engine::Mz, "blob.bin"),
+            ("img/顔 グラ.v2.webp_", Engine::Mz, "img/顔 グラ.v2.webp"),
+            ("image.png.rpgmvp", Engine::Mv, "image.png.png"),
+        ] {
+            assert_eq!(
+                decrypted_name(Path::new(encrypted)),
+                Path::new(decrypted)
+            );
+            assert_eq!(
+                encrypted_name(Path::new(decrypted), engine),
+                Path::new(encrypted)
+            );
+        }
+
+        // Extensions are matched regardless of case.
+        assert_eq!(
+            decrypted_name(Path::new("IMAGE.RPGMVP")),
+            Path::new("IMAGE.png")
+        );
+        assert_eq!(
+            encrypted_name(Path::new("IMAGE.PNG"), Engine::Mz),
+            Path::new("IMAGE.png_")
+        );
+
+        // Names that can't be mapped are kept.
+        for name in ["readme.txt", "image", ".rpgmvp", "image.", "a.rpgmvp/b"] {
+            assert_eq!(decrypted_name(Path::new(name)), Path::new(name));
+            assert_eq!(
+                encrypted_name(Path::new(name), Engine::Mv),
+                Path::new(name)
+            );
+        }
+        assert_eq!(
+            encrypted_name(Path::new("image.rpgmvp"), Engine::Mz),
+            Path::new("image.rpgmvp")
+        );
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+            let name = PathBuf::from(OsStr::from_bytes(b"\xff\xfe.ogg_"));
+            assert_eq!(
+                decrypted_name(&name).as_os_str().as_bytes(),
+                b"\xff\xfe.ogg"
+            );
+        }
+    }
+}
+
+mod batch {
+    use super::{is_valid_m4a, is_valid_ogg, is_valid_png};
+    use rpgm_asset_decrypter_lib::{
+        DEFAULT_KEY, Encrypter, EncryptionKey, Engine, Error, FileType,
+        batch::{
+            BatchOptions, CollisionPolicy, FileStatus, MismatchPolicy,
+            OutputPolicy, ProgressEvent, RepackOptions, TypeCheck, check_type,
+            decrypt_dir, decrypt_dir_threads, decrypt_dir_with_progress,
+            encrypt_dir,
+        },
+        path::OutputNaming,
+    };
+    use std::{env::temp_dir, fs, sync::Mutex};
+
+    const PNG_BLOCK: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+
+    #[test]
+    fn scoped_threads() {
+        let input = temp_dir().join("rpgm-asset-decrypter-scoped-threads");
+        let output = temp_dir().join("rpgm-asset-decrypter-scoped-threads-out");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+        fs::create_dir_all(&input).unwrap();
+
+        for i in 0..12 {
+            let (source, ext) = match i % 3 {
+                0 => (super::MV_PNG_ABOBA, "rpgmvp"),
+                1 => (super::MV_OGG_ABOBA, "rpgmvo"),
+                _ => (super::MZ_M4A_ABOBA, "m4a_"),
+            };
+            fs::copy(source, input.join(format!("{:02}.{}", i, ext))).unwrap();
+        }
+        fs::write(input.join("broken.rpgmvp"), b"aboba").unwrap();
+
+        let sequential =
+            decrypt_dir(&input, &output, &BatchOptions::new()).unwrap();
+
+        for &threads in &[0, 1, 3, 64] {
+            let _ = fs::remove_dir_all(&output);
+            let report = decrypt_dir_threads(
+                &input,
+                &output,
+                &BatchOptions::new(),
+                threads,
+            )
+            .unwrap();
+
+            assert_eq!(report.key, sequential.key);
+            assert_eq!(report.files.len(), 13);
+            assert_eq!(report.failed().count(), 1);
+            assert!(
+                report
+                    .files
+                    .iter()
+                    .zip(&sequential.files)
+                    .all(|(file, expected)| file.input == expected.input
+                        && file.output == expected.output),
+                "{}",
+                threads
+            );
+
+            let decrypted = fs::read(output.join("03.png")).unwrap();
+            assert!(is_valid_png(&decrypted));
+        }
+
+        fs::remove_dir_all(&input).unwrap();
+        fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn www_layout() {
+        const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+        let root = temp_dir().join("rpgm-asset-decrypter-www-layout");
+        let output = temp_dir().join("rpgm-asset-decrypter-www-layout-out");
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::remove_dir_all(&output);
+
+        let www = root.join("www");
+        fs::create_dir_all(www.join("data")).unwrap();
+        fs::create_dir_all(www.join("img/pictures")).unwrap();
+        fs::create_dir_all(www.join("audio/bgm")).unwrap();
+        fs::write(
+            www.join("data/System.json"),
+            format!(
+                r#"{{"hasEncryptedImages":true,"encryptionKey":"{}"}}"#,
+                KEY
+            ),
+        )
+        .unwrap();
+        fs::copy(super::MV_PNG_ABOBA, www.join("img/pictures/a.rpgmvp"))
+            .unwrap();
+        fs::copy(super::MV_OGG_ABOBA, www.join("audio/bgm/b.rpgmvo")).unwrap();
+
+        // JSON has no known plaintext, so it only decrypts with the key of System.json.
+        let encrypter =
+            Encrypter::with_key(EncryptionKey::from_hex_str(KEY).unwrap());
+        let map = br#"{"events":[null,null,null]}"#;
+        fs::write(www.join("data/Map001.rpgmvj"), encrypter.encrypt(map))
+            .unwrap();
+
+        let report = decrypt_dir(
+            &root,
+            &output,
+            &BatchOptions::new().include(["img/**", "data/**"]),
+        )
+        .unwrap();
+
+        assert_eq!(report.key, Some(KEY.parse().unwrap()));
+        assert_eq!(report.files.len(), 2);
+        assert!(
+            report
+                .files
+                .iter()
+                .all(|file| matches!(file.status, FileStatus::Succeeded))
+        );
+        assert_eq!(fs::read(output.join("www/data/Map001.json")).unwrap(), map);
+        assert!(is_valid_png(
+            &fs::read(output.join("www/img/pictures/a.png")).unwrap()
+        ));
+
+        // Pointing at a subfolder still finds System.json.
+        fs::remove_dir_all(&output).unwrap();
+        let report =
+            decrypt_dir(&www.join("data"), &output, &BatchOptions::new())
+                .unwrap();
+        assert_eq!(report.key, Some(KEY.parse().unwrap()));
+        assert_eq!(fs::read(output.join("Map001.json")).unwrap(), map);
+
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[test]
+    fn check_type_policies() {
+        assert_eq!(
+            check_type(PNG_BLOCK, FileType::PNG, MismatchPolicy::Error)
+                .unwrap(),
+            TypeCheck::Match
+        );
+        assert!(matches!(
+            check_type(PNG_BLOCK, FileType::OGG, MismatchPolicy::Error),
+            Err(Error::TypeMismatch {
+                declared: FileType::OGG,
+                detected: FileType::PNG
+            })
+        ));
+
+        let trusted =
+            check_type(PNG_BLOCK, FileType::OGG, MismatchPolicy::TrustContent)
+                .unwrap();
+        assert_eq!(trusted.output_type(FileType::OGG), Some(FileType::PNG));
+
+        let skipped =
+            check_type(PNG_BLOCK, FileType::OGG, MismatchPolicy::Skip).unwrap();
+        assert_eq!(skipped.output_type(FileType::OGG), None);
+    }
+
+    #[test]
+    fn preserve_metadata() {
+        use std::time::{Duration, SystemTime};
+
+        let input = temp_dir().join("rpgm-asset-decrypter-metadata-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-metadata-output");
+        let repacked = temp_dir().join("rpgm-asset-decrypter-metadata-repack");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+        let _ = fs::remove_dir_all(&repacked);
+
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(super::MZ_PNG_DEFAULT, input.join("a.png_")).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(input.join("a.png_"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(
+                input.join("a.png_"),
+                fs::Permissions::from_mode(0o640),
+            )
+            .unwrap();
+        }
+
+        let report = decrypt_dir(
+            &input,
+            &output,
+            &BatchOptions::new().preserve_metadata(true),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 1);
+
+        let metadata = fs::metadata(output.join("a.png")).unwrap();
+        assert_eq!(metadata.modified().unwrap(), mtime);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        }
+
+        let report = encrypt_dir(
+            &output,
+            &repacked,
+            &RepackOptions::new()
+                .key(DEFAULT_KEY.parse().unwrap())
+                .engine(Engine::Mz)
+                .preserve_metadata(true),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 1);
+        assert_eq!(
+            fs::metadata(repacked.join("a.png_"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            mtime
+        );
+    }
+
+    #[test]
+    fn decrypt_dir_include_exclude() {
+        let input = temp_dir().join("rpgm-asset-decrypter-filter-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-filter-output");
+        let _ = fs::remove_dir_all(&input);
+
+        for dir in ["img/pictures", "img/faces", "audio/bgm", "audio/se"] {
+            fs::create_dir_all(input.join(dir)).unwrap();
+        }
+
+        fs::copy(super::MZ_PNG_DEFAULT, input.join("img/pictures/a.png_"))
+            .unwrap();
+        fs::copy(super::MZ_PNG_DEFAULT, input.join("img/faces/b.png_"))
+            .unwrap();
+        fs::copy(super::MZ_OGG_DEFAULT, input.join("audio/bgm/c.ogg_"))
+            .unwrap();
+        fs::copy(super::MZ_OGG_DEFAULT, input.join("audio/se/d.ogg_")).unwrap();
+
+        let inputs = |options: &BatchOptions| {
+            let _ = fs::remove_dir_all(&output);
+            decrypt_dir(&input, &output, options)
+                .unwrap()
+                .succeeded()
+                .map(|file| {
+                    file.input.strip_prefix(&input).unwrap().to_path_buf()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            inputs(&BatchOptions::new().include(["img/pictures/**"])),
+            ["img/pictures/a.png_"].map(std::path::PathBuf::from)
+        );
+        assert_eq!(
+            inputs(&BatchOptions::new().exclude(["audio/bgm"])),
+            ["audio/se/d.ogg_", "img/faces/b.png_", "img/pictures/a.png_"]
+                .map(std::path::PathBuf::from)
+        );
+        assert_eq!(
+            inputs(
+                &BatchOptions::new()
+                    .include(["audio/**", "img/faces/*.png_"])
+                    .exclude(["**/d.*"])
+            ),
+            ["audio/bgm/c.ogg_", "img/faces/b.png_"]
+                .map(std::path::PathBuf::from)
+        );
+        assert!(!output.join("audio/se/d.ogg").exists());
+    }
+
+    #[test]
+    fn decrypt_dir_collisions() {
+        let input = temp_dir().join("rpgm-asset-decrypter-collision-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-collision-output");
+        let _ = fs::remove_dir_all(&input);
+
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(super::MZ_PNG_DEFAULT, input.join("a.png_")).unwrap();
+        fs::copy(super::MZ_OGG_DEFAULT, input.join("b.ogg_")).unwrap();
+
+        let run = |policy: CollisionPolicy| {
+            let _ = fs::remove_dir_all(&output);
+            fs::create_dir_all(&output).unwrap();
+            fs::write(output.join("a.png"), b"old").unwrap();
+
+            decrypt_dir(&input, &output, &BatchOptions::new().collision(policy))
+                .unwrap()
+        };
+
+        let report = run(CollisionPolicy::Skip);
+        assert_eq!(report.succeeded().count(), 1);
+        assert_eq!(report.skipped().count(), 1);
+        assert_eq!(report.collisions().count(), 1);
+        assert_eq!(fs::read(output.join("a.png")).unwrap(), b"old");
+
+        let report = run(CollisionPolicy::Error);
+        assert!(matches!(
+            &report.files[0].status,
+            FileStatus::Failed(err) if matches!(
+                &err.source,
+                Error::Io(err) if err.kind() == std::io::ErrorKind::AlreadyExists
+            )
+        ));
+        assert_eq!(fs::read(output.join("a.png")).unwrap(), b"old");
+
+        let report = run(CollisionPolicy::RenameWithSuffix);
+        assert_eq!(report.succeeded().count(), 2);
+        assert_eq!(report.files[0].output, Some(output.join("a.1.png")));
+        assert!(is_valid_png(&fs::read(output.join("a.1.png")).unwrap()));
+        assert_eq!(fs::read(output.join("a.png")).unwrap(), b"old");
+
+        let report = run(CollisionPolicy::Overwrite);
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(report.files[0].collided);
+        assert!(!report.files[1].collided);
+        assert!(is_valid_png(&fs::read(output.join("a.png")).unwrap()));
+    }
+
+    #[test]
+    fn report_totals() {
+        let input = temp_dir().join("rpgm-asset-decrypter-totals-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-totals-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(super::MZ_PNG_DEFAULT, input.join("a.png_")).unwrap();
+        fs::copy(super::MZ_OGG_DEFAULT, input.join("b.ogg_")).unwrap();
+        fs::write(input.join("c.m4a_"), b"garbage").unwrap();
+
+        let report =
+            decrypt_dir(&input, &output, &BatchOptions::new()).unwrap();
+        let size = |path: &str| fs::metadata(path).unwrap().len();
+
+        assert_eq!(report.succeeded().count(), 2);
+        assert_eq!(report.failed().count(), 1);
+        assert_eq!(
+            report.bytes_in,
+            size(super::MZ_PNG_DEFAULT) + size(super::MZ_OGG_DEFAULT) + 7
+        );
+        assert_eq!(
+            report.bytes_out,
+            report.bytes_in - 7 - 2 * super::HEADER_LENGTH as u64
+        );
+        assert_eq!(report.files[0].bytes_out, size(super::MZ_PNG_DEFAULT) - 16);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&report).unwrap();
+
+            assert_eq!(json["succeeded"], 2);
+            assert_eq!(json["skipped"], 0);
+            assert_eq!(json["bytes_out"], report.bytes_out);
+            assert_eq!(
+                json["failed"][0]["path"],
+                input.join("c.m4a_").to_str().unwrap()
+            );
+            assert_eq!(json["files"][2]["status"], "failed");
+            assert!(json["elapsed"].is_f64());
+        }
+    }
+
+    #[test]
+    fn decrypt_dir_per_directory_keys() {
+        const ABOBA_KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+        let input = temp_dir().join("rpgm-asset-decrypter-keys-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-keys-output");
+        let _ = fs::remove_dir_all(&input);
+
+        fs::create_dir_all(input.join("img")).unwrap();
+        fs::create_dir_all(input.join("audio/bgm")).unwrap();
+        fs::copy(super::MZ_PNG_ABOBA, input.join("img/a.png_")).unwrap();
+        fs::copy(super::MZ_OGG_DEFAULT, input.join("audio/bgm/b.ogg_"))
+            .unwrap();
+
+        let succeeded = |options: &BatchOptions| {
+            let _ = fs::remove_dir_all(&output);
+            decrypt_dir(&input, &output, options)
+                .unwrap()
+                .succeeded()
+                .count()
+        };
+
+        // Determined key doesn't match `audio/bgm`, which gets its own.
+        assert_eq!(succeeded(&BatchOptions::new()), 2);
+        assert!(is_valid_ogg(
+            &fs::read(output.join("audio/bgm/b.ogg")).unwrap()
+        ));
+
+        // Explicit key is trusted as is.
+        assert_eq!(
+            succeeded(&BatchOptions::new().key(ABOBA_KEY.parse().unwrap())),
+            1
+        );
+        assert_eq!(
+            succeeded(
+                &BatchOptions::new()
+                    .key(ABOBA_KEY.parse().unwrap())
+                    .key_override("audio/**", DEFAULT_KEY.parse().unwrap())
+            ),
+            2
+        );
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+
+    #[test]
+    fn decrypt_dir_default_key() {
+        let input = temp_dir().join("rpgm-asset-decrypter-batch-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-batch-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+
+        fs::create_dir_all(input.join("img")).unwrap();
+        fs::create_dir_all(input.join("audio")).unwrap();
+        fs::copy(
+            "./tests/assets/mv/test-default.rpgmvp",
+            input.join("img/a.rpgmvp"),
+        )
+        .unwrap();
+        fs::copy(
+            "./tests/assets/mz/test-default.ogg_",
+            input.join("audio/b.ogg_"),
+        )
+        .unwrap();
+        fs::copy(
+            "./tests/assets/mz/test-default.m4a_",
+            input.join("audio/c.m4a_"),
+        )
+        .unwrap();
+
+        let options =
+            BatchOptions::new().naming(OutputNaming::Suffix("dec".to_owned()));
+        let report = decrypt_dir(&input, &output, &options).unwrap();
+
+        assert_eq!(report.key, Some(DEFAULT_KEY.parse().unwrap()));
+        assert_eq!(report.succeeded().count(), 3);
+        assert_eq!(report.files[0].input, input.join("audio/b.ogg_"));
+
+        assert!(is_valid_png(
+            &fs::read(output.join("img/a.dec.png")).unwrap()
+        ));
+        assert!(is_valid_ogg(
+            &fs::read(output.join("audio/b.dec.ogg")).unwrap()
+        ));
+        assert!(is_valid_m4a(
+            &fs::read(output.join("audio/c.dec.m4a")).unwrap()
+        ));
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+
+    #[test]
+    fn decrypt_dir_output_policies() {
+        let input = temp_dir().join("rpgm-asset-decrypter-batch-policy");
+        let tree = temp_dir().join("rpgm-asset-decrypter-batch-policy-tree");
+
+        let setup = || {
+            let _ = fs::remove_dir_all(&input);
+            let _ = fs::remove_dir_all(&tree);
+            fs::create_dir_all(input.join("img")).unwrap();
+            fs::create_dir_all(input.join("audio")).unwrap();
+            fs::copy(
+                "./tests/assets/mv/test-default.rpgmvp",
+                input.join("img/a.rpgmvp"),
+            )
+            .unwrap();
+            // Both decrypt to `img/a.png`.
+            fs::copy(
+                "./tests/assets/mz/test-default.png_",
+                input.join("img/a.png_"),
+            )
+            .unwrap();
+            fs::copy(
+                "./tests/assets/mz/test-default.ogg_",
+                input.join("audio/b.ogg_"),
+            )
+            .unwrap();
+        };
+
+        setup();
+        let report =
+            decrypt_dir(&input, &input, &BatchOptions::new().dry_run(true))
+                .unwrap();
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(report.failed().count(), 1);
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .filter(|file| matches!(file.status, FileStatus::Planned))
+                .count(),
+            2
+        );
+        assert!(!input.join("img/a.png").exists());
+        assert!(!input.join("audio/b.ogg").exists());
+
+        // Dry run plans outputs of the actual policy.
+        setup();
+        let report = decrypt_dir(
+            &input,
+            &input,
+            &BatchOptions::new()
+                .output_policy(OutputPolicy::Overwrite)
+                .dry_run(true),
+        )
+        .unwrap();
+        assert_eq!(report.planned().count(), 2);
+        assert_eq!(
+            report.type_counts(),
+            vec![(FileType::OGG, 1), (FileType::PNG, 1)]
+        );
+        assert_eq!(report.key, Some(DEFAULT_KEY.parse().unwrap()));
+        assert_eq!(report.files[0].output, Some(input.join("audio/b.ogg")));
+        assert!(!input.join("audio/b.ogg").exists());
+        assert!(input.join("audio/b.ogg_").exists());
+
+        setup();
+        let report = decrypt_dir(
+            &input,
+            &input,
+            &BatchOptions::new().output_policy(OutputPolicy::SideBySide {
+                suffix: "dec".into(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(is_valid_png(
+            &fs::read(input.join("img/a.dec.png")).unwrap()
+        ));
+        assert!(is_valid_ogg(
+            &fs::read(input.join("audio/b.dec.ogg")).unwrap()
+        ));
+        assert!(input.join("audio/b.ogg_").exists());
+
+        setup();
+        let report = decrypt_dir(
+            &input,
+            &input,
+            &BatchOptions::new().output_policy(OutputPolicy::SeparateTree {
+                root: tree.clone(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(is_valid_ogg(&fs::read(tree.join("audio/b.ogg")).unwrap()));
+        assert!(!input.join("audio/b.ogg").exists());
+
+        setup();
+        let report = decrypt_dir(
+            &input,
+            &input,
+            &BatchOptions::new().output_policy(OutputPolicy::Overwrite),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(is_valid_ogg(&fs::read(input.join("audio/b.ogg")).unwrap()));
+        assert!(!input.join("audio/b.ogg_").exists());
+        assert!(is_valid_png(&fs::read(input.join("img/a.png")).unwrap()));
+        // The file that lost the collision is left untouched.
+        assert_eq!(
+            usize::from(input.join("img/a.rpgmvp").exists())
+                + usize::from(input.join("img/a.png_").exists()),
+            1
+        );
+
+        fs::remove_dir_all(&input).unwrap();
+        let _ = fs::remove_dir_all(&tree);
+    }
+
+    #[test]
+    fn decrypt_dir_transactional() {
+        let input = temp_dir().join("rpgm-asset-decrypter-transaction");
+        let output = temp_dir().join("rpgm-asset-decrypter-transaction-out");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+
+        fs::create_dir_all(input.join("img")).unwrap();
+        fs::copy(
+            "./tests/assets/mv/test-default.rpgmvp",
+            input.join("img/a.rpgmvp"),
+        )
+        .unwrap();
+        fs::copy("./tests/assets/mz/test-default.ogg_", input.join("b.ogg_"))
+            .unwrap();
+        fs::write(input.join("c.rpgmvp"), b"aboba").unwrap();
+
+        let options = BatchOptions::new()
+            .key(DEFAULT_KEY.parse().unwrap())
+            .transactional(true);
+        let report =
+            decrypt_dir(&input, &output.join("nested"), &options).unwrap();
+        assert_eq!(report.failed().count(), 1);
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .filter(|file| matches!(file.status, FileStatus::RolledBack))
+                .count(),
+            2
+        );
+        assert!(!output.exists());
+
+        fs::remove_file(input.join("c.rpgmvp")).unwrap();
+        let report = decrypt_dir(
+            &input,
+            &input,
+            &options.output_policy(OutputPolicy::Overwrite),
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+        assert!(is_valid_png(&fs::read(input.join("img/a.png")).unwrap()));
+        assert!(is_valid_ogg(&fs::read(input.join("b.ogg")).unwrap()));
+        assert!(!input.join("img/a.rpgmvp").exists());
+
+        let mut names: Vec<_> = fs::read_dir(&input)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["b.ogg", "img"]);
+
+        fs::remove_dir_all(&input).unwrap();
+    }
+
+    #[test]
+    fn decrypt_dir_progress() {
+        let input = temp_dir().join("rpgm-asset-decrypter-progress-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-progress-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(
+            "./tests/assets/mv/test-default.rpgmvp",
+            input.join("a.rpgmvp"),
+        )
+        .unwrap();
+        fs::write(input.join("b.rpgmvp"), b"aboba").unwrap();
+
+        let events = Mutex::new(Vec::new());
+        let report = decrypt_dir_with_progress(
+            &input,
+            &output,
+            &BatchOptions::new(),
+            &|event: ProgressEvent<'_>| {
+                let event = match event {
+                    ProgressEvent::Started { bytes, .. } => ("started", bytes),
+                    ProgressEvent::Succeeded { bytes, .. } => {
+                        ("succeeded", bytes)
+                    }
+                    ProgressEvent::Skipped { .. } => ("skipped", 0),
+                    ProgressEvent::Failed { .. } => ("failed", 0),
+                };
+                events.lock().unwrap().push(event);
+            },
+        )
+        .unwrap();
+
+        let mut events = events.into_inner().unwrap();
+        events.sort_unstable();
+
+        let size = fs::metadata(input.join("a.rpgmvp")).unwrap().len();
+        assert_eq!(
+            events,
+            vec![
+                ("failed", 0),
+                ("started", 5),
+                ("started", size),
+                ("succeeded", size - 16),
+            ]
+        );
+        assert_eq!(report.failed().count(), 1);
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+
+    #[test]
+    fn encrypt_dir_repack() {
+        let input = temp_dir().join("rpgm-asset-decrypter-repack-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-repack-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+
+        fs::create_dir_all(input.join("img")).unwrap();
+        fs::create_dir_all(output.join("data")).unwrap();
+        fs::copy(
+            "./tests/assets/mv/test-default.rpgmvp",
+            input.join("img/a.rpgmvp"),
+        )
+        .unwrap();
+        decrypt_dir(&input, &input, &BatchOptions::new()).unwrap();
+        fs::remove_file(input.join("img/a.rpgmvp")).unwrap();
+
+        let system_json = output.join("data/System.json");
+        fs::write(
+            &system_json,
+            format!(
+                r#"{{"gameTitle":"Test","encryptionKey":"{DEFAULT_KEY}","hasEncryptedAudio":false}}"#
+            ),
+        )
+        .unwrap();
+
+        let options = RepackOptions::new()
+            .system_json(&system_json)
+            .engine(Engine::Mz)
+            .update_system_json(true);
+        let report = encrypt_dir(&input, &output, &options).unwrap();
+
+        assert_eq!(report.succeeded().count(), 1);
+        assert_eq!(
+            fs::read(output.join("img/a.png_")).unwrap(),
+            fs::read("./tests/assets/mv/test-default.rpgmvp").unwrap()
+        );
+        assert_eq!(
+            fs::read_to_string(&system_json).unwrap(),
+            format!(
+                r#"{{"gameTitle":"Test","encryptionKey":"{DEFAULT_KEY}","hasEncryptedAudio":false,"hasEncryptedImages":true}}"#
+            )
+        );
+
+        assert!(matches!(
+            encrypt_dir(&input, &output, &RepackOptions::new())
+                .map_err(|err| err.source),
+            Err(Error::KeyNotSet)
+        ));
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+
+    #[test]
+    fn decrypt_dir_custom_signature() {
+        use rpgm_asset_decrypter_lib::{
+            Decrypter, registry::SignatureRegistry,
+        };
+
+        const SIGNATURE: [u8; 16] = *b"[\nnull,\n{\"id\":1,";
+
+        let input = temp_dir().join("rpgm-asset-decrypter-registry-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-registry-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+        fs::create_dir_all(&input).unwrap();
+
+        let plain = b"[\nnull,\n{\"id\":1,\"name\":\"Harold\"}\n]".to_vec();
+        let mut encrypter = Decrypter::new();
+        encrypter
+            .set_key_from_str("150f15e73422e0a5ba5b59f997fc2350")
+            .unwrap();
+        fs::write(
+            input.join("Actors.rpgmvj"),
+            encrypter.encrypt(&plain).unwrap(),
+        )
+        .unwrap();
+
+        let mut signatures = SignatureRegistry::new();
+        signatures.register("rpgmvj", "json", SIGNATURE);
+        assert_eq!(
+            signatures.file_type("rpgmvj"),
+            Some(FileType::Other(SIGNATURE))
+        );
+        assert_eq!(signatures.file_type("rpgmvp"), Some(FileType::PNG));
+
+        let report = decrypt_dir(
+            &input,
+            &output,
+            &BatchOptions::new().signatures(signatures),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.key,
+            Some("150f15e73422e0a5ba5b59f997fc2350".parse().unwrap())
+        );
+        assert_eq!(fs::read(output.join("Actors.json")).unwrap(), plain);
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+}
+mod fs {
+    use rpgm_asset_decrypter_lib::Error;
+    use rpgm_asset_decrypter_lib::fs::{
+        cleanup_partials, encrypted_files, partial_path, write_atomic,
+        write_with_timeout,
+    };
+    use std::{
+        env::temp_dir,
+        fs,
+        path::PathBuf,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("rpgm-asset-decrypter-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_atomic_and_cleanup_partials() {
+        let dir = test_dir("partials");
+        let output = dir.join("image.png");
+
+        write_atomic(&output, b"data").unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"data");
+        assert!(!partial_path(&output).exists());
+
+        fs::create_dir(dir.join("nested")).unwrap();
+        let stale = partial_path(&dir.join("nested").join("audio.ogg"));
+        fs::write(&stale, b"").unwrap();
+        let foreign = dir.join("nested").join("download.part");
+        fs::write(&foreign, b"").unwrap();
+
+        assert_eq!(cleanup_partials(&dir).unwrap(), vec![stale.clone()]);
+        assert!(!stale.exists());
+        assert!(foreign.exists());
+        assert!(output.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn write_with_timeout_discards_late_output() {
+        let dir = test_dir("write-timeout");
+        let output = dir.join("audio.ogg");
+
+        write_with_timeout(&output, b"data".to_vec(), Some(Duration::MAX))
+            .unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"data");
+        fs::remove_file(&output).unwrap();
+
+        let result = write_with_timeout(
+            &output,
+            vec![0; 64 * 1024 * 1024],
+            Some(Duration::ZERO),
+        );
+        assert!(matches!(
+            result.map_err(|err| err.source),
+            Err(Error::Timeout)
+        ));
+
+        // The write keeps running on its detached thread, until it removes its partial file.
+        let partial = partial_path(&output);
+        let start = Instant::now();
+        thread::sleep(Duration::from_millis(100));
+
+        while partial.exists() && start.elapsed() < Duration::from_secs(30) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(!partial.exists());
+        assert!(!output.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_files_sorted() {
+        let dir = test_dir("walk");
+
+        for path in ["b/z.rpgmvp", "b/a.ogg_", "a.rpgmvo", "c.png", "b.txt"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        assert_eq!(
+            encrypted_files(&dir).unwrap(),
+            vec![
+                dir.join("a.rpgmvo"),
+                dir.join("b/a.ogg_"),
+                dir.join("b/z.rpgmvp")
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_symlinks() {
+        use rpgm_asset_decrypter_lib::fs::{
+            SymlinkPolicy, WalkOptions, walk_with,
+        };
+        use std::os::unix::fs::symlink;
+
+        let dir = test_dir("walk-symlinks");
+        fs::create_dir_all(dir.join("www/img")).unwrap();
+        fs::create_dir_all(dir.join("extra")).unwrap();
+        fs::write(dir.join("www/a.png_"), b"").unwrap();
+        fs::write(dir.join("www/img/b.png_"), b"").unwrap();
+        fs::write(dir.join("extra/c.png_"), b"").unwrap();
+        symlink(dir.join("www/a.png_"), dir.join("www/link.png_")).unwrap();
+        symlink(dir.join("extra"), dir.join("www/extra")).unwrap();
+        // Junction-like link back into the walked tree.
+        symlink(dir.join("www"), dir.join("www/img/loop")).unwrap();
+        symlink(dir.join("missing"), dir.join("www/broken.png_")).unwrap();
+
+        let www = dir.join("www");
+        let walk = |options: WalkOptions| {
+            walk_with(&www, &options, |_| true)
+                .unwrap()
+                .into_iter()
+                .map(|path| path.strip_prefix(&www).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            walk(WalkOptions::new()),
+            ["a.png_", "img/b.png_", "link.png_"].map(PathBuf::from)
+        );
+        assert_eq!(
+            walk(WalkOptions::new().symlinks(SymlinkPolicy::Follow)),
+            ["a.png_", "extra/c.png_", "img/b.png_", "link.png_"]
+                .map(PathBuf::from)
+        );
+        assert_eq!(
+            walk(WalkOptions::new().symlinks(SymlinkPolicy::Ignore)),
+            ["a.png_", "img/b.png_"].map(PathBuf::from)
+        );
+        assert_eq!(
+            walk(
+                WalkOptions::new()
+                    .symlinks(SymlinkPolicy::Follow)
+                    .max_depth(0)
+            ),
+            ["a.png_", "link.png_"].map(PathBuf::from)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+mod system_json {
+    use rpgm_asset_decrypter_lib::{
+        Error,
+        json::read_string,
+        project::find_encryption_info,
+        system_json::{
+            extract_key_from_system_json, find_key_in_dir,
+            search_encryption_info, set_encryption,
+        },
+    };
+    use std::{env::temp_dir, fs};
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    #[test]
+    fn tolerant_parsing() {
+        let with_bom = format!(
+            "\u{feff}{{\"hasEncryptedAudio\":true,\"encryptionKey\":\"{}\"}}",
+            KEY
+        );
+        let info = extract_key_from_system_json(with_bom.as_bytes()).unwrap();
+        assert_eq!(info.key, KEY);
+        assert!(info.has_encrypted_audio);
+
+        // Minified into a script, with a decoy that isn't a valid key.
+        let script = format!(
+            r#"var $dataSystem={{"encryptionKey":"not a key"}};$dataSystem={{"hasEncryptedImages" : true,"encryptionKey" : "{}"}};"#,
+            KEY
+        );
+        let info = extract_key_from_system_json(script.as_bytes()).unwrap();
+        assert_eq!(info.key, KEY);
+        assert!(info.has_encrypted_images && !info.has_encrypted_audio);
+        assert_eq!(search_encryption_info(script.as_bytes()).unwrap(), info);
+
+        assert!(matches!(
+            search_encryption_info(br#"{"encryptionKey": 42}"#),
+            Err(Error::KeyNotSet)
+        ));
+    }
+
+    #[test]
+    fn find_renamed_system_json() {
+        let root = temp_dir().join("rpgm-asset-decrypter-renamed-system-json");
+        let _ = fs::remove_dir_all(&root);
+
+        let data = root.join("www/data");
+        fs::create_dir_all(&data).unwrap();
+        fs::create_dir_all(root.join("www/img")).unwrap();
+        fs::write(data.join("Map001.json"), br#"{"events":[]}"#).unwrap();
+        fs::write(
+            data.join("Plugins.dat"),
+            format!(r#"cfg=({{"encryptionKey":"{}"}})"#, KEY),
+        )
+        .unwrap();
+
+        let (path, info) = find_key_in_dir(&data).unwrap();
+        assert_eq!(path, data.join("Plugins.dat"));
+        assert_eq!(info.key, KEY);
+        assert_eq!(
+            find_encryption_info(&root.join("www/img")).unwrap().0,
+            data.join("Plugins.dat")
+        );
+
+        // Renamed `System.json` wins over other files.
+        fs::write(
+            data.join("System.txt"),
+            format!(
+                r#"{{"encryptionKey":"{}"}}"#,
+                rpgm_asset_decrypter_lib::DEFAULT_KEY
+            ),
+        )
+        .unwrap();
+        let (path, info) = find_key_in_dir(&data).unwrap();
+        assert_eq!(path, data.join("System.txt"));
+        assert_eq!(info.key, rpgm_asset_decrypter_lib::DEFAULT_KEY);
+
+        fs::remove_dir_all(&root).unwrap();
+        assert!(find_key_in_dir(&data).is_none());
+    }
+
+    #[test]
+    fn extract_key() {
+        let data = br#"{
+            "advanced": {"gameId": 1, "screenWidth": 816, "fallbackFonts": "Verdana, sans-serif"},
+            "airship": {"bgm": {"name": "Ship3", "pan": 0}, "characterIndex": 3},
+            "armorTypes": ["", "General Armor", "Magic \"Armor\""],
+            "hasEncryptedImages": true,
+            "hasEncryptedAudio": false,
+            "encryptionKey": "d41d8cd98f00b204e9800998ecf8427e",
+            "versionId": 42
+        }"#;
+
+        let info = extract_key_from_system_json(data).unwrap();
+        assert_eq!(info.key, "d41d8cd98f00b204e9800998ecf8427e");
+        assert!(info.has_encrypted_images);
+        assert!(!info.has_encrypted_audio);
+
+        assert!(matches!(
+            extract_key_from_system_json(br#"{"gameTitle": "Test"}"#),
+            Err(Error::KeyNotSet)
+        ));
+        assert!(matches!(
+            extract_key_from_system_json(br#"{"encryptionKey": "#),
+            Err(Error::InvalidJson)
+        ));
+    }
+
+    #[test]
+    fn set_encryption_preserves_formatting() {
+        let mut data = br#"{
+    "gameTitle": "Test",
+    "hasEncryptedImages": false,
+    "encryptionKey": "d41d8cd98f00b204e9800998ecf8427e"
+}"#
+        .to_vec();
+
+        set_encryption(
+            &mut data,
+            Some("150f15e73422e0a5ba5b59f997fc2350"),
+            true,
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(data.clone()).unwrap(),
+            r#"{
+    "gameTitle": "Test",
+    "hasEncryptedImages": true,
+    "encryptionKey": "150f15e73422e0a5ba5b59f997fc2350"
+,"hasEncryptedAudio":true}"#
+        );
+
+        set_encryption(&mut data, None, false, false).unwrap();
+        let info = extract_key_from_system_json(&data).unwrap();
+        assert_eq!(info.key, "150f15e73422e0a5ba5b59f997fc2350");
+        assert!(!info.has_encrypted_images && !info.has_encrypted_audio);
+
+        assert!(matches!(
+            set_encryption(&mut data, Some("short"), true, true),
+            Err(Error::InvalidKeyLength)
+        ));
+    }
+
+    #[test]
+    fn json_strings() {
+        let data = r#" "a\"\\\/\nA😀é\ud83d\ude00" ,"#.as_bytes();
+        let mut pos = 0;
+
+        assert_eq!(
+            read_string(data, &mut pos).as_deref(),
+            Some("a\"\\/\nA\u{1f600}\u{e9}\u{1f600}")
+        );
+        assert_eq!(&data[pos..], b" ,");
+
+        // Not a string, unknown escape, unpaired surrogate, unterminated.
+        for data in [&b"1"[..], br#""\q""#, br#""\ud83d""#, br#""open"#] {
+            let mut pos = 0;
+            assert_eq!(read_string(data, &mut pos), None);
+            assert_eq!(pos, 0);
+        }
+    }
+}
+
+#[cfg(all(feature = "ffi", not(feature = "safe")))]
+mod ffi {
+    use super::{MV_PNG_ABOBA, is_valid_png};
+    use rpgm_asset_decrypter_lib::ffi::*;
+    use std::{fs::read, slice};
+
+    #[test]
+    fn decrypt_encrypt() {
+        let data = read(MV_PNG_ABOBA).unwrap();
+
+        unsafe {
+            let decrypter = rpgm_decrypter_new();
+            let mut decrypted = RpgmBuffer {
+                data: std::ptr::null_mut(),
+                len: 0,
+            };
+
+            assert_eq!(
+                rpgm_decrypt(
+                    decrypter,
+                    data.as_ptr(),
+                    data.len(),
+                    0,
+                    &mut decrypted
+                ),
+                RpgmStatus::Ok
+            );
+            assert!(is_valid_png(slice::from_raw_parts(
+                decrypted.data,
+                decrypted.len
+            )));
+
+            let mut key = [0; 33];
+            assert_eq!(
+                rpgm_get_key(decrypter, key.as_mut_ptr()),
+                RpgmStatus::Ok
+            );
+
+            let mut encrypted = RpgmBuffer {
+                data: std::ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(
+                rpgm_encrypt(
+                    decrypter,
+                    decrypted.data,
+                    decrypted.len,
+                    &mut encrypted
+                ),
+                RpgmStatus::Ok
+            );
+            assert_eq!(
+                slice::from_raw_parts(encrypted.data, encrypted.len),
+                data
+            );
+
+            assert_eq!(
+                rpgm_decrypt(decrypter, data.as_ptr(), 4, 0, &mut decrypted),
+                RpgmStatus::InvalidHeader
+            );
+
+            rpgm_buffer_free(decrypted);
+            rpgm_buffer_free(encrypted);
+            rpgm_decrypter_free(decrypter);
+        }
+    }
+}
+
+mod rgss {
+    use rpgm_asset_decrypter_lib::rgss::{RgssVersion, extract, list, version};
+
+    fn advance(key: u32) -> u32 {
+        key.wrapping_mul(7).wrapping_add(3)
+    }
+
+    fn xor_data(data: &[u8], mut key: u32) -> Vec<u8> {
+        let mut data = data.to_vec();
+
+        for chunk in data.chunks_mut(4) {
+            for (byte, key_byte) in chunk.iter_mut().zip(key.to_le_bytes()) {
+                *byte ^= key_byte;
+            }
+
+            key = advance(key);
+        }
+
+        data
+    }
+
+    const FILES: &[(&str, &[u8])] = &[
+        ("Data\\Actors.rxdata", b"\x04\x08[\x06"),
+        ("Graphics\\Titles\\title.png", b"\x89PNG\r\n\x1a\n payload"),
+    ];
+
+    #[test]
+    fn v1_archive() {
+        let mut archive = b"RGSSAD\0\x01".to_vec();
+        let mut key = 0xDEAD_CAFE_u32;
+
+        for (name, data) in FILES {
+            archive.extend((name.len() as u32 ^ key).to_le_bytes());
+            key = advance(key);
+
+            for byte in name.bytes() {
+                archive.push(byte ^ key as u8);
+                key = advance(key);
+            }
+
+            archive.extend((data.len() as u32 ^ key).to_le_bytes());
+            key = advance(key);
+            archive.extend(xor_data(data, key));
+        }
+
+        assert_eq!(version(&archive).unwrap(), RgssVersion::V1);
+
+        let entries = list(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "Graphics/Titles/title.png");
+
+        for (entry, (_, data)) in entries.iter().zip(FILES) {
+            assert_eq!(extract(&archive, entry).unwrap(), *data);
+        }
+    }
+
+    #[test]
+    fn v3_archive() {
+        let seed = 0x1234_5678_u32;
+        let key = seed.wrapping_mul(9).wrapping_add(3);
+        let file_key = 0xCAFE_BABE_u32;
+
+        let mut archive = b"RGSSAD\0\x03".to_vec();
+        archive.extend(seed.to_le_bytes());
+
+        let table_len: usize =
+            FILES.iter().map(|(name, _)| 16 + name.len()).sum();
+        let mut offset = archive.len() + table_len + 4;
+
+        for (name, data) in FILES {
+            archive.extend((offset as u32 ^ key).to_le_bytes());
+            archive.extend((data.len() as u32 ^ key).to_le_bytes());
+            archive.extend((file_key ^ key).to_le_bytes());
+            archive.extend((name.len() as u32 ^ key).to_le_bytes());
+            archive.extend(
+                name.bytes()
+                    .enumerate()
+                    .map(|(i, byte)| byte ^ key.to_le_bytes()[i % 4]),
+            );
+            offset += data.len();
+        }
+
+        archive.extend(key.to_le_bytes());
+
+        for (_, data) in FILES {
+            archive.extend(xor_data(data, file_key));
+        }
+
+        assert_eq!(version(&archive).unwrap(), RgssVersion::V3);
+
+        let entries = list(&archive).unwrap();
+        assert_eq!(entries[0].name, "Data/Actors.rxdata");
+
+        for (entry, (_, data)) in entries.iter().zip(FILES) {
+            assert_eq!(extract(&archive, entry).unwrap(), *data);
+        }
+    }
+}
+
+mod key_recovery {
+    use super::{MV_OGG_ABOBA, MV_PNG_ABOBA, MZ_M4A_ABOBA};
+    use rpgm_asset_decrypter_lib::key_recovery::KeyRecovery;
+    use std::fs::read;
+
+    #[test]
+    fn candidates() {
+        let mut recovery = KeyRecovery::new();
+
+        for path in [MV_OGG_ABOBA, MZ_M4A_ABOBA, MV_PNG_ABOBA] {
+            recovery.add_file(&read(path).unwrap()).unwrap();
+        }
+
+        let candidates = recovery.candidates();
+        let best = &candidates[0];
+
+        assert!(best.is_complete());
+        assert_eq!(
+            best.encryption_key().map(|key| key.to_string()).as_deref(),
+            Some("150f15e73422e0a5ba5b59f997fc2350")
+        );
+        assert_eq!(best.matches, vec![Some("ogg"), Some("m4a"), Some("png")]);
+    }
+
+    #[cfg(feature = "md5")]
+    #[test]
+    fn brute_force() {
+        use super::MZ_PNG_ABOBA;
+        use rpgm_asset_decrypter_lib::{FileType, key_recovery::brute_force};
+
+        let encrypted = read(MZ_PNG_ABOBA).unwrap();
+        let key = brute_force(&encrypted, FileType::PNG, ["password", "aboba"]);
+
+        assert_eq!(
+            key.map(|key| key.to_string()).as_deref(),
+            Some("150f15e73422e0a5ba5b59f997fc2350")
+        );
+        assert!(
+            brute_force(&encrypted, FileType::PNG, ["password", "qwerty"])
+                .is_none()
+        );
+        assert!(brute_force(&encrypted, FileType::OGG, ["aboba"]).is_none());
+    }
+
+    #[test]
+    fn scan_scripts() {
+        use rpgm_asset_decrypter_lib::key_recovery::scan_scripts;
+        use std::{env::temp_dir, fs};
+
+        let root = temp_dir().join("rpgm-asset-decrypter-scan-scripts");
+        let _ = fs::remove_dir_all(&root);
+
+        let js = root.join("www/js");
+        fs::create_dir_all(js.join("plugins")).unwrap();
+        fs::write(
+            js.join("rpg_core.js"),
+            "Decrypter._encryptionKey = \"\";\n\
+             Decrypter._encryptionKey = $dataSystem.encryptionKey;\n\
+             if (this._encryptionKey == \"0123456789abcdef0123456789abcdef\") {}\n\
+             this._encryptionKey = \"150f15e73422e0a5ba5b59f997fc2350\";\n",
+        )
+        .unwrap();
+        fs::write(
+            js.join("plugins/Patch.js"),
+            "var cfg = { 'encryptionKey': 'd41d8cd98f00b204e9800998ecf8427e' };\n\
+             $dataSystem.encryptionKey = `150f15e73422e0a5ba5b59f997fc2350`;",
+        )
+        .unwrap();
+        fs::write(
+            js.join("notes.txt"),
+            "encryptionKey = \"ffffffffffffffffffffffffffffffff\"",
+        )
+        .unwrap();
+
+        let keys = scan_scripts(&root).unwrap();
+        let keys: Vec<_> = keys
+            .iter()
+            .map(|found| (found.key.to_string(), found.path.clone()))
+            .collect();
+
+        assert_eq!(
+            keys,
+            vec![
+                (
+                    "d41d8cd98f00b204e9800998ecf8427e".to_owned(),
+                    js.join("plugins/Patch.js")
+                ),
+                (
+                    "150f15e73422e0a5ba5b59f997fc2350".to_owned(),
+                    js.join("plugins/Patch.js")
+                ),
+            ]
+        );
+
+        fs::remove_file(js.join("plugins/Patch.js")).unwrap();
+        let found = &scan_scripts(&root).unwrap()[0];
+        let script = fs::read(&found.path).unwrap();
+        assert_eq!(
+            &script[found.offset..found.offset + 34],
+            b"\"150f15e73422e0a5ba5b59f997fc2350\""
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+mod project {
+    use rpgm_asset_decrypter_lib::{
+        Engine,
+        project::{DetectEngine, find_system_json, system_json_path},
+    };
+    use std::{env::temp_dir, fs};
+
+    #[test]
+    fn locate_system_json() {
+        let root = temp_dir().join("rpgm-asset-decrypter-find-system-json");
+        let _ = fs::remove_dir_all(&root);
+
+        let game = root.join("game");
+        fs::create_dir_all(game.join("www/data")).unwrap();
+        fs::create_dir_all(game.join("www/img/pictures/sub")).unwrap();
+        fs::create_dir_all(game.join("saves")).unwrap();
+        fs::write(game.join("www/data/System.json"), b"{}").unwrap();
+
+        let system_json = game.join("www/data/System.json");
+        for path in [
+            game.clone(),
+            game.join("www"),
+            game.join("www/data"),
+            game.join("www/img/pictures/sub"),
+        ] {
+            assert_eq!(find_system_json(&path), Some(system_json.clone()));
+        }
+
+        // Not a content folder, so the game it's in isn't searched.
+        assert_eq!(find_system_json(&game.join("saves")), None);
+        assert_eq!(find_system_json(&root), None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn detect_engine() {
+        let root = temp_dir().join("rpgm-asset-decrypter-project");
+        let _ = fs::remove_dir_all(&root);
+
+        fs::create_dir_all(root.join("www/data")).unwrap();
+        assert_eq!(Engine::detect(&root), Some(Engine::Mv));
+        assert_eq!(
+            system_json_path(&root, Engine::Mv),
+            root.join("www/data/System.json")
+        );
+
+        fs::remove_dir_all(root.join("www")).unwrap();
+        fs::create_dir_all(root.join("data")).unwrap();
+        assert_eq!(Engine::detect(&root), Some(Engine::Mz));
+        assert_eq!(
+            system_json_path(&root, Engine::Mz),
+            root.join("data/System.json")
+        );
+
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::copy(
+            "./tests/assets/mv/test-default.rpgmvp",
+            root.join("img/a.rpgmvp"),
+        )
+        .unwrap();
+        assert_eq!(Engine::detect(&root), Some(Engine::Mv));
+
+        fs::write(root.join("game.rmmzproject"), b"RPGMZ 1.0.0").unwrap();
+        assert_eq!(Engine::detect(&root), Some(Engine::Mz));
+
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(Engine::detect(&root), None);
+    }
+}
+
+#[cfg(all(feature = "mmap", not(feature = "safe")))]
+mod mmap {
+    use super::MZ_OGG_ABOBA;
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, FileType,
+        mmap::{decrypt_mmap, decrypt_mmap_with},
+    };
+    use std::{fs::read, io::Read};
+
+    #[test]
+    fn decrypt_mmap_view() {
+        let plain = Decrypter::new()
+            .decrypt(&read(MZ_OGG_ABOBA).unwrap(), FileType::OGG)
+            .unwrap();
+
+        let view = decrypt_mmap(MZ_OGG_ABOBA.as_ref()).unwrap();
+        assert_eq!(view.len(), plain.len());
+        assert_eq!(view.to_vec(), plain);
+
+        let mut read_back = Vec::new();
+        view.reader().read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, plain);
+
+        let mut buf = [0; 8];
+        assert_eq!(view.read_at(12, &mut buf), 8);
+        assert_eq!(buf, plain[12..20]);
+
+        let mut decrypter = Decrypter::new();
+        decrypter
+            .set_key_from_str("150f15e73422e0a5ba5b59f997fc2350")
+            .unwrap();
+        let view =
+            decrypt_mmap_with(MZ_OGG_ABOBA.as_ref(), &mut decrypter).unwrap();
+        assert_eq!(view.header()[..], plain[..16]);
+    }
+}
+
+mod preview {
+    use super::{MV_PNG_DEFAULT, MZ_OGG_DEFAULT};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Error, FileType,
+        preview::{OggCodec, ogg_info},
+    };
+    use std::{fs::read, time::Duration};
+
+    #[test]
+    fn ogg_metadata() {
+        let encrypted = read(MZ_OGG_DEFAULT).unwrap();
+        let info = ogg_info(&encrypted).unwrap();
+
+        assert_eq!(info.codec, OggCodec::Vorbis);
+        assert_eq!(info.channels, 1);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.duration, Some(Duration::from_secs(5)));
+        assert!(info.vendor.is_some());
+
+        let plain =
+            Decrypter::new().decrypt(&encrypted, FileType::OGG).unwrap();
+        assert_eq!(ogg_info(&plain).unwrap(), info);
+
+        assert!(matches!(
+            ogg_info(&read(MV_PNG_DEFAULT).unwrap()),
+            Err(Error::KeyMismatch { .. }
+                | Error::UnexpectedEOF
+                | Error::Malformed { .. })
+        ));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn decode_encrypted_png() {
+        use rpgm_asset_decrypter_lib::preview::{decode_png, decode_png_with};
+
+        let mut data = read(super::MZ_PNG_ABOBA).unwrap();
+        let image = decode_png(&mut data).unwrap();
+        assert!(image.width() > 0 && image.height() > 0);
+
+        // Decrypted in place, so decoding it again takes the plain path.
+        let decrypted = data[16..].to_vec();
+        let mut plain = decrypted.clone();
+        assert_eq!(decode_png(&mut plain).unwrap(), image);
+        assert_eq!(plain, decrypted);
+
+        let mut decrypter = Decrypter::new();
+        decrypter
+            .set_key_from_str("150f15e73422e0a5ba5b59f997fc2350")
+            .unwrap();
+        let err =
+            decode_png_with(&mut decrypter, &mut read(MV_PNG_DEFAULT).unwrap())
+                .unwrap_err();
+        assert!(matches!(err, Error::KeyMismatch { .. }));
+
+        let mut ogg = read(super::MZ_OGG_ABOBA).unwrap();
+        assert!(matches!(decode_png(&mut ogg), Err(Error::Io(_))));
+    }
+}
+
+#[cfg(feature = "remote")]
+mod remote {
+    use super::{MZ_OGG_ABOBA, MZ_PNG_ABOBA};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Error, FileType,
+        remote::{RangeClient, decrypt_remote},
+    };
+    use std::{
+        convert::TryFrom,
+        fs::read,
+        io::{self, Read},
+    };
+
+    /// Serves files from disk, recording requested ranges.
+    #[derive(Default)]
+    struct DiskClient {
+        requests: Vec<(u64, Option<u64>)>,
+    }
+
+    impl RangeClient for DiskClient {
+        type Body = io::Cursor<Vec<u8>>;
+
+        fn get_range(
+            &mut self,
+            url: &str,
+            start: u64,
+            end: Option<u64>,
+        ) -> io::Result<Self::Body> {
+            self.requests.push((start, end));
+
+            let data = read(url)?;
+            let start = usize::try_from(start).unwrap().min(data.len());
+            let end = end.map_or(data.len(), |end| {
+                usize::try_from(end).unwrap().min(data.len())
+            });
+            Ok(io::Cursor::new(data[start..end].to_vec()))
+        }
+    }
+
+    #[test]
+    fn decrypt_remote_asset() {
+        for (url, file_type, prefix_length) in [
+            (MZ_PNG_ABOBA, FileType::PNG, 32),
+            (MZ_OGG_ABOBA, FileType::OGG, 4096),
+        ] {
+            let plain = Decrypter::new()
+                .decrypt(&read(url).unwrap(), file_type)
+                .unwrap();
+
+            let mut client = DiskClient::default();
+            let mut decrypter = Decrypter::new();
+            let mut decrypted = Vec::new();
+            decrypt_remote(&mut client, url, &mut decrypter, file_type)
+                .unwrap()
+                .read_to_end(&mut decrypted)
+                .unwrap();
+
+            assert_eq!(decrypted, plain);
+            assert_eq!(client.requests[0], (0, Some(32)));
+            assert_eq!(client.requests.last(), Some(&(prefix_length, None)));
+
+            // With the key known, only the header is fetched upfront.
+            let mut client = DiskClient::default();
+            let mut decrypted = Vec::new();
+            decrypt_remote(&mut client, url, &mut decrypter, file_type)
+                .unwrap()
+                .read_to_end(&mut decrypted)
+                .unwrap();
+
+            assert_eq!(decrypted, plain);
+            assert_eq!(client.requests, [(0, Some(32)), (32, None)]);
+        }
+
+        assert!(matches!(
+            decrypt_remote(
+                &mut DiskClient::default(),
+                MZ_PNG_ABOBA,
+                &mut Decrypter::new(),
+                FileType::WAV
+            ),
+            Err(Error::KeyNotSet)
+        ));
+    }
+}
+
+#[cfg(feature = "testing")]
+mod testing {
+    use super::{MZ_M4A_ABOBA, MZ_OGG_DEFAULT, MZ_PNG_ABOBA};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, EncryptionKey, FileType,
+        preview::ogg_info,
+        testing::{
+            assert_decrypts_to, assert_round_trip, encrypt_fixture,
+            make_fixture,
+        },
+        verify::{ValidationResult, validate},
+    };
+    use std::{fs::read, time::Duration};
+
+    #[test]
+    fn golden_round_trip() {
+        for &(path, file_type) in &[
+            (MZ_PNG_ABOBA, FileType::PNG),
+            (MZ_OGG_DEFAULT, FileType::OGG),
+            (MZ_M4A_ABOBA, FileType::M4A),
+        ] {
+            let golden = read(path).unwrap();
+            let mut decrypter = Decrypter::new();
+            let plain = decrypter.decrypt(&golden, file_type).unwrap();
+            let key = decrypter.encryption_key().unwrap();
+
+            assert_eq!(encrypt_fixture(&plain, &key), golden);
+            assert_eq!(assert_round_trip(&plain, &key, file_type), golden);
+            assert_decrypts_to(&golden, file_type, &plain);
+        }
+
+        let key = EncryptionKey::from_bytes([7; 16]);
+        let json = br#"{"encryptionKey":"aboba"}"#;
+        let encrypted = assert_round_trip(json, &key, FileType::JSON);
+        assert_eq!(encrypted.len(), 16 + json.len());
+    }
+
+    #[test]
+    fn fixtures() {
+        let key =
+            EncryptionKey::from_hex_str("150f15e73422e0a5ba5b59f997fc2350")
+                .unwrap();
+
+        for &(file_type, min_size) in &[
+            (FileType::PNG, 93),
+            (FileType::OGG, 150),
+            (FileType::M4A, 64),
+            (FileType::WEBP, 34),
+            (FileType::WAV, 44),
+            (FileType::JSON, 2),
+            (FileType::Other(*b"GIF89a\x01\0\x01\0\0\0\0\0\0\0"), 16),
+        ] {
+            for &size in &[0, min_size, min_size + 1, 300, 200_000] {
+                let fixture = make_fixture(file_type, &key, size);
+                let expected = match file_type {
+                    FileType::WEBP | FileType::WAV => {
+                        (size.max(min_size) + 1) & !1
+                    }
+                    _ => size.max(min_size),
+                };
+
+                assert_eq!(
+                    fixture.plain.len(),
+                    expected,
+                    "{} {}",
+                    file_type,
+                    size
+                );
+                assert_eq!(fixture, make_fixture(file_type, &key, size));
+                assert_eq!(
+                    fixture.encrypted,
+                    encrypt_fixture(&fixture.plain, &key)
+                );
+                assert!(
+                    matches!(
+                        validate(&fixture.plain, file_type),
+                        ValidationResult::Valid | ValidationResult::Unsupported
+                    ),
+                    "{} {}",
+                    file_type,
+                    size
+                );
+
+                if !file_type.is_json() {
+                    let mut decrypter = Decrypter::new();
+                    decrypter
+                        .set_key_from_file(&fixture.encrypted, file_type)
+                        .unwrap();
+                    assert_eq!(decrypter.encryption_key(), Some(key));
+                }
+
+                let _ = assert_round_trip(&fixture.plain, &key, file_type);
+            }
+        }
+
+        let ogg = make_fixture(FileType::OGG, &key, 0);
+        let info = ogg_info(&ogg.encrypted).unwrap();
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.duration, Some(Duration::from_secs(1)));
+        assert_eq!(info.vendor.as_deref(), Some("rpgm-asset-decrypter"));
+
+        #[cfg(feature = "image")]
+        {
+            let mut png = make_fixture(FileType::PNG, &key, 0).encrypted;
+            let image = rpgm_asset_decrypter_lib::preview::decode_png(&mut png)
+                .unwrap();
+            assert_eq!(image.dimensions(), (1, 1));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "data differs at offset 16")]
+    fn reports_first_difference() {
+        let golden = read(MZ_PNG_ABOBA).unwrap();
+        let mut plain =
+            Decrypter::new().decrypt(&golden, FileType::PNG).unwrap();
+        plain[16] ^= 0xFF;
+
+        assert_decrypts_to(&golden, FileType::PNG, &plain);
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialization {
+    use rpgm_asset_decrypter_lib::{
+        DEFAULT_KEY, EncryptionKey, Engine, Error, FileType, HEADER_LENGTH,
+        scan::{AssetRecord, write_json},
+    };
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn file_types_and_engines() {
+        for &(file_type, name) in &[
+            (FileType::PNG, "png"),
+            (FileType::OGG, "ogg"),
+            (FileType::M4A, "m4a"),
+            (FileType::WEBP, "webp"),
+            (FileType::WAV, "wav"),
+            (FileType::JSON, "json"),
+            (
+                FileType::Other(*b"wOFF\0\x01\0\0\0\0\0\0\0\0\0\0"),
+                "other:774f4646000100000000000000000000",
+            ),
+        ] {
+            assert_eq!(serde_json::to_value(file_type).unwrap(), json!(name));
+            assert_eq!(
+                serde_json::from_value::<FileType>(json!(name)).unwrap(),
+                file_type
+            );
+        }
+
+        for &(engine, name) in &[(Engine::Mv, "mv"), (Engine::Mz, "mz")] {
+            assert_eq!(serde_json::to_value(engine).unwrap(), json!(name));
+            assert_eq!(
+                serde_json::from_value::<Engine>(json!(name)).unwrap(),
+                engine
+            );
+        }
+
+        for name in ["PNG", "rpgmvp", "other:zz", "MV"] {
+            assert!(serde_json::from_value::<FileType>(json!(name)).is_err());
+            assert!(serde_json::from_value::<Engine>(json!(name)).is_err());
+        }
+
+        let key: EncryptionKey = DEFAULT_KEY.parse().unwrap();
+        assert_eq!(serde_json::to_value(key).unwrap(), json!(DEFAULT_KEY));
+        assert_eq!(
+            serde_json::from_value::<EncryptionKey>(json!(DEFAULT_KEY))
+                .unwrap(),
+            key
+        );
+        assert!(
+            serde_json::from_value::<EncryptionKey>(json!("aboba")).is_err()
+        );
+
+        let toml = toml::to_string(&std::collections::BTreeMap::from([(
+            "file_type",
+            FileType::M4A,
+        )]))
+        .unwrap();
+        assert_eq!(toml.trim(), r#"file_type = "m4a""#);
+    }
+
+    #[test]
+    fn errors() {
+        let errors = vec![
+            Error::KeyNotSet,
+            Error::MalformedKey {
+                position: 3,
+                reason: "not a hex digit",
+            },
+            Error::InvalidMagic {
+                expected: *b"RPGMV\0\0\0\0\x03\x01\0\0\0\0\0",
+                got: [0xff; HEADER_LENGTH],
+            },
+            Error::AlreadyDecrypted(FileType::PNG),
+            Error::UnexpectedEOF,
+            Error::Malformed {
+                offset: 4,
+                reason: "expected OGG page",
+            },
+            Error::TypeMismatch {
+                declared: FileType::OGG,
+                detected: FileType::M4A,
+            },
+            Error::KeyMismatch {
+                expected_signature: [0; HEADER_LENGTH],
+                got: [1; HEADER_LENGTH],
+            },
+            Error::DoubleEncrypted,
+            Error::TooManyLayers,
+            Error::Io(std::io::ErrorKind::NotFound.into()),
+        ];
+
+        for err in &errors {
+            let json = serde_json::to_value(err).unwrap();
+            let loaded: Error = serde_json::from_value(json.clone()).unwrap();
+
+            assert_eq!(loaded.to_string(), err.to_string());
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), json);
+        }
+
+        assert_eq!(
+            serde_json::to_value(&errors[0]).unwrap(),
+            json!({ "kind": "key_not_set" })
+        );
+        assert_eq!(
+            serde_json::to_value(&errors[5]).unwrap(),
+            json!({
+                "kind": "malformed",
+                "offset": 4,
+                "reason": "expected OGG page"
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(&errors[6]).unwrap(),
+            json!({ "kind": "type_mismatch", "declared": "ogg", "detected": "m4a" })
+        );
+        assert_eq!(
+            serde_json::to_value(&errors[7]).unwrap()["got"],
+            json!("01010101010101010101010101010101")
+        );
+
+        assert!(
+            serde_json::from_value::<Error>(json!({ "kind": "aboba" }))
+                .is_err()
+        );
+        assert!(
+            serde_json::from_value::<Error>(json!({
+                "kind": "invalid_magic",
+                "expected": "zz",
+                "got": "zz"
+            }))
+            .is_err()
+        );
+
+        // Reasons not produced by the crate aren't kept.
+        assert!(matches!(
+            serde_json::from_value::<Error>(json!({
+                "kind": "malformed",
+                "offset": 0,
+                "reason": "x".repeat(1 << 20)
+            })),
+            Ok(Error::Malformed {
+                reason: "unknown reason",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn asset_records() {
+        let records = vec![AssetRecord {
+            path: PathBuf::from("img/a.rpgmvp"),
+            file_type: FileType::PNG,
+            size: 100,
+            encrypted: true,
+            key_fingerprint: Some(String::from("abcdef")),
+            dimensions: Some((2, 3)),
+            duration: None,
+        }];
+
+        let mut json = Vec::new();
+        write_json(&records, &mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        assert_eq!(json, serde_json::to_value(&records).unwrap());
+        assert_eq!(
+            serde_json::from_value::<Vec<AssetRecord>>(json).unwrap(),
+            records
+        );
+    }
+}
+
+mod job {
+    use super::{MV_OGG_ABOBA, MV_PNG_ABOBA, is_valid_png};
+    use rpgm_asset_decrypter_lib::{
+        Engine, Error,
+        job::{Job, Operation, Task, TaskOutcome, run_job},
+    };
+    use std::{env::temp_dir, fs, io::ErrorKind, path::Path};
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    fn game(root: &Path) {
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(root.join("game/img")).unwrap();
+        fs::create_dir_all(root.join("game/audio")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("game/img/a.rpgmvp")).unwrap();
+        fs::copy(MV_OGG_ABOBA, root.join("game/audio/b.rpgmvo")).unwrap();
+    }
+
+    #[test]
+    fn run_tasks() {
+        let root = temp_dir().join("rpgm-asset-decrypter-job");
+        game(&root);
+
+        let job = Job::new()
+            .task(
+                Task::new(
+                    Operation::Decrypt,
+                    root.join("game"),
+                    root.join("assets"),
+                )
+                .key(KEY.parse().unwrap()),
+            )
+            .task(
+                Task::new(
+                    Operation::Encrypt,
+                    root.join("assets"),
+                    root.join("repacked"),
+                )
+                .key(KEY.parse().unwrap())
+                .engine(Engine::Mz),
+            )
+            .task(Task::new(
+                Operation::Encrypt,
+                root.join("missing"),
+                root.join("repacked"),
+            ))
+            .task(Task::new(
+                Operation::Decrypt,
+                root.join("game"),
+                root.join("assets"),
+            ))
+            .fail_fast(true);
+
+        let report = run_job(&job);
+        assert_eq!(report.tasks.len(), 4);
+        assert!(!report.is_success());
+        assert!(report.tasks[0].is_success());
+        assert!(report.tasks[1].is_success());
+        assert!(matches!(report.tasks[2].outcome, TaskOutcome::Failed(_)));
+        assert!(matches!(report.tasks[3].outcome, TaskOutcome::Skipped));
+
+        assert!(is_valid_png(
+            &fs::read(root.join("assets/img/a.png")).unwrap()
+        ));
+        assert_eq!(
+            fs::read(root.join("repacked/img/a.png_")).unwrap(),
+            fs::read(MV_PNG_ABOBA).unwrap()
+        );
+
+        // Without `fail_fast`, all tasks run.
+        let report = run_job(&job.fail_fast(false));
+        assert!(report.tasks[3].is_success());
+
+        // Fields of the other operation fail the task, instead of being ignored.
+        let mut encrypt = Task::new(
+            Operation::Encrypt,
+            root.join("assets"),
+            root.join("filtered"),
+        )
+        .key(KEY.parse().unwrap());
+        encrypt.include.push(String::from("img/**"));
+
+        let decrypt = Task::new(
+            Operation::Decrypt,
+            root.join("game"),
+            root.join("filtered"),
+        )
+        .engine(Engine::Mv);
+
+        let report = run_job(&Job::new().task(encrypt).task(decrypt));
+
+        for task in &report.tasks {
+            assert!(matches!(
+                &task.outcome,
+                TaskOutcome::Failed(err) if matches!(
+                    &err.source,
+                    Error::Io(err) if err.kind() == ErrorKind::InvalidInput
+                )
+            ));
+        }
+
+        assert!(!root.join("filtered").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn job_file() {
+        let root = temp_dir().join("rpgm-asset-decrypter-job-file");
+        game(&root);
+
+        let job: Job = serde_json::from_str(&format!(
+            r#"{{
+                "tasks": [
+                    {{ "operation": "decrypt", "input": "game", "output": "assets", "include": ["img/**"] }},
+                    {{ "operation": "encrypt", "input": "assets", "output": "repacked", "key": "{}", "engine": "mz" }}
+                ]
+            }}"#,
+            KEY
+        ))
+        .unwrap();
+        assert_eq!(job.tasks[1].engine, Some(Engine::Mz));
+        assert!(!job.fail_fast);
+
+        let toml = toml::to_string(&job).unwrap();
+        assert_eq!(toml::from_str::<Job>(&toml).unwrap(), job);
+
+        let report = run_job(&job.relative_to(&root));
+        assert!(report.is_success());
+        assert!(root.join("repacked/img/a.png_").exists());
+        assert!(!root.join("assets/audio").exists());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["succeeded"], true);
+        assert_eq!(json["tasks"][0]["operation"], "decrypt");
+        assert_eq!(json["tasks"][0]["status"], "completed");
+        assert_eq!(json["tasks"][1]["report"]["succeeded"], 1);
+
+        assert!(
+            serde_json::from_str::<Job>(
+                r#"{"tasks":[{"operation":"decrypt","input":"a","output":"b","aboba":1}]}"#
+            )
+            .is_err()
+        );
+
+        // Invalid keys are rejected when the job is loaded, for any operation.
+        for operation in ["decrypt", "encrypt"] {
+            assert!(
+                serde_json::from_str::<Job>(&format!(
+                    r#"{{"tasks":[{{"operation":"{operation}","input":"a","output":"b","key":"aboba"}}]}}"#
+                ))
+                .is_err()
+            );
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+#[cfg(feature = "watch")]
+mod watch {
+    use super::{MV_OGG_ABOBA, MV_PNG_ABOBA, is_valid_ogg, is_valid_png};
+    use rpgm_asset_decrypter_lib::{
+        batch::BatchOptions, watch::decrypt_on_change,
+    };
+    use std::{env::temp_dir, fs, time::Duration};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn decrypts_new_files() {
+        let src = temp_dir().join("rpgm-asset-decrypter-watch");
+        let dst = temp_dir().join("rpgm-asset-decrypter-watch-out");
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dst);
+        fs::create_dir_all(src.join("img")).unwrap();
+
+        let watch = decrypt_on_change(
+            &src,
+            &dst,
+            &BatchOptions::new().exclude(["audio/se/**"]),
+        )
+        .unwrap();
+
+        fs::copy(MV_PNG_ABOBA, src.join("img/a.rpgmvp")).unwrap();
+        let report = watch.next_report(TIMEOUT).unwrap().unwrap();
+        assert_eq!(report.succeeded().count(), 1);
+        assert!(is_valid_png(&fs::read(dst.join("img/a.png")).unwrap()));
+
+        // Plain and excluded files are ignored, new directories are watched.
+        fs::write(src.join("img/notes.txt"), b"aboba").unwrap();
+        fs::create_dir_all(src.join("audio/se")).unwrap();
+        fs::copy(MV_OGG_ABOBA, src.join("audio/se/c.rpgmvo")).unwrap();
+        fs::create_dir_all(src.join("audio/bgm")).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        fs::copy(MV_OGG_ABOBA, src.join("audio/bgm/b.rpgmvo")).unwrap();
+
+        let report = watch.next_report(TIMEOUT).unwrap().unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(report.files[0].input.file_name().unwrap(), "b.rpgmvo");
+        assert!(is_valid_ogg(
+            &fs::read(dst.join("audio/bgm/b.ogg")).unwrap()
+        ));
+        assert!(!dst.join("audio/se").exists());
+
+        watch.stop();
+        fs::remove_dir_all(&src).unwrap();
+        fs::remove_dir_all(&dst).unwrap();
+    }
+
+    #[test]
+    fn encrypts_edited_files() {
+        use rpgm_asset_decrypter_lib::{
+            Decrypter, EncryptionKey, Error, FileType, batch::RepackOptions,
+            watch::encrypt_on_change,
+        };
+
+        let edited = temp_dir().join("rpgm-asset-decrypter-watch-edited");
+        let game = temp_dir().join("rpgm-asset-decrypter-watch-game");
+        let _ = fs::remove_dir_all(&edited);
+        let _ = fs::remove_dir_all(&game);
+        fs::create_dir_all(edited.join("img/pictures")).unwrap();
+
+        assert!(matches!(
+            encrypt_on_change(&edited, &game, &RepackOptions::new()),
+            Err(err) if matches!(err.source, Error::KeyNotSet)
+        ));
+
+        let key: EncryptionKey =
+            "150f15e73422e0a5ba5b59f997fc2350".parse().unwrap();
+        let watch =
+            encrypt_on_change(&edited, &game, &RepackOptions::new().key(key))
+                .unwrap();
+
+        let encrypted = fs::read(MV_PNG_ABOBA).unwrap();
+        let plain =
+            Decrypter::new().decrypt(&encrypted, FileType::PNG).unwrap();
+        fs::write(edited.join("img/pictures/a.png"), &plain).unwrap();
+        fs::write(edited.join("img/pictures/a.psd"), b"aboba").unwrap();
+
+        let report = watch.next_report(TIMEOUT).unwrap().unwrap();
+        assert_eq!(report.files.len(), 1);
+        assert_eq!(
+            fs::read(game.join("img/pictures/a.rpgmvp")).unwrap(),
+            encrypted
+        );
+
+        drop(watch);
+        fs::remove_dir_all(&edited).unwrap();
+        fs::remove_dir_all(&game).unwrap();
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod async_api {
+    use super::{MV_PNG_DEFAULT, is_valid_png};
+    use rpgm_asset_decrypter_lib::{
+        DEFAULT_KEY,
+        batch::{BatchOptions, decrypt_dir_async},
+        decrypt_file_async,
+        fs::{partial_path, write_atomic_async},
+    };
+    use std::{env::temp_dir, fs};
+
+    #[tokio::test]
+    async fn decrypt_async() {
+        let input = temp_dir().join("rpgm-asset-decrypter-async-input");
+        let output = temp_dir().join("rpgm-asset-decrypter-async-output");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(MV_PNG_DEFAULT, input.join("a.rpgmvp")).unwrap();
+
+        let file_output = input.join("a.png");
+        decrypt_file_async(&input.join("a.rpgmvp"), &file_output)
+            .await
+            .unwrap();
+        assert!(is_valid_png(&fs::read(&file_output).unwrap()));
+
+        let report = decrypt_dir_async(
+            input.clone(),
+            output.clone(),
+            BatchOptions::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.key, Some(DEFAULT_KEY.parse().unwrap()));
+        assert!(is_valid_png(&fs::read(output.join("a.png")).unwrap()));
+
+        // Renaming onto a non-empty directory fails, and leaves no partial file behind.
+        let occupied = output.join("occupied");
+        fs::create_dir_all(occupied.join("inner")).unwrap();
+        assert!(write_atomic_async(&occupied, b"data").await.is_err());
+        assert!(!partial_path(&occupied).exists());
+
+        fs::remove_dir_all(input).unwrap();
+        fs::remove_dir_all(output).unwrap();
+    }
+}
+
+mod scan {
+    use super::{MV_OGG_ABOBA, MV_PNG_ABOBA, MZ_OGG_ABOBA};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, EncryptionKey, Engine, FileType,
+        fs::encrypted_files,
+        scan::{
+            ScannedAsset, encrypted_assets, find_duplicates, inventory,
+            write_csv, write_json,
+        },
+    };
+    use std::{env::temp_dir, fs, path::Path};
+
+    #[test]
+    fn encrypted_assets_lazily() {
+        let root = temp_dir().join("rpgm-asset-decrypter-scan");
+        let _ = fs::remove_dir_all(&root);
+
+        fs::create_dir_all(root.join("img/pictures")).unwrap();
+        fs::create_dir_all(root.join("audio/bgm")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("img/pictures/a.rpgmvp")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("img/b.rpgmvp")).unwrap();
+        fs::copy(MZ_OGG_ABOBA, root.join("audio/bgm/c.ogg_")).unwrap();
+        fs::write(root.join("img/readme.txt"), b"not an asset").unwrap();
+
+        let assets: Vec<ScannedAsset> =
+            encrypted_assets(&root).map(Result::unwrap).collect();
+
+        let paths: Vec<_> = assets.iter().map(|asset| &asset.path).collect();
+        assert_eq!(
+            paths,
+            encrypted_files(&root).unwrap().iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(assets[0].relative_path, Path::new("audio/bgm/c.ogg_"));
+        assert_eq!(assets[0].file_type, FileType::OGG);
+        assert_eq!(assets[0].engine, Engine::Mz);
+        assert_eq!(assets[0].size, fs::metadata(MZ_OGG_ABOBA).unwrap().len());
+
+        assert_eq!(assets[1].relative_path, Path::new("img/b.rpgmvp"));
+        assert_eq!(assets[1].file_type, FileType::PNG);
+        assert_eq!(assets[1].engine, Engine::Mv);
+
+        let first = encrypted_assets(&root).next().unwrap().unwrap();
+        assert_eq!(first, assets[0]);
+
+        fs::remove_dir_all(&root).unwrap();
+        assert!(encrypted_assets(&root).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn duplicates() {
+        let root = temp_dir().join("rpgm-asset-decrypter-scan-duplicates");
+        let _ = fs::remove_dir_all(&root);
+
+        fs::create_dir_all(root.join("img/pictures")).unwrap();
+        fs::create_dir_all(root.join("audio/bgm")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("img/pictures/a.rpgmvp")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("img/b.rpgmvp")).unwrap();
+        fs::copy(MZ_OGG_ABOBA, root.join("audio/bgm/c.ogg_")).unwrap();
+
+        // Same size, different content.
+        let mut changed = fs::read(MV_PNG_ABOBA).unwrap();
+        *changed.last_mut().unwrap() ^= 0xFF;
+        fs::write(root.join("img/pictures/d.rpgmvp"), changed).unwrap();
+
+        let duplicates = find_duplicates(&root);
+        assert!(duplicates.errors.is_empty());
+        assert_eq!(duplicates.groups.len(), 1);
+
+        let group = &duplicates.groups[0];
+        let paths: Vec<_> = group
+            .assets
+            .iter()
+            .map(|asset| &asset.relative_path)
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("img/b.rpgmvp"),
+                Path::new("img/pictures/a.rpgmvp")
+            ]
+        );
+
+        let size = fs::metadata(MV_PNG_ABOBA).unwrap().len();
+        assert_eq!(group.size(), size - 16);
+        assert_eq!(duplicates.redundant_bytes(), size);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn inventory_records() {
+        let root = temp_dir().join("rpgm-asset-decrypter-scan-inventory");
+        let _ = fs::remove_dir_all(&root);
+
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::create_dir_all(root.join("audio")).unwrap();
+        fs::copy(MV_PNG_ABOBA, root.join("img/a.rpgmvp")).unwrap();
+        fs::copy(MV_OGG_ABOBA, root.join("audio/b.rpgmvo")).unwrap();
+
+        let ogg = Decrypter::new()
+            .decrypt(&fs::read(MV_OGG_ABOBA).unwrap(), FileType::OGG)
+            .unwrap();
+        fs::write(root.join("audio/c.ogg"), ogg).unwrap();
+
+        // 2 seconds of 8 kHz mono 8-bit audio.
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0".to_vec();
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(b"\x01\0\x08\0data");
+        wav.extend_from_slice(&16000u32.to_le_bytes());
+        wav.resize(wav.len() + 16000, 0x80);
+        fs::write(root.join("audio/d, e.wav"), wav).unwrap();
+
+        fs::write(root.join("readme.txt"), b"not an asset").unwrap();
+
+        let records = inventory(&root).unwrap();
+        let paths: Vec<_> =
+            records.iter().map(|record| record.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("audio/b.rpgmvo"),
+                Path::new("audio/c.ogg"),
+                Path::new("audio/d, e.wav"),
+                Path::new("img/a.rpgmvp")
+            ]
+        );
+
+        let fingerprint =
+            EncryptionKey::from_hex_str("150f15e73422e0a5ba5b59f997fc2350")
+                .unwrap()
+                .fingerprint();
+
+        let (encrypted_ogg, ogg) = (&records[0], &records[1]);
+        assert_eq!(encrypted_ogg.file_type, FileType::OGG);
+        assert!(encrypted_ogg.encrypted);
+        assert_eq!(encrypted_ogg.key_fingerprint, Some(fingerprint.clone()));
+        assert_eq!(encrypted_ogg.duration, Some(5.0));
+        assert!(!ogg.encrypted);
+        assert_eq!(ogg.key_fingerprint, None);
+        assert_eq!(ogg.duration, encrypted_ogg.duration);
+
+        assert_eq!(records[2].file_type, FileType::WAV);
+        assert_eq!(records[2].duration, Some(2.0));
+
+        let png = &records[3];
+        assert_eq!(png.size, fs::metadata(MV_PNG_ABOBA).unwrap().len());
+        assert_eq!(png.key_fingerprint, Some(fingerprint));
+        assert_eq!(png.dimensions, Some((1920, 1080)));
+        assert_eq!(png.duration, None);
+
+        let mut csv = Vec::new();
+        write_csv(&records, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "path,file_type,size,encrypted,key_fingerprint,width,height,duration"
+        );
+        assert_eq!(lines[3], "\"audio/d, e.wav\",wav,16044,false,,,,2.0");
+
+        let mut json = Vec::new();
+        write_json(&records, &mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(
+            "[{\"path\":\"audio/b.rpgmvo\",\"file_type\":\"ogg\""
+        ));
+        assert!(json.contains(
+            "{\"path\":\"audio/d, e.wav\",\"file_type\":\"wav\",\"size\":16044,\"encrypted\":false,\"key_fingerprint\":null,\"dimensions\":null,\"duration\":2.0}"
+        ));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+mod audit {
+    use super::{
+        MV_PNG_ABOBA, MV_PNG_DEFAULT, MZ_M4A_ABOBA, MZ_M4A_DEFAULT,
+        MZ_OGG_ABOBA,
+    };
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Encrypter, EncryptionKey, FileType, RPGM_HEADER,
+        audit::{AuditIssue, audit},
+    };
+    use std::{env::temp_dir, fs, path::Path};
+
+    #[test]
+    fn finds_broken_assets() {
+        let root = temp_dir().join("rpgm-asset-decrypter-audit");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("img")).unwrap();
+        fs::create_dir_all(root.join("audio")).unwrap();
+
+        let key =
+            EncryptionKey::from_hex_str("150f15e73422e0a5ba5b59f997fc2350")
+                .unwrap();
+        let png = fs::read(MV_PNG_ABOBA).unwrap();
+
+        fs::copy(MV_PNG_ABOBA, root.join("img/intact.rpgmvp")).unwrap();
+        fs::copy(MZ_OGG_ABOBA, root.join("audio/intact.ogg_")).unwrap();
+        fs::copy(MZ_M4A_ABOBA, root.join("audio/intact.m4a_")).unwrap();
+        fs::copy(MV_PNG_DEFAULT, root.join("img/other_key.rpgmvp")).unwrap();
+        fs::copy(MZ_M4A_DEFAULT, root.join("audio/other_key.m4a_")).unwrap();
+        fs::copy(MZ_OGG_ABOBA, root.join("img/ogg.rpgmvp")).unwrap();
+        fs::write(
+            root.join("img/plain.rpgmvp"),
+            Decrypter::new().decrypt(&png, FileType::PNG).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            root.join("img/twice.rpgmvp"),
+            Encrypter::with_key(key).encrypt(&png),
+        )
+        .unwrap();
+
+        let default_key = EncryptionKey::from_hex_str(
+            Decrypter::new()
+                .set_key_from_file(
+                    &fs::read(MV_PNG_DEFAULT).unwrap(),
+                    FileType::PNG,
+                )
+                .unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            root.join("img/twice_other_key.rpgmvp"),
+            Encrypter::with_key(default_key).encrypt(&png),
+        )
+        .unwrap();
+
+        let mut corrupted = png.clone();
+        corrupted[16..64].fill(0);
+        fs::write(root.join("img/corrupted.rpgmvp"), corrupted).unwrap();
+
+        let mut truncated = RPGM_HEADER.to_vec();
+        truncated.extend_from_slice(&png[16..24]);
+        fs::write(root.join("img/truncated.rpgmvp"), truncated).unwrap();
+
+        fs::write(root.join("img/garbage.rpgmvp"), b"garbage, not an asset")
+            .unwrap();
+
+        let report = audit(&root, &key);
+        assert!(report.errors.is_empty());
+        assert!(!report.is_clean());
+        assert_eq!(report.checked, 12);
+
+        let findings: Vec<_> = report
+            .findings
+            .iter()
+            .map(|finding| {
+                (finding.asset.relative_path.as_path(), finding.issue.clone())
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (
+                    Path::new("audio/other_key.m4a_"),
+                    AuditIssue::WrongKey {
+                        fingerprint: default_key.fingerprint()
+                    }
+                ),
+                (Path::new("img/corrupted.rpgmvp"), AuditIssue::Corrupted),
+                (Path::new("img/garbage.rpgmvp"), AuditIssue::InvalidHeader),
+                (
+                    Path::new("img/ogg.rpgmvp"),
+                    AuditIssue::TypeMismatch {
+                        detected: FileType::OGG
+                    }
+                ),
+                (
+                    Path::new("img/other_key.rpgmvp"),
+                    AuditIssue::WrongKey {
+                        fingerprint: default_key.fingerprint()
+                    }
+                ),
+                (
+                    Path::new("img/plain.rpgmvp"),
+                    AuditIssue::NotEncrypted(FileType::PNG)
+                ),
+                (Path::new("img/truncated.rpgmvp"), AuditIssue::Truncated),
+                (Path::new("img/twice.rpgmvp"), AuditIssue::DoubleEncrypted),
+                (
+                    Path::new("img/twice_other_key.rpgmvp"),
+                    AuditIssue::DoubleEncrypted
+                ),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+mod patch {
+    use super::{MV_OGG_ABOBA, MV_PNG_ABOBA};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Error, FileType, HEADER_LENGTH, manifest,
+        patch::{PATCH_MAGIC, apply_patch, create_patch},
+    };
+    use std::fs::read;
+
+    #[test]
+    fn create_and_apply() {
+        let original = read(MV_PNG_ABOBA).unwrap();
+        let mut replacement =
+            Decrypter::new().decrypt(&original, FileType::PNG).unwrap();
+        let middle = replacement.len() / 2;
+        replacement[middle..middle + 4].copy_from_slice(b"edit");
+        replacement.extend_from_slice(b"appended");
+
+        let patch =
+            create_patch(&original, &replacement, FileType::PNG).unwrap();
+        assert!(patch.len() < 128);
+
+        let patched = apply_patch(&original, &patch).unwrap();
+        assert_eq!(
+            &patched[..HEADER_LENGTH * 2],
+            &original[..HEADER_LENGTH * 2]
+        );
+        assert_eq!(
+            Decrypter::new().decrypt(&patched, FileType::PNG).unwrap(),
+            replacement
+        );
+
+        let other = read(MV_OGG_ABOBA).unwrap();
+        assert!(matches!(
+            apply_patch(&other, &patch),
+            Err(Error::PatchMismatch)
+        ));
+        assert!(matches!(
+            apply_patch(&original, &patch[..patch.len() - 3]),
+            Err(Error::UnexpectedEOF | Error::PatchMismatch)
+        ));
+        assert!(matches!(
+            apply_patch(&original, b"not a patch"),
+            Err(Error::InvalidHeader)
+        ));
+
+        // Copies past the declared target length are rejected as they come.
+        let original = [0; 100];
+        let mut patch = PATCH_MAGIC.to_vec();
+        patch.push(100);
+        patch.extend_from_slice(&manifest::hash(&original).to_le_bytes());
+        patch.push(0);
+        patch.extend_from_slice(&[0; 8]);
+
+        for _ in 0..1000 {
+            patch.extend_from_slice(&[0, 0, 100]);
+        }
+
+        assert!(matches!(
+            apply_patch(&original, &patch),
+            Err(Error::PatchMismatch)
+        ));
+    }
+}
+
+mod key_store {
+    use super::{MV_PNG_ABOBA, MV_PNG_DEFAULT};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Error, FileType,
+        batch::{BatchOptions, decrypt_dir},
+        key_store::KeyStore,
+    };
+    use std::{
+        env::temp_dir,
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn derive_once_and_reuse() {
+        let root = temp_dir().join("rpgm-asset-decrypter-key-store");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("game/img")).unwrap();
+        fs::create_dir_all(root.join("empty")).unwrap();
+        fs::copy(MV_PNG_DEFAULT, root.join("game/img/a.rpgmvp")).unwrap();
+
+        let mut store = KeyStore::new();
+        assert!(store.is_empty());
+
+        let key = store.get_or_derive(&root.join("game")).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(&root.join("game")), Some(key));
+        assert_eq!(
+            store.find(&root.join("game/img/a.rpgmvp")),
+            Some((root.join("game").as_path(), key))
+        );
+        assert!(store.find(&root.join("empty")).is_none());
+
+        assert!(matches!(
+            store.get_or_derive(&root.join("empty")).unwrap_err().source,
+            Error::KeyNotSet
+        ));
+
+        // Recorded key takes precedence over the files.
+        let aboba = {
+            let data = fs::read(MV_PNG_ABOBA).unwrap();
+            let mut decrypter = Decrypter::new();
+            decrypter.set_key_from_file(&data, FileType::PNG).unwrap();
+            decrypter.encryption_key().unwrap()
+        };
+        assert_ne!(aboba, key);
+        store.insert(root.join("game"), aboba);
+        assert_eq!(store.get_or_derive(&root.join("game/img")).unwrap(), aboba);
+        assert_eq!(store.remove(&root.join("game")), Some(aboba));
+
+        let store = Arc::new(Mutex::new(store));
+        let options = BatchOptions::new().key_store(Arc::clone(&store));
+        let output = root.join("output");
+
+        let report =
+            decrypt_dir(&root.join("game"), &output, &options).unwrap();
+        assert_eq!(report.succeeded().count(), 1);
+        assert_eq!(store.lock().unwrap().get(&root.join("game")), Some(key));
+
+        // Files are decrypted with the stored key, even if it's wrong.
+        store.lock().unwrap().insert(root.join("game"), aboba);
+        let report =
+            decrypt_dir(&root.join("game"), &output, &options).unwrap();
+        assert_eq!(report.key, Some(aboba));
+        assert_eq!(report.failed().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_and_load() {
+        use rpgm_asset_decrypter_lib::{
+            DEFAULT_KEY, EncryptionKey, key_store::content_hash,
+        };
+
+        let key = EncryptionKey::from_hex_str(DEFAULT_KEY).unwrap();
+        let hash = content_hash(b"System.json");
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, content_hash(b"System.json"));
+
+        let mut store = KeyStore::new();
+        store.insert("games/a", key);
+        store.insert_hash(&hash, key);
+        assert_eq!(store.len(), 2);
+
+        let json = serde_json::to_string(&store).unwrap();
+        assert!(json.contains(DEFAULT_KEY));
+        assert_eq!(serde_json::from_str::<KeyStore>(&json).unwrap(), store);
+
+        let toml = toml::to_string(&store).unwrap();
+        let loaded: KeyStore = toml::from_str(&toml).unwrap();
+        assert_eq!(loaded.get_hash(&hash), Some(key));
+        assert_eq!(loaded, store);
+
+        assert!(
+            serde_json::from_str::<KeyStore>(r#"{"paths":{"a":"zz"}}"#)
+                .is_err()
+        );
+        assert!(serde_json::from_str::<KeyStore>("{}").unwrap().is_empty());
+    }
+}
+
+mod manifest {
+    use super::{MV_PNG_DEFAULT, MZ_OGG_DEFAULT};
+    use rpgm_asset_decrypter_lib::{
+        DEFAULT_KEY, EncryptionKey,
+        batch::{
+            BatchOptions, FileStatus, RepackOptions, decrypt_dir, encrypt_dir,
+        },
+        manifest::{Manifest, hash, sha256, to_hex},
+    };
+    use std::{env::temp_dir, fs, path::Path};
+
+    #[test]
+    fn resume_from_manifest() {
+        let input = temp_dir().join("rpgm-asset-decrypter-manifest");
+        let output = temp_dir().join("rpgm-asset-decrypter-manifest-out");
+        let manifest_path =
+            temp_dir().join("rpgm-asset-decrypter-manifest.jsonl");
+        let _ = fs::remove_dir_all(&input);
+        let _ = fs::remove_dir_all(&output);
+        let _ = fs::remove_file(&manifest_path);
+
+        fs::create_dir_all(input.join("img")).unwrap();
+        fs::copy(MV_PNG_DEFAULT, input.join("img/a.rpgmvp")).unwrap();
+        fs::copy(MZ_OGG_DEFAULT, input.join("b.ogg_")).unwrap();
+
+        let options = BatchOptions::new().manifest(&manifest_path);
+        let report = decrypt_dir(&input, &output, &options).unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.len(), 2);
+        let record = manifest.get(Path::new("img/a.rpgmvp")).unwrap();
+        assert_eq!(record.output, output.join("img/a.png"));
+        assert_eq!(
+            record.hash,
+            hash(&fs::read(output.join("img/a.png")).unwrap())
+        );
+        assert!(manifest.records().all(|record| record.is_intact()));
+
+        // Interrupted write of the ogg output.
+        fs::write(output.join("b.ogg"), b"Ogg").unwrap();
+
+        let report = decrypt_dir(
+            &input,
+            &output,
+            &options.resume_from(Manifest::load(&manifest_path).unwrap()),
+        )
+        .unwrap();
+        assert!(matches!(report.files[0].status, FileStatus::Succeeded));
+        assert!(matches!(report.files[1].status, FileStatus::Skipped));
+        assert_eq!(report.files[1].output, Some(output.join("img/a.png")));
+
+        let manifest = Manifest::load(&manifest_path).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert!(manifest.records().all(|record| record.is_intact()));
+
+        fs::remove_dir_all(&input).unwrap();
+        fs::remove_dir_all(&output).unwrap();
+        fs::remove_file(&manifest_path).unwrap();
+    }
+
+    #[test]
+    fn parse_lines() {
+        let text = concat!(
+            r#"{"input":"a \"quoted\"\\path","output":"\u00e9\ud83d\ude00","size":3,"hash":"00000000000000ff"}"#,
+            "\n",
+            r#"{"input":"b","output":"c","size":1}"#,
+            "\n",
+            r#"{"input":"a \"quoted\"\\path","output":"o","size":4,"hash":"ff"}"#,
+            "\n",
+            r#"{"input":"truncated","output":"c","si"#,
+        );
+
+        let manifest = Manifest::parse(text);
+        assert_eq!(manifest.len(), 1);
+
+        let record = manifest.get(Path::new("a \"quoted\"\\path")).unwrap();
+        assert_eq!(record.output, Path::new("o"));
+        assert_eq!(record.size, 4);
+        assert_eq!(record.hash, 0xff);
+
+        let manifest = Manifest::parse(text.lines().next().unwrap());
+        let record = manifest.records().next().unwrap();
+        assert_eq!(record.output, Path::new("é😀"));
+        assert!(!record.is_intact());
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            to_hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            to_hex(&sha256(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        let input = temp_dir().join("rpgm-asset-decrypter-checksums");
+        let output = temp_dir().join("rpgm-asset-decrypter-checksums-out");
+        let repacked = temp_dir().join("rpgm-asset-decrypter-checksums-repack");
+        let decrypt_manifest =
+            temp_dir().join("rpgm-asset-decrypter-checksums.jsonl");
+        let encrypt_manifest =
+            temp_dir().join("rpgm-asset-decrypter-checksums-repack.jsonl");
+        for dir in [&input, &output, &repacked] {
+            let _ = fs::remove_dir_all(dir);
+        }
+        let _ = fs::remove_file(&decrypt_manifest);
+        let _ = fs::remove_file(&encrypt_manifest);
+
+        fs::create_dir_all(&input).unwrap();
+        fs::copy(MV_PNG_DEFAULT, input.join("a.rpgmvp")).unwrap();
+
+        let options = BatchOptions::new()
+            .manifest(&decrypt_manifest)
+            .checksums(true);
+        decrypt_dir(&input, &output, &options).unwrap();
+
+        let manifest = Manifest::load(&decrypt_manifest).unwrap();
+        let record = manifest.get(Path::new("a.rpgmvp")).unwrap();
+        assert_eq!(
+            record.input_sha256,
+            Some(sha256(&fs::read(MV_PNG_DEFAULT).unwrap()))
+        );
+        assert_eq!(
+            record.output_sha256,
+            Some(sha256(&fs::read(output.join("a.png")).unwrap()))
+        );
+        assert!(record.is_intact());
+
+        let options = RepackOptions::new()
+            .key(EncryptionKey::from_hex_str(DEFAULT_KEY).unwrap())
+            .manifest(&encrypt_manifest)
+            .checksums(true);
+        encrypt_dir(&output, &repacked, &options).unwrap();
+
+        let manifest = Manifest::load(&encrypt_manifest).unwrap();
+        let repack_record = manifest.get(Path::new("a.png")).unwrap();
+        assert_eq!(repack_record.input_sha256, record.output_sha256);
+        // Repacking an unmodified asset restores the original file.
+        assert_eq!(repack_record.output_sha256, record.input_sha256);
+
+        for dir in [&input, &output, &repacked] {
+            fs::remove_dir_all(dir).unwrap();
+        }
+        fs::remove_file(&decrypt_manifest).unwrap();
+        fs::remove_file(&encrypt_manifest).unwrap();
+    }
+}
+
+mod asar {
+    use super::{MZ_OGG_ABOBA, MZ_PNG_ABOBA};
+    use rpgm_asset_decrypter_lib::{
+        Decrypter, Error, FileError, FileType,
+        batch::{
+            BatchOptions, FileStatus, ProgressEvent, decrypt_dir,
+            decrypt_dir_threads, decrypt_dir_with_progress, decrypt_vfs,
+        },
+        container::AsarContainer,
+        path::OutputNaming,
+        scan::encrypted_assets,
+        vfs::VirtualFs,
+    };
+    use std::{
+        collections::BTreeMap,
+        env::temp_dir,
+        fs,
+        io::{self, Cursor},
+        path::{Path, PathBuf},
+        sync::Mutex,
+    };
+
+    const KEY: &str = "150f15e73422e0a5ba5b59f997fc2350";
+
+    /// Packs files the way `@electron/asar` does: size pickle, header pickle with JSON string, then file data.
+    fn app_asar() -> Vec<u8> {
+        let png = fs::read(MZ_PNG_ABOBA).unwrap();
+        let ogg = fs::read(MZ_OGG_ABOBA).unwrap();
+        let system_json = format!(r#"{{"encryptionKey":"{KEY}"}}"#);
+
+        let json = format!(
+            r#"{{"files":{{"img":{{"files":{{"a.png_":{{"size":{},"offset":"0","integrity":{{"blocks":["x"]}}}}}}}},"audio":{{"files":{{"b.ogg_":{{"size":{},"offset":"{}"}}}}}},"data":{{"files":{{"System.json":{{"size":{},"offset":"{}"}}}}}},"..":{{"files":{{"evil.png_":{{"size":{},"offset":"0"}}}}}}}}}}"#,
+            png.len(),
+            ogg.len(),
+            png.len(),
+            system_json.len(),
+            png.len() + ogg.len(),
+            png.len(),
+        );
+
+        let mut asar = asar(&json);
+        asar.extend_from_slice(&png);
+        asar.extend_from_slice(&ogg);
+        asar.extend_from_slice(system_json.as_bytes());
+        asar
+    }
+
+    /// Returns asar archive with header `json`, to be followed by file data.
+    fn asar(json: &str) -> Vec<u8> {
+        let mut header = Vec::new();
+        let padded = (json.len() + 3) / 4 * 4;
+        header.extend_from_slice(&(4 + padded as u32).to_le_bytes());
+        header.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        header.extend_from_slice(json.as_bytes());
+        header.resize(8 + padded, 0);
+
+        let mut asar = Vec::new();
+        asar.extend_from_slice(&4u32.to_le_bytes());
+        asar.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        asar.extend_from_slice(&header);
+        asar
+    }
+
+    #[test]
+    fn read_and_decrypt() {
+        let mut container =
+            AsarContainer::new(Cursor::new(app_asar())).unwrap();
+        assert_eq!(
+            container.entries().collect::<Vec<_>>(),
+            ["audio/b.ogg_", "data/System.json", "img/a.png_"]
+        );
+        assert_eq!(
+            container.encrypted_entries(),
+            ["audio/b.ogg_", "img/a.png_"]
+        );
+
+        let expected = Decrypter::new()
+            .decrypt(&fs::read(MZ_PNG_ABOBA).unwrap(), FileType::PNG)
+            .unwrap();
+        assert_eq!(
+            container
+                .decrypt(&mut Decrypter::new(), "img/a.png_")
+                .unwrap(),
+            expected
+        );
+        assert!(container.read("missing.png_").is_err());
+
+        let overflowing = asar(&format!(
+            r#"{{"files":{{"a.png_":{{"size":16,"offset":"{}"}}}}}}"#,
+            u64::MAX
+        ));
+        let mut container =
+            AsarContainer::new(Cursor::new(overflowing)).unwrap();
+        assert!(matches!(
+            container.read("a.png_"),
+            Err(Error::UnexpectedEOF)
+        ));
+    }
+
+    #[test]
+    fn scan_and_decrypt_dir() {
+        let dir = temp_dir().join("rpgm-asset-decrypter-asar");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("app.asar");
+        let output = dir.join("output");
+        fs::write(&archive, app_asar()).unwrap();
+
+        let assets: Vec<_> =
+            encrypted_assets(&archive).map(Result::unwrap).collect();
+        assert_eq!(assets.len(), 2);
+        assert_eq!(assets[1].path, archive.join("img/a.png_"));
+        assert_eq!(assets[1].file_type, FileType::PNG);
+        assert_eq!(assets[1].size, fs::metadata(MZ_PNG_ABOBA).unwrap().len());
+
+        let report =
+            decrypt_dir(&archive, &output, &BatchOptions::new()).unwrap();
+        assert_eq!(report.key, Some(KEY.parse().unwrap()));
+        assert!(
+            report
+                .files
+                .iter()
+                .all(|file| matches!(file.status, FileStatus::Succeeded))
+        );
+        assert!(output.join("img/a.png").is_file());
+        assert!(output.join("audio/b.ogg").is_file());
+        assert!(!dir.join("evil.png").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decrypt_dir_honors_options() {
+        let dir = temp_dir().join("rpgm-asset-decrypter-asar-options");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("app.asar");
+        let output = dir.join("output");
+        fs::write(&archive, app_asar()).unwrap();
+
+        let options = BatchOptions::new().include(["img/**"]).dry_run(true);
+
+        for report in [
+            decrypt_dir(&archive, &output, &options).unwrap(),
+            decrypt_dir_threads(&archive, &output, &options, 2).unwrap(),
+        ] {
+            assert_eq!(report.files.len(), 1);
+            assert!(matches!(report.files[0].status, FileStatus::Planned));
+            assert_eq!(report.files[0].output, Some(output.join("img/a.png")));
+        }
+
+        assert!(!output.exists());
+
+        let events = Mutex::new(Vec::new());
+        let report = decrypt_dir_with_progress(
+            &archive,
+            &output,
+            &BatchOptions::new(),
+            &|event: ProgressEvent<'_>| {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(matches!(event, ProgressEvent::Started { .. }));
+            },
+        )
+        .unwrap();
+        assert_eq!(report.succeeded().count(), 2);
+        assert_eq!(events.into_inner().unwrap(), [true, false, true, false]);
+
+        let err = decrypt_dir(
+            &archive,
+            &output,
+            &BatchOptions::new().transactional(true),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.source,
+            Error::Io(err) if err.kind() == io::ErrorKind::InvalidInput
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// In-memory filesystem, to check that [`decrypt_vfs`] doesn't depend on real paths.
+    #[derive(Default)]
+    struct MemoryFs(BTreeMap<PathBuf, Vec<u8>>);
+
+    impl VirtualFs for MemoryFs {
+        fn list(&mut self) -> Result<Vec<PathBuf>, FileError> {
+            Ok(self.0.keys().cloned().collect())
+        }
+
+        fn read(&mut self, path: &Path) -> Result<Vec<u8>, FileError> {
+            self.0.get(path).cloned().ok_or_else(|| {
+                FileError::new(path, io::Error::from(io::ErrorKind::NotFound))
+            })
+        }
+
+        fn write(&mut self, path: &Path, data: &[u8]) -> Result<(), FileError> {
+            self.0.insert(path.to_path_buf(), data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decrypt_vfs_into_memory() {
+        let mut container =
+            AsarContainer::new(Cursor::new(app_asar())).unwrap();
+        let mut output = MemoryFs::default();
+        let options =
+            BatchOptions::new().naming(OutputNaming::Suffix("dec".to_owned()));
+
+        let report =
+            decrypt_vfs(&mut container, &mut output, &options).unwrap();
+        assert_eq!(report.key, Some(KEY.parse().unwrap()));
+        assert_eq!(report.succeeded().count(), 2);
+        assert_eq!(
+            output.list().unwrap(),
+            [
+                PathBuf::from("audio/b.dec.ogg"),
+                PathBuf::from("img/a.dec.png")
+            ]
+        );
+        assert!(container.write(Path::new("x.png"), &[]).is_err());
+
+        let expected = Decrypter::new()
+            .decrypt(&fs::read(MZ_PNG_ABOBA).unwrap(), FileType::PNG)
+            .unwrap();
+        let mut input = MemoryFs::default();
+        input
+            .write(Path::new("img/a.png_"), &fs::read(MZ_PNG_ABOBA).unwrap())
+            .unwrap();
+
+        let report =
+            decrypt_vfs(&mut input, &mut output, &BatchOptions::new()).unwrap();
+        assert_eq!(report.succeeded().count(), 1);
+        assert_eq!(output.read(Path::new("img/a.png")).unwrap(), expected);
+    }
+}
+
+#[cfg(feature = "zip")]
+mod container {
+    use super::{MV_OGG_DEFAULT, MV_PNG_DEFAULT};
+    use rpgm_asset_decrypter_lib::{
+        DEFAULT_KEY, Decrypter, FileType,
+        batch::FileStatus,
+        container::{ZipContainer, decrypt_zip},
+    };
+    use std::{
+        env::temp_dir,
+        fs,
+        io::{Cursor, Write},
+    };
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    fn package_nw() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let entries = [
+            ("www/img/a.rpgmvp", fs::read(MV_PNG_DEFAULT).unwrap()),
+            ("www/audio/b.rpgmvo", fs::read(MV_OGG_DEFAULT).unwrap()),
+            ("www/index.html", b"<html></html>".to_vec()),
+            ("../evil.rpgmvp", fs::read(MV_PNG_DEFAULT).unwrap()),
+        ];
+
+        for (name, data) in entries {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn decrypt_package_nw() {
+        let dir = temp_dir().join("rpgm-asset-decrypter-container");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut container =
+            ZipContainer::new(Cursor::new(package_nw())).unwrap();
+        assert_eq!(
+            container.encrypted_entries(),
+            ["www/audio/b.rpgmvo", "www/img/a.rpgmvp"]
+        );
+
+        let plain = container
+            .decrypt(&mut Decrypter::new(), "www/img/a.rpgmvp")
+            .unwrap();
+        let expected = Decrypter::new()
+            .decrypt(&fs::read(MV_PNG_DEFAULT).unwrap(), FileType::PNG)
+            .unwrap();
+        assert_eq!(plain, expected);
+
+        let archive = dir.join("package.nw");
+        let output = dir.join("output");
+        fs::write(&archive, package_nw()).unwrap();
+
+        let report = decrypt_zip(&archive, &output, None).unwrap();
+        assert_eq!(report.key, Some(DEFAULT_KEY.parse().unwrap()));
+        assert_eq!(report.files.len(), 2);
+        assert!(
+            report
+                .files
+                .iter()
+                .all(|file| matches!(file.status, FileStatus::Succeeded))
+        );
+        assert_eq!(report.files[1].input, archive.join("www/img/a.rpgmvp"));
+        assert_eq!(fs::read(output.join("www/img/a.png")).unwrap(), expected);
+        assert!(output.join("www/audio/b.ogg").is_file());
+        assert!(!dir.join("evil.png").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}